@@ -0,0 +1,179 @@
+use image::{ImageFormat, ImageReader};
+use log::debug;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use zip::ZipArchive;
+
+// Un archivio ZIP si usa come una cartella: le voci hanno il percorso dell'archivio seguito dal
+// nome della voce (es. backup.zip/2023/IMG_0001.jpg) e vengono lette in memoria quando servono.
+// Solo in lettura: i file scelti vengono estratti in output come gli altri
+
+// Archivi già aperti, per non rileggere l'indice centrale a ogni voce. Il lucchetto di ciascun
+// archivio serializza le letture, che condividono lo stesso file
+type OpenArchive = Arc<Mutex<ZipArchive<BufReader<File>>>>;
+static ARCHIVES: Mutex<Option<HashMap<PathBuf, OpenArchive>>> = Mutex::new(None);
+
+/// Contenuto di un file su disco o di una voce di un archivio, da leggere anche con seek.
+pub enum Source {
+    File(BufReader<File>),
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::File(file) => file.read(buf),
+            Source::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl BufRead for Source {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Source::File(file) => file.fill_buf(),
+            Source::Memory(cursor) => cursor.fill_buf(),
+        }
+    }
+    
+    fn consume(&mut self, amount: usize) {
+        match self {
+            Source::File(file) => file.consume(amount),
+            Source::Memory(cursor) => cursor.consume(amount),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        match self {
+            Source::File(file) => file.seek(position),
+            Source::Memory(cursor) => cursor.seek(position),
+        }
+    }
+}
+
+/// Vero se `path` è un archivio ZIP da usare al posto di una cartella.
+pub fn is_archive(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")) && path.is_file()
+}
+
+/// Vero se `path` è una voce di un archivio e non un file su disco.
+pub fn is_inside(path: &Path) -> bool {
+    split(path).is_some()
+}
+
+/// File su disco che contiene `path`: l'archivio per le voci, altrimenti il percorso stesso.
+pub fn on_disk(path: &Path) -> &Path {
+    split(path).map_or(path, |(archive, _)| archive)
+}
+
+/// Voci dell'archivio che sono file, ordinate per nome. Le voci con percorsi assoluti o che
+/// risalgono con ".." vengono ignorate.
+pub fn list_files(archive: &Path) -> io::Result<Vec<PathBuf>> {
+    let opened = open_archive(archive)?;
+    let zip = opened.lock().unwrap_or_else(|e| e.into_inner());
+    let mut files: Vec<PathBuf> = (0..zip.len())
+        .filter_map(|index| {
+            let entry = zip.name_for_index(index)?;
+            // Le cartelle hanno il nome che termina con "/"
+            if entry.ends_with('/') {
+                return None;
+            }
+            safe_name(entry).map(|name| archive.join(name))
+        })
+        .collect();
+    files.sort();
+    debug!("Archivio {:?}: {} file", archive, files.len());
+    Ok(files)
+}
+
+/// Byte del file o della voce dell'archivio.
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let Some((archive, name)) = split(path) else {
+        return fs::read(path);
+    };
+    let opened = open_archive(archive)?;
+    let mut zip = opened.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entry = zip.by_name(&name).map_err(io::Error::other)?;
+    let mut bytes = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Apre il file, o legge in memoria la voce dell'archivio.
+pub fn open(path: &Path) -> io::Result<Source> {
+    if is_inside(path) {
+        Ok(Source::Memory(Cursor::new(read(path)?)))
+    } else {
+        Ok(Source::File(BufReader::new(File::open(path)?)))
+    }
+}
+
+/// Come `ImageReader::open`: formato dall'estensione, corretto dai primi byte se li riconosce.
+pub fn image_reader(path: &Path) -> io::Result<ImageReader<Source>> {
+    let mut reader = ImageReader::new(open(path)?);
+    if let Ok(format) = ImageFormat::from_path(path) {
+        reader.set_format(format);
+    }
+    reader.with_guessed_format()
+}
+
+/// Dimensione in byte; per le voci quella non compressa, letta dall'indice dell'archivio.
+pub fn len(path: &Path) -> io::Result<u64> {
+    let Some((archive, name)) = split(path) else {
+        return fs::metadata(path).map(|metadata| metadata.len());
+    };
+    let opened = open_archive(archive)?;
+    let mut zip = opened.lock().unwrap_or_else(|e| e.into_inner());
+    let entry = zip.by_name(&name).map_err(io::Error::other)?;
+    Ok(entry.size())
+}
+
+/// Copia il file in `to`; una voce dell'archivio viene estratta.
+pub fn copy(from: &Path, to: &Path) -> io::Result<u64> {
+    if !is_inside(from) {
+        return fs::copy(from, to);
+    }
+    let bytes = read(from)?;
+    fs::write(to, &bytes)?;
+    Ok(bytes.len() as u64)
+}
+
+// Archivio che contiene `path` e nome della voce, con "/" come separatore. Prima si guarda
+// l'estensione, così i percorsi normali non costano accessi al disco
+fn split(path: &Path) -> Option<(&Path, String)> {
+    let archive = path.ancestors()
+        .skip(1)
+        .filter(|ancestor| ancestor.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")))
+        .find(|ancestor| ancestor.is_file())?;
+    let name = path.strip_prefix(archive).ok()?
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?
+        .join("/");
+    Some((archive, name))
+}
+
+// Nome della voce come percorso relativo, se non esce dall'archivio
+fn safe_name(entry: &str) -> Option<PathBuf> {
+    let name = PathBuf::from(entry);
+    name.components()
+        .all(|component| matches!(component, std::path::Component::Normal(_)))
+        .then_some(name)
+}
+
+fn open_archive(path: &Path) -> io::Result<OpenArchive> {
+    let mut archives = ARCHIVES.lock().unwrap_or_else(|e| e.into_inner());
+    let archives = archives.get_or_insert_with(HashMap::new);
+    if let Some(opened) = archives.get(path) {
+        return Ok(Arc::clone(opened));
+    }
+    let zip = ZipArchive::new(BufReader::new(File::open(path)?)).map_err(io::Error::other)?;
+    let opened = Arc::new(Mutex::new(zip));
+    archives.insert(path.to_path_buf(), Arc::clone(&opened));
+    Ok(opened)
+}
\ No newline at end of file
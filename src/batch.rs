@@ -0,0 +1,231 @@
+use crate::file_manager::FileManager;
+use crate::gui_v2::{IdenticalAction, SessionStats};
+use crate::image_analyzer::{FileDamage, ImageAnalysis, ScoringWeights};
+use crate::logging;
+use crate::report::{self, AnalysisEntry, ReportEntry};
+use crate::session::PairDecision;
+use log::{error, info, warn};
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// Caratteri della barra nella riga di avanzamento
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+/// Sceglie senza GUI l'immagine migliore di ogni coppia, con lo stesso criterio che la GUI
+/// evidenzia come consigliato, e la copia in output. Le coppie con un file illeggibile vengono
+/// saltate; `min_score`, `identical` e `same_size` valgono come nella GUI. Nella deduplicazione
+/// (`pair_groups`) il file scelto affronta il successivo del gruppo e solo l'ultimo vincitore
+/// viene copiato.
+pub fn run(
+    pairs: &[(PathBuf, PathBuf)],
+    pair_groups: Option<&[usize]>,
+    file_manager: &FileManager,
+    weights: &ScoringWeights,
+    min_score: Option<u8>,
+    identical: Option<IdenticalAction>,
+    same_size: Option<IdenticalAction>,
+) -> SessionStats {
+    let same_group = |index1: usize, index2: usize| {
+        pair_groups.is_some_and(|groups| groups.get(index1).is_some() && groups.get(index1) == groups.get(index2))
+    };
+    
+    let mut stats = SessionStats {
+        selected: 0,
+        skipped: 0,
+        kept_both: 0,
+        auto_identical: 0,
+        auto_same_size: 0,
+        damaged_files: Vec::new(),
+    };
+    // Coppie effettive, con il vincitore dei confronti precedenti del gruppo al primo posto
+    let mut actual_pairs = Vec::with_capacity(pairs.len());
+    let mut decisions = Vec::with_capacity(pairs.len());
+    let mut winner: Option<PathBuf> = None;
+    let mut compression = BTreeMap::new();
+    let progress = ProgressLine::new(pairs.len(), "coppie", 2);
+    
+    for (index, (path1, path2)) in pairs.iter().enumerate() {
+        let first = match winner.take() {
+            Some(winner) if index > 0 && same_group(index - 1, index) => winner,
+            _ => path1.clone(),
+        };
+        let advances = same_group(index, index + 1);
+        
+        // --same-size e --identical: registrate come coppie identiche, senza decodificarle.
+        // L'hash si confronta prima dell'analisi
+        let decision = if let Some(action) = same_size.filter(|_| FileManager::same_size(&first, path2)) {
+            stats.auto_same_size += 1;
+            if action == IdenticalAction::Copy {
+                PairDecision::Selected(copy(file_manager, &first))
+            } else {
+                PairDecision::Skipped
+            }
+        } else if identical.is_some() && ImageAnalysis::files_identical(&first, path2) {
+            stats.auto_identical += 1;
+            if advances {
+                PairDecision::Advanced(first.clone())
+            } else if identical == Some(IdenticalAction::Copy) {
+                PairDecision::Selected(copy(file_manager, &first))
+            } else {
+                PairDecision::Skipped
+            }
+        } else {
+            match (ImageAnalysis::analyze_image(&first, weights), ImageAnalysis::analyze_image(path2, weights)) {
+                (Ok(analysis1), Ok(analysis2)) => {
+                    compression.insert(index, [analysis1.compression_label(weights), analysis2.compression_label(weights)]);
+                    for analysis in [&analysis1, &analysis2] {
+                        if let Some((width, height)) = analysis.exif_dimensions_mismatch() {
+                            warn!("Coppia {}: {} dichiara {}x{} nell'EXIF ma misura {}x{} (modificato dopo lo scatto?)",
+                                index + 1, analysis.file_path.display(), width, height, analysis.width, analysis.height);
+                        }
+                    }
+                    if min_score.is_some_and(|min| analysis1.quality_score < min && analysis2.quality_score < min) {
+                        info!("Coppia {}: entrambe sotto il punteggio minimo, saltata", index + 1);
+                        stats.skipped += 1;
+                        PairDecision::Skipped
+                    } else {
+                        // A parità di punteggio vince la prima, come nella GUI
+                        let chosen = if analysis1.is_preferred_over(&analysis2, weights) { &first } else { path2 };
+                        info!("Coppia {}/{}: scelto {}", index + 1, pairs.len(), chosen.display());
+                        if advances {
+                            PairDecision::Advanced(chosen.clone())
+                        } else {
+                            stats.selected += 1;
+                            PairDecision::Selected(copy(file_manager, chosen))
+                        }
+                    }
+                }
+                (result1, result2) => {
+                    for (path, result) in [(&first, result1), (path2, result2)] {
+                        if let Err(e) = result {
+                            let damage = FileDamage::classify(path, &e);
+                            warn!("File {}: {}", damage.label(), path.display());
+                            if !stats.damaged_files.iter().any(|(known, _)| known == path) {
+                                stats.damaged_files.push((path.clone(), damage));
+                            }
+                        }
+                    }
+                    stats.skipped += 1;
+                    PairDecision::Skipped
+                }
+            }
+        };
+        
+        // Una coppia saltata lascia in gara il file che la apriva
+        winner = Some(match &decision {
+            PairDecision::Advanced(path) => path.clone(),
+            _ => first.clone(),
+        });
+        actual_pairs.push((first, path2.clone()));
+        decisions.push(Some(decision));
+        progress.update(index + 1, &format!("{} selezionate, {} saltate", stats.selected, stats.skipped));
+    }
+    progress.finish();
+    
+    let entries = ReportEntry::from_session(&actual_pairs, &decisions, &BTreeMap::new(), &compression);
+    if let Err(e) = report::write_report(&file_manager.output_folder, &entries) {
+        error!("Errore nella scrittura del report: {}", e);
+    }
+    
+    stats
+}
+
+/// Analisi senza decisioni né copie (--analyze-only): ogni file delle coppie viene analizzato e
+/// finisce nel report di inventario, con il file che la modalità batch sceglierebbe.
+pub fn analyze_pairs(pairs: &[(PathBuf, PathBuf)], weights: &ScoringWeights) -> Vec<AnalysisEntry> {
+    let progress = ProgressLine::new(pairs.len(), "coppie", 2);
+    let mut entries = Vec::with_capacity(pairs.len() * 2);
+    
+    for (index, (path1, path2)) in pairs.iter().enumerate() {
+        let result1 = ImageAnalysis::analyze_image(path1, weights);
+        let result2 = ImageAnalysis::analyze_image(path2, weights);
+        let preferred = match (&result1, &result2) {
+            (Ok(analysis1), Ok(analysis2)) => Some(analysis1.is_preferred_over(analysis2, weights)),
+            _ => None,
+        };
+        entries.push(AnalysisEntry::new(Some(index + 1), path1, preferred, result1, weights));
+        entries.push(AnalysisEntry::new(Some(index + 1), path2, preferred.map(|first| !first), result2, weights));
+        progress.update(index + 1, "");
+    }
+    progress.finish();
+    
+    entries
+}
+
+/// Come `analyze_pairs` per i file di una sola cartella, senza abbinamenti.
+pub fn analyze_files(files: &[PathBuf], weights: &ScoringWeights) -> Vec<AnalysisEntry> {
+    let progress = ProgressLine::new(files.len(), "file", 1);
+    let entries = files.iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let entry = AnalysisEntry::new(None, path, None, ImageAnalysis::analyze_image(path, weights), weights);
+            progress.update(index + 1, "");
+            entry
+        })
+        .collect();
+    progress.finish();
+    
+    entries
+}
+
+// Riga riscritta con \r su stderr, solo se è un terminale: in un file o in una pipe
+// resterebbero migliaia di righe intermedie
+struct ProgressLine {
+    total: usize,
+    // Cosa si conta ("coppie", "file") e quante immagini si analizzano per ciascuno
+    unit: &'static str,
+    images_per_item: usize,
+    started_at: Instant,
+    enabled: bool,
+}
+
+impl ProgressLine {
+    fn new(total: usize, unit: &'static str, images_per_item: usize) -> ProgressLine {
+        let enabled = std::io::stderr().is_terminal();
+        logging::set_progress_line(enabled);
+        ProgressLine { total, unit, images_per_item, started_at: Instant::now(), enabled }
+    }
+    
+    // [########      ] 42% — 4200/10000 coppie — 320 selezionate, 80 saltate — 58 img/s
+    fn update(&self, done: usize, detail: &str) {
+        if !self.enabled {
+            return;
+        }
+        let fraction = done as f64 / self.total.max(1) as f64;
+        let filled = (fraction * PROGRESS_BAR_WIDTH as f64).round() as usize;
+        let rate = (done * self.images_per_item) as f64 / self.started_at.elapsed().as_secs_f64().max(0.001);
+        let detail = if detail.is_empty() { String::new() } else { format!(" — {}", detail) };
+        
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K[{}{}] {:>3.0}% — {}/{} {}{} — {:.0} img/s",
+            "#".repeat(filled),
+            " ".repeat(PROGRESS_BAR_WIDTH - filled),
+            fraction * 100.0,
+            done,
+            self.total,
+            self.unit,
+            detail,
+            rate);
+        let _ = stderr.flush();
+    }
+    
+    // La riga finale resta visibile sopra il riepilogo
+    fn finish(&self) {
+        if self.enabled {
+            eprintln!();
+            logging::set_progress_line(false);
+        }
+    }
+}
+
+fn copy(file_manager: &FileManager, path: &Path) -> Option<PathBuf> {
+    match file_manager.copy_to_output_with_metadata(path, None, 0) {
+        Ok(dest_path) => Some(dest_path),
+        Err(e) => {
+            error!("Impossibile copiare {}: {}", path.display(), e);
+            None
+        }
+    }
+}
\ No newline at end of file
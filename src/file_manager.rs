@@ -1,20 +1,295 @@
+use crate::archive;
+use crate::glob::GlobPattern;
+use crate::image_analyzer::{self, ImageAnalysis};
+use crate::live_photo;
+use crate::scan_index;
 use anyhow::{Context, Result};
-use std::collections::HashMap;
+use log::{debug, error, info, warn};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use image::codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder};
+use image::{DynamicImage, ImageEncoder};
+
+/// Cartella di output predefinita, relativa alla cartella di lavoro.
+pub const OUTPUT_FOLDER: &str = "output";
+
+// Qualità con cui si ricodifica un JPEG ruotato quando non basta il tag di orientamento
+const ROTATION_JPEG_QUALITY: u8 = 95;
+
+/// Distanza massima ammessa tra due hash percettivi: gli hash sono a 64 bit.
+pub const MAX_SIMILARITY_DISTANCE: u32 = 64;
+
+/// Criterio con cui i file delle due cartelle vengono messi in coppia.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MatchStrategy {
+    // Stesso nome file
+    Name,
+    // Stesso percorso relativo alla cartella (sottocartelle comprese)
+    RelativePath,
+    // Hash percettivo con distanza di Hamming al massimo `max_distance` (0-64)
+    Similarity { max_distance: u32 },
+    // Stesso orario di scatto EXIF, entro una tolleranza in secondi
+    Timestamp { tolerance_secs: f64 },
+    // Stesso contenuto (SHA256), qualunque sia il nome
+    Content,
+}
+
+impl MatchStrategy {
+    pub fn label(&self) -> &'static str {
+        match self {
+            MatchStrategy::Name => "stesso nome",
+            MatchStrategy::RelativePath => "stesso percorso relativo",
+            MatchStrategy::Similarity { .. } => "immagine simile",
+            MatchStrategy::Timestamp { .. } => "stesso orario di scatto",
+            MatchStrategy::Content => "stesso contenuto",
+        }
+    }
+}
+
+/// Come i metadati di un'altra immagine vengono applicati al file copiato in output.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum MetadataMode {
+    // Aggiunge solo i tag assenti nel file scelto, senza toccare quelli esistenti
+    Merge,
+    // Sostituisce tutti i tag con quelli della sorgente
+    Overwrite,
+}
+
+/// Formato in cui ricodificare i file scelti (--convert).
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    // Senza perdita: l'encoder WebP disponibile non supporta la qualità
+    Webp,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+        }
+    }
+    
+    // Il file è già nel formato di destinazione: va copiato senza ricodificarlo
+    fn matches(self, path: &Path) -> bool {
+        let extension = path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match self {
+            OutputFormat::Jpeg => extension == "jpg" || extension == "jpeg",
+            OutputFormat::Png => extension == "png",
+            OutputFormat::Webp => extension == "webp",
+        }
+    }
+}
+
+// Segnaposto ammessi in --name-template
+const NAME_TOKENS: [&str; 4] = ["origname", "date", "time", "camera"];
+
+/// Modello del nome dei file in output, es. "{date}_{camera}_{origname}". I dati mancanti
+/// (foto senza EXIF) lasciano il segnaposto vuoto e i separatori rimasti vengono compattati.
+#[derive(Clone, Debug)]
+pub struct NameTemplate(String);
+
+impl FromStr for NameTemplate {
+    type Err = String;
+    
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}')
+                .ok_or_else(|| format!("parentesi graffa non chiusa in {:?}", s))?;
+            let token = &rest[start + 1..start + end];
+            if !NAME_TOKENS.contains(&token) {
+                return Err(format!("segnaposto {{{}}} sconosciuto (ammessi: {})", token, NAME_TOKENS.map(|t| format!("{{{}}}", t)).join(", ")));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        if s.contains(['/', '\\']) {
+            return Err("il modello non può contenere separatori di percorso".to_string());
+        }
+        Ok(NameTemplate(s.to_string()))
+    }
+}
+
+impl NameTemplate {
+    /// Nome (senza estensione) per `source_path`, con i dati EXIF del file stesso.
+    pub fn render(&self, source_path: &Path) -> String {
+        let origname = source_path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let datetime = ImageAnalysis::read_capture_datetime(source_path);
+        let date = datetime.as_ref()
+            .map(|dt| format!("{:04}{:02}{:02}", dt.year, dt.month, dt.day))
+            .unwrap_or_default();
+        let time = datetime.as_ref()
+            .map(|dt| format!("{:02}{:02}{:02}", dt.hour, dt.minute, dt.second))
+            .unwrap_or_default();
+        // Spazi e caratteri non ammessi nei nomi file diventano trattini
+        let camera: String = ImageAnalysis::read_camera(source_path)
+            .unwrap_or_default()
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '-' })
+            .collect();
+        
+        // I segnaposto con un valore diventano prima un carattere riservato, così la compattazione
+        // tocca solo i separatori del modello e mai quelli dei valori ("_MG_1234" resta tale)
+        let values = [origname.as_str(), date.as_str(), time.as_str(), camera.as_str()];
+        let placeholder = |index: usize| char::from_u32(0xE000 + index as u32).unwrap_or_default();
+        let mut name = self.0.clone();
+        for (index, (token, value)) in NAME_TOKENS.iter().zip(values).enumerate() {
+            let replacement = if value.is_empty() { String::new() } else { placeholder(index).to_string() };
+            name = name.replace(&format!("{{{}}}", token), &replacement);
+        }
+        
+        // Un segnaposto vuoto lascia separatori doppi o ai bordi ("_IMG_0001")
+        let mut compacted = String::with_capacity(name.len());
+        for c in name.chars() {
+            let is_separator = matches!(c, '_' | '-' | ' ' | '.');
+            if is_separator && compacted.chars().last().is_none_or(|last| matches!(last, '_' | '-' | ' ' | '.')) {
+                continue;
+            }
+            compacted.push(c);
+        }
+        let mut compacted = compacted.trim_end_matches(['_', '-', ' ', '.']).to_string();
+        for (index, value) in values.iter().enumerate() {
+            compacted = compacted.replace(placeholder(index), value);
+        }
+        
+        if compacted.is_empty() { origname } else { compacted }
+    }
+}
+
+/// Suffissi delle due sottocartelle di ogni coppia (--pair-suffixes), es. "_phone,_camera"
+/// abbina `viaggio_phone/` a `viaggio_camera/`.
+#[derive(Clone, Debug)]
+pub struct PairSuffixes {
+    pub first: String,
+    pub second: String,
+}
+
+impl FromStr for PairSuffixes {
+    type Err = String;
+    
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let Some((first, second)) = s.split_once(',') else {
+            return Err("servono due suffissi separati da virgola, es. _phone,_camera".to_string());
+        };
+        let (first, second) = (first.trim(), second.trim());
+        if first.is_empty() || second.is_empty() || first == second {
+            return Err("i due suffissi devono essere diversi e non vuoti".to_string());
+        }
+        Ok(PairSuffixes { first: first.to_string(), second: second.to_string() })
+    }
+}
+
+/// Due sottocartelle di una cartella madre da confrontare tra loro (--pairs-in). Il nome
+/// della coppia dà il nome alla sua cartella di output.
+#[derive(Clone, Debug)]
+pub struct FolderPair {
+    pub name: String,
+    pub folder1: PathBuf,
+    pub folder2: PathBuf,
+}
+
+/// Ricodifica dei file in output: formato e qualità JPEG (1-100).
+#[derive(Clone, Copy, Debug)]
+pub struct Conversion {
+    pub format: OutputFormat,
+    pub quality: u8,
+}
+
+/// Campi EXIF correggibili a mano prima dell'esportazione (Maiusc+E nel confronto).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExifFields {
+    // Formato EXIF: "AAAA:MM:GG HH:MM:SS"
+    pub date_time_original: String,
+    pub artist: String,
+    pub copyright: String,
+}
+
+impl ExifFields {
+    /// Valori presenti nel file; i campi mancanti restano vuoti.
+    pub fn read(path: &Path) -> ExifFields {
+        let [date_time_original, artist, copyright] = ImageAnalysis::read_ascii_tags(
+            path,
+            [exif::Tag::DateTimeOriginal, exif::Tag::Artist, exif::Tag::Copyright],
+        );
+        ExifFields {
+            date_time_original: date_time_original.unwrap_or_default(),
+            artist: artist.unwrap_or_default(),
+            copyright: copyright.unwrap_or_default(),
+        }
+    }
+    
+    pub fn date_is_valid(&self) -> bool {
+        let date = self.date_time_original.trim();
+        date.is_empty() || exif::DateTime::from_ascii(date.as_bytes()).is_ok()
+    }
+    
+    /// Tag exiftool e nuovo valore dei campi cambiati rispetto a `original`.
+    /// Un campo svuotato rimuove il tag.
+    pub fn changes(&self, original: &ExifFields) -> Vec<(&'static str, String)> {
+        [
+            ("DateTimeOriginal", &self.date_time_original, &original.date_time_original),
+            ("Artist", &self.artist, &original.artist),
+            ("Copyright", &self.copyright, &original.copyright),
+        ]
+        .into_iter()
+        .filter(|(_, value, original)| value.trim() != original.trim())
+        .map(|(tag, value, _)| (tag, value.trim().to_string()))
+        .collect()
+    }
+}
 
 #[derive(Clone)]
 pub struct FileManager {
     pub folder1: PathBuf,
     pub folder2: PathBuf,
     pub output_folder: PathBuf,
+    // Coppie create a mano dall'utente (nomi diversi), valide per la sessione
+    pub manual_pairs: Vec<(PathBuf, PathBuf)>,
+    pub match_strategy: MatchStrategy,
+    // Copia i file in output/AAAA/MM/ secondo la data di scatto EXIF
+    pub organize_by_date: bool,
+    pub metadata_mode: MetadataMode,
+    // Tiene solo le coppie con almeno un file modificato dopo questa data
+    pub modified_since: Option<SystemTime>,
+    // Nomi file ammessi (--manifest): gli altri vengono ignorati già durante la scansione
+    pub manifest: Option<HashSet<OsString>>,
+    // Modelli dei file da ignorare durante la scansione (--exclude)
+    pub exclude: Vec<GlobPattern>,
+    // Se presente i file scelti vengono ricodificati invece che copiati
+    pub conversion: Option<Conversion>,
+    // Confronto con un riferimento: ogni file della cartella 1 viene abbinato a questo file
+    pub reference: Option<PathBuf>,
+    // Rinomina i file in output secondo il modello invece di mantenere il nome originale
+    pub name_template: Option<NameTemplate>,
+    // Annota l'hash SHA256 di ogni file copiato così com'è, per verificare le copie a fine sessione
+    pub verify: bool,
+    // Copie da verificare: destinazione e hash del file originale. Condivise tra i cloni
+    copied_hashes: Arc<Mutex<BTreeMap<PathBuf, String>>>,
+    // Con l'immagine scelta copia anche il video e l'HEIC della Live Photo (--copy-live-video)
+    pub copy_live_companions: bool,
+    // File affiancati copiati con ogni immagine, da togliere se la scelta viene annullata
+    copied_companions: Arc<Mutex<BTreeMap<PathBuf, Vec<PathBuf>>>>,
 }
 
 impl FileManager {
     pub fn new(folder1: PathBuf, folder2: PathBuf) -> Result<Self> {
-        let output_folder = PathBuf::from("output");
-        
+        Self::with_output(folder1, folder2, PathBuf::from(OUTPUT_FOLDER))
+    }
+    
+    /// Come `new`, con i file scelti, il report e la sessione in `output_folder`.
+    pub fn with_output(folder1: PathBuf, folder2: PathBuf, output_folder: PathBuf) -> Result<Self> {
         if !folder1.exists() {
             anyhow::bail!("Folder 1 does not exist: {:?}", folder1);
         }
@@ -30,42 +305,511 @@ impl FileManager {
             folder1,
             folder2,
             output_folder,
+            manual_pairs: Vec::new(),
+            match_strategy: MatchStrategy::Name,
+            organize_by_date: false,
+            metadata_mode: MetadataMode::Merge,
+            modified_since: None,
+            manifest: None,
+            exclude: Vec::new(),
+            conversion: None,
+            reference: None,
+            name_template: None,
+            verify: false,
+            copied_hashes: Arc::new(Mutex::new(BTreeMap::new())),
+            copy_live_companions: false,
+            copied_companions: Arc::new(Mutex::new(BTreeMap::new())),
         })
     }
     
-    pub fn find_matching_files(&self) -> Result<Vec<(PathBuf, PathBuf)>> {
-        let mut folder1_files = HashMap::new();
-        let mut matching_pairs = Vec::new();
+    /// Cerca le coppie di file secondo `match_strategy`. Se `progress` è presente viene
+    /// aggiornato con (file elaborati, file trovati) durante la scansione.
+    pub fn find_matching_files(&self, progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Result<Vec<(PathBuf, PathBuf)>> {
+        if let Some(progress) = progress {
+            *progress.lock().unwrap() = (0, 0);
+        }
         
-        for entry in WalkDir::new(&self.folder1)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| !e.file_type().is_dir())
+        let mut matching_pairs = match &self.reference {
+            Some(reference) => Self::reference_pairs(&self.folder1, reference, self.manifest.as_ref(), &self.exclude, progress)?,
+            None => Self::match_folders(&self.folder1, &self.folder2, &self.match_strategy, self.manifest.as_ref(), &self.exclude, progress)?,
+        };
+        
+        // Con un riferimento conta solo la data dei candidati: il riferimento è lo stesso per tutti
+        if let Some(since) = self.modified_since {
+            matching_pairs.retain(|(file1, file2)| {
+                Self::modified_after(file1, since) || (self.reference.is_none() && Self::modified_after(file2, since))
+            });
+        }
+        
+        // Cartelle sovrapposte, collegamenti simbolici o fisici: un file non va confrontato con sé stesso
+        let before = matching_pairs.len();
+        matching_pairs.retain(|(file1, file2)| !Self::same_physical_file(file1, file2));
+        if matching_pairs.len() < before {
+            info!("{} coppie ignorate: i due percorsi indicano lo stesso file", before - matching_pairs.len());
+        }
+        
+        // Le coppie manuali seguono quelle trovate automaticamente
+        matching_pairs.extend(self.manual_pairs.iter().cloned());
+        
+        Ok(matching_pairs)
+    }
+    
+    // Stesso dispositivo e inode su Unix (vale anche per i collegamenti fisici), altrimenti
+    // stesso percorso reale
+    fn same_physical_file(file1: &Path, file2: &Path) -> bool {
+        #[cfg(unix)]
         {
-            if Self::is_image_file(entry.path()) {
-                let file_name = entry.file_name().to_string_lossy().to_string();
-                folder1_files.insert(file_name, entry.path().to_path_buf());
+            use std::os::unix::fs::MetadataExt;
+            if let (Ok(metadata1), Ok(metadata2)) = (fs::metadata(file1), fs::metadata(file2)) {
+                return metadata1.dev() == metadata2.dev() && metadata1.ino() == metadata2.ino();
+            }
+        }
+        matches!((fs::canonicalize(file1), fs::canonicalize(file2)), (Ok(real1), Ok(real2)) if real1 == real2)
+    }
+    
+    /// Coppie tra due cartelle secondo `strategy`, senza il filtro per data né le coppie manuali.
+    /// Con `manifest` vengono considerati solo i file il cui nome compare nell'elenco, e
+    /// quelli che corrispondono a uno dei modelli `exclude` vengono ignorati.
+    pub fn match_folders(folder1: &Path, folder2: &Path, strategy: &MatchStrategy, manifest: Option<&HashSet<OsString>>, exclude: &[GlobPattern], progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let files1 = Self::collect_listed_files(folder1, manifest, exclude, progress)?;
+        let files2 = Self::collect_listed_files(folder2, manifest, exclude, progress)?;
+        
+        Ok(match *strategy {
+            MatchStrategy::Name => Self::match_by_name(files1, files2, progress),
+            MatchStrategy::RelativePath => Self::match_by_relative_path(folder1, folder2, files1, files2, progress),
+            MatchStrategy::Similarity { max_distance } => {
+                let hashes1 = Self::read_perceptual_hashes(files1, progress);
+                let hashes2 = Self::read_perceptual_hashes(files2, progress);
+                Self::match_by_similarity(&hashes1, &hashes2, max_distance)
+            }
+            MatchStrategy::Timestamp { tolerance_secs } => {
+                Self::match_by_timestamp(files1, files2, tolerance_secs, progress)
             }
+            MatchStrategy::Content => Self::match_by_content(files1, files2, progress),
+        })
+    }
+    
+    /// Ogni immagine di `folder` (tranne il riferimento stesso) abbinata a `reference`.
+    pub fn reference_pairs(folder: &Path, reference: &Path, manifest: Option<&HashSet<OsString>>, exclude: &[GlobPattern], progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let reference_id = fs::canonicalize(reference).unwrap_or_else(|_| reference.to_path_buf());
+        let mut candidates = Self::collect_listed_files(folder, manifest, exclude, progress)?;
+        candidates.retain(|path| fs::canonicalize(path).unwrap_or_else(|_| path.clone()) != reference_id);
+        candidates.sort();
+        
+        Ok(candidates.into_iter()
+            .map(|candidate| {
+                Self::advance_progress(progress);
+                (candidate, reference.to_path_buf())
+            })
+            .collect())
+    }
+    
+    fn match_by_name(files1: Vec<PathBuf>, files2: Vec<PathBuf>, progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Vec<(PathBuf, PathBuf)> {
+        let mut folder1_files = HashMap::new();
+        let mut matching_pairs = Vec::new();
+        
+        for path in files1 {
+            let file_name = path.file_name().unwrap_or_default().to_os_string();
+            folder1_files.insert(file_name, path);
+            Self::advance_progress(progress);
         }
         
-        for entry in WalkDir::new(&self.folder2)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| !e.file_type().is_dir())
-        {
-            if Self::is_image_file(entry.path()) {
-                let file_name = entry.file_name().to_string_lossy().to_string();
-                if let Some(path1) = folder1_files.get(&file_name) {
-                    matching_pairs.push((path1.clone(), entry.path().to_path_buf()));
-                }
+        for path in files2 {
+            if let Some(path1) = path.file_name().and_then(|name| folder1_files.get(name)) {
+                matching_pairs.push((path1.clone(), path));
             }
+            Self::advance_progress(progress);
         }
         
         matching_pairs.sort_by(|a, b| {
             a.0.file_name().cmp(&b.0.file_name())
         });
         
-        Ok(matching_pairs)
+        matching_pairs
+    }
+    
+    fn match_by_relative_path(folder1: &Path, folder2: &Path, files1: Vec<PathBuf>, files2: Vec<PathBuf>, progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Vec<(PathBuf, PathBuf)> {
+        let mut folder1_files = HashMap::new();
+        let mut matching_pairs = Vec::new();
+        
+        for path in files1 {
+            if let Ok(relative) = path.strip_prefix(folder1) {
+                folder1_files.insert(relative.to_path_buf(), path.clone());
+            }
+            Self::advance_progress(progress);
+        }
+        
+        for path in files2 {
+            if let Some(path1) = path.strip_prefix(folder2).ok().and_then(|relative| folder1_files.get(relative)) {
+                matching_pairs.push((path1.clone(), path));
+            }
+            Self::advance_progress(progress);
+        }
+        
+        matching_pairs.sort();
+        matching_pairs
+    }
+    
+    /// Hash percettivo di ogni file leggibile; i file che non si decodificano vengono ignorati.
+    pub fn read_perceptual_hashes(files: Vec<PathBuf>, progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Vec<(u64, PathBuf)> {
+        files.into_iter()
+            .filter_map(|path| {
+                let hash = image_analyzer::perceptual_hash(&path);
+                Self::advance_progress(progress);
+                hash.map(|hash| (hash, path))
+            })
+            .collect()
+    }
+    
+    /// Abbina i file con l'hash percettivo più vicino entro `max_distance` bit diversi.
+    /// Le coppie più simili vengono scelte per prime e ogni file viene usato al massimo una volta.
+    pub fn match_by_similarity(hashes1: &[(u64, PathBuf)], hashes2: &[(u64, PathBuf)], max_distance: u32) -> Vec<(PathBuf, PathBuf)> {
+        let mut candidates = Vec::new();
+        for (index1, (hash1, _)) in hashes1.iter().enumerate() {
+            for (index2, (hash2, _)) in hashes2.iter().enumerate() {
+                let distance = (hash1 ^ hash2).count_ones();
+                if distance <= max_distance {
+                    candidates.push((distance, index1, index2));
+                }
+            }
+        }
+        candidates.sort_unstable();
+        
+        let mut used1 = vec![false; hashes1.len()];
+        let mut used2 = vec![false; hashes2.len()];
+        let mut matching_pairs = Vec::new();
+        
+        for (_, index1, index2) in candidates {
+            if used1[index1] || used2[index2] {
+                continue;
+            }
+            used1[index1] = true;
+            used2[index2] = true;
+            matching_pairs.push((hashes1[index1].1.clone(), hashes2[index2].1.clone()));
+        }
+        
+        matching_pairs.sort();
+        matching_pairs
+    }
+    
+    // Abbina ogni file della cartella 1 al file della cartella 2 con l'orario di scatto
+    // più vicino entro la tolleranza; ogni file viene usato al massimo una volta.
+    fn match_by_timestamp(files1: Vec<PathBuf>, files2: Vec<PathBuf>, tolerance_secs: f64, progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Vec<(PathBuf, PathBuf)> {
+        let read_timestamps = |files: Vec<PathBuf>| -> Vec<(f64, PathBuf)> {
+            let mut timestamps: Vec<(f64, PathBuf)> = files.into_iter()
+                .filter_map(|path| {
+                    let timestamp = ImageAnalysis::read_capture_timestamp(&path);
+                    Self::advance_progress(progress);
+                    timestamp.map(|t| (t, path))
+                })
+                .collect();
+            timestamps.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+            timestamps
+        };
+        
+        let timestamps1 = read_timestamps(files1);
+        let timestamps2 = read_timestamps(files2);
+        let mut used = vec![false; timestamps2.len()];
+        let mut matching_pairs = Vec::new();
+        
+        for (time1, path1) in timestamps1 {
+            // Primo candidato non troppo vecchio, poi si scorre finché si resta in tolleranza
+            let start = timestamps2.partition_point(|(time2, _)| *time2 < time1 - tolerance_secs);
+            let best = (start..timestamps2.len())
+                .take_while(|&i| timestamps2[i].0 <= time1 + tolerance_secs)
+                .filter(|&i| !used[i])
+                .min_by(|&a, &b| {
+                    (timestamps2[a].0 - time1).abs().total_cmp(&(timestamps2[b].0 - time1).abs())
+                });
+            
+            if let Some(index) = best {
+                used[index] = true;
+                matching_pairs.push((path1, timestamps2[index].1.clone()));
+            }
+        }
+        
+        matching_pairs
+    }
+    
+    // Abbina i file identici byte per byte. L'hash si calcola sui byte del file, senza decodificare
+    // l'immagine, e solo per i file la cui dimensione compare in entrambe le cartelle: file di
+    // dimensioni diverse non possono avere lo stesso contenuto
+    fn match_by_content(mut files1: Vec<PathBuf>, mut files2: Vec<PathBuf>, progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Vec<(PathBuf, PathBuf)> {
+        files1.sort();
+        files2.sort();
+        let with_sizes = |files: Vec<PathBuf>| -> Vec<(u64, PathBuf)> {
+            files.into_iter()
+                .filter_map(|path| archive::len(&path).ok().map(|length| (length, path)))
+                .collect()
+        };
+        let sized1 = with_sizes(files1);
+        let sized2 = with_sizes(files2);
+        let sizes1: HashSet<u64> = sized1.iter().map(|(size, _)| *size).collect();
+        let sizes2: HashSet<u64> = sized2.iter().map(|(size, _)| *size).collect();
+        
+        // Copie identiche nella cartella 1: ognuna viene abbinata al massimo una volta
+        let mut by_hash: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for (size, path) in sized1 {
+            if sizes2.contains(&size)
+                && let Ok(hash) = image_analyzer::retry_transient(|| ImageAnalysis::calculate_file_hash(&path))
+            {
+                by_hash.entry(hash).or_default().push(path);
+            }
+            Self::advance_progress(progress);
+        }
+        
+        let mut matching_pairs = Vec::new();
+        for (size, path) in sized2 {
+            if sizes1.contains(&size)
+                && let Ok(hash) = image_analyzer::retry_transient(|| ImageAnalysis::calculate_file_hash(&path))
+                && let Some(candidates) = by_hash.get_mut(&hash)
+                && !candidates.is_empty()
+            {
+                matching_pairs.push((candidates.remove(0), path));
+            }
+            Self::advance_progress(progress);
+        }
+        
+        matching_pairs.sort();
+        matching_pairs
+    }
+    
+    /// Raggruppa le immagini duplicate di una sola cartella: stesso contenuto (SHA256) oppure
+    /// hash percettivo entro `max_distance`. Restituisce solo i gruppi con almeno due file.
+    pub fn find_duplicate_groups(folder: &Path, max_distance: u32, exclude: &[GlobPattern], progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Result<Vec<Vec<PathBuf>>> {
+        if let Some(progress) = progress {
+            *progress.lock().unwrap() = (0, 0);
+        }
+        
+        let mut files = Self::collect_listed_files(folder, None, exclude, progress)?;
+        files.sort();
+        
+        // Lo SHA256 trova anche le copie esatte dei file che non si riescono a decodificare
+        let fingerprints: Vec<(Option<String>, Option<u64>)> = files.iter()
+            .map(|path| {
+                let file_hash = ImageAnalysis::calculate_file_hash(path).ok();
+                let perceptual_hash = image_analyzer::perceptual_hash(path);
+                Self::advance_progress(progress);
+                (file_hash, perceptual_hash)
+            })
+            .collect();
+        
+        // Union-find: i duplicati di un duplicato finiscono nello stesso gruppo
+        let mut parent: Vec<usize> = (0..files.len()).collect();
+        for i in 0..files.len() {
+            for j in (i + 1)..files.len() {
+                let (file_hash_i, perceptual_i) = &fingerprints[i];
+                let (file_hash_j, perceptual_j) = &fingerprints[j];
+                let same_content = file_hash_i.is_some() && file_hash_i == file_hash_j;
+                let similar = matches!((perceptual_i, perceptual_j), (Some(a), Some(b)) if (a ^ b).count_ones() <= max_distance);
+                
+                if same_content || similar {
+                    let root_i = Self::find_root(&mut parent, i);
+                    let root_j = Self::find_root(&mut parent, j);
+                    parent[root_j] = root_i;
+                }
+            }
+        }
+        
+        let mut groups: BTreeMap<usize, Vec<PathBuf>> = BTreeMap::new();
+        for (index, path) in files.into_iter().enumerate() {
+            groups.entry(Self::find_root(&mut parent, index)).or_default().push(path);
+        }
+        
+        Ok(groups.into_values().filter(|group| group.len() > 1).collect())
+    }
+    
+    fn find_root(parent: &mut [usize], mut index: usize) -> usize {
+        while parent[index] != index {
+            parent[index] = parent[parent[index]];
+            index = parent[index];
+        }
+        index
+    }
+    
+    /// Trasforma i gruppi di duplicati in coppie da confrontare: il primo file di ogni gruppo
+    /// contro ciascuno degli altri. Restituisce anche il gruppo di appartenenza di ogni coppia.
+    pub fn duplicate_pairs(groups: &[Vec<PathBuf>]) -> (Vec<(PathBuf, PathBuf)>, Vec<usize>) {
+        groups.iter()
+            .enumerate()
+            .flat_map(|(group, files)| {
+                files[1..].iter().map(move |other| ((files[0].clone(), other.clone()), group))
+            })
+            .unzip()
+    }
+    
+    /// Restituisce i file di ciascuna cartella che non fanno parte di nessuna coppia,
+    /// né trovata automaticamente né abbinata manualmente.
+    pub fn find_unmatched_files(&self) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+        let pairs = self.find_matching_files(None)?;
+        let paired: HashSet<&PathBuf> = pairs.iter()
+            .flat_map(|(a, b)| [a, b])
+            .collect();
+        
+        let mut unmatched1: Vec<PathBuf> = Self::collect_listed_files(&self.folder1, None, &self.exclude, None)?
+            .into_iter()
+            .filter(|p| !paired.contains(p))
+            .collect();
+        let mut unmatched2: Vec<PathBuf> = Self::collect_listed_files(&self.folder2, None, &self.exclude, None)?
+            .into_iter()
+            .filter(|p| !paired.contains(p))
+            .collect();
+        
+        unmatched1.sort();
+        unmatched2.sort();
+        
+        Ok((unmatched1, unmatched2))
+    }
+    
+    // Ogni immagine trovata incrementa il totale del progresso
+    pub fn collect_image_files(folder: &Path, progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Result<Vec<PathBuf>> {
+        Self::collect_listed_files(folder, None, &[], progress)
+    }
+    
+    /// File immagine di `folder` il cui nome compare in `manifest` (se presente) e che non
+    /// corrispondono a nessuno dei modelli `exclude`. `folder` può essere un archivio ZIP.
+    pub fn collect_listed_files(folder: &Path, manifest: Option<&HashSet<OsString>>, exclude: &[GlobPattern], progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Result<Vec<PathBuf>> {
+        // Il totale del progresso cresce durante la scansione, non solo alla fine
+        let mut files = Vec::new();
+        let mut keep = |path: PathBuf| {
+            let listed = manifest.is_none_or(|names| path.file_name().is_some_and(|name| names.contains(name)));
+            if listed && !GlobPattern::any_matches(exclude, folder, &path) && Self::is_image_file(&path) {
+                if let Some(progress) = progress {
+                    progress.lock().unwrap().1 += 1;
+                }
+                files.push(path);
+            }
+        };
+        if archive::is_archive(folder) {
+            archive::list_files(folder)
+                .with_context(|| format!("Failed to open archive {:?}", folder))?
+                .into_iter()
+                .for_each(keep);
+        } else {
+            scan_index::list_files(folder, &mut keep);
+        }
+        Ok(files)
+    }
+    
+    /// Legge un elenco di nomi file, uno per riga. Righe vuote e commenti (#) vengono ignorati;
+    /// se una riga contiene un percorso conta solo il nome finale.
+    pub fn read_manifest(path: &Path) -> Result<HashSet<OsString>> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest {:?}", path))?;
+        
+        let names: HashSet<OsString> = content.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| Path::new(line).file_name().map(OsStr::to_os_string))
+            .collect();
+        
+        if names.is_empty() {
+            anyhow::bail!("Manifest {:?} contains no file names", path);
+        }
+        
+        Ok(names)
+    }
+    
+    /// Coppie di sottocartelle dirette di `parent` che differiscono solo per il suffisso, in
+    /// ordine di nome. Il nome della coppia è la parte comune, senza separatori finali; le coppie
+    /// che si distinguono solo per il separatore (es. `trip_` e `trip-`) lo mantengono.
+    pub fn discover_folder_pairs(parent: &Path, suffixes: &PairSuffixes) -> Result<Vec<FolderPair>> {
+        let mut subdirs: Vec<String> = fs::read_dir(parent)
+            .with_context(|| format!("Failed to read folder {:?}", parent))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        subdirs.sort();
+        
+        let mut stems = Vec::new();
+        for name in &subdirs {
+            let Some(stem) = name.strip_suffix(&suffixes.first).filter(|stem| !stem.is_empty()) else {
+                continue;
+            };
+            let sibling = format!("{}{}", stem, suffixes.second);
+            if !subdirs.contains(&sibling) {
+                warn!("Nessuna cartella {:?} da abbinare a {:?}", sibling, name);
+                continue;
+            }
+            stems.push((name, stem, sibling));
+        }
+        
+        // Il nome è la sottocartella di output della coppia: deve restare unico
+        let trim = |stem: &str| stem.trim_end_matches(['_', '-', ' ', '.']).to_string();
+        let pairs = stems.iter()
+            .map(|(name, stem, sibling)| {
+                let trimmed = trim(stem);
+                let shared = stems.iter().filter(|(_, other, _)| trim(other) == trimmed).count() > 1;
+                FolderPair {
+                    name: if trimmed.is_empty() || shared { stem.to_string() } else { trimmed },
+                    folder1: parent.join(name),
+                    folder2: parent.join(sibling),
+                }
+            })
+            .collect();
+        Ok(pairs)
+    }
+    
+    /// Coppie di sottocartelle di `parent` elencate in `map`, una per riga nella forma
+    /// "cartella1 => cartella2". Righe vuote e commenti (#) vengono ignorati.
+    pub fn read_folder_map(parent: &Path, map: &Path) -> Result<Vec<FolderPair>> {
+        let content = fs::read_to_string(map)
+            .with_context(|| format!("Failed to read folder map {:?}", map))?;
+        
+        let mut pairs = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((name1, name2)) = line.split_once("=>").map(|(a, b)| (a.trim(), b.trim())) else {
+                anyhow::bail!("Line {} of {:?} is not in the form \"folder1 => folder2\"", number + 1, map);
+            };
+            // Solo sottocartelle di `parent`: il nome della coppia diventa una cartella di output
+            for name in [name1, name2] {
+                if !Path::new(name).components().all(|component| matches!(component, Component::Normal(_))) {
+                    anyhow::bail!("Line {} of {:?}: {:?} is not a subfolder of {:?}", number + 1, map, name, parent);
+                }
+            }
+            let (folder1, folder2) = (parent.join(name1), parent.join(name2));
+            for folder in [&folder1, &folder2] {
+                if !folder.is_dir() {
+                    anyhow::bail!("Line {} of {:?}: folder {:?} does not exist", number + 1, map, folder);
+                }
+            }
+            pairs.push(FolderPair { name: format!("{}+{}", name1, name2), folder1, folder2 });
+        }
+        
+        if pairs.is_empty() {
+            anyhow::bail!("Folder map {:?} contains no folder pairs", map);
+        }
+        Ok(pairs)
+    }
+    
+    /// Stesso peso in byte e stesse dimensioni in pixel (quasi sempre lo stesso file). Le
+    /// dimensioni vengono dall'intestazione: niente decodifica né hash, quindi il controllo costa
+    /// poco anche su migliaia di coppie. Un file di cui non si leggono le dimensioni resta nel
+    /// confronto.
+    pub fn same_size(file1: &Path, file2: &Path) -> bool {
+        let length = |path: &Path| archive::len(path).ok();
+        let dimensions = |path: &Path| archive::image_reader(path).ok()?.into_dimensions().ok();
+        length(file1).is_some_and(|length1| Some(length1) == length(file2))
+            && dimensions(file1).is_some_and(|dimensions1| Some(dimensions1) == dimensions(file2))
+    }
+    
+    // Le voci di un archivio hanno la data dell'archivio
+    fn modified_after(path: &Path, since: SystemTime) -> bool {
+        fs::metadata(archive::on_disk(path))
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| modified > since)
+            .unwrap_or(false)
+    }
+    
+    fn advance_progress(progress: Option<&Arc<Mutex<(usize, usize)>>>) {
+        if let Some(progress) = progress {
+            progress.lock().unwrap().0 += 1;
+        }
     }
     
     fn is_image_file(path: &Path) -> bool {
@@ -75,7 +819,7 @@ impl FileManager {
                 matches!(
                     ext_lower.as_str(),
                     "jpg" | "jpeg" | "png" | "gif" | "bmp" | 
-                    "tiff" | "tif" | "webp" | "raw" | "cr2" | 
+                    "tiff" | "tif" | "webp" | "psd" | "raw" | "cr2" | 
                     "nef" | "arw" | "dng"
                 )
             }
@@ -83,44 +827,200 @@ impl FileManager {
         }
     }
     
+    /// Copia il file in output e, con `copy_live_companions`, i file della stessa Live Photo,
+    /// rinominati come la copia (`IMG_0001_1.jpg` + `IMG_0001_1.mov`).
     pub fn copy_to_output(&self, source_path: &Path) -> Result<PathBuf> {
+        let dest_path = self.copy_file(source_path)?;
+        if self.copy_live_companions {
+            self.copy_companions(source_path, &dest_path);
+        }
+        Ok(dest_path)
+    }
+    
+    // Un file affiancato non copiato non annulla la copia dell'immagine
+    fn copy_companions(&self, source_path: &Path, dest_path: &Path) {
+        let mut copied = Vec::new();
+        for companion in live_photo::companions(source_path) {
+            let Some(extension) = companion.extension() else {
+                continue;
+            };
+            let target = dest_path.with_extension(extension);
+            if target.exists() {
+                warn!("File affiancato non copiato, {:?} esiste già", target);
+                continue;
+            }
+            match fs::copy(&companion, &target) {
+                Ok(_) => {
+                    debug!("File affiancato copiato: {:?} -> {:?}", companion, target);
+                    copied.push(target);
+                }
+                Err(e) => warn!("Impossibile copiare il file affiancato {:?}: {}", companion, e),
+            }
+        }
+        if !copied.is_empty() {
+            self.copied_companions.lock().unwrap().insert(dest_path.to_path_buf(), copied);
+        }
+    }
+    
+    fn copy_file(&self, source_path: &Path) -> Result<PathBuf> {
         let file_name = source_path
             .file_name()
             .with_context(|| "Failed to get file name")?;
         
-        let dest_path = self.output_folder.join(file_name);
-        
-        if dest_path.exists() {
-            let stem = source_path
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("file");
-            let ext = source_path
-                .extension()
-                .and_then(|e| e.to_str())
-                .unwrap_or("");
-            
-            let mut counter = 1;
-            let mut new_dest_path = dest_path.clone();
-            while new_dest_path.exists() {
-                let new_name = if ext.is_empty() {
-                    format!("{}_{}", stem, counter)
+        // Con --convert l'estensione diventa quella del formato di destinazione
+        let conversion = self.conversion.filter(|conversion| !conversion.format.matches(source_path));
+        // Nome e estensione restano OsStr per non alterare i nomi non UTF-8 (salvo con un modello)
+        let rendered = self.name_template.as_ref().map(|template| OsString::from(template.render(source_path)));
+        let stem = rendered.as_deref()
+            .or_else(|| source_path.file_stem())
+            .unwrap_or(OsStr::new("file"));
+        let ext = match conversion {
+            Some(conversion) => Some(OsStr::new(conversion.format.extension())),
+            None => source_path.extension(),
+        };
+        let with_suffix = |suffix: String| {
+            let mut name = stem.to_os_string();
+            name.push(suffix);
+            if let Some(ext) = ext {
+                name.push(".");
+                name.push(ext);
+            }
+            name
+        };
+        
+        let dest_dir = self.output_dir_for(source_path)?;
+        let mut dest_path = if conversion.is_some() || rendered.is_some() {
+            dest_dir.join(with_suffix(String::new()))
+        } else {
+            dest_dir.join(file_name)
+        };
+        
+        let mut counter = 1;
+        while dest_path.exists() {
+            dest_path = dest_dir.join(with_suffix(format!("_{}", counter)));
+            counter += 1;
+        }
+        
+        if let Some(conversion) = conversion {
+            match Self::convert_file(source_path, &dest_path, conversion) {
+                Ok(()) => {
+                    // L'encoder scrive solo i pixel (e il profilo ICC): i tag vanno riportati
+                    Self::copy_tags(source_path, &dest_path, MetadataMode::Overwrite, None);
+                    return Ok(dest_path);
+                }
+                Err(e) => {
+                    // Es. RAW non decodificabili: meglio l'originale che nessuna copia
+                    warn!("Conversione non riuscita, copio il file originale: {:#}", e);
+                    let _ = fs::remove_file(&dest_path);
+                    let unconverted = FileManager { conversion: None, ..self.clone() };
+                    return unconverted.copy_file(source_path);
+                }
+            }
+        }
+        
+        archive::copy(source_path, &dest_path)
+            .with_context(|| format!("Failed to copy file to {:?}", dest_path))?;
+        
+        if self.verify {
+            match image_analyzer::retry_transient(|| ImageAnalysis::calculate_file_hash(source_path)) {
+                Ok(hash) => {
+                    self.copied_hashes.lock().unwrap().insert(dest_path.clone(), hash);
+                }
+                Err(e) => warn!("Impossibile calcolare l'hash di {:?}, la copia non verrà verificata: {}", source_path, e),
+            }
+        }
+        
+        Ok(dest_path)
+    }
+    
+    /// Esclude dalla verifica un file in output modificato dopo la copia (rotazione, metadati)
+    /// o cancellato: non deve più coincidere con l'originale.
+    pub fn forget_copy(&self, dest_path: &Path) {
+        self.copied_hashes.lock().unwrap().remove(dest_path);
+    }
+    
+    /// Ricalcola l'hash di ogni file copiato con `verify` e lo confronta con quello dell'originale.
+    /// Restituisce il numero di copie controllate e quelle non valide, con il motivo.
+    pub fn verify_copies(&self) -> (usize, Vec<(PathBuf, String)>) {
+        let copies = self.copied_hashes.lock().unwrap().clone();
+        let failures = copies.iter()
+            .filter_map(|(dest_path, expected)| {
+                match image_analyzer::retry_transient(|| ImageAnalysis::calculate_file_hash(dest_path)) {
+                    Ok(hash) if hash == *expected => None,
+                    Ok(_) => Some((dest_path.clone(), "contenuto diverso dall'originale".to_string())),
+                    Err(e) => Some((dest_path.clone(), format!("non leggibile: {}", e))),
+                }
+            })
+            .collect();
+        (copies.len(), failures)
+    }
+    
+    fn convert_file(source_path: &Path, dest_path: &Path, conversion: Conversion) -> Result<()> {
+        let (img, icc_profile) = image_analyzer::open_with_icc_profile(source_path)
+            .with_context(|| format!("Failed to decode {:?}", source_path))?;
+        let file = fs::File::create(dest_path)
+            .with_context(|| format!("Failed to create {:?}", dest_path))?;
+        let writer = std::io::BufWriter::new(file);
+        
+        let has_alpha = img.color().has_alpha();
+        let result = match conversion.format {
+            OutputFormat::Jpeg => {
+                let mut encoder = JpegEncoder::new_with_quality(writer, conversion.quality);
+                if let Some(icc) = icc_profile {
+                    let _ = encoder.set_icc_profile(icc);
+                }
+                // JPEG non ha trasparenza
+                DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(encoder)
+            }
+            OutputFormat::Png => {
+                let mut encoder = PngEncoder::new(writer);
+                if let Some(icc) = icc_profile {
+                    let _ = encoder.set_icc_profile(icc);
+                }
+                // I 16 bit per canale vengono mantenuti
+                let sixteen_bit = img.color().bytes_per_pixel() / img.color().channel_count() > 1;
+                let img = match (sixteen_bit, has_alpha) {
+                    (true, true) => DynamicImage::ImageRgba16(img.to_rgba16()),
+                    (true, false) => DynamicImage::ImageRgb16(img.to_rgb16()),
+                    (false, true) => DynamicImage::ImageRgba8(img.to_rgba8()),
+                    (false, false) => DynamicImage::ImageRgb8(img.to_rgb8()),
+                };
+                img.write_with_encoder(encoder)
+            }
+            OutputFormat::Webp => {
+                let mut encoder = WebPEncoder::new_lossless(writer);
+                if let Some(icc) = icc_profile {
+                    let _ = encoder.set_icc_profile(icc);
+                }
+                let img = if has_alpha {
+                    DynamicImage::ImageRgba8(img.to_rgba8())
                 } else {
-                    format!("{}_{}.{}", stem, counter, ext)
+                    DynamicImage::ImageRgb8(img.to_rgb8())
                 };
-                new_dest_path = self.output_folder.join(new_name);
-                counter += 1;
+                img.write_with_encoder(encoder)
             }
-            
-            fs::copy(source_path, &new_dest_path)
-                .with_context(|| format!("Failed to copy file to {:?}", new_dest_path))?;
-            
-            Ok(new_dest_path)
-        } else {
-            fs::copy(source_path, &dest_path)
-                .with_context(|| format!("Failed to copy file to {:?}", dest_path))?;
-            
-            Ok(dest_path)
+        };
+        
+        result.with_context(|| format!("Failed to encode {:?}", dest_path))
+    }
+    
+    // Cartella di destinazione: output/AAAA/MM/ se richiesto e la data di scatto è disponibile,
+    // altrimenti la cartella di output piatta
+    fn output_dir_for(&self, source_path: &Path) -> Result<PathBuf> {
+        if !self.organize_by_date {
+            return Ok(self.output_folder.clone());
+        }
+        
+        match ImageAnalysis::read_capture_datetime(source_path) {
+            Some(datetime) if datetime.year > 0 && (1..=12).contains(&datetime.month) => {
+                let dir = self.output_folder
+                    .join(format!("{:04}", datetime.year))
+                    .join(format!("{:02}", datetime.month));
+                fs::create_dir_all(&dir)
+                    .with_context(|| format!("Failed to create output directory {:?}", dir))?;
+                Ok(dir)
+            }
+            _ => Ok(self.output_folder.clone()),
         }
     }
     
@@ -140,85 +1040,206 @@ impl FileManager {
         }
     }
     
-    pub fn copy_to_output_with_metadata(&self, source_path: &Path, metadata_source: Option<&Path>) -> Result<PathBuf> {
+    /// Copia il file in output applicando, se richiesto, i metadati di un altro file
+    /// e una rotazione di `quarter_turns` quarti di giro in senso orario.
+    pub fn copy_to_output_with_metadata(&self, source_path: &Path, metadata_source: Option<&Path>, quarter_turns: u8) -> Result<PathBuf> {
         // First, copy the file normally
         let dest_path = self.copy_to_output(source_path)?;
         
-        // If there's a metadata source, apply metadata to the OUTPUT file
-        if let Some(meta_source) = metadata_source {
-            println!("Applicazione metadati da {:?} al file di output {:?}", meta_source, dest_path);
-            
-            // Use exiftool to copy metadata from source to the OUTPUT file
-            let output = std::process::Command::new("exiftool")
-                .args(&[
-                    "-overwrite_original",
-                    "-TagsFromFile",
-                    meta_source.to_str().unwrap(),
-                    "-all:all",
-                    dest_path.to_str().unwrap()
-                ])
-                .output();
-            
-            match output {
-                Ok(result) => {
-                    if result.status.success() {
-                        println!("Metadati trasferiti con successo al file di output!");
-                    } else {
-                        eprintln!("Errore exiftool: {}", String::from_utf8_lossy(&result.stderr));
-                    }
-                }
+        // La rotazione avviene solo qui, alla conferma. Nei JPEG si scrive nel tag di orientamento;
+        // negli altri formati ricodifica i pixel e perde i metadati, che vengono poi ripristinati
+        // dal file originale salvo sovrascrittura da un'altra sorgente
+        let rotated = !quarter_turns.is_multiple_of(4);
+        if rotated || metadata_source.is_some() {
+            self.forget_copy(&dest_path);
+        }
+        let orientation = if rotated {
+            match self.rotate_file(&dest_path, quarter_turns) {
+                Ok(orientation) => Some(orientation),
                 Err(e) => {
-                    eprintln!("Impossibile eseguire exiftool: {}.", e);
-                    eprintln!("I metadati non sono stati trasferiti, ma il file è stato copiato.");
-                    eprintln!("Per utilizzare questa funzione, installa exiftool:");
-                    eprintln!("  Ubuntu/Debian: sudo apt install libimage-exiftool-perl");
-                    eprintln!("  Fedora: sudo dnf install perl-Image-ExifTool");
-                    eprintln!("  macOS: brew install exiftool");
+                    // La coppia resta da decidere: una copia lasciata qui verrebbe duplicata al nuovo tentativo
+                    if let Err(delete_error) = self.delete_from_output(&dest_path) {
+                        warn!("Impossibile eliminare la copia non ruotata {:?}: {}", dest_path, delete_error);
+                    }
+                    return Err(e);
                 }
             }
+        } else {
+            None
+        };
+        let reencoded = orientation == Some(1);
+        
+        // In modalità merge i metadati del file scelto vanno ripristinati prima di
+        // aggiungere quelli mancanti dalla sorgente
+        let restore_own = reencoded && (metadata_source.is_none() || self.metadata_mode == MetadataMode::Merge);
+        if restore_own {
+            info!("Ripristino metadati originali nel file ruotato {:?}", dest_path);
+            Self::copy_tags(source_path, &dest_path, MetadataMode::Overwrite, orientation);
+        }
+        
+        // If there's a metadata source, apply metadata to the OUTPUT file
+        if let Some(meta_source) = metadata_source {
+            info!("Applicazione metadati da {:?} al file di output {:?}", meta_source, dest_path);
+            Self::copy_tags(meta_source, &dest_path, self.metadata_mode, orientation.filter(|_| !restore_own));
         }
         
         Ok(dest_path)
     }
     
+    // Copia i tag con exiftool; gli errori vengono segnalati ma il file copiato resta valido
+    // `orientation` sostituisce quello copiato: la rotazione scelta nel confronto prevale
+    fn copy_tags(from: &Path, to: &Path, mode: MetadataMode, orientation: Option<u16>) {
+        // exiftool non legge dentro gli archivi
+        if archive::is_inside(from) {
+            warn!("Metadati non trasferiti: {:?} è dentro un archivio", from);
+            return;
+        }
+        let mut args: Vec<&OsStr> = vec![
+            OsStr::new("-overwrite_original"),
+            OsStr::new("-TagsFromFile"),
+            from.as_os_str(),
+            OsStr::new("-all:all"),
+        ];
+        // Scrive solo i tag (e i gruppi) che non esistono già nella destinazione
+        if mode == MetadataMode::Merge {
+            args.extend([OsStr::new("-wm"), OsStr::new("cg")]);
+        }
+        // Con i pixel già ruotati l'orientamento è 1, per non ruotare due volte
+        let orientation = orientation.map(|orientation| OsString::from(format!("-Orientation#={}", orientation)));
+        if let Some(orientation) = &orientation {
+            args.push(orientation);
+        }
+        args.push(to.as_os_str());
+        
+        // Use exiftool to copy metadata from source to the OUTPUT file
+        let output = std::process::Command::new("exiftool")
+            .args(&args)
+            .output();
+        
+        match output {
+            Ok(result) => {
+                if result.status.success() {
+                    info!("Metadati trasferiti con successo al file di output");
+                } else {
+                    error!("Errore exiftool: {}", String::from_utf8_lossy(&result.stderr));
+                }
+            }
+            Err(e) => {
+                error!(
+                    "Impossibile eseguire exiftool: {}. I metadati non sono stati trasferiti, ma il file è stato copiato.\n\
+                    Per utilizzare questa funzione, installa exiftool:\n  \
+                    Ubuntu/Debian: sudo apt install libimage-exiftool-perl\n  \
+                    Fedora: sudo dnf install perl-Image-ExifTool\n  \
+                    macOS: brew install exiftool",
+                    e
+                );
+            }
+        }
+    }
+    
+    /// Scrive con exiftool i tag indicati (vedi `ExifFields::changes`) in un file già in output.
+    pub fn write_exif_fields(path: &Path, changes: &[(&str, String)]) -> Result<()> {
+        let mut args: Vec<OsString> = vec![OsString::from("-overwrite_original")];
+        args.extend(changes.iter().map(|(tag, value)| OsString::from(format!("-{}={}", tag, value))));
+        args.push(path.as_os_str().to_os_string());
+        
+        let output = std::process::Command::new("exiftool")
+            .args(&args)
+            .output()
+            .context("Failed to run exiftool")?;
+        if !output.status.success() {
+            anyhow::bail!("exiftool failed on {:?}: {}", path, String::from_utf8_lossy(&output.stderr).trim());
+        }
+        
+        info!("Metadati corretti nel file di output {:?}: {:?}", path, changes);
+        Ok(())
+    }
+    
+    // Restituisce l'orientamento EXIF che il file ruotato deve avere: quello che corrisponde
+    // alla rotazione se è bastato scrivere il tag (JPEG), 1 se i pixel sono stati ricodificati
+    fn rotate_file(&self, path: &Path, quarter_turns: u8) -> Result<u16> {
+        // Il confronto non applica l'orientamento EXIF: la rotazione parte dai pixel così come sono
+        let orientation = match quarter_turns % 4 {
+            1 => 6,
+            2 => 3,
+            _ => 8,
+        };
+        let format = image::ImageReader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .with_context(|| format!("Failed to open image {:?} for rotation", path))?
+            .format();
+        let jpeg = format == Some(image::ImageFormat::Jpeg);
+        if jpeg {
+            match Self::write_exif_fields(path, &[("Orientation#", orientation.to_string())]) {
+                Ok(()) => return Ok(orientation),
+                Err(e) => warn!("Orientamento non scrivibile, ricodifico il JPEG ruotato: {:#}", e),
+            }
+        }
+        
+        let img = image::open(path)
+            .with_context(|| format!("Failed to open image {:?} for rotation", path))?;
+        let rotated = image_analyzer::rotate_clockwise(&img, quarter_turns);
+        let result = if jpeg {
+            let quality = self.conversion
+                .filter(|conversion| conversion.format == OutputFormat::Jpeg)
+                .map_or(ROTATION_JPEG_QUALITY, |conversion| conversion.quality);
+            let file = fs::File::create(path)
+                .with_context(|| format!("Failed to create {:?}", path))?;
+            let encoder = JpegEncoder::new_with_quality(std::io::BufWriter::new(file), quality);
+            DynamicImage::ImageRgb8(rotated.to_rgb8()).write_with_encoder(encoder)
+        } else {
+            rotated.save(path)
+        };
+        result.with_context(|| format!("Failed to save rotated image {:?}", path))?;
+        Ok(1)
+    }
+    
     pub fn delete_from_output(&self, file_path: &Path) -> Result<()> {
-        println!("DEBUG: Tentativo di cancellazione file: {:?}", file_path);
+        debug!("Tentativo di cancellazione file: {:?}", file_path);
+        self.forget_copy(file_path);
+        
+        let companions = self.copied_companions.lock().unwrap().remove(file_path).unwrap_or_default();
+        for companion in companions {
+            if let Err(e) = fs::remove_file(&companion) {
+                warn!("Impossibile eliminare il file affiancato {:?}: {}", companion, e);
+            }
+        }
         
         // Try multiple times with sync between attempts
         for attempt in 1..=3 {
-            println!("DEBUG: Tentativo {}/3", attempt);
+            debug!("Tentativo {}/3", attempt);
             
             // Force sync before checking
             if let Err(e) = std::process::Command::new("sync").output() {
-                println!("DEBUG: Impossibile eseguire sync: {}", e);
+                debug!("Impossibile eseguire sync: {}", e);
             }
             
-            println!("DEBUG: File esiste? {}", file_path.exists());
+            debug!("File esiste? {}", file_path.exists());
             
             if file_path.exists() {
                 match fs::remove_file(file_path) {
                     Ok(_) => {
-                        println!("DEBUG: File eliminato dall'output: {:?}", file_path);
+                        debug!("File eliminato dall'output: {:?}", file_path);
                         
                         // Force sync after deletion
                         if let Err(e) = std::process::Command::new("sync").output() {
-                            println!("DEBUG: Impossibile eseguire sync post-cancellazione: {}", e);
+                            debug!("Impossibile eseguire sync post-cancellazione: {}", e);
                         }
                         
                         // Verify deletion
                         if file_path.exists() {
-                            println!("WARNING: File still exists after deletion attempt {}!", attempt);
+                            warn!("File still exists after deletion attempt {}!", attempt);
                             if attempt < 3 {
                                 std::thread::sleep(std::time::Duration::from_millis(100));
                                 continue;
                             }
                         } else {
-                            println!("DEBUG: Deletion confirmed - file no longer exists");
+                            debug!("Deletion confirmed - file no longer exists");
                             return Ok(());
                         }
                     }
                     Err(e) => {
-                        println!("DEBUG: Errore durante cancellazione tentativo {}: {}", attempt, e);
+                        debug!("Errore durante cancellazione tentativo {}: {}", attempt, e);
                         if attempt < 3 {
                             std::thread::sleep(std::time::Duration::from_millis(100));
                             continue;
@@ -228,11 +1249,70 @@ impl FileManager {
                     }
                 }
             } else {
-                println!("DEBUG: File non trovato al tentativo {}, impossibile cancellare: {:?}", attempt, file_path);
+                debug!("File non trovato al tentativo {}, impossibile cancellare: {:?}", attempt, file_path);
                 return Ok(());
             }
         }
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Percorso inesistente: nessun dato EXIF, quindi {date}, {time} e {camera} restano vuoti
+    fn render(template: &str, file_name: &str) -> String {
+        let template: NameTemplate = template.parse().unwrap();
+        template.render(&std::env::temp_dir().join("photoscope-missing").join(file_name))
+    }
+    
+    #[test]
+    fn empty_placeholders_leave_no_separators() {
+        assert_eq!(render("{date}_{camera}_{origname}", "IMG_0001.jpg"), "IMG_0001");
+        assert_eq!(render("{origname}-{date}", "IMG_0001.jpg"), "IMG_0001");
+    }
+    
+    #[test]
+    fn original_name_keeps_its_separators() {
+        assert_eq!(render("{date}_{origname}", "_MG_1234.jpg"), "_MG_1234");
+        assert_eq!(render("{camera} {origname}", "a - b.jpg"), "a - b");
+        assert_eq!(render("copia_{origname}", "_MG_1234.jpg"), "copia__MG_1234");
+    }
+    
+    // Cartella temporanea con le sottocartelle indicate, diversa per ogni test
+    fn folder_with(test: &str, subdirs: &[&str]) -> PathBuf {
+        let parent = std::env::temp_dir().join(format!("photoscope-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&parent);
+        for subdir in subdirs {
+            fs::create_dir_all(parent.join(subdir)).unwrap();
+        }
+        parent
+    }
+    
+    #[test]
+    fn folder_pairs_that_differ_only_by_separator_keep_it() {
+        let parent = folder_with("pairs", &["trip_raw", "trip_jpg", "trip-raw", "trip-jpg", "home raw", "home jpg"]);
+        let suffixes: PairSuffixes = "raw,jpg".parse().unwrap();
+        let names: Vec<String> = FileManager::discover_folder_pairs(&parent, &suffixes).unwrap()
+            .into_iter()
+            .map(|pair| pair.name)
+            .collect();
+        let _ = fs::remove_dir_all(&parent);
+        assert_eq!(names, ["home", "trip-", "trip_"]);
+    }
+    
+    #[test]
+    fn folder_map_rejects_folders_outside_parent() {
+        let parent = folder_with("map", &["a", "b"]);
+        let map = parent.join("map.txt");
+        fs::write(&map, "a => ../b\n").unwrap();
+        let result = FileManager::read_folder_map(&parent, &map);
+        fs::write(&map, "a => b\n").unwrap();
+        let valid = FileManager::read_folder_map(&parent, &map);
+        let _ = fs::remove_dir_all(&parent);
+        assert!(result.is_err());
+        assert_eq!(valid.unwrap().len(), 1);
+    }
+}
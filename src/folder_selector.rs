@@ -1,54 +1,99 @@
+use crate::file_manager::{FileManager, MatchStrategy, MAX_SIMILARITY_DISTANCE};
+use crate::glob::GlobPattern;
+use crate::theme::{Theme, ThemeKind};
 use anyhow::Result;
 use eframe::egui;
-use egui::{Color32, Frame, Margin, RichText, CornerRadius, Stroke, Vec2, Visuals, FontId};
+use egui::{Color32, Frame, Margin, RichText, CornerRadius, Stroke, Vec2, FontId};
 use egui_phosphor::regular;
 use rfd::FileDialog;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
-// Colori del tema (consistenti con gui_v2.rs)
-const BG_COLOR: Color32 = Color32::from_rgb(24, 26, 31);
-const CARD_BG: Color32 = Color32::from_rgb(32, 34, 41);
-const CARD_HOVER: Color32 = Color32::from_rgb(38, 40, 48);
-const ACCENT_BLUE: Color32 = Color32::from_rgb(59, 130, 246);
-const ACCENT_GREEN: Color32 = Color32::from_rgb(34, 197, 94);
-const DANGER_RED: Color32 = Color32::from_rgb(239, 68, 68);
-const TEXT_PRIMARY: Color32 = Color32::from_rgb(229, 231, 235);
-const TEXT_SECONDARY: Color32 = Color32::from_rgb(148, 163, 184);
-const WARNING_YELLOW: Color32 = Color32::from_rgb(251, 146, 60);
+
+// Valori iniziali dei parametri quando si passa a una strategia che li richiede
+const DEFAULT_SIMILARITY_THRESHOLD: u32 = 10;
+const DEFAULT_TIME_TOLERANCE: f64 = 1.0;
+
+// Cartelle e strategia per cui è stata calcolata una stima
+type EstimateKey = (PathBuf, PathBuf, MatchStrategy);
+
+// Hash percettivi delle due cartelle, calcolati una volta sola: cambiare la soglia
+// richiede solo di ripetere l'abbinamento
+struct SimilarityCache {
+    folders: (PathBuf, PathBuf),
+    hashes1: Vec<(u64, PathBuf)>,
+    hashes2: Vec<(u64, PathBuf)>,
+}
 
 pub struct FolderSelectorApp {
     folder1: Option<PathBuf>,
     folder2: Option<PathBuf>,
     folders_selected: bool,
+    
+    // Impostazioni di abbinamento; soglia e tolleranza restano memorizzate cambiando strategia
+    match_strategy: MatchStrategy,
+    similarity_threshold: u32,
+    time_tolerance: f64,
+    // File esclusi da --exclude, che la stima non deve contare
+    pub exclude: Vec<GlobPattern>,
+    
+    // Stima delle coppie calcolata in background
+    estimate: Arc<Mutex<Option<(EstimateKey, usize)>>>,
+    estimating: Arc<Mutex<bool>>,
+    similarity_cache: Arc<Mutex<Option<SimilarityCache>>>,
+    
+    // Tema condiviso con la finestra di confronto
+    theme: Theme,
 }
 
 impl FolderSelectorApp {
-    pub fn new() -> Self {
+    pub fn new(match_strategy: MatchStrategy) -> Self {
+        let similarity_threshold = match match_strategy {
+            MatchStrategy::Similarity { max_distance } => max_distance,
+            _ => DEFAULT_SIMILARITY_THRESHOLD,
+        };
+        let time_tolerance = match match_strategy {
+            MatchStrategy::Timestamp { tolerance_secs } => tolerance_secs,
+            _ => DEFAULT_TIME_TOLERANCE,
+        };
+        
         FolderSelectorApp {
             folder1: None,
             folder2: None,
             folders_selected: false,
+            match_strategy,
+            similarity_threshold,
+            time_tolerance,
+            exclude: Vec::new(),
+            estimate: Arc::new(Mutex::new(None)),
+            estimating: Arc::new(Mutex::new(false)),
+            similarity_cache: Arc::new(Mutex::new(None)),
+            theme: Theme::from_kind(ThemeKind::load()),
         }
     }
     
-    pub fn run(mut self) -> Result<Option<(PathBuf, PathBuf)>> {
+    /// Restituisce le cartelle scelte e la strategia di abbinamento, `None` se l'utente esce.
+    pub fn run(mut self) -> Result<Option<(PathBuf, PathBuf, MatchStrategy)>> {
         let options = eframe::NativeOptions {
             viewport: egui::ViewportBuilder::default()
-                .with_fullscreen(true)
+                .with_inner_size([1000.0, 950.0])
                 .with_title("PhotoScope Pro - Setup"),
+            persist_window: true,
             ..Default::default()
         };
         
         let result = std::sync::Arc::new(std::sync::Mutex::new(None));
         let result_clone = result.clone();
         
-        eframe::run_simple_native("PhotoScope Setup", options, move |ctx, _frame| {
-            self.setup_custom_style(ctx);
+        crate::monitor::run_simple_native("PhotoScope Setup", options, move |ctx, _frame| {
+            self.setup_custom_style(ctx, &self.theme);
             self.update(ctx);
             
             if self.folders_selected {
                 if let (Some(f1), Some(f2)) = (&self.folder1, &self.folder2) {
-                    *result_clone.lock().unwrap() = Some((f1.clone(), f2.clone()));
+                    *result_clone.lock().unwrap() = Some((f1.clone(), f2.clone(), self.match_strategy.clone()));
                     ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                 }
             }
@@ -57,7 +102,7 @@ impl FolderSelectorApp {
         Ok(result.lock().unwrap().clone())
     }
     
-    fn setup_custom_style(&self, ctx: &egui::Context) {
+    fn setup_custom_style(&self, ctx: &egui::Context, theme: &Theme) {
         // Initialize Phosphor fonts
         let mut fonts = egui::FontDefinitions::default();
         egui_phosphor::add_to_fonts(&mut fonts, egui_phosphor::Variant::Regular);
@@ -84,22 +129,21 @@ impl FolderSelectorApp {
         style.spacing.button_padding = Vec2::new(16.0, 10.0);
         
         // Visual tweaks
-        style.visuals = Visuals::dark();
-        style.visuals.window_fill = BG_COLOR;
-        style.visuals.panel_fill = BG_COLOR;
-        style.visuals.extreme_bg_color = CARD_BG;
-        style.visuals.widgets.noninteractive.bg_fill = CARD_BG;
-        style.visuals.widgets.inactive.bg_fill = CARD_BG;
-        style.visuals.widgets.hovered.bg_fill = CARD_HOVER;
-        style.visuals.widgets.active.bg_fill = ACCENT_BLUE;
-        style.visuals.selection.bg_fill = ACCENT_BLUE;
+        style.visuals = theme.visuals();
         // Window rounding and widget rounding are handled differently in egui 0.32
         
         ctx.set_style(style);
     }
     
     fn update(&mut self, ctx: &egui::Context) {
-        // Non più necessario con fullscreen impostato nelle opzioni
+        // F11 alterna fullscreen e finestra
+        if ctx.input(|i| i.key_pressed(egui::Key::F11)) {
+            let is_fullscreen = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(!is_fullscreen));
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::T)) {
+            self.theme.cycle();
+        }
         
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical_centered(|ui| {
@@ -124,7 +168,10 @@ impl FolderSelectorApp {
                         // Folder 2 Card
                         self.show_folder_card(ui, 2);
                         
-                        ui.add_space(30.0);
+                        ui.add_space(20.0);
+                        
+                        // Strategia di abbinamento e stima delle coppie
+                        self.show_matching_card(ui);
                         
                         ui.add_space(40.0);
                         
@@ -135,31 +182,36 @@ impl FolderSelectorApp {
         });
     }
     
-    fn show_header(&self, ui: &mut egui::Ui) {
-        ui.heading(RichText::new(format!("{} PhotoScope Pro", regular::APERTURE)).size(32.0).color(TEXT_PRIMARY));
+    fn show_header(&mut self, ui: &mut egui::Ui) {
+        ui.heading(RichText::new(format!("{} PhotoScope Pro", regular::APERTURE)).size(32.0).color(self.theme.text_primary));
         ui.add_space(8.0);
-        ui.label(RichText::new("Professional Image Comparison Tool").size(18.0).color(TEXT_SECONDARY));
+        ui.label(RichText::new("Professional Image Comparison Tool").size(18.0).color(self.theme.text_secondary));
         ui.add_space(12.0);
         ui.label(RichText::new("Seleziona le cartelle da confrontare per trovare le migliori versioni delle tue immagini")
             .size(16.0)
-            .color(TEXT_SECONDARY));
+            .color(self.theme.text_secondary));
+        ui.add_space(8.0);
+        let theme_label = format!("{} Tema: {} (T)", regular::PALETTE, self.theme.kind.label());
+        if ui.small_button(RichText::new(theme_label).size(14.0).color(self.theme.text_secondary)).clicked() {
+            self.theme.cycle();
+        }
     }
     
     fn show_folder_card(&mut self, ui: &mut egui::Ui, num: u8) {
         let folder_ref = if num == 1 { &self.folder1 } else { &self.folder2 };
         let has_folder = folder_ref.is_some();
         let folder_path = folder_ref.as_ref().and_then(|p| p.to_str()).unwrap_or("Nessuna cartella selezionata");
-        let color = if num == 1 { ACCENT_BLUE } else { Color32::from_rgb(251, 146, 60) };
+        let color = if num == 1 { self.theme.accent_blue } else { self.theme.accent_orange };
         
         let mut new_path = None;
         
         Frame::NONE
-            .fill(CARD_BG)
+            .fill(self.theme.card_bg)
             .corner_radius(CornerRadius::same(12))
             .stroke(if has_folder {
                 Stroke::new(2.0, color)
             } else {
-                Stroke::new(1.0, Color32::from_gray(50))
+                Stroke::new(1.0, self.theme.border)
             })
             .inner_margin(Margin::same(20))
             .shadow(egui::epaint::Shadow {
@@ -193,16 +245,16 @@ impl FolderSelectorApp {
                 
                 // Path display
                 Frame::NONE
-                    .fill(Color32::from_gray(20))
+                    .fill(self.theme.image_bg)
                     .corner_radius(CornerRadius::same(6))
                     .inner_margin(Margin::symmetric(12, 8))
                     .show(ui, |ui| {
                         ui.set_min_height(30.0);
                         
                         let text_color = if has_folder {
-                            ACCENT_GREEN
+                            self.theme.accent_green
                         } else {
-                            TEXT_SECONDARY
+                            self.theme.text_secondary
                         };
                         
                         ui.label(RichText::new(folder_path)
@@ -221,6 +273,133 @@ impl FolderSelectorApp {
         }
     }
     
+    fn show_matching_card(&mut self, ui: &mut egui::Ui) {
+        Frame::NONE
+            .fill(self.theme.card_bg)
+            .corner_radius(CornerRadius::same(12))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .inner_margin(Margin::same(20))
+            .show(ui, |ui| {
+                ui.label(RichText::new(format!("{} Abbinamento", regular::LINK))
+                    .size(20.0)
+                    .color(self.theme.text_primary)
+                    .strong());
+                ui.add_space(8.0);
+                
+                let strategies = [
+                    (MatchStrategy::Name, "Nome file"),
+                    (MatchStrategy::RelativePath, "Percorso relativo"),
+                    (MatchStrategy::Similarity { max_distance: self.similarity_threshold }, "Somiglianza visiva"),
+                    (MatchStrategy::Timestamp { tolerance_secs: self.time_tolerance }, "Orario di scatto"),
+                    (MatchStrategy::Content, "Contenuto identico"),
+                ];
+                ui.horizontal(|ui| {
+                    for (strategy, label) in strategies {
+                        let selected = std::mem::discriminant(&self.match_strategy) == std::mem::discriminant(&strategy);
+                        if ui.radio(selected, label).clicked() {
+                            self.match_strategy = strategy;
+                        }
+                    }
+                });
+                
+                match &mut self.match_strategy {
+                    MatchStrategy::Similarity { max_distance } => {
+                        ui.add_space(4.0);
+                        ui.add(egui::Slider::new(max_distance, 0..=MAX_SIMILARITY_DISTANCE).text("distanza massima"));
+                        ui.label(RichText::new("0 abbina solo immagini praticamente identiche, valori alti anche versioni più diverse")
+                            .size(13.0)
+                            .color(self.theme.text_secondary));
+                        self.similarity_threshold = *max_distance;
+                    }
+                    MatchStrategy::Timestamp { tolerance_secs } => {
+                        ui.add_space(4.0);
+                        ui.add(egui::Slider::new(tolerance_secs, 0.0..=60.0).text("tolleranza (s)"));
+                        self.time_tolerance = *tolerance_secs;
+                    }
+                    MatchStrategy::Content => {
+                        ui.add_space(4.0);
+                        ui.label(RichText::new("Trova le copie esatte anche con nomi diversi; i file vengono letti per intero per calcolarne l'hash")
+                            .size(13.0)
+                            .color(self.theme.text_secondary));
+                    }
+                    MatchStrategy::Name | MatchStrategy::RelativePath => {}
+                }
+                
+                ui.add_space(8.0);
+                self.show_estimate(ui);
+            });
+    }
+    
+    fn show_estimate(&mut self, ui: &mut egui::Ui) {
+        let (Some(folder1), Some(folder2)) = (&self.folder1, &self.folder2) else {
+            ui.label(RichText::new("Seleziona entrambe le cartelle per stimare le coppie").size(14.0).color(self.theme.text_secondary));
+            return;
+        };
+        if folder1 == folder2 {
+            return;
+        }
+        
+        let key = (folder1.clone(), folder2.clone(), self.match_strategy.clone());
+        let count = self.estimate.lock().unwrap().as_ref()
+            .filter(|(estimated, _)| *estimated == key)
+            .map(|(_, count)| *count);
+        
+        match count {
+            Some(count) => {
+                let color = if count > 0 { self.theme.accent_green } else { self.theme.warning };
+                ui.label(RichText::new(format!("{} Coppie stimate: {}", regular::LINK_SIMPLE, count)).size(16.0).color(color));
+            }
+            None => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(RichText::new("Stima delle coppie in corso...").size(14.0).color(self.theme.text_secondary));
+                });
+                self.start_estimate(key);
+                ui.ctx().request_repaint_after(Duration::from_millis(100));
+            }
+        }
+    }
+    
+    // Un solo calcolo alla volta: al termine, se le impostazioni sono cambiate, ne parte un altro
+    fn start_estimate(&self, key: EstimateKey) {
+        {
+            let mut estimating = self.estimating.lock().unwrap();
+            if *estimating {
+                return;
+            }
+            *estimating = true;
+        }
+        
+        let estimate = self.estimate.clone();
+        let estimating = self.estimating.clone();
+        let similarity_cache = self.similarity_cache.clone();
+        let exclude = self.exclude.clone();
+        
+        thread::spawn(move || {
+            let (folder1, folder2, strategy) = &key;
+            let count = match *strategy {
+                MatchStrategy::Similarity { max_distance } => {
+                    let mut cache = similarity_cache.lock().unwrap();
+                    let folders = (folder1.clone(), folder2.clone());
+                    if cache.as_ref().is_none_or(|cache| cache.folders != folders) {
+                        *cache = Some(SimilarityCache {
+                            folders,
+                            hashes1: FileManager::read_perceptual_hashes(FileManager::collect_listed_files(folder1, None, &exclude, None).unwrap_or_default(), None),
+                            hashes2: FileManager::read_perceptual_hashes(FileManager::collect_listed_files(folder2, None, &exclude, None).unwrap_or_default(), None),
+                        });
+                    }
+                    cache.as_ref()
+                        .map(|cache| FileManager::match_by_similarity(&cache.hashes1, &cache.hashes2, max_distance).len())
+                        .unwrap_or(0)
+                }
+                _ => FileManager::match_folders(folder1, folder2, strategy, None, &exclude, None).map_or(0, |pairs| pairs.len()),
+            };
+            
+            *estimate.lock().unwrap() = Some((key, count));
+            *estimating.lock().unwrap() = false;
+        });
+    }
+    
     fn show_actions(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
         let both_selected = self.folder1.is_some() && self.folder2.is_some();
         let same_folder = if let (Some(f1), Some(f2)) = (&self.folder1, &self.folder2) {
@@ -234,7 +413,7 @@ impl FolderSelectorApp {
             
             // Start button
             ui.add_enabled_ui(both_selected && !same_folder, |ui| {
-                let btn_color = if both_selected && !same_folder { ACCENT_GREEN } else { Color32::from_gray(80) };
+                let btn_color = if both_selected && !same_folder { self.theme.accent_green } else { self.theme.disabled };
                 if self.modern_button(ui, &format!("{} Avvia Confronto", regular::PLAY), btn_color, Vec2::new(150.0, 45.0)) {
                     self.folders_selected = true;
                 }
@@ -243,7 +422,7 @@ impl FolderSelectorApp {
             ui.add_space(20.0);
             
             // Exit button
-            if self.modern_button(ui, &format!("{} Esci", regular::X), DANGER_RED, Vec2::new(150.0, 45.0)) {
+            if self.modern_button(ui, &format!("{} Esci", regular::X), self.theme.danger_red, Vec2::new(150.0, 45.0)) {
                 ctx.send_viewport_cmd(egui::ViewportCommand::Close);
             }
         });
@@ -253,17 +432,17 @@ impl FolderSelectorApp {
             ui.add_space(20.0);
             
             Frame::NONE
-                .fill(DANGER_RED.gamma_multiply(0.2))
+                .fill(self.theme.danger_red.gamma_multiply(0.2))
                 .corner_radius(CornerRadius::same(8))
                 .inner_margin(Margin::symmetric(16, 12))
                 .show(ui, |ui| {
                     ui.set_min_width(ui.available_width());
                     ui.vertical_centered(|ui| {
                         ui.horizontal(|ui| {
-                            ui.label(RichText::new(regular::WARNING.to_string()).color(DANGER_RED).size(20.0));
+                            ui.label(RichText::new(regular::WARNING.to_string()).color(self.theme.danger_red).size(20.0));
                             ui.add_space(8.0);
                             ui.label(RichText::new("Non puoi selezionare la stessa cartella due volte")
-                                .color(DANGER_RED)
+                                .color(self.theme.danger_red)
                                 .size(16.0));
                         });
                     });
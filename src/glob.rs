@@ -0,0 +1,70 @@
+use std::path::Path;
+use std::str::FromStr;
+
+/// Modello di esclusione (--exclude). `*` e `?` non attraversano le cartelle, `**` sì
+/// (`**/` anche zero cartelle). Senza `/` il modello vale per il solo nome del file, in
+/// qualsiasi sottocartella (`*_thumb.*`); con `/` per il percorso relativo alla cartella
+/// scandita (`**/cache/**`).
+#[derive(Clone, Debug)]
+pub struct GlobPattern {
+    pattern: Vec<char>,
+    name_only: bool,
+}
+
+impl FromStr for GlobPattern {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Le barre rovesciate di Windows valgono come separatori
+        let pattern = s.trim().replace('\\', "/");
+        let pattern = pattern.trim_start_matches("./");
+        if pattern.is_empty() {
+            return Err("modello di esclusione vuoto".to_string());
+        }
+        Ok(GlobPattern {
+            pattern: pattern.chars().collect(),
+            name_only: !pattern.contains('/'),
+        })
+    }
+}
+
+impl GlobPattern {
+    /// Vero se `path`, contenuto in `folder`, corrisponde al modello.
+    pub fn matches(&self, folder: &Path, path: &Path) -> bool {
+        let subject = if self.name_only {
+            path.file_name().map(|name| name.to_string_lossy().into_owned())
+        } else {
+            path.strip_prefix(folder).ok().map(|relative| {
+                relative.components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+        };
+        subject.is_some_and(|subject| matches(&self.pattern, &subject.chars().collect::<Vec<_>>()))
+    }
+    
+    /// Vero se almeno uno dei modelli esclude il file.
+    pub fn any_matches(patterns: &[GlobPattern], folder: &Path, path: &Path) -> bool {
+        patterns.iter().any(|pattern| pattern.matches(folder, path))
+    }
+}
+
+fn matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            // Zero o più cartelle intere: si riprova dopo ogni separatore
+            matches(rest, text) || text.iter()
+                .enumerate()
+                .any(|(index, &c)| c == '/' && matches(rest, &text[index + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|index| matches(rest, &text[index..])),
+        ['*', rest @ ..] => {
+            let segment = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+            (0..=segment).any(|index| matches(rest, &text[index..]))
+        }
+        ['?', rest @ ..] => text.first().is_some_and(|&c| c != '/') && matches(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && matches(rest, &text[1..]),
+    }
+}
\ No newline at end of file
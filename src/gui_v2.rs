@@ -1,33 +1,217 @@
-use crate::file_manager::FileManager;
-use crate::image_analyzer::ImageAnalysis;
+use crate::archive;
+use crate::file_manager::{ExifFields, FileManager};
+use crate::image_analyzer::{self, FileDamage, ImageAnalysis, ScoringWeights};
+use crate::keymap::KeyBindings;
+use crate::live_photo;
+use crate::report::{self, ReportEntry};
+use crate::session::{AutosavePolicy, PairAnnotation, PairDecision, SessionState};
+use crate::theme::{Theme, ThemeKind};
+use crate::thumbnail_cache;
 use anyhow::Result;
 use eframe::egui;
-use egui::{Color32, ColorImage, Context, FontId, Frame, Margin, RichText, CornerRadius, Stroke, TextureHandle, Vec2, Visuals};
+use egui::{Color32, ColorImage, Context, FontId, Frame, Margin, RichText, CornerRadius, Stroke, TextureHandle, Vec2};
 use egui_phosphor::regular;
 use image::{DynamicImage, GenericImageView, imageops::FilterType};
+use log::{debug, error, info, warn};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ops::Range;
+use std::ffi::OsString;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
 const MAX_TEXTURE_SIZE: u32 = 2048;
+// Filtro per l'anteprima a schermo: su un'immagine da 96MP ridotta a 2048px Triangle
+// impiega circa la metà di Lanczos3 (1,2s contro 2,2s) con differenze non visibili a video.
+// I file in output non vengono mai ridimensionati, quindi la qualità finale non cambia.
+const PREVIEW_FILTER: FilterType = FilterType::Triangle;
+// Diametro della lente sullo schermo e lato della regione campionata a piena risoluzione
+const LOUPE_SIZE: f32 = 220.0;
+const LOUPE_SOURCE_SIZE: u32 = 110;
+// Lato minimo della zona di messa a fuoco, in frazione dell'immagine
+const FOCUS_REGION_MIN_SIZE: f32 = 0.01;
+// Ingrandimento massimo delle immagini con pizzico o Ctrl+rotella
+const MAX_IMAGE_ZOOM: f32 = 8.0;
+// Modalità touch: pulsanti più grandi
+const TOUCH_BUTTON_SIZE: Vec2 = Vec2::new(150.0, 64.0);
+// L'area immagini occupa l'altezza libera del pannello centrale, lasciando ai metadati
+// almeno METADATA_MIN_HEIGHT; +/- la scalano tra i due limiti di IMAGE_HEIGHT_FACTOR_RANGE
+const MIN_IMAGE_HEIGHT: f32 = 160.0;
+const METADATA_MIN_HEIGHT: f32 = 200.0;
+const IMAGE_HEIGHT_FACTOR_RANGE: (f32, f32) = (0.5, 1.5);
+const IMAGE_HEIGHT_FACTOR_STEP: f32 = 0.1;
+// Durata in secondi della dissolvenza quando compare una nuova coppia
+const TRANSITION_DURATION: f32 = 0.2;
+// Scorrimento orizzontale iniziale (in punti) delle immagini in entrata
+const TRANSITION_SLIDE: f32 = 24.0;
+// Secondi per cui resta visibile la conferma di un'azione (es. percorso copiato)
+const TOAST_DURATION: f32 = 2.0;
+// Secondi senza risultati dal thread di caricamento dopo i quali la coppia si considera bloccata
+const LOADING_TIMEOUT: f32 = 60.0;
+// Durante il caricamento il risultato dei thread viene controllato circa 30 volte al secondo,
+// invece che a ogni frame
+const LOADING_POLL_INTERVAL: Duration = Duration::from_millis(33);
+// SSIM oltre cui le due immagini sono la stessa foto e sotto cui sono probabilmente foto diverse
+const SSIM_NEAR_IDENTICAL: f64 = 0.98;
+const SSIM_DIFFERENT_PHOTO: f64 = 0.5;
+// Colore dei bordi a fuoco: rosso pieno, come nei mirino delle mirrorless
+const PEAKING_COLOR: [u8; 3] = [255, 0, 0];
+// Tag assegnabili con Ctrl+1, Ctrl+2, Ctrl+3
+const QUICK_TAGS: [&str; 3] = ["preferita", "da rivedere", "da modificare"];
+// Lato massimo delle miniature nella schermata di gruppo
+const GROUP_THUMBNAIL_SIZE: u32 = 512;
+// Cronologia delle decisioni (J): coppie ricordate e lato massimo delle miniature
+const HISTORY_LENGTH: usize = 12;
+const HISTORY_THUMBNAIL_SIZE: u32 = 64;
 
-// Colori del tema
-const BG_COLOR: Color32 = Color32::from_rgb(24, 26, 31);
-const CARD_BG: Color32 = Color32::from_rgb(32, 34, 41);
-const CARD_HOVER: Color32 = Color32::from_rgb(38, 40, 48);
-const ACCENT_BLUE: Color32 = Color32::from_rgb(59, 130, 246);
-const ACCENT_GREEN: Color32 = Color32::from_rgb(34, 197, 94);
-const ACCENT_ORANGE: Color32 = Color32::from_rgb(251, 146, 60);
-const DANGER_RED: Color32 = Color32::from_rgb(239, 68, 68);
-const TEXT_PRIMARY: Color32 = Color32::from_rgb(229, 231, 235);
-const TEXT_SECONDARY: Color32 = Color32::from_rgb(148, 163, 184);
-const GOLD_STAR: Color32 = Color32::from_rgb(250, 204, 21);
 
 #[derive(Clone)]
 enum AppState {
     ShowingImages,
+    // Deduplicazione: tutti i file di un gruppo di almeno tre duplicati affiancati
+    ShowingGroup,
     Loading(String),
     ProcessingChoice(u8, PathBuf),
+    // Caricamento fallito in modo imprevisto: l'utente può riprovare o saltare la coppia
+    Error(String),
+    // Tutte le coppie decise: riepilogo finché l'utente non chiude
+    Summary,
+}
+
+// Immagine mostrata come griglia di texture, per superare il lato massimo supportato dalla GPU.
+// Ogni tile ha la sua posizione in coordinate relative (0..1) dell'immagine intera.
+#[derive(Clone)]
+struct ImageTiles {
+    tiles: Vec<(TextureHandle, egui::Rect)>,
+    size: Vec2,
+}
+
+// Parte visibile di un'immagine su cui calcolare il focus peaking: la maschera vale solo per
+// questa combinazione di file, pagina, rotazione ed esposizione
+#[derive(Clone, PartialEq)]
+struct PeakingRequest {
+    path: PathBuf,
+    page: usize,
+    rotation: u8,
+    gain: Option<f64>,
+    // Riquadro in coordinate relative (0..1) dell'immagine come è mostrata
+    region: egui::Rect,
+    // Pixel sullo schermo della parte visibile: oltre non servono
+    max_size: [u32; 2],
+}
+
+// Risultato del caricamento di una coppia, prodotto dai thread di lavoro
+struct PairData {
+    analysis1: ImageAnalysis,
+    analysis2: ImageAnalysis,
+    image1: DynamicImage,
+    image2: DynamicImage,
+    // None se l'anteprima viene dalla cache: l'immagine intera si decodifica dopo averla mostrata
+    full1: Option<DynamicImage>,
+    full2: Option<DynamicImage>,
+    // Numero dell'immagine che sembra un ritaglio dell'altra
+    cropped: Option<u8>,
+    ssim: Option<f64>,
+}
+
+enum PairLoadError {
+    // Una coppia con almeno un file vuoto, troncato o illeggibile non si può confrontare
+    Damaged(Vec<(PathBuf, FileDamage)>),
+    // Panic nel thread di lavoro (es. decoder su un file malformato), con il suo messaggio
+    Panicked(String),
+    // Non un errore: file identici byte per byte con --identical, riconosciuti dall'hash prima
+    // di decodificarli
+    Identical,
+}
+
+type PairLoadResult = Result<PairData, PairLoadError>;
+
+// Un file di un gruppo di duplicati, con il motivo per cui non si legge al posto dell'analisi
+struct GroupMember {
+    path: PathBuf,
+    analysis: Result<ImageAnalysis, FileDamage>,
+    thumbnail: Option<DynamicImage>,
+}
+
+// Gruppo caricato in background: indice della sua prima coppia e file nell'ordine delle coppie
+type GroupLoad = (usize, Vec<GroupMember>);
+
+// Schermata di gruppo: coppie del gruppo, file con la loro miniatura e migliore per punteggio
+struct GroupView {
+    pairs: Range<usize>,
+    members: Vec<(GroupMember, Option<TextureHandle>)>,
+    best: Option<usize>,
+}
+
+// Coppia decisa di recente: miniature ruotate come erano a schermo e lato scelto (entrambi
+// per "tieni entrambe", nessuno per "salta"). Le texture si creano al primo disegno
+struct RecentDecision {
+    index: usize,
+    chosen: [bool; 2],
+    thumbnails: [DynamicImage; 2],
+    textures: Option<[TextureHandle; 2]>,
+}
+
+// Miniature EXIF di una coppia in caricamento: indice della coppia e miniatura di ciascun file
+type ThumbnailLoad = (usize, Option<DynamicImage>, Option<DynamicImage>);
+
+// Serie di coppie identiche trovata in background: prima coppia e quante sono
+type IdenticalRun = (usize, usize);
+
+// Pagina di un TIFF caricata in background: indice della coppia, immagine (1 o 2), pagina,
+// e immagine intera con la sua anteprima. Le due immagini possono arrivare nello stesso frame
+type PageLoad = (usize, u8, usize, Result<(DynamicImage, DynamicImage)>);
+
+// Maschera di focus peaking calcolata in background: immagine (1 o 2), richiesta, riquadro
+// coperto e maschera
+type PeakingLoad = (u8, PeakingRequest, egui::Rect, DynamicImage);
+
+/// Cosa fare delle coppie di file identici byte per byte senza mostrarle.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum IdenticalAction {
+    // Copia il primo file in output, come se fosse stato scelto
+    Copy,
+    Skip,
+}
+
+/// Presentazione automatica: la coppia (o il file scelto) avanza da sola ogni `interval` secondi.
+pub struct Slideshow {
+    interval: f32,
+    paused: bool,
+    // Istante (animation_time) da cui si conta l'intervallo della slide corrente
+    shown_at: f32,
+}
+
+impl Slideshow {
+    pub fn new(interval: Duration) -> Self {
+        Slideshow {
+            interval: interval.as_secs_f32(),
+            paused: false,
+            shown_at: 0.0,
+        }
+    }
+}
+
+// Editor EXIF aperto con Maiusc+E: immagine (1 o 2) e valori in modifica
+struct ExifEditor {
+    num: u8,
+    fields: ExifFields,
+}
+
+// Conteggi finali della sessione
+#[derive(Default)]
+pub struct SessionStats {
+    pub selected: usize,
+    pub skipped: usize,
+    pub kept_both: usize,
+    // Coppie di file identici risolte senza mostrarle
+    pub auto_identical: usize,
+    // Coppie con stesso peso e dimensioni risolte senza decodificarle (--same-size)
+    pub auto_same_size: usize,
+    // File saltati perché non leggibili, da risincronizzare se vuoti o troncati
+    pub damaged_files: Vec<(PathBuf, FileDamage)>,
 }
 
 pub struct PhotoComparisonApp {
@@ -41,27 +225,155 @@ pub struct PhotoComparisonApp {
     // Analisi correnti
     current_analysis1: Option<ImageAnalysis>,
     current_analysis2: Option<ImageAnalysis>,
-    texture1: Option<TextureHandle>,
-    texture2: Option<TextureHandle>,
+    texture1: Option<ImageTiles>,
+    texture2: Option<ImageTiles>,
+    // Texture a piena risoluzione invece dell'anteprima limitata a MAX_TEXTURE_SIZE
+    full_resolution: bool,
+    // Numero dell'immagine che sembra un ritaglio dell'altra
+    cropped: Option<u8>,
+    // Somiglianza strutturale delle due anteprime (0-1)
+    ssim: Option<f64>,
+    // Esposizione pareggiata (H), solo a schermo: guadagno di luminanza delle due immagini,
+    // calcolato sulle anteprime
+    normalize_exposure: bool,
+    exposure_gains: Option<[f64; 2]>,
+    // Focus peaking (K): bordi a fuoco evidenziati, solo a schermo. La maschera si calcola in
+    // background sull'immagine intera, per la sola parte visibile
+    focus_peaking: bool,
+    peaking_visible: [Option<(egui::Rect, [u32; 2])>; 2],
+    peaking_pending: [Option<PeakingRequest>; 2],
+    // Con la richiesta, il riquadro coperto davvero dalla maschera: i pixel interi ritagliati
+    peaking_overlays: [Option<(PeakingRequest, egui::Rect, ImageTiles)>; 2],
+    peaking_data: Arc<Mutex<Vec<PeakingLoad>>>,
+    // Metadati delle due immagini in un'unica tabella allineata per tag (M)
+    metadata_diff: bool,
+    // Campi EXIF corretti delle due immagini, scritti nella copia in output dell'immagine scelta
+    exif_edits: [Option<ExifFields>; 2],
+    exif_editor: Option<ExifEditor>,
+    
+    // Anteprime già decodificate, riusate per ruotare la texture senza ricaricare il file
+    preview1: Option<DynamicImage>,
+    preview2: Option<DynamicImage>,
+    // Rotazione scelta dall'utente, in quarti di giro orari
+    rotation1: u8,
+    rotation2: u8,
+    // Pagina mostrata dei TIFF multipagina (da 0); si scorre con PagSu/PagGiù
+    page1: usize,
+    page2: usize,
+    page_data: Arc<Mutex<Vec<PageLoad>>>,
+    
+    // Immagini a piena risoluzione per la lente d'ingrandimento
+    full1: Option<DynamicImage>,
+    full2: Option<DynamicImage>,
+    // Coppia di cui si stanno decodificando le immagini intere mancanti (anteprime dalla cache)
+    full_requested: Option<usize>,
+    loupe_texture1: Option<TextureHandle>,
+    loupe_texture2: Option<TextureHandle>,
+    // Area occupata da ciascuna immagine e punto (relativo) sotto il cursore
+    image_rect1: Option<egui::Rect>,
+    image_rect2: Option<egui::Rect>,
+    loupe_target: Option<Vec2>,
+    loupe_enabled: bool,
+    // Zona di messa a fuoco tracciata con Maiusc+trascinamento, in frazioni dell'immagine
+    // mostrata e uguale sulle due, con la nitidezza misurata al suo interno
+    focus_region: Option<egui::Rect>,
+    focus_drag_start: Option<Vec2>,
+    focus_sharpness: Option<[f64; 2]>,
+    // Ingrandimento comune alle due immagini (1 = intera) e spostamento del centro, in
+    // frazioni dell'area immagine. Pizzico, trascinamento o Ctrl+rotella; doppio clic azzera
+    image_zoom: f32,
+    image_pan: Vec2,
+    // Attivata al primo tocco sullo schermo
+    touch_mode: bool,
+    // Altezza dell'area immagine nel frame corrente, fattore applicato all'altezza calcolata
+    // (+/-) e altezza delle righe della card sopra l'immagine, misurata al frame precedente
+    image_height: f32,
+    image_height_factor: f32,
+    image_header_height: f32,
     
     // File manager
     file_manager: FileManager,
     
+    // Pesi usati per calcolare il punteggio qualità, modificabili dalla finestra dei pesi (O)
+    scoring_weights: ScoringWeights,
+    weights_editor: bool,
+    // Elenco completo dei tasti (F1 o ?)
+    shortcut_help: bool,
+    
+    // Le coppie in cui entrambe le immagini hanno un punteggio inferiore vengono saltate
+    pub min_score: Option<u8>,
+    
+    // Le coppie di file identici (stesso SHA256) vengono copiate o saltate senza mostrarle
+    pub identical_action: Option<IdenticalAction>,
+    // Le coppie con stesso peso e dimensioni in pixel vengono copiate o saltate senza decodificarle
+    pub same_size_action: Option<IdenticalAction>,
+    
+    // Ogni quante decisioni, o dopo quanto tempo, si salvano sessione e report
+    pub autosave: AutosavePolicy,
+    // Decisioni prese dopo l'ultimo salvataggio e istante dell'ultimo salvataggio
+    unsaved_decisions: usize,
+    last_save: Instant,
+    
+    // Deduplicazione di una cartella: gruppo di duplicati di ogni coppia. Le coppie di un
+    // gruppo sono consecutive e il file scelto passa al confronto successivo
+    pub pair_groups: Option<Vec<usize>>,
+    // I gruppi di almeno tre file si decidono in un'unica schermata
+    group_data: Arc<Mutex<Option<GroupLoad>>>,
+    // Coppie identiche consecutive da risolvere in blocco (Maiusc+I)
+    identical_run: Arc<Mutex<Option<IdenticalRun>>>,
+    // Coppie controllate e da controllare mentre la ricerca della serie è in corso. Su molte
+    // coppie grandi può durare più di LOADING_TIMEOUT senza essere bloccata
+    identical_progress: Arc<Mutex<Option<(usize, usize)>>>,
+    group_view: Option<GroupView>,
+    
+    // Presentazione: nessuna decisione, le coppie scorrono a tempo. La slide successiva viene
+    // caricata in anticipo (con il suo indice) mentre è mostrata quella corrente
+    pub slideshow: Option<Slideshow>,
+    prefetched: Arc<Mutex<Option<(usize, PairLoadResult)>>>,
+    
     // Thread communication
     loading_message: Arc<Mutex<Option<String>>>,
-    next_data: Arc<Mutex<Option<(ImageAnalysis, ImageAnalysis, DynamicImage, DynamicImage)>>>,
+    // Coppia caricata con il suo indice: un thread rimasto indietro non deve sostituire quella corrente
+    next_data: Arc<Mutex<Option<(usize, PairLoadResult)>>>,
+    
+    // Miniature EXIF mostrate durante il caricamento, finché la decodifica completa non è pronta
+    thumbnail_data: Arc<Mutex<Option<ThumbnailLoad>>>,
+    loading_thumbnails: Option<(usize, [Option<TextureHandle>; 2])>,
     
     // Statistiche
     selected_count: Arc<Mutex<usize>>,
     skipped_count: Arc<Mutex<usize>>,
+    kept_both_count: Arc<Mutex<usize>>,
+    // Coppie risolte da --identical o da una serie di identiche: come quelle di --same-size
+    // non contano tra le selezionate né tra le saltate
+    identical_pairs: Arc<Mutex<BTreeSet<usize>>>,
+    // Coppie risolte da --same-size: non contano tra le selezionate né tra le saltate
+    same_size_pairs: Arc<Mutex<BTreeSet<usize>>>,
+    damaged_files: Arc<Mutex<Vec<(PathBuf, FileDamage)>>>,
+    // File della coppia in errore troppo grandi da decodificare, apribili con l'app di sistema
+    oversized_files: Vec<PathBuf>,
     
     // Flags
     exit_program: bool,
+    // Finestra di conferma dell'uscita quando restano coppie da decidere
+    confirm_exit: bool,
     
     // UI state
     hover_image1: bool,
     hover_image2: bool,
     animation_time: f32,
+    // Inizio di questa esecuzione, per la durata mostrata nel riepilogo
+    started_at: Instant,
+    // Messaggio di conferma e istante (animation_time) in cui è comparso
+    toast: Option<(String, f32)>,
+    // Messaggio da mostrare quando arriva la prossima coppia, dopo un caricamento
+    pending_toast: Option<String>,
+    theme: Theme,
+    keys: KeyBindings,
+    // Istante (in animation_time) in cui sono arrivate le immagini della coppia corrente
+    transition_start: f32,
+    // Istante (in animation_time) in cui è iniziato il caricamento in corso
+    loading_since: Option<f32>,
     
     // Metadata transfer state
     metadata_transfer_source: Option<PathBuf>,
@@ -70,14 +382,38 @@ pub struct PhotoComparisonApp {
     // Navigation history
     navigation_history: Vec<usize>,
     
-    // Track copied files for each index (None = skipped, Some(path) = copied)
-    copied_files: Vec<Option<PathBuf>>,
+    // Revisione in sola lettura con le frecce: coppia da decidere a cui tornare
+    // (None = si sta decidendo la coppia corrente)
+    review_return: Option<usize>,
+    
+    // Testo della finestra "vai alla coppia" (None = chiusa)
+    jump_input: Option<String>,
+    
+    // Note e tag per indice di coppia, e testo della finestra nota (None = chiusa)
+    annotations: BTreeMap<usize, PairAnnotation>,
+    note_input: Option<String>,
+    
+    // Decision taken for each index (None = not decided yet)
+    decisions: Vec<Option<PairDecision>>,
+    // Coppie rimandate (R), riproposte in quest'ordine quando le altre sono tutte decise
+    deferred: Vec<usize>,
+    // Valutazione della compressione dei due file di ogni coppia mostrata, per il report
+    compression_labels: BTreeMap<usize, [String; 2]>,
+    
+    // Ultime coppie decise, dalla più recente, e pannello della cronologia aperto (J)
+    recent_decisions: VecDeque<RecentDecision>,
+    history_panel: bool,
+    
+    // Ultima copia in output non riuscita (disco pieno, sola lettura...): la sessione non
+    // avanza finché una copia non va a buon fine. Resta visibile finché non viene chiusa
+    write_error: Option<String>,
 }
 
 impl PhotoComparisonApp {
     pub fn new(
         pairs: Vec<(PathBuf, PathBuf)>,
         file_manager: FileManager,
+        scoring_weights: ScoringWeights,
     ) -> Self {
         PhotoComparisonApp {
             state: AppState::Loading("Caricamento prima coppia...".to_string()),
@@ -87,40 +423,157 @@ impl PhotoComparisonApp {
             current_analysis2: None,
             texture1: None,
             texture2: None,
+            full_resolution: false,
+            normalize_exposure: false,
+            exposure_gains: None,
+            focus_peaking: false,
+            peaking_visible: [None, None],
+            peaking_pending: [None, None],
+            peaking_overlays: [None, None],
+            peaking_data: Arc::new(Mutex::new(Vec::new())),
+            metadata_diff: false,
+            exif_edits: [None, None],
+            exif_editor: None,
+            cropped: None,
+            ssim: None,
+            preview1: None,
+            preview2: None,
+            rotation1: 0,
+            rotation2: 0,
+            page1: 0,
+            page2: 0,
+            page_data: Arc::new(Mutex::new(Vec::new())),
+            full1: None,
+            full2: None,
+            full_requested: None,
+            loupe_texture1: None,
+            loupe_texture2: None,
+            image_rect1: None,
+            image_rect2: None,
+            loupe_target: None,
+            loupe_enabled: true,
+            focus_region: None,
+            focus_drag_start: None,
+            focus_sharpness: None,
+            image_zoom: 1.0,
+            image_pan: Vec2::ZERO,
+            touch_mode: false,
+            image_height: 600.0,
+            image_height_factor: 1.0,
+            image_header_height: 80.0,
             file_manager,
+            scoring_weights,
+            weights_editor: false,
+            shortcut_help: false,
+            min_score: None,
+            identical_action: None,
+            same_size_action: None,
+            autosave: AutosavePolicy::default(),
+            unsaved_decisions: 0,
+            last_save: Instant::now(),
+            pair_groups: None,
+            group_data: Arc::new(Mutex::new(None)),
+            identical_run: Arc::new(Mutex::new(None)),
+            identical_progress: Arc::new(Mutex::new(None)),
+            group_view: None,
+            slideshow: None,
+            prefetched: Arc::new(Mutex::new(None)),
             loading_message: Arc::new(Mutex::new(None)),
             next_data: Arc::new(Mutex::new(None)),
+            thumbnail_data: Arc::new(Mutex::new(None)),
+            loading_thumbnails: None,
             selected_count: Arc::new(Mutex::new(0)),
             skipped_count: Arc::new(Mutex::new(0)),
+            kept_both_count: Arc::new(Mutex::new(0)),
+            identical_pairs: Arc::new(Mutex::new(BTreeSet::new())),
+            same_size_pairs: Arc::new(Mutex::new(BTreeSet::new())),
+            damaged_files: Arc::new(Mutex::new(Vec::new())),
+            oversized_files: Vec::new(),
             exit_program: false,
+            confirm_exit: false,
             hover_image1: false,
             hover_image2: false,
             animation_time: 0.0,
+            started_at: Instant::now(),
+            toast: None,
+            pending_toast: None,
+            theme: Theme::from_kind(ThemeKind::load()),
+            keys: KeyBindings::load(),
+            transition_start: 0.0,
+            loading_since: None,
             metadata_transfer_source: None,
             metadata_transfer_pending: false,
             navigation_history: Vec::new(),
-            copied_files: Vec::new(),
+            review_return: None,
+            jump_input: None,
+            annotations: BTreeMap::new(),
+            note_input: None,
+            decisions: Vec::new(),
+            deferred: Vec::new(),
+            compression_labels: BTreeMap::new(),
+            recent_decisions: VecDeque::new(),
+            history_panel: false,
+            write_error: None,
+        }
+    }
+    
+    /// Riprende una sessione interrotta: ripristina le decisioni e riparte dalla prima coppia non decisa
+    pub fn resume(&mut self, session: SessionState) {
+        for decision in session.decisions.iter().flatten() {
+            match decision {
+                PairDecision::Skipped => *self.skipped_count.lock().unwrap() += 1,
+                PairDecision::Selected(_) => *self.selected_count.lock().unwrap() += 1,
+                PairDecision::KeptBoth(_) => *self.kept_both_count.lock().unwrap() += 1,
+                PairDecision::Advanced(_) => {}
+            }
+        }
+        
+        // P continua a funzionare anche sulle coppie decise prima dell'interruzione
+        self.navigation_history = session.decisions.iter()
+            .enumerate()
+            .filter(|(_, decision)| decision.is_some())
+            .map(|(index, _)| index)
+            .collect();
+        if session.folders_swapped(&self.file_manager) {
+            let file_manager = &mut self.file_manager;
+            std::mem::swap(&mut file_manager.folder1, &mut file_manager.folder2);
         }
+        // Le coppie salvate possono avere l'ordine invertito rispetto alla scansione
+        self.all_pairs = session.pairs;
+        self.decisions = session.decisions;
+        self.annotations = session.annotations;
+        self.deferred = session.deferred;
+        self.compression_labels = session.compression;
+        self.current_index = session.current_index;
+        self.state = AppState::Loading("Ripresa sessione...".to_string());
     }
     
-    pub fn run(mut self) -> Result<(usize, usize)> {
+    pub fn run(mut self) -> Result<SessionStats> {
         let final_selected = self.selected_count.clone();
         let final_skipped = self.skipped_count.clone();
+        let final_kept_both = self.kept_both_count.clone();
+        let final_identical = self.identical_pairs.clone();
+        let final_same_size = self.same_size_pairs.clone();
+        let final_damaged = self.damaged_files.clone();
         
         if !self.all_pairs.is_empty() {
             self.load_current_pair();
         }
         
+        // Finestra ampia ma non fullscreen: posizione e dimensioni vengono
+        // ricordate tra un avvio e l'altro tramite lo storage di eframe
         let options = eframe::NativeOptions {
             viewport: egui::ViewportBuilder::default()
-                .with_fullscreen(true)
+                .with_inner_size([1600.0, 1000.0])
+                .with_min_inner_size([1000.0, 700.0])
                 .with_title("PhotoScope Pro - Image Comparison Tool")
                 .with_icon(Self::create_icon()),
+            persist_window: true,
             ..Default::default()
         };
         
-        eframe::run_simple_native("PhotoScope Pro", options, move |ctx, _frame| {
-            self.setup_custom_style(ctx);
+        crate::monitor::run_simple_native("PhotoScope Pro", options, move |ctx, _frame| {
+            self.setup_custom_style(ctx, &self.theme);
             self.update(ctx);
             
             if self.exit_program {
@@ -128,7 +581,14 @@ impl PhotoComparisonApp {
             }
         }).map_err(|e| anyhow::anyhow!("GUI error: {}", e))?;
         
-        Ok((*final_selected.lock().unwrap(), *final_skipped.lock().unwrap()))
+        Ok(SessionStats {
+            selected: *final_selected.lock().unwrap(),
+            skipped: *final_skipped.lock().unwrap(),
+            kept_both: *final_kept_both.lock().unwrap(),
+            auto_identical: final_identical.lock().unwrap().len(),
+            auto_same_size: final_same_size.lock().unwrap().len(),
+            damaged_files: final_damaged.lock().unwrap().clone(),
+        })
     }
     
     fn create_icon() -> egui::IconData {
@@ -139,7 +599,7 @@ impl PhotoComparisonApp {
         }
     }
     
-    fn setup_custom_style(&self, ctx: &Context) {
+    fn setup_custom_style(&self, ctx: &Context, theme: &Theme) {
         // Initialize Phosphor fonts
         let mut fonts = egui::FontDefinitions::default();
         egui_phosphor::add_to_fonts(&mut fonts, egui_phosphor::Variant::Regular);
@@ -167,15 +627,7 @@ impl PhotoComparisonApp {
         style.spacing.indent = 20.0;
         
         // Visual tweaks
-        style.visuals = Visuals::dark();
-        style.visuals.window_fill = BG_COLOR;
-        style.visuals.panel_fill = BG_COLOR;
-        style.visuals.extreme_bg_color = CARD_BG;
-        style.visuals.widgets.noninteractive.bg_fill = CARD_BG;
-        style.visuals.widgets.inactive.bg_fill = CARD_BG;
-        style.visuals.widgets.hovered.bg_fill = CARD_HOVER;
-        style.visuals.widgets.active.bg_fill = ACCENT_BLUE;
-        style.visuals.selection.bg_fill = ACCENT_BLUE;
+        style.visuals = theme.visuals();
         style.visuals.window_shadow = egui::epaint::Shadow {
             offset: [0, 4],
             blur: 8,
@@ -194,19 +646,198 @@ impl PhotoComparisonApp {
     }
     
     fn update(&mut self, ctx: &Context) {
-        // Non più necessario con fullscreen impostato nelle opzioni
+        // F11 alterna fullscreen e finestra
+        if ctx.input(|i| i.key_pressed(egui::Key::F11)) {
+            let is_fullscreen = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(!is_fullscreen));
+        }
         
         self.animation_time += ctx.input(|i| i.unstable_dt);
         
-        // Controlla se ci sono nuovi dati dal thread
-        if let Some((analysis1, analysis2, img1, img2)) = self.next_data.lock().unwrap().take() {
-            self.current_analysis1 = Some(analysis1);
-            self.current_analysis2 = Some(analysis2);
-            self.texture1 = self.image_to_texture(ctx, img1, "img1");
-            self.texture2 = self.image_to_texture(ctx, img2, "img2");
-            self.state = AppState::ShowingImages;
+        // Salvataggio a tempo delle decisioni non ancora salvate, anche senza altri eventi
+        if self.unsaved_decisions > 0
+            && let Some(interval) = self.autosave.interval
+        {
+            self.save_session_if_due();
+            ctx.request_repaint_after(interval.saturating_sub(self.last_save.elapsed()));
+        }
+        
+        // Anche la chiusura della finestra passa dalla conferma
+        if ctx.input(|i| i.viewport().close_requested())
+            && !self.exit_program
+            && matches!(self.state, AppState::ShowingImages | AppState::ShowingGroup)
+            && self.slideshow.is_none()
+            && self.undecided_pairs() > 0
+        {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.confirm_exit = true;
+        } else if ctx.input(|i| i.viewport().close_requested()) && self.unsaved_decisions > 0 {
+            // Chiusura senza conferma (es. durante un caricamento): le ultime decisioni non vanno perse
+            self.save_session();
+        }
+        
+        // Controlla se ci sono nuovi dati dal thread, scartando quelli di un'altra coppia
+        let next_data = self.next_data.lock().unwrap().take()
+            .filter(|(index, _)| *index == self.current_index)
+            .map(|(_, result)| result);
+        match next_data {
+            // Nella presentazione i file illeggibili si saltano senza registrare nulla
+            Some(Err(PairLoadError::Damaged(damaged))) if self.slideshow.is_some() => {
+                for (path, damage) in damaged {
+                    warn!("File {}: {}", damage.label(), path.display());
+                }
+                if !self.advance_slide(1) {
+                    self.state = AppState::Error("Ultimo file non leggibile".to_string());
+                }
+            }
+            // In revisione non si registra nulla: il file si è rovinato dopo la decisione
+            Some(Err(PairLoadError::Damaged(damaged))) if self.review_return.is_some() => {
+                let files: Vec<String> = damaged.iter()
+                    .map(|(path, damage)| format!("File {}: {}", damage.label(), path.display()))
+                    .collect();
+                self.state = AppState::Error(files.join("\n"));
+            }
+            Some(Err(PairLoadError::Damaged(damaged))) if damaged.iter().any(|(_, damage)| *damage == FileDamage::TooLarge) => {
+                self.show_oversized_pair(damaged);
+            }
+            Some(Err(PairLoadError::Damaged(damaged))) => self.skip_damaged_pair(damaged),
+            Some(Err(PairLoadError::Panicked(message))) => {
+                error!("Errore imprevisto caricando la coppia {}: {}", self.current_index + 1, message);
+                self.state = AppState::Error(message);
+            }
+            Some(Err(PairLoadError::Identical)) if self.resolves_identical() => self.auto_resolve_identical(),
+            // Nel frattempo è iniziata una revisione o c'è un errore di scrittura: la coppia si mostra
+            Some(Err(PairLoadError::Identical)) => self.load_current_pair(),
+            Some(Ok(data)) if self.review_return.is_none() && self.is_below_min_score(&data.analysis1, &data.analysis2) => {
+                self.auto_skip_current("Coppia sotto il punteggio minimo, salto...");
+            }
+            Some(Ok(data)) => {
+                self.loading_thumbnails = None;
+                // I pesi possono essere cambiati (O) mentre la coppia era in caricamento
+                self.current_analysis1 = Some(self.rescored(data.analysis1));
+                self.current_analysis2 = Some(self.rescored(data.analysis2));
+                if let (Some(analysis1), Some(analysis2)) = (&self.current_analysis1, &self.current_analysis2) {
+                    let weights = &self.scoring_weights;
+                    self.compression_labels.insert(self.current_index, [analysis1.compression_label(weights), analysis2.compression_label(weights)]);
+                }
+                self.preview1 = Some(data.image1);
+                self.preview2 = Some(data.image2);
+                self.full1 = data.full1;
+                self.full2 = data.full2;
+                self.full_requested = None;
+                self.cropped = data.cropped;
+                self.ssim = data.ssim;
+                self.rotation1 = 0;
+                self.rotation2 = 0;
+                self.page1 = 0;
+                self.page2 = 0;
+                self.exif_edits = [None, None];
+                if let Some(message) = self.pending_toast.take() {
+                    self.toast = Some((message, self.animation_time));
+                }
+                self.update_exposure_gains();
+                self.refresh_texture(ctx, 1);
+                self.refresh_texture(ctx, 2);
+                self.image_zoom = 1.0;
+                self.image_pan = Vec2::ZERO;
+                self.clear_focus_region();
+                self.transition_start = self.animation_time;
+                self.state = AppState::ShowingImages;
+                if let Some(slideshow) = &mut self.slideshow {
+                    slideshow.shown_at = self.animation_time;
+                    self.prefetch_next_slide();
+                }
+            }
+            None => {}
+        }
+        
+        // Miniature EXIF: servono solo finché la coppia è ancora in caricamento
+        let thumbnail_data = self.thumbnail_data.lock().unwrap().take();
+        if let Some((index, thumbnail1, thumbnail2)) = thumbnail_data
+            && index == self.current_index
+            && matches!(self.state, AppState::Loading(_) | AppState::ProcessingChoice(_, _))
+            && (thumbnail1.is_some() || thumbnail2.is_some())
+        {
+            let to_texture = |thumbnail: Option<DynamicImage>, name: &str| {
+                thumbnail.map(|thumbnail| {
+                    let rgba = thumbnail.to_rgba8();
+                    let color_image = ColorImage::from_rgba_unmultiplied([rgba.width() as usize, rgba.height() as usize], rgba.as_raw());
+                    ctx.load_texture(name, color_image, egui::TextureOptions::default())
+                })
+            };
+            self.loading_thumbnails = Some((index, [to_texture(thumbnail1, "thumbnail1"), to_texture(thumbnail2, "thumbnail2")]));
+        }
+        
+        // Gruppo di duplicati caricato: scartato se nel frattempo si è passati altrove
+        let group_data = self.group_data.lock().unwrap().take();
+        if let Some((index, members)) = group_data
+            && index == self.current_index
+            && matches!(self.state, AppState::Loading(_))
+        {
+            self.open_group_view(ctx, members);
+        }
+        
+        // Serie di coppie identiche pronta: si risolve solo se nel frattempo non ci si è spostati
+        if let Some((checked, total)) = *self.identical_progress.lock().unwrap()
+            && let AppState::Loading(message) = &mut self.state
+        {
+            *message = format!("Cerco le coppie identiche successive... {}/{}", checked, total);
+        }
+        let identical_run = self.identical_run.lock().unwrap().take();
+        if let Some((start, count)) = identical_run
+            && start == self.current_index
+            && matches!(self.state, AppState::Loading(_))
+        {
+            self.resolve_identical_run(count);
+        }
+        
+        // Pagine TIFF caricate: scartate se nel frattempo si è passati a un'altra coppia
+        let page_data = std::mem::take(&mut *self.page_data.lock().unwrap());
+        for (index, num, page, result) in page_data {
+            if index != self.current_index {
+                continue;
+            }
+            match result {
+                Ok((full, preview)) => {
+                    if num == 1 {
+                        (self.full1, self.preview1, self.page1) = (Some(full), Some(preview), page);
+                    } else {
+                        (self.full2, self.preview2, self.page2) = (Some(full), Some(preview), page);
+                    }
+                    self.focus_sharpness = None;
+                    self.update_exposure_gains();
+                    self.refresh_texture(ctx, 1);
+                    self.refresh_texture(ctx, 2);
+                }
+                Err(e) => {
+                    warn!("Impossibile caricare la pagina {} dell'immagine {}: {}", page + 1, num, e);
+                    self.toast = Some((format!("Pagina {} non leggibile", page + 1), self.animation_time));
+                }
+            }
+        }
+        
+        self.load_missing_full_images();
+        self.update_focus_peaking(ctx);
+        
+        // Presentazione: allo scadere dell'intervallo si passa alla slide successiva
+        if let Some(slideshow) = &self.slideshow
+            && !slideshow.paused
+            && matches!(self.state, AppState::ShowingImages)
+        {
+            let remaining = slideshow.interval - (self.animation_time - slideshow.shown_at);
+            if remaining > 0.0 {
+                ctx.request_repaint_after(Duration::from_secs_f32(remaining));
+            } else if !self.advance_slide(1) {
+                // Fine della presentazione: resta sull'ultima slide
+                if let Some(slideshow) = &mut self.slideshow {
+                    slideshow.paused = true;
+                }
+            }
         }
         
+        self.check_loading_timeout();
+        self.show_write_error_banner(ctx);
+        
         match self.state.clone() {
             AppState::Loading(msg) => {
                 self.show_loading_ui(ctx, &msg);
@@ -214,15 +845,44 @@ impl PhotoComparisonApp {
             AppState::ShowingImages => {
                 self.show_comparison_ui(ctx);
             }
+            AppState::ShowingGroup => {
+                self.show_group_ui(ctx);
+            }
             AppState::ProcessingChoice(choice, path) => {
                 self.process_choice(choice, path);
                 self.show_loading_ui(ctx, "Elaborazione scelta...");
             }
+            AppState::Error(message) => {
+                self.show_error_ui(ctx, &message);
+            }
+            AppState::Summary => {
+                self.show_summary_ui(ctx);
+            }
         }
         
-        if matches!(self.state, AppState::Loading(_) | AppState::ProcessingChoice(_, _)) {
-            ctx.request_repaint();
+        if matches!(self.state, AppState::Loading(_) | AppState::ProcessingChoice(_, _)) || self.peaking_pending.iter().any(Option::is_some) {
+            ctx.request_repaint_after(LOADING_POLL_INTERVAL);
+        }
+    }
+    
+    // Se il thread di caricamento non produce nulla entro LOADING_TIMEOUT si passa alla schermata
+    // di errore, da cui si può riprovare o saltare la coppia. Un risultato che arriva dopo viene
+    // mostrato solo se nel frattempo non si è passati a un'altra coppia
+    fn check_loading_timeout(&mut self) {
+        if !matches!(self.state, AppState::Loading(_)) || self.identical_progress.lock().unwrap().is_some() {
+            self.loading_since = None;
+            return;
+        }
+        let since = *self.loading_since.get_or_insert(self.animation_time);
+        if self.animation_time - since < LOADING_TIMEOUT {
+            return;
         }
+        
+        let files = self.pair_at(self.current_index)
+            .map(|(path1, path2)| format!("\n{}\n{}", path1.display(), path2.display()))
+            .unwrap_or_default();
+        error!("Caricamento della coppia {} bloccato da {} secondi", self.current_index + 1, LOADING_TIMEOUT);
+        self.state = AppState::Error(format!("Nessun risultato dopo {} secondi: il caricamento potrebbe essersi bloccato{}", LOADING_TIMEOUT, files));
     }
     
     fn show_comparison_ui(&mut self, ctx: &Context) {
@@ -233,684 +893,3635 @@ impl PhotoComparisonApp {
             ui.add_space(3.0);
         });
         
+        // Revisione: la coppia è già stata decisa e i comandi di scelta sono disattivati
+        if let Some(frontier) = self.review_return {
+            let decision = self.decisions.get(self.current_index)
+                .and_then(|decision| decision.as_ref())
+                .map(PairDecision::label)
+                .unwrap_or("non decisa");
+            egui::TopBottomPanel::top("review_banner")
+                .frame(Frame::NONE.fill(self.theme.accent_blue.gamma_multiply(0.25)).inner_margin(Margin::symmetric(16, 8)))
+                .show(ctx, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.label(RichText::new(format!("{} REVISIONE coppia {}: {}. Sola lettura, ←/→ per scorrere; la coppia da decidere è la {}",
+                            regular::EYE, self.current_index + 1, decision, frontier + 1))
+                            .size(18.0)
+                            .color(self.theme.accent_blue)
+                            .strong());
+                    });
+                });
+        }
+        
+        // File identici byte per byte: qualunque scelta dà lo stesso risultato
+        if let (Some(a1), Some(a2)) = (&self.current_analysis1, &self.current_analysis2)
+            && self.slideshow.is_none()
+            && a1.is_file_identical(a2)
+        {
+            egui::TopBottomPanel::top("identical_banner")
+                .frame(Frame::NONE.fill(self.theme.accent_green.gamma_multiply(0.25)).inner_margin(Margin::symmetric(16, 8)))
+                .show(ctx, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.label(RichText::new(format!("{} FILE IDENTICI: stesso contenuto byte per byte (SHA256). Salta ({}) o scegli indifferentemente", regular::COPY_SIMPLE, KeyBindings::label(self.keys.skip)))
+                            .size(18.0)
+                            .color(self.theme.accent_green)
+                            .strong());
+                        if self.review_return.is_none()
+                            && ui.button(format!("{} Risolvi tutte le identiche consecutive (Maiusc+I)", regular::FAST_FORWARD)).clicked()
+                        {
+                            self.start_identical_run();
+                        }
+                    });
+                });
+        } else if let Some(ssim) = self.ssim
+            && self.slideshow.is_none()
+            && ssim >= SSIM_NEAR_IDENTICAL
+        {
+            // Stessa foto a meno della compressione: conta solo quale versione è migliore
+            egui::TopBottomPanel::top("identical_banner")
+                .frame(Frame::NONE.fill(self.theme.accent_green.gamma_multiply(0.15)).inner_margin(Margin::symmetric(16, 6)))
+                .show(ctx, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.label(RichText::new(format!("{} VISIVAMENTE IDENTICHE (SSIM {:.1}%): scegli in base a qualità e metadati", regular::EQUALS, ssim * 100.0))
+                            .size(16.0)
+                            .color(self.theme.accent_green)
+                            .strong());
+                    });
+                });
+        }
+        
         // Footer con controlli compatto
         egui::TopBottomPanel::bottom("controls").show(ctx, |ui| {
             ui.add_space(3.0);
-            self.show_modern_controls(ui);
+            if self.slideshow.is_some() {
+                self.show_slideshow_controls(ui);
+            } else {
+                self.show_modern_controls(ui);
+            }
             ui.add_space(3.0);
         });
         
+        if self.slideshow.is_none() {
+            self.show_history_panel(ctx);
+        }
+        
         // Area principale con immagini
         egui::CentralPanel::default().show(ctx, |ui| {
             self.show_modern_images(ui);
         });
         
-        self.handle_keyboard_input(ctx);
+        self.show_toast(ctx);
+        
+        if self.confirm_exit {
+            self.show_exit_dialog(ctx);
+        } else if self.jump_input.is_some() {
+            self.show_jump_dialog(ctx);
+        } else if self.note_input.is_some() {
+            self.show_note_dialog(ctx);
+        } else if self.exif_editor.is_some() {
+            self.show_exif_editor(ctx);
+        } else if self.weights_editor {
+            self.show_weights_editor(ctx);
+        } else if self.shortcut_help {
+            self.show_shortcut_help(ctx);
+        } else if self.slideshow.is_some() {
+            self.handle_slideshow_input(ctx);
+        } else if matches!(self.state, AppState::ShowingGroup) {
+            self.handle_group_input(ctx);
+        } else {
+            self.handle_touch_input(ctx);
+            self.handle_keyboard_input(ctx);
+        }
     }
     
-    fn show_modern_header(&self, ui: &mut egui::Ui) {
-        ui.horizontal(|ui| {
-            // Titolo compatto
-            ui.label(RichText::new("PhotoScope Pro").size(18.0).color(TEXT_PRIMARY).strong());
-            ui.separator();
-            
-            // Progress inline
-            let progress = (self.current_index as f32) / (self.all_pairs.len() as f32);
-            ui.add(egui::ProgressBar::new(progress)
-                .desired_width(200.0)
-                .text(format!("{}/{}", self.current_index + 1, self.all_pairs.len())));
-            
-            ui.separator();
-            
-            // Stats compatti
-            ui.label(RichText::new(format!("{} {} | {} {} | Total: {}",
-                regular::CHECK,
-                *self.selected_count.lock().unwrap(),
-                regular::ARROW_RIGHT,
-                *self.skipped_count.lock().unwrap(),
-                self.all_pairs.len())).size(14.0).color(TEXT_SECONDARY));
-            
-            // Show metadata transfer indicator if pending
-            if self.metadata_transfer_pending {
-                ui.separator();
-                ui.label(RichText::new(format!("{} Metadati pronti per trasferimento", regular::SWAP))
-                    .size(14.0)
-                    .color(ACCENT_GREEN)
-                    .strong());
-            }
-        });
+    fn show_toast(&mut self, ctx: &Context) {
+        let Some((message, start)) = &self.toast else {
+            return;
+        };
+        let remaining = TOAST_DURATION - (self.animation_time - start);
+        if remaining <= 0.0 {
+            self.toast = None;
+            return;
+        }
+        
+        egui::Area::new(egui::Id::new("toast"))
+            .anchor(egui::Align2::CENTER_BOTTOM, Vec2::new(0.0, -90.0))
+            .order(egui::Order::Foreground)
+            .interactable(false)
+            .show(ctx, |ui| {
+                Frame::NONE
+                    .fill(self.theme.card_bg)
+                    .stroke(Stroke::new(1.0, self.theme.accent_green))
+                    .corner_radius(CornerRadius::same(8))
+                    .inner_margin(Margin::symmetric(16, 8))
+                    .show(ui, |ui| {
+                        ui.label(RichText::new(format!("{} {}", regular::CHECK_CIRCLE, message))
+                            .size(15.0)
+                            .color(self.theme.text_primary));
+                    });
+            });
+        
+        // Ridisegna alla scadenza anche se nel frattempo non arrivano eventi
+        ctx.request_repaint_after(Duration::from_secs_f32(remaining));
     }
     
-    fn show_modern_images(&mut self, ui: &mut egui::Ui) {
-        let available_width = ui.available_width();
-        // Calcolo corretto considerando tutti gli spazi: 15px sinistra + 20px centro + 15px destra
-        let total_spacing = 15.0 + 20.0 + 15.0;
-        let card_width = (available_width - total_spacing) / 2.0;
+    // Copia negli appunti il percorso assoluto dell'immagine 1 o 2
+    fn copy_path(&mut self, ctx: &Context, num: u8) {
+        let analysis = if num == 1 { &self.current_analysis1 } else { &self.current_analysis2 };
+        let Some(analysis) = analysis else {
+            return;
+        };
+        let path = std::path::absolute(&analysis.file_path).unwrap_or_else(|_| analysis.file_path.clone());
         
-        let (analysis1, analysis2, texture1, texture2) = match (&self.current_analysis1, &self.current_analysis2) {
-            (Some(a1), Some(a2)) => (a1.clone(), a2.clone(), self.texture1.clone(), self.texture2.clone()),
-            _ => return,
+        ctx.copy_text(path.display().to_string());
+        self.toast = Some((format!("Percorso immagine {} copiato negli appunti", num), self.animation_time));
+    }
+    
+    // Apre nel file manager la cartella dell'immagine, con il file selezionato se possibile
+    fn reveal_file(&mut self, num: u8) {
+        let analysis = if num == 1 { &self.current_analysis1 } else { &self.current_analysis2 };
+        let Some(analysis) = analysis else {
+            return;
         };
+        // Per le voci di un archivio si mostra l'archivio
+        let file_path = archive::on_disk(&analysis.file_path);
+        let path = std::path::absolute(file_path).unwrap_or_else(|_| file_path.to_path_buf());
         
-        let quality_1_better = analysis1.quality_score >= analysis2.quality_score;
-        let quality_2_better = analysis2.quality_score > analysis1.quality_score;
-        let hover1 = self.hover_image1;
-        let hover2 = self.hover_image2;
+        reveal_in_file_manager(&path);
+        self.toast = Some((format!("Apertura della cartella dell'immagine {}", num), self.animation_time));
+    }
+    
+    // Un ESC di troppo non deve chiudere una sessione lunga: se restano coppie si chiede conferma
+    fn request_exit(&mut self) {
+        if self.undecided_pairs() > 0 {
+            self.confirm_exit = true;
+        } else {
+            self.exit_program = true;
+        }
+    }
+    
+    fn undecided_pairs(&self) -> usize {
+        (0..self.all_pairs.len())
+            .filter(|&index| self.decisions.get(index).is_none_or(|decision| decision.is_none()))
+            .count()
+    }
+    
+    fn show_exit_dialog(&mut self, ctx: &Context) {
+        let undecided = self.undecided_pairs();
+        let mut save = ctx.input(|i| i.key_pressed(egui::Key::Enter));
+        let mut discard = false;
+        let mut cancelled = ctx.input(|i| i.key_pressed(egui::Key::Escape));
         
-        // Prima riga: le immagini affiancate
-        ui.horizontal(|ui| {
-            ui.set_max_width(available_width);
-            ui.add_space(15.0);
-            
-            // Immagine 1
-            self.show_image_card(ui, 1, analysis1.clone(), texture1, card_width, 
-                hover1, quality_1_better);
-            
-            ui.add_space(20.0);
-            
-            // Immagine 2
-            self.show_image_card(ui, 2, analysis2.clone(), texture2, card_width,
-                hover2, quality_2_better);
-            
-            ui.add_space(15.0);
-        });
+        egui::Window::new(format!("{} Uscire dalla sessione?", regular::SIGN_OUT))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("Restano {} coppie da decidere su {}.", undecided, self.all_pairs.len()));
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Esci e salva la sessione (Invio)").clicked() {
+                        save = true;
+                    }
+                    if ui.button("Esci senza salvare")
+                        .on_hover_text("I file già copiati restano in output, ma al prossimo avvio non verrà proposto di riprendere")
+                        .clicked()
+                    {
+                        discard = true;
+                    }
+                    if ui.button(format!("Annulla ({})", KeyBindings::label(egui::Key::Escape))).clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
         
-        // Seconda riga: i metadati (se presenti) sotto le immagini
-        if !analysis1.exif_data.is_empty() || !analysis2.exif_data.is_empty() {
-            ui.add_space(8.0);
-            
-            ui.horizontal(|ui| {
-                ui.set_max_width(available_width);
-                ui.add_space(15.0);
-                
-                // Metadati immagine 1 (o spazio vuoto per allineamento)
-                ui.vertical(|ui| {
-                    ui.set_max_width(card_width);
-                    if !analysis1.exif_data.is_empty() {
-                        self.show_metadata_card(ui, &analysis1.exif_data, card_width);
-                    } else {
-                        // Spazio vuoto per mantenere allineamento
-                        ui.allocate_space(Vec2::new(card_width, 0.0));
+        if save {
+            self.save_session();
+            self.exit_program = true;
+        } else if discard {
+            SessionState::clear(&self.file_manager);
+            self.unsaved_decisions = 0;
+            self.exit_program = true;
+        } else if cancelled {
+            self.confirm_exit = false;
+        }
+    }
+    
+    fn show_jump_dialog(&mut self, ctx: &Context) {
+        let total = self.all_pairs.len();
+        let mut confirmed = false;
+        let mut cancelled = ctx.input(|i| i.key_pressed(egui::Key::Escape));
+        
+        egui::Window::new(format!("{} Vai alla coppia", regular::HASH))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if let Some(input) = self.jump_input.as_mut() {
+                        let response = ui.add(egui::TextEdit::singleline(input).desired_width(80.0));
+                        response.request_focus();
+                        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            confirmed = true;
+                        }
                     }
+                    ui.label(RichText::new(format!("di {}", total)).color(self.theme.text_secondary));
                 });
                 
-                ui.add_space(20.0);
-                
-                // Metadati immagine 2 (o spazio vuoto per allineamento)
-                ui.vertical(|ui| {
-                    ui.set_max_width(card_width);
-                    if !analysis2.exif_data.is_empty() {
-                        self.show_metadata_card(ui, &analysis2.exif_data, card_width);
-                    } else {
-                        // Spazio vuoto per mantenere allineamento
-                        ui.allocate_space(Vec2::new(card_width, 0.0));
+                ui.horizontal(|ui| {
+                    if ui.button("Vai").clicked() {
+                        confirmed = true;
+                    }
+                    if ui.button("Annulla").clicked() {
+                        cancelled = true;
                     }
                 });
+            });
+        
+        if confirmed {
+            // Numeri fuori intervallo vengono portati alla prima o all'ultima coppia
+            if let Some(number) = self.jump_input.as_deref().and_then(|input| input.trim().parse::<usize>().ok()) {
+                self.jump_to(number.clamp(1, total.max(1)) - 1);
+            }
+            self.jump_input = None;
+        } else if cancelled {
+            self.jump_input = None;
+        }
+    }
+    
+    fn show_note_dialog(&mut self, ctx: &Context) {
+        let mut confirmed = false;
+        let mut cancelled = ctx.input(|i| i.key_pressed(egui::Key::Escape));
+        
+        egui::Window::new(format!("{} Nota coppia {}", regular::NOTE_PENCIL, self.current_index + 1))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                if let Some(input) = self.note_input.as_mut() {
+                    let response = ui.add(egui::TextEdit::singleline(input).desired_width(400.0).hint_text("es. bokeh migliore, controllare fuoco"));
+                    response.request_focus();
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        confirmed = true;
+                    }
+                }
                 
-                ui.add_space(15.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Salva").clicked() {
+                        confirmed = true;
+                    }
+                    if ui.button("Annulla").clicked() {
+                        cancelled = true;
+                    }
+                });
             });
+        
+        if confirmed {
+            let note = self.note_input.take().unwrap_or_default();
+            self.update_annotation(|annotation| annotation.note = note.trim().to_string());
+        } else if cancelled {
+            self.note_input = None;
         }
     }
     
-    fn show_image_card(&mut self, ui: &mut egui::Ui, 
-                       num: u8, 
-                       analysis: ImageAnalysis, 
-                       texture: Option<TextureHandle>,
-                       width: f32,
-                       is_hovered: bool,
-                       is_best: bool) {
-        ui.vertical(|ui| {
+    // Riaprendo l'editor si riparte dalle correzioni già fatte, altrimenti dai valori del file
+    fn open_exif_editor(&mut self, num: u8) {
+        let analysis = if num == 1 { &self.current_analysis1 } else { &self.current_analysis2 };
+        let Some(analysis) = analysis else {
+            return;
+        };
+        let fields = self.exif_edits[num as usize - 1].clone()
+            .unwrap_or_else(|| ExifFields::read(&analysis.file_path));
+        self.exif_editor = Some(ExifEditor { num, fields });
+    }
+    
+    fn show_exif_editor(&mut self, ctx: &Context) {
+        let mut confirmed = false;
+        let mut cancelled = ctx.input(|i| i.key_pressed(egui::Key::Escape));
+        let mut switch_to = None;
+        let theme = self.theme;
+        let Some(editor) = self.exif_editor.as_mut() else {
+            return;
+        };
+        let date_valid = editor.fields.date_is_valid();
+        
+        egui::Window::new(format!("{} Metadati immagine {}", regular::PENCIL_SIMPLE, editor.num))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for num in [1, 2] {
+                        if ui.selectable_label(editor.num == num, format!("Immagine {}", num)).clicked() && editor.num != num {
+                            switch_to = Some(num);
+                        }
+                    }
+                });
+                ui.add_space(4.0);
+                
+                let fields = &mut editor.fields;
+                egui::Grid::new("exif_editor").num_columns(2).spacing([12.0, 6.0]).show(ui, |ui| {
+                    ui.label("Data di scatto");
+                    let date_color = if date_valid { theme.text_primary } else { theme.danger_red };
+                    ui.add(egui::TextEdit::singleline(&mut fields.date_time_original)
+                        .desired_width(300.0)
+                        .text_color(date_color)
+                        .hint_text("AAAA:MM:GG HH:MM:SS"));
+                    ui.end_row();
+                    
+                    ui.label("Autore");
+                    ui.add(egui::TextEdit::singleline(&mut fields.artist).desired_width(300.0));
+                    ui.end_row();
+                    
+                    ui.label("Copyright");
+                    ui.add(egui::TextEdit::singleline(&mut fields.copyright).desired_width(300.0));
+                    ui.end_row();
+                });
+                
+                ui.label(RichText::new("Le modifiche vengono scritte solo nella copia in output, se questa immagine viene scelta")
+                    .size(12.0)
+                    .color(theme.text_secondary));
+                ui.add_space(4.0);
+                
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(date_valid, egui::Button::new("Salva (Invio)")).clicked() {
+                        confirmed = true;
+                    }
+                    if ui.button("Annulla").clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+        
+        if ctx.input(|i| i.key_pressed(egui::Key::Enter)) && date_valid {
+            confirmed = true;
+        }
+        
+        if confirmed {
+            let editor = self.exif_editor.take().unwrap();
+            self.exif_edits[editor.num as usize - 1] = Some(editor.fields);
+            self.toast = Some((format!("Metadati dell'immagine {} corretti", editor.num), self.animation_time));
+        } else if cancelled {
+            self.exif_editor = None;
+        } else if let Some(num) = switch_to {
+            self.open_exif_editor(num);
+        }
+    }
+    
+    fn rescored(&self, mut analysis: ImageAnalysis) -> ImageAnalysis {
+        analysis.quality_score = analysis.calculate_quality_score(&self.scoring_weights);
+        analysis
+    }
+    
+    // Pesi del punteggio provati dal vivo sulla coppia mostrata. Valgono per il resto della
+    // sessione; la riga --weights permette di riusarli in un'esecuzione batch
+    fn show_weights_editor(&mut self, ctx: &Context) {
+        let mut closed = ctx.input(|i| i.key_pressed(egui::Key::Escape) || i.key_pressed(egui::Key::O));
+        let mut weights = self.scoring_weights;
+        let mut copy_arg = false;
+        
+        egui::Window::new(format!("{} Pesi del punteggio", regular::SLIDERS_HORIZONTAL))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::RIGHT_TOP, Vec2::new(-16.0, 60.0))
+            .show(ctx, |ui| {
+                let labels = ["Risoluzione", "Compressione", "Nitidezza", "Rumore"];
+                for (component, label) in labels.into_iter().enumerate() {
+                    let mut value = weights.components()[component];
+                    if ui.add(egui::Slider::new(&mut value, 0..=100).text(label)).changed() {
+                        weights = weights.with_component(component, value);
+                    }
+                }
+                ui.add_space(4.0);
+                
+                if let (Some(analysis1), Some(analysis2)) = (&self.current_analysis1, &self.current_analysis2) {
+                    let score1 = analysis1.calculate_quality_score(&weights);
+                    let score2 = analysis2.calculate_quality_score(&weights);
+                    ui.label(RichText::new(format!("Punteggi con questi pesi: immagine 1 {}, immagine 2 {}", score1, score2))
+                        .color(self.theme.text_primary));
+                }
+                
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(format!("--weights {}", weights)).monospace().color(self.theme.text_secondary));
+                    if ui.small_button(regular::COPY).on_hover_text("Copia l'opzione per la riga di comando").clicked() {
+                        copy_arg = true;
+                    }
+                });
+                ui.add_space(4.0);
+                
+                ui.horizontal(|ui| {
+                    if ui.button("Predefiniti").clicked() {
+                        weights = weights.with_default_components();
+                    }
+                    if ui.button("Chiudi (O)").clicked() {
+                        closed = true;
+                    }
+                });
+            });
+        
+        if weights != self.scoring_weights {
+            self.scoring_weights = weights;
+            self.current_analysis1 = self.current_analysis1.take().map(|analysis| self.rescored(analysis));
+            self.current_analysis2 = self.current_analysis2.take().map(|analysis| self.rescored(analysis));
+        }
+        if copy_arg {
+            ctx.copy_text(format!("--weights {}", weights));
+            self.toast = Some(("Opzione --weights copiata negli appunti".to_string(), self.animation_time));
+        }
+        if closed {
+            self.weights_editor = false;
+        }
+    }
+    
+    // Tasti del confronto divisi per sezione, con quelli personalizzati letti da keys.json: l'elenco
+    // segue le stesse associazioni usate da handle_decision_input e handle_keyboard_input
+    fn shortcut_legend(&self) -> [(&'static str, Vec<(String, String)>); 2] {
+        let keys = self.keys;
+        let key = |key: egui::Key| KeyBindings::label(key).to_string();
+        let fixed = |label: &str| label.to_string();
+        let decisions = vec![
+            (key(keys.choose_left), "Scegli l'immagine 1".to_string()),
+            (key(keys.choose_right), "Scegli l'immagine 2".to_string()),
+            (format!("Maiusc+{}/{}", key(keys.choose_left), key(keys.choose_right)), "Usa i metadati dell'immagine 1 o 2 per la copia scelta".to_string()),
+            (key(keys.transfer_meta), "Trasferisci i metadati dell'altra immagine".to_string()),
+            (fixed("B"), "Tieni entrambe".to_string()),
+            (key(keys.skip), "Salta la coppia".to_string()),
+            (key(keys.defer), "Rimanda la coppia in fondo alla coda".to_string()),
+            (key(keys.undo), "Torna alla coppia precedente annullandone la decisione".to_string()),
+            (fixed("X"), "Inverti le due cartelle".to_string()),
+            (fixed("N"), "Nota sulla coppia".to_string()),
+            (fixed("Ctrl+1-3"), format!("Tag rapidi: {}", QUICK_TAGS.join(", "))),
+            (fixed("G"), "Vai alla coppia numero...".to_string()),
+            (fixed("Maiusc+E"), "Correggi i campi EXIF dell'immagine sotto il cursore".to_string()),
+            (fixed("Maiusc+I"), "Risolvi in blocco le coppie identiche consecutive".to_string()),
+            (key(keys.exit), "Esci (con conferma se restano coppie)".to_string()),
+        ];
+        let view = [
+            ("←/→", "Rivedi le coppie già decise"),
+            ("C/V", "Copia il percorso dell'immagine 1 o 2"),
+            ("Maiusc+C/V", "Mostra l'immagine 1 o 2 nel file manager"),
+            ("Q/E", "Ruota a sinistra o a destra l'immagine sotto il cursore"),
+            ("F", "Piena risoluzione"),
+            ("L", "Lente d'ingrandimento"),
+            ("H", "Uniforma l'esposizione delle due immagini"),
+            ("K", "Focus peaking"),
+            ("M", "Differenze tra i metadati"),
+            ("J", "Cronologia delle decisioni"),
+            ("O", "Pesi del punteggio"),
+            ("T", "Tema successivo"),
+            ("PagSu/PagGiù", "Pagina precedente o successiva dei TIFF multipagina"),
+            ("+/-", "Ingrandisci o riduci l'area immagini"),
+            ("Ctrl+rotella", "Zoom sulle immagini (doppio clic per tornare all'intera)"),
+            ("Maiusc+trascina", "Zona di messa a fuoco: nitidezza misurata solo lì, su entrambe (Maiusc+clic la toglie)"),
+            ("F11", "Schermo intero"),
+            ("F1, ?", "Questo elenco"),
+        ].into_iter().map(|(key, action)| (fixed(key), fixed(action))).collect();
+        [("Decisioni", decisions), ("Visualizzazione", view)]
+    }
+    
+    fn show_shortcut_help(&mut self, ctx: &Context) {
+        let mut closed = ctx.input(|i| {
+            i.key_pressed(egui::Key::Escape) || i.key_pressed(egui::Key::F1) || i.key_pressed(egui::Key::Questionmark)
+        });
+        
+        egui::Window::new(format!("{} Scorciatoie da tastiera", regular::KEYBOARD))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().max_height(ctx.screen_rect().height() * 0.75).show(ui, |ui| {
+                    for (section, shortcuts) in self.shortcut_legend() {
+                        ui.label(RichText::new(section).size(15.0).strong().color(self.theme.text_primary));
+                        egui::Grid::new(section).num_columns(2).spacing([24.0, 4.0]).striped(true).show(ui, |ui| {
+                            for (key, action) in shortcuts {
+                                ui.label(RichText::new(key).monospace().strong().color(self.theme.accent_blue));
+                                ui.label(RichText::new(action).color(self.theme.text_primary));
+                                ui.end_row();
+                            }
+                        });
+                        ui.add_space(8.0);
+                    }
+                });
+                
+                if ui.button("Chiudi (F1)").clicked() {
+                    closed = true;
+                }
+            });
+        
+        if closed {
+            self.shortcut_help = false;
+        }
+    }
+    
+    // Scrive nella copia in output le correzioni EXIF fatte sull'immagine scelta
+    fn apply_exif_edits(&self, num: u8, source: &Path, dest_path: &Path) {
+        let Some(fields) = &self.exif_edits[num as usize - 1] else {
+            return;
+        };
+        let changes = fields.changes(&ExifFields::read(source));
+        if changes.is_empty() {
+            return;
+        }
+        self.file_manager.forget_copy(dest_path);
+        if let Err(e) = FileManager::write_exif_fields(dest_path, &changes) {
+            error!("Impossibile correggere i metadati di {:?}: {:#}", dest_path, e);
+        }
+    }
+    
+    fn toggle_tag(&mut self, tag: &str) {
+        self.update_annotation(|annotation| {
+            if let Some(position) = annotation.tags.iter().position(|t| t == tag) {
+                annotation.tags.remove(position);
+            } else {
+                annotation.tags.push(tag.to_string());
+            }
+        });
+    }
+    
+    // Modifica l'annotazione della coppia corrente e salva subito sessione e report
+    fn update_annotation(&mut self, change: impl FnOnce(&mut PairAnnotation)) {
+        let annotation = self.annotations.entry(self.current_index).or_default();
+        change(annotation);
+        if annotation.is_empty() {
+            self.annotations.remove(&self.current_index);
+        }
+        self.save_session();
+    }
+    
+    fn jump_to(&mut self, index: usize) {
+        if index == self.current_index || index >= self.all_pairs.len() {
+            return;
+        }
+        
+        self.navigation_history.push(self.current_index);
+        self.current_index = index;
+        
+        self.metadata_transfer_pending = false;
+        self.metadata_transfer_source = None;
+        
+        self.save_session();
+        self.state = AppState::Loading(format!("Caricamento coppia {}...", index + 1));
+        self.load_current_pair();
+    }
+    
+    // Frecce sinistra/destra: rivede le coppie già passate senza modificarle. La coppia da
+    // decidere resta in review_return e si ritrova tornando avanti fino a lei
+    fn review_step(&mut self, step: isize) {
+        if let Some(index) = self.current_index.checked_add_signed(step) {
+            self.review_to(index);
+        }
+    }
+    
+    fn review_to(&mut self, index: usize) {
+        let frontier = self.review_return.unwrap_or(self.current_index);
+        if index == self.current_index || index > frontier {
+            return;
+        }
+        
+        // Nessun salvataggio: la sessione ripresa riparte comunque dalla coppia da decidere
+        self.review_return = (index < frontier).then_some(frontier);
+        self.current_index = index;
+        
+        self.metadata_transfer_pending = false;
+        self.metadata_transfer_source = None;
+        
+        self.state = AppState::Loading(format!("Caricamento coppia {}...", index + 1));
+        self.load_current_pair();
+    }
+    
+    // Striscia delle ultime decisioni sopra i controlli: un clic riapre la coppia in revisione
+    fn show_history_panel(&mut self, ctx: &Context) {
+        // Le decisioni annullate (P) non fanno più parte della cronologia
+        let decisions = &self.decisions;
+        self.recent_decisions.retain(|recent| decisions.get(recent.index).is_some_and(|decision| decision.is_some()));
+        if self.recent_decisions.is_empty() {
+            return;
+        }
+        
+        for recent in self.recent_decisions.iter_mut().filter(|recent| recent.textures.is_none()) {
+            recent.textures = Some([0, 1].map(|side| {
+                let rgba = recent.thumbnails[side].to_rgba8();
+                let color_image = ColorImage::from_rgba_unmultiplied([rgba.width() as usize, rgba.height() as usize], rgba.as_raw());
+                ctx.load_texture(format!("history{}_{}", recent.index, side), color_image, egui::TextureOptions::default())
+            }));
+        }
+        
+        let mut clicked = None;
+        egui::TopBottomPanel::bottom("history").show(ctx, |ui| {
+            let header = egui::CollapsingHeader::new(RichText::new(format!("{} Ultime decisioni (J)", regular::CLOCK_COUNTER_CLOCKWISE))
+                    .size(13.0)
+                    .color(self.theme.text_secondary))
+                .open(Some(self.history_panel))
+                .show(ui, |ui| {
+                    egui::ScrollArea::horizontal().show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            for recent in &self.recent_decisions {
+                                let Some(textures) = &recent.textures else {
+                                    continue;
+                                };
+                                let label = self.decisions[recent.index].as_ref().map(PairDecision::label).unwrap_or_default();
+                                let stroke = if recent.index == self.current_index {
+                                    Stroke::new(2.0, self.theme.accent_blue)
+                                } else {
+                                    Stroke::new(1.0, self.theme.border)
+                                };
+                                
+                                let response = Frame::NONE
+                                    .fill(self.theme.card_bg)
+                                    .stroke(stroke)
+                                    .corner_radius(CornerRadius::same(6))
+                                    .inner_margin(Margin::same(4))
+                                    .show(ui, |ui| {
+                                        ui.horizontal(|ui| {
+                                            for (texture, chosen) in textures.iter().zip(recent.chosen) {
+                                                let (rect, _) = ui.allocate_exact_size(Vec2::splat(HISTORY_THUMBNAIL_SIZE as f32), egui::Sense::hover());
+                                                ui.painter().rect_filled(rect, CornerRadius::same(3), self.theme.image_bg);
+                                                let size = texture.size_vec2();
+                                                let scale = (rect.width() / size.x).min(rect.height() / size.y);
+                                                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                                                let tint = if chosen || recent.chosen == [false, false] { Color32::WHITE } else { Color32::from_gray(110) };
+                                                ui.painter().image(texture.id(), egui::Rect::from_center_size(rect.center(), size * scale), uv, tint);
+                                                if chosen {
+                                                    ui.painter().rect_stroke(rect, CornerRadius::same(3), Stroke::new(2.0, self.theme.accent_green), egui::StrokeKind::Inside);
+                                                }
+                                            }
+                                        });
+                                        ui.label(RichText::new(format!("#{} {}", recent.index + 1, label)).size(11.0).color(self.theme.text_secondary));
+                                    })
+                                    .response
+                                    .interact(egui::Sense::click());
+                                
+                                if response.on_hover_text(format!("Rivedi la coppia {}", recent.index + 1)).clicked() {
+                                    clicked = Some(recent.index);
+                                }
+                            }
+                        });
+                    });
+                });
+            if header.header_response.clicked() {
+                self.history_panel = !self.history_panel;
+            }
+        });
+        
+        if let Some(index) = clicked {
+            self.review_to(index);
+        }
+    }
+    
+    fn show_modern_header(&self, ui: &mut egui::Ui) {
+        // Gli avvisi possono essere molti: vanno a capo invece di uscire dalla finestra
+        ui.horizontal_wrapped(|ui| {
+            // Titolo compatto
+            ui.label(RichText::new("PhotoScope Pro").size(18.0).color(self.theme.text_primary).strong());
+            ui.separator();
+            
+            // Progress inline
+            let progress = (self.current_index as f32) / (self.all_pairs.len() as f32);
+            ui.add(egui::ProgressBar::new(progress)
+                .desired_width(200.0)
+                .text(format!("{}/{}", self.current_index + 1, self.all_pairs.len())));
+            
+            ui.separator();
+            
+            // Stats compatti
+            if self.slideshow.is_none() {
+                ui.label(RichText::new(format!("{} {} | {} {} | {} {} | Total: {}",
+                    regular::CHECK,
+                    *self.selected_count.lock().unwrap(),
+                    regular::ARROW_RIGHT,
+                    *self.skipped_count.lock().unwrap(),
+                    regular::COPY,
+                    *self.kept_both_count.lock().unwrap(),
+                    self.all_pairs.len())).size(14.0).color(self.theme.text_secondary));
+                if !self.deferred.is_empty() {
+                    ui.label(RichText::new(format!("{} {} rimandate", regular::HOURGLASS, self.deferred.len()))
+                        .size(14.0)
+                        .color(self.theme.accent_blue));
+                }
+            }
+            
+            // Deduplicazione: gruppo corrente e confronto all'interno del gruppo
+            if let Some(groups) = &self.pair_groups
+                && let Some(&group) = groups.get(self.current_index)
+            {
+                let round = groups[..self.current_index].iter().filter(|&&g| g == group).count() + 1;
+                let rounds = groups.iter().filter(|&&g| g == group).count();
+                let total_groups = groups.last().map(|g| g + 1).unwrap_or(0);
+                ui.separator();
+                ui.label(RichText::new(format!("{} Gruppo {}/{} · confronto {}/{}",
+                    regular::STACK, group + 1, total_groups, round, rounds))
+                    .size(14.0)
+                    .color(self.theme.gold_star));
+            }
+            
+            // Differenze tra le due immagini: la freccia punta verso la maggiore
+            if let (Some(a1), Some(a2)) = (&self.current_analysis1, &self.current_analysis2)
+                && !self.is_single_image()
+            {
+                ui.separator();
+                Self::show_delta(ui, &self.theme, "MP", a1.megapixels, a2.megapixels, 1);
+                Self::show_delta(ui, &self.theme, "MB", a1.file_size_mb, a2.file_size_mb, 1);
+                Self::show_delta(ui, &self.theme, "Qualità", a1.quality_score as f64, a2.quality_score as f64, 0);
+                
+                if let Some(ssim) = self.ssim {
+                    let color = if ssim >= SSIM_NEAR_IDENTICAL {
+                        self.theme.accent_green
+                    } else if ssim < SSIM_DIFFERENT_PHOTO {
+                        self.theme.danger_red
+                    } else {
+                        self.theme.text_secondary
+                    };
+                    ui.separator();
+                    ui.label(RichText::new(format!("SSIM {:.1}%", ssim * 100.0)).size(14.0).color(color))
+                        .on_hover_text("Somiglianza strutturale: vicina al 100% è la stessa foto a qualità diverse, bassa per foto diverse con lo stesso nome");
+                }
+            }
+            
+            // Stesso nome ma fotocamere diverse: probabilmente foto diverse
+            if let (Some(a1), Some(a2)) = (&self.current_analysis1, &self.current_analysis2)
+                && let Some((camera1, camera2)) = a1.camera_mismatch(a2)
+            {
+                ui.separator();
+                ui.label(RichText::new(format!("{} Fotocamere diverse: {} / {}", regular::WARNING, camera1, camera2))
+                    .size(14.0)
+                    .color(self.theme.danger_red)
+                    .strong());
+            }
+            
+            // Dimensioni EXIF non aggiornate: il file è stato modificato dopo lo scatto
+            if let (Some(a1), Some(a2)) = (&self.current_analysis1, &self.current_analysis2) {
+                let stale: Vec<String> = [a1, a2].iter()
+                    .enumerate()
+                    .filter_map(|(index, analysis)| {
+                        let (width, height) = analysis.exif_dimensions_mismatch()?;
+                        Some(format!("{}: EXIF {}×{}, reali {}×{}", index + 1, width, height, analysis.width, analysis.height))
+                    })
+                    .collect();
+                if !stale.is_empty() {
+                    ui.separator();
+                    ui.label(RichText::new(format!("{} Dimensioni EXIF diverse ({})", regular::RULER, stale.join("; ")))
+                        .size(14.0)
+                        .color(self.theme.warning)
+                        .strong())
+                        .on_hover_text("PixelXDimension/PixelYDimension non corrispondono all'immagine: probabilmente modificata (ridimensionata o ritagliata) dopo lo scatto, con metadati non aggiornati");
+                }
+            }
+            
+            // Un ritaglio non è un duplicato di qualità
+            if let Some(cropped) = self.cropped {
+                let full = if cropped == 1 { 2 } else { 1 };
+                ui.separator();
+                ui.label(RichText::new(format!("{} L'immagine {} sembra un ritaglio dell'immagine {}", regular::CROP, cropped, full))
+                    .size(14.0)
+                    .color(self.theme.danger_red)
+                    .strong());
+            } else if let (Some(a1), Some(a2)) = (&self.current_analysis1, &self.current_analysis2)
+                && let Some((ratio1, ratio2)) = a1.aspect_ratio_mismatch(a2)
+            {
+                // Proporzioni diverse senza ritaglio riconosciuto: probabilmente reinquadrata (es. export social)
+                ui.separator();
+                ui.label(RichText::new(format!("{} Proporzioni diverse: {:.2} / {:.2}", regular::FRAME_CORNERS, ratio1, ratio2))
+                    .size(14.0)
+                    .color(self.theme.danger_red)
+                    .strong())
+                    .on_hover_text("Probabilmente non è la stessa foto o è stata reinquadrata: non trattarla come un semplice duplicato");
+            }
+            
+            // Stessi pixel: l'unica differenza reale sono i metadati (i file identici hanno il loro banner)
+            if let (Some(a1), Some(a2)) = (&self.current_analysis1, &self.current_analysis2)
+                && a1.is_pixel_identical(a2)
+                && !a1.is_file_identical(a2)
+            {
+                ui.separator();
+                ui.label(RichText::new(format!("{} Pixel identici: differiscono solo i metadati (W per trasferirli)", regular::EQUALS))
+                    .size(14.0)
+                    .color(self.theme.gold_star)
+                    .strong());
+            }
+            
+            if self.full_resolution {
+                ui.separator();
+                ui.label(RichText::new(format!("{} Piena risoluzione (F)", regular::MAGNIFYING_GLASS_PLUS))
+                    .size(14.0)
+                    .color(self.theme.accent_blue));
+            }
+            
+            if self.focus_peaking {
+                ui.separator();
+                ui.label(RichText::new(format!("{} Focus peaking (K)", regular::SCAN))
+                    .size(14.0)
+                    .color(self.theme.danger_red))
+                    .on_hover_text("In rosso i bordi netti: l'immagine con più bordi evidenziati sul soggetto è quella a fuoco");
+            }
+            
+            if self.normalize_exposure {
+                ui.separator();
+                ui.label(RichText::new(format!("{} Esposizione pareggiata (H)", regular::CIRCLE_HALF))
+                    .size(14.0)
+                    .color(self.theme.accent_blue))
+                    .on_hover_text("Solo a schermo: i file copiati restano invariati");
+            }
+            
+            // Nota e tag della coppia corrente
+            if let Some(annotation) = self.annotations.get(&self.current_index) {
+                ui.separator();
+                if !annotation.tags.is_empty() {
+                    ui.label(RichText::new(format!("{} {}", regular::TAG, annotation.tags.join(", ")))
+                        .size(14.0)
+                        .color(self.theme.gold_star));
+                }
+                if !annotation.note.is_empty() {
+                    ui.label(RichText::new(format!("{} {}", regular::NOTE_PENCIL, annotation.note))
+                        .size(14.0)
+                        .color(self.theme.text_primary));
+                }
+            }
+            
+            // Show metadata transfer indicator if pending
+            if self.metadata_transfer_pending {
+                ui.separator();
+                ui.label(RichText::new(format!("{} Metadati pronti per trasferimento", regular::SWAP))
+                    .size(14.0)
+                    .color(self.theme.accent_green)
+                    .strong());
+            }
+        });
+    }
+    
+    fn show_delta(ui: &mut egui::Ui, theme: &Theme, label: &str, value1: f64, value2: f64, decimals: usize) {
+        let delta = (value1 - value2).abs();
+        // Differenze che si arrotondano a zero contano come uguali
+        let (arrow, color) = if (delta * 10f64.powi(decimals as i32)).round() == 0.0 {
+            (regular::EQUALS, theme.text_secondary)
+        } else if value1 > value2 {
+            (regular::CARET_LEFT, theme.accent_blue)
+        } else {
+            (regular::CARET_RIGHT, theme.accent_orange)
+        };
+        
+        ui.label(RichText::new(format!("{} {} {:.*}", label, arrow, decimals, delta))
+            .size(14.0)
+            .color(color));
+    }
+    
+    fn show_modern_images(&mut self, ui: &mut egui::Ui) {
+        let available_width = ui.available_width();
+        // Calcolo corretto considerando tutti gli spazi: 15px sinistra + 20px centro + 15px destra
+        let total_spacing = 15.0 + 20.0 + 15.0;
+        let card_width = (available_width - total_spacing) / 2.0;
+        
+        let (analysis1, analysis2, texture1, texture2) = match (&self.current_analysis1, &self.current_analysis2) {
+            (Some(a1), Some(a2)) => (a1.clone(), a2.clone(), self.texture1.clone(), self.texture2.clone()),
+            _ => return,
+        };
+        
+        let quality_1_better = analysis1.is_preferred_over(&analysis2, &self.scoring_weights);
+        let quality_2_better = !quality_1_better;
+        let hover1 = self.hover_image1;
+        let hover2 = self.hover_image2;
+        self.loupe_target = None;
+        self.measure_focus_region();
+        
+        let single = self.is_single_image();
+        let has_metadata = !single && (!analysis1.exif_data.is_empty() || !analysis2.exif_data.is_empty());
+        self.image_height = self.fit_image_height(ui.available_height(), has_metadata);
+        // Le card la rimisurano mentre vengono disegnate
+        self.image_header_height = 0.0;
+        
+        // Presentazione dei file scelti: una sola immagine a tutta larghezza
+        if single {
+            ui.horizontal(|ui| {
+                ui.add_space(15.0);
+                self.show_image_card(ui, 1, analysis1, texture1, available_width - 30.0, hover1, false);
+            });
+            if self.loupe_enabled
+                && let Some(target) = self.loupe_target
+            {
+                self.show_loupe(ui.ctx(), 1, target);
+            }
+            return;
+        }
+        
+        // Prima riga: le immagini affiancate
+        ui.horizontal(|ui| {
+            ui.set_max_width(available_width);
+            ui.add_space(15.0);
+            
+            // Immagine 1
+            self.show_image_card(ui, 1, analysis1.clone(), texture1, card_width, 
+                hover1, quality_1_better);
+            
+            ui.add_space(20.0);
+            
+            // Immagine 2
+            self.show_image_card(ui, 2, analysis2.clone(), texture2, card_width,
+                hover2, quality_2_better);
+            
+            ui.add_space(15.0);
+        });
+        
+        // La lente segue il cursore e viene replicata nello stesso punto dell'altra immagine
+        if self.loupe_enabled
+            && let Some(target) = self.loupe_target
+        {
+            self.show_loupe(ui.ctx(), 1, target);
+            self.show_loupe(ui.ctx(), 2, target);
+        }
+        
+        // Seconda riga: i metadati (se presenti) sotto le immagini
+        if self.metadata_diff && (!analysis1.exif_data.is_empty() || !analysis2.exif_data.is_empty()) {
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                ui.add_space(15.0);
+                self.show_metadata_diff_card(ui, &analysis1.exif_data, &analysis2.exif_data, 2.0 * card_width + 20.0);
+            });
+        } else if !analysis1.exif_data.is_empty() || !analysis2.exif_data.is_empty() {
+            ui.add_space(8.0);
+            
+            ui.horizontal(|ui| {
+                ui.set_max_width(available_width);
+                ui.add_space(15.0);
+                
+                // Metadati immagine 1 (o spazio vuoto per allineamento)
+                ui.vertical(|ui| {
+                    ui.set_max_width(card_width);
+                    if !analysis1.exif_data.is_empty() {
+                        self.show_metadata_card(ui, &analysis1.exif_data, card_width);
+                    } else {
+                        // Spazio vuoto per mantenere allineamento
+                        ui.allocate_space(Vec2::new(card_width, 0.0));
+                    }
+                });
+                
+                ui.add_space(20.0);
+                
+                // Metadati immagine 2 (o spazio vuoto per allineamento)
+                ui.vertical(|ui| {
+                    ui.set_max_width(card_width);
+                    if !analysis2.exif_data.is_empty() {
+                        self.show_metadata_card(ui, &analysis2.exif_data, card_width);
+                    } else {
+                        // Spazio vuoto per mantenere allineamento
+                        ui.allocate_space(Vec2::new(card_width, 0.0));
+                    }
+                });
+                
+                ui.add_space(15.0);
+            });
+        }
+    }
+    
+    // Altezza dell'area immagine: lo spazio libero nel pannello meno le righe della card sopra
+    // l'immagine, i margini e lo spazio minimo dei metadati, scalata dal fattore scelto con +/-
+    fn fit_image_height(&self, available_height: f32, has_metadata: bool) -> f32 {
+        // Margini interni della card (16px * 2) e del frame immagine (8px * 2)
+        let card_height = self.image_header_height + 32.0 + 16.0;
+        let metadata_height = if has_metadata { METADATA_MIN_HEIGHT + 8.0 } else { 0.0 };
+        let fit = (available_height - card_height - metadata_height).max(MIN_IMAGE_HEIGHT);
+        // Ingrandita, l'immagine toglie spazio ai metadati ma resta dentro il pannello
+        let max = (available_height - card_height).max(MIN_IMAGE_HEIGHT);
+        (fit * self.image_height_factor).clamp(MIN_IMAGE_HEIGHT, max)
+    }
+    
+    fn adjust_image_height(&mut self, grow: bool) {
+        let step = if grow { IMAGE_HEIGHT_FACTOR_STEP } else { -IMAGE_HEIGHT_FACTOR_STEP };
+        let (min, max) = IMAGE_HEIGHT_FACTOR_RANGE;
+        // Arrotondato al passo, per non accumulare errori sommando decimi
+        let factor = ((self.image_height_factor + step) / IMAGE_HEIGHT_FACTOR_STEP).round() * IMAGE_HEIGHT_FACTOR_STEP;
+        self.image_height_factor = factor.clamp(min, max);
+        self.toast = Some((format!("Altezza immagini: {:.0}%", self.image_height_factor * 100.0), self.animation_time));
+    }
+    
+    fn show_image_card(&mut self, ui: &mut egui::Ui, 
+                       num: u8, 
+                       analysis: ImageAnalysis, 
+                       texture: Option<ImageTiles>,
+                       width: f32,
+                       is_hovered: bool,
+                       is_best: bool) {
+        let mut copy_requested = false;
+        let mut reveal_requested = false;
+        ui.vertical(|ui| {
             ui.set_max_width(width);
             
-            // Card container
-            let card_bg = if is_hovered { CARD_HOVER } else { CARD_BG };
-            Frame::NONE
-                .fill(card_bg)
-                .corner_radius(CornerRadius::same(12))
-                .stroke(if is_best { 
-                    Stroke::new(2.0, ACCENT_GREEN)
-                } else { 
-                    Stroke::new(1.0, Color32::from_gray(50))
-                })
-                .shadow(egui::epaint::Shadow {
-                    offset: [0, if is_hovered { 4 } else { 2 }],
-                    blur: if is_hovered { 12 } else { 4 },
-                    spread: 0,
-                    color: Color32::from_black_alpha(60),
-                })
-                .inner_margin(Margin::same(16))
-                .show(ui, |ui| {
-                    // Header minimo della card
+            // Card container
+            let card_bg = if is_hovered { self.theme.card_hover } else { self.theme.card_bg };
+            Frame::NONE
+                .fill(card_bg)
+                .corner_radius(CornerRadius::same(12))
+                .stroke(if is_best { 
+                    Stroke::new(2.0, self.theme.accent_green)
+                } else { 
+                    Stroke::new(1.0, self.theme.border)
+                })
+                .shadow(egui::epaint::Shadow {
+                    offset: [0, if is_hovered { 4 } else { 2 }],
+                    blur: if is_hovered { 12 } else { 4 },
+                    spread: 0,
+                    color: Color32::from_black_alpha(60),
+                })
+                .inner_margin(Margin::same(16))
+                .show(ui, |ui| {
+                    let header_top = ui.cursor().top();
+                    
+                    // Header minimo della card
+                    ui.horizontal(|ui| {
+                        let color = if num == 1 { self.theme.accent_blue } else { self.theme.accent_orange };
+                        
+                        // Ottieni il nome del file
+                        let filename = analysis.display_name();
+                        
+                        // Tronca il nome se troppo lungo (per caratteri, non per byte)
+                        let max_chars = 30;
+                        let char_count = filename.chars().count();
+                        let is_truncated = char_count > max_chars;
+                        let display_name = if is_truncated {
+                            let tail: String = filename.chars().skip(char_count - (max_chars - 3)).collect();
+                            format!("...{}", tail)
+                        } else {
+                            filename.clone()
+                        };
+                        
+                        // Crea il label con troncamento
+                        let label_text = format!("[{}] {}", num, display_name);
+                        let label = egui::Label::new(
+                            RichText::new(label_text)
+                                .size(14.0)
+                                .color(color)
+                        ).truncate().sense(egui::Sense::click());
+                        
+                        let response = ui.add(label).on_hover_cursor(egui::CursorIcon::Copy);
+                        
+                        // Click sul nome: copia il percorso completo
+                        if response.clicked() {
+                            copy_requested = true;
+                        }
+                        
+                        // Mostra tooltip con nome completo se troncato
+                        let hint = if num == 1 { "C" } else { "V" };
+                        if is_truncated {
+                            response.on_hover_text(format!("{}\nClick o {} per copiare il percorso", analysis.file_path.display(), hint));
+                        } else {
+                            response.on_hover_text(format!("Click o {} per copiare il percorso", hint));
+                        }
+                        
+                        if ui.small_button(RichText::new(regular::FOLDER_OPEN).color(color))
+                            .on_hover_text(format!("Mostra il file nella sua cartella (Maiusc+{})", hint))
+                            .clicked()
+                        {
+                            reveal_requested = true;
+                        }
+                        
+                        // Check if this image is the metadata source
+                        let is_metadata_source = self.metadata_transfer_pending && 
+                            self.metadata_transfer_source.as_ref()
+                                .map(|p| *p == analysis.file_path)
+                                .unwrap_or(false);
+                        
+                        if is_metadata_source {
+                            ui.label(RichText::new(format!(" {} META SORGENTE", regular::DATABASE)).color(self.theme.accent_green).strong())
+                                .on_hover_text(format!("I metadati di questo file verranno applicati a quello scelto. Maiusc+{} annulla",
+                                    KeyBindings::label(if num == 1 { self.keys.choose_left } else { self.keys.choose_right })));
+                        } else if is_best {
+                            ui.label(RichText::new(format!(" {} MIGLIORE", regular::STAR)).color(self.theme.accent_green).strong());
+                        }
+                        
+                        if self.file_manager.reference.as_ref() == Some(&analysis.file_path) {
+                            ui.label(RichText::new(format!("{} RIFERIMENTO", regular::CROSSHAIR)).color(self.theme.gold_star).strong());
+                        }
+                        
+                        if let Some(profile) = &analysis.color_profile {
+                            ui.label(RichText::new(format!("{} {}", regular::PALETTE, profile)).size(12.0).color(self.theme.gold_star))
+                                .on_hover_text("Profilo colore non sRGB, convertito in sRGB per la visualizzazione");
+                        }
+                        
+                        if analysis.upscale_suspected {
+                            ui.label(RichText::new(format!("{} INGRANDITA?", regular::WARNING)).size(12.0).color(self.theme.warning))
+                                .on_hover_text("Poco dettaglio tra pixel vicini per questa risoluzione: probabile copia ingrandita \
+                                    (o molto sfocata). Il punteggio risoluzione conta un quarto dei pixel");
+                        }
+                        
+                        if live_photo::has_video(&analysis.live_companions) {
+                            let names: Vec<String> = analysis.live_companions.iter()
+                                .filter_map(|path| path.file_name())
+                                .map(|name| name.to_string_lossy().into_owned())
+                                .collect();
+                            let copied = if self.file_manager.copy_live_companions {
+                                "vengono copiati in output con l'immagine"
+                            } else {
+                                "con --copy-live-video vengono copiati in output con l'immagine"
+                            };
+                            ui.label(RichText::new(format!("{} LIVE", regular::FILM_STRIP)).size(12.0).color(self.theme.accent_blue))
+                                .on_hover_text(format!("Live Photo: {} ({})", names.join(", "), copied));
+                        }
+                        
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.small_button(regular::ARROW_CLOCKWISE).on_hover_text("Ruota a destra (E)").clicked() {
+                                self.rotate_image(ui.ctx(), num, true);
+                            }
+                            if ui.small_button(regular::ARROW_COUNTER_CLOCKWISE).on_hover_text("Ruota a sinistra (Q)").clicked() {
+                                self.rotate_image(ui.ctx(), num, false);
+                            }
+                            
+                            let rotation = if num == 1 { self.rotation1 } else { self.rotation2 };
+                            if rotation != 0 {
+                                ui.label(RichText::new(format!("{}°", rotation as u32 * 90)).size(12.0).color(self.theme.gold_star));
+                            }
+                        });
+                    });
+                    
+                    // Info compatte su una riga con dimensioni e percentuale qualità
+                    ui.label(RichText::new(format!("{}×{} | {:.1}MP | {:.1}MB | {} ({}%) {}{}",
+                        analysis.width,
+                        analysis.height,
+                        analysis.megapixels,
+                        analysis.file_size_mb,
+                        analysis.get_quality_stars(),
+                        analysis.quality_score,
+                        analysis.dpi_label().map(|dpi| format!("| {} ", dpi)).unwrap_or_default(),
+                        if analysis.metadata_count > 0 { format!("| {} meta", analysis.metadata_count) } else { String::new() }
+                    )).size(12.0).color(self.theme.text_secondary));
+                    
+                    // Esposizione: alte luci bruciate in rosso, ombre chiuse in blu
+                    let highlights = analysis.clipping.highlights_warning();
+                    let shadows = analysis.clipping.shadows_warning();
+                    if highlights.is_some() || shadows.is_some() {
+                        ui.horizontal(|ui| {
+                            if let Some(channels) = highlights {
+                                ui.label(RichText::new(format!("{} Alte luci bruciate: {}", regular::SUN, channels))
+                                    .size(12.0)
+                                    .color(self.theme.danger_red))
+                                    .on_hover_text("Pixel a 255 per canale: dettagli persi nelle luci");
+                            }
+                            if let Some(channels) = shadows {
+                                ui.label(RichText::new(format!("{} Ombre chiuse: {}", regular::MOON, channels))
+                                    .size(12.0)
+                                    .color(self.theme.accent_blue))
+                                    .on_hover_text("Pixel a 0 per canale: dettagli persi nelle ombre");
+                            }
+                        });
+                    }
+                    
+                    // Animazioni: si confronta solo il primo fotogramma. Le pagine dei TIFF si scorrono,
+                    // dei PSD si mostra il composito: un'esportazione appiattita non è pari al master a livelli
+                    if analysis.frame_count > 1 && image_analyzer::is_tiff_path(&analysis.file_path) {
+                        let page = if num == 1 { self.page1 } else { self.page2 };
+                        ui.label(RichText::new(format!("{} Pagina {}/{} (PagSu/PagGiù)", regular::FILES, page + 1, analysis.frame_count))
+                            .size(12.0)
+                            .color(self.theme.gold_star));
+                    } else if analysis.frame_count > 1 {
+                        ui.label(RichText::new(format!("{} {} fotogrammi: confrontato solo il primo", regular::FILM_STRIP, analysis.frame_count))
+                            .size(12.0)
+                            .color(self.theme.gold_star));
+                    }
+                    if analysis.layer_count > 0 {
+                        ui.label(RichText::new(format!("{} {} livelli: confrontato il composito", regular::STACK, analysis.layer_count))
+                            .size(12.0)
+                            .color(self.theme.gold_star));
+                    }
+                    
+                    // Sidecar XMP: la valutazione pesa sulla scelta consigliata più del punteggio
+                    if let Some(xmp) = &analysis.xmp {
+                        let mut parts: Vec<String> = xmp.rating_label().into_iter().chain(xmp.label.clone()).collect();
+                        if !xmp.keywords.is_empty() {
+                            parts.push(format!("{} parole chiave", xmp.keywords.len()));
+                        }
+                        if !xmp.history.is_empty() {
+                            parts.push(format!("{} modifiche", xmp.history.len()));
+                        }
+                        let mut details = vec![format!("Sidecar: {}", xmp.path.display())];
+                        if !xmp.keywords.is_empty() {
+                            details.push(format!("Parole chiave: {}", xmp.keywords.join(", ")));
+                        }
+                        details.extend(xmp.history.iter().cloned());
+                        ui.label(RichText::new(format!("{} XMP {}", regular::FILE_TEXT, parts.join(" | ")))
+                            .size(12.0)
+                            .color(self.theme.gold_star))
+                            .on_hover_text(details.join("\n"));
+                    }
+                    
+                    ui.add_space(4.0);
+                    
+                    // Area immagine - stessa altezza per tutte, calcolata da show_modern_images
+                    self.image_header_height = self.image_header_height.max(ui.cursor().top() - header_top);
+                    let image_height = self.image_height;
+                    // Consideriamo i margini interni della card (16px * 2) e del frame immagine (8px * 2)
+                    let image_width = width - 32.0 - 16.0;
+                    
+                    Frame::NONE
+                        .fill(self.theme.image_bg)
+                        .corner_radius(CornerRadius::same(8))
+                        .inner_margin(Margin::same(8))
+                        .show(ui, |ui| {
+                            ui.set_min_height(image_height);
+                            ui.set_min_width(image_width);
+                            
+                            if let Some(texture) = texture {
+                                let size = texture.size;
+                                let scale_x = image_width / size.x;
+                                let scale_y = image_height / size.y;
+                                let scale = scale_x.min(scale_y);
+                                let scaled_size = Vec2::new(size.x * scale, size.y * scale);
+                                
+                                // Centra l'immagine nell'area disponibile
+                                let x_offset = (image_width - scaled_size.x) / 2.0;
+                                let y_offset = (image_height - scaled_size.y) / 2.0;
+                                
+                                // Dissolvenza con leggero scorrimento quando arriva una nuova coppia
+                                let progress = self.transition_progress();
+                                if progress < 1.0 {
+                                    ui.ctx().request_repaint();
+                                }
+                                let eased = 1.0 - (1.0 - progress).powi(3);
+                                let tint = Color32::from_white_alpha((eased * 255.0) as u8);
+                                let slide = Vec2::new((1.0 - eased) * TRANSITION_SLIDE, 0.0);
+                                
+                                ui.add_space(y_offset.max(0.0));
+                                ui.horizontal(|ui| {
+                                    ui.add_space(x_offset.max(0.0));
+                                    let (rect, response) = ui.allocate_exact_size(scaled_size, egui::Sense::click_and_drag());
+                                    self.pan_with_pointer(&response);
+                                    
+                                    // Ingrandita, l'immagine esce dalla sua area: si disegna solo la parte visibile
+                                    let view = self.zoomed_view(rect);
+                                    self.select_focus_region(&response, view);
+                                    let painter = ui.painter().with_clip_rect(rect.translate(slide));
+                                    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                                    for (tile, tile_rect) in &texture.tiles {
+                                        let tile_rect = egui::Rect::from_min_max(
+                                            view.min + tile_rect.min.to_vec2() * view.size(),
+                                            view.min + tile_rect.max.to_vec2() * view.size(),
+                                        ).translate(slide);
+                                        painter.image(tile.id(), tile_rect, uv, tint);
+                                    }
+                                    self.paint_focus_peaking(&painter, num, view.translate(slide), tint);
+                                    self.paint_focus_region(&painter, num, view.translate(slide));
+                                    
+                                    let visible = rect.intersect(view);
+                                    let region = egui::Rect::from_min_max(
+                                        ((visible.min - view.min) / view.size()).to_pos2(),
+                                        ((visible.max - view.min) / view.size()).to_pos2(),
+                                    );
+                                    let pixels = visible.size() * ui.ctx().pixels_per_point();
+                                    self.peaking_visible[num as usize - 1] = Some((region, [pixels.x.ceil() as u32, pixels.y.ceil() as u32]));
+                                    
+                                    if num == 1 {
+                                        self.hover_image1 = response.hovered();
+                                        self.image_rect1 = Some(response.rect);
+                                    } else {
+                                        self.hover_image2 = response.hovered();
+                                        self.image_rect2 = Some(response.rect);
+                                    }
+                                    
+                                    if let Some(pos) = response.hover_pos() {
+                                        self.loupe_target = Some((pos - view.min) / view.size());
+                                    }
+                                });
+                            } else {
+                                // Mostra spinner centrato
+                                ui.add_space(image_height / 2.0 - 20.0);
+                                ui.horizontal(|ui| {
+                                    ui.add_space(image_width / 2.0 - 20.0);
+                                    ui.spinner();
+                                });
+                            }
+                        });
+                });
+        });
+        
+        if copy_requested {
+            self.copy_path(ui.ctx(), num);
+        }
+        if reveal_requested {
+            self.reveal_file(num);
+        }
+    }
+    
+    
+    // Area in cui disegnare l'immagine che occupa `rect`, con ingrandimento e spostamento
+    fn zoomed_view(&self, rect: egui::Rect) -> egui::Rect {
+        egui::Rect::from_center_size(rect.center() + self.image_pan * rect.size(), rect.size() * self.image_zoom)
+    }
+    
+    // Trascinamento con mouse o un dito per spostare l'immagine ingrandita, doppio clic per
+    // tornare all'immagine intera
+    fn pan_with_pointer(&mut self, response: &egui::Response) {
+        if response.double_clicked() {
+            self.image_zoom = 1.0;
+            self.image_pan = Vec2::ZERO;
+        } else if response.dragged()
+            && self.image_zoom > 1.0
+            && response.ctx.input(|i| i.multi_touch().is_none() && !i.modifiers.shift)
+        {
+            self.image_pan += response.drag_delta() / response.rect.size();
+            self.clamp_pan();
+        }
+    }
+    
+    // Maiusc+trascinamento traccia la zona di messa a fuoco, riportata identica sull'altra
+    // immagine; Maiusc+clic la toglie. Le coordinate sono relative all'immagine, non all'area
+    fn select_focus_region(&mut self, response: &egui::Response, view: egui::Rect) {
+        if !response.ctx.input(|i| i.modifiers.shift) {
+            self.focus_drag_start = None;
+            return;
+        }
+        let relative = |pos: egui::Pos2| ((pos - view.min) / view.size()).clamp(Vec2::ZERO, Vec2::splat(1.0));
+        
+        if response.clicked() {
+            self.clear_focus_region();
+        } else if response.drag_started()
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            self.focus_drag_start = Some(relative(pos));
+        } else if let (Some(start), Some(pos)) = (self.focus_drag_start, response.interact_pointer_pos()) {
+            let region = egui::Rect::from_two_pos(start.to_pos2(), relative(pos).to_pos2());
+            if response.drag_stopped() {
+                self.focus_drag_start = None;
+                // Una zona di pochi pixel non dà una misura affidabile
+                if region.width() < FOCUS_REGION_MIN_SIZE || region.height() < FOCUS_REGION_MIN_SIZE {
+                    self.clear_focus_region();
+                    return;
+                }
+            }
+            self.focus_region = Some(region);
+            self.focus_sharpness = None;
+        }
+    }
+    
+    fn clear_focus_region(&mut self) {
+        self.focus_region = None;
+        self.focus_drag_start = None;
+        self.focus_sharpness = None;
+    }
+    
+    // Nitidezza nella zona, sulle immagini intere; si ricalcola quando cambia la zona, la pagina
+    // o la rotazione, e finché le immagini intere non sono disponibili resta da misurare
+    fn measure_focus_region(&mut self) {
+        let Some(region) = self.focus_region else {
+            return;
+        };
+        if self.focus_sharpness.is_some() || self.focus_drag_start.is_some() {
+            return;
+        }
+        let crop = |full: &Option<DynamicImage>, rotation: u8| {
+            let full = full.as_ref()?;
+            let corner1 = Self::source_point(region.min.to_vec2(), rotation);
+            let corner2 = Self::source_point(region.max.to_vec2(), rotation);
+            let source = egui::Rect::from_two_pos(corner1.to_pos2(), corner2.to_pos2());
+            let (width, height) = (full.width() as f32, full.height() as f32);
+            let x = (source.min.x * width) as u32;
+            let y = (source.min.y * height) as u32;
+            let crop_width = ((source.width() * width) as u32).clamp(1, full.width() - x.min(full.width() - 1));
+            let crop_height = ((source.height() * height) as u32).clamp(1, full.height() - y.min(full.height() - 1));
+            Some(full.crop_imm(x, y, crop_width, crop_height))
+        };
+        if let (Some(region1), Some(region2)) = (crop(&self.full1, self.rotation1), crop(&self.full2, self.rotation2)) {
+            self.focus_sharpness = image_analyzer::region_sharpness(&region1, &region2);
+        }
+    }
+    
+    // Punto dell'immagine originale corrispondente a un punto (relativo) dell'anteprima ruotata
+    fn source_point(target: Vec2, rotation: u8) -> Vec2 {
+        match rotation {
+            1 => Vec2::new(target.y, 1.0 - target.x),
+            2 => Vec2::new(1.0 - target.x, 1.0 - target.y),
+            3 => Vec2::new(1.0 - target.y, target.x),
+            _ => target,
+        }
+    }
+    
+    // Riquadro della zona di messa a fuoco con la sua nitidezza; la più nitida delle due in verde
+    fn paint_focus_region(&self, painter: &egui::Painter, num: u8, view: egui::Rect) {
+        let Some(region) = self.focus_region else {
+            return;
+        };
+        let rect = egui::Rect::from_min_max(view.min + region.min.to_vec2() * view.size(), view.min + region.max.to_vec2() * view.size());
+        let (text, color) = match self.focus_sharpness {
+            Some(sharpness) => {
+                let (own, other) = (sharpness[num as usize - 1], sharpness[2 - num as usize]);
+                let color = if own >= other { self.theme.accent_green } else { self.theme.text_primary };
+                (format!("{} Nitidezza zona {:.0}", regular::SCAN, own), color)
+            }
+            None => (format!("{} Zona di messa a fuoco", regular::SCAN), self.theme.gold_star),
+        };
+        painter.rect_stroke(rect, CornerRadius::same(2), Stroke::new(2.0, color), egui::StrokeKind::Outside);
+        let font = FontId::proportional(13.0);
+        let galley = painter.layout_no_wrap(text, font, color);
+        // Sopra il riquadro, o sotto se in cima all'immagine non c'è spazio
+        let mut label_pos = rect.left_top() - Vec2::new(0.0, galley.size().y + 4.0);
+        if label_pos.y < painter.clip_rect().top() {
+            label_pos = rect.left_bottom() + Vec2::new(0.0, 4.0);
+        }
+        painter.rect_filled(egui::Rect::from_min_size(label_pos, galley.size()).expand(2.0), CornerRadius::same(3), self.theme.card_bg.gamma_multiply(0.85));
+        painter.galley(label_pos, galley, color);
+    }
+    
+    // Pizzico (o Ctrl+rotella) per ingrandire attorno al punto indicato, due dita per spostare.
+    // Le due immagini restano allineate: ingrandimento e spostamento sono in comune
+    fn handle_touch_input(&mut self, ctx: &Context) {
+        let (touched, zoom_delta, touch) = ctx.input(|i| (i.any_touches(), i.zoom_delta(), i.multi_touch()));
+        if touched && !self.touch_mode {
+            self.touch_mode = true;
+        }
+        
+        let pos = touch.map(|touch| touch.center_pos).or_else(|| ctx.pointer_hover_pos());
+        let Some(pos) = pos else {
+            return;
+        };
+        let Some(rect) = [self.image_rect1, self.image_rect2].into_iter().flatten().find(|rect| rect.contains(pos)) else {
+            return;
+        };
+        
+        if zoom_delta != 1.0 {
+            let zoom = (self.image_zoom * zoom_delta).clamp(1.0, MAX_IMAGE_ZOOM);
+            // Il punto sotto le dita resta fermo mentre l'immagine si ingrandisce
+            let anchor = (pos - rect.center()) / rect.size();
+            self.image_pan = anchor - (anchor - self.image_pan) * (zoom / self.image_zoom);
+            self.image_zoom = zoom;
+        }
+        if let Some(touch) = touch {
+            self.image_pan += touch.translation_delta / rect.size();
+        }
+        self.clamp_pan();
+    }
+    
+    // L'immagine ingrandita copre sempre tutta la sua area
+    fn clamp_pan(&mut self) {
+        let limit = (self.image_zoom - 1.0) / 2.0;
+        self.image_pan = self.image_pan.clamp(Vec2::splat(-limit), Vec2::splat(limit));
+    }
+    
+    fn button_size(&self) -> Vec2 {
+        if self.touch_mode { TOUCH_BUTTON_SIZE } else { Vec2::new(120.0, 35.0) }
+    }
+    
+    // Avanzamento della transizione della coppia corrente, da 0.0 a 1.0
+    fn transition_progress(&self) -> f32 {
+        ((self.animation_time - self.transition_start) / TRANSITION_DURATION).clamp(0.0, 1.0)
+    }
+    
+    fn show_loupe(&mut self, ctx: &Context, num: u8, target: Vec2) {
+        let (full, rect, rotation) = if num == 1 {
+            (&self.full1, self.image_rect1, self.rotation1)
+        } else {
+            (&self.full2, self.image_rect2, self.rotation2)
+        };
+        let (Some(full), Some(rect)) = (full, rect) else {
+            return;
+        };
+        let view = self.zoomed_view(rect);
+        
+        // Punto corrispondente nell'immagine originale, annullando la rotazione dell'anteprima
+        let Vec2 { x: u, y: v } = Self::source_point(target, rotation);
+        
+        let size = LOUPE_SOURCE_SIZE.min(full.width()).min(full.height());
+        let x = ((u * full.width() as f32) as u32).saturating_sub(size / 2).min(full.width() - size);
+        let y = ((v * full.height() as f32) as u32).saturating_sub(size / 2).min(full.height() - size);
+        let mut region = image_analyzer::rotate_clockwise(&full.crop_imm(x, y, size, size), rotation);
+        if let Some(gains) = self.exposure_gains {
+            region = image_analyzer::apply_exposure_gain(&region, gains[num as usize - 1]);
+        }
+        let region = region.to_rgba8();
+        let color_image = ColorImage::from_rgba_unmultiplied([size as usize, size as usize], region.as_raw());
+        
+        // La texture della lente viene aggiornata invece di ricrearla a ogni frame
+        let slot = if num == 1 { &mut self.loupe_texture1 } else { &mut self.loupe_texture2 };
+        match slot {
+            Some(texture) => texture.set(color_image, egui::TextureOptions::NEAREST),
+            None => *slot = Some(ctx.load_texture(format!("loupe{}", num), color_image, egui::TextureOptions::NEAREST)),
+        }
+        let Some(texture) = slot else {
+            return;
+        };
+        
+        let center = view.min + target * view.size();
+        let loupe_rect = egui::Rect::from_center_size(center, Vec2::splat(LOUPE_SIZE));
+        let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+        let color = if num == 1 { self.theme.accent_blue } else { self.theme.accent_orange };
+        
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new(("loupe", num))));
+        painter.add(egui::epaint::RectShape::filled(loupe_rect, CornerRadius::same((LOUPE_SIZE / 2.0) as u8), Color32::WHITE)
+            .with_texture(texture.id(), uv));
+        painter.circle_stroke(center, LOUPE_SIZE / 2.0, Stroke::new(2.0, color));
+    }
+    
+    fn show_metadata_card(&self, ui: &mut egui::Ui, exif_data: &Vec<(String, String)>, width: f32) {
+        // Calcola l'altezza disponibile
+        let available_height = ui.available_height();
+        
+        // Usa un'altezza fissa se lo spazio disponibile è troppo piccolo
+        let card_height = if available_height > 200.0 {
+            available_height - 10.0
+        } else {
+            200.0 // Altezza minima garantita
+        };
+        
+        Frame::NONE
+            .fill(self.theme.card_bg)
+            .corner_radius(CornerRadius::same(12))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .shadow(egui::epaint::Shadow {
+                offset: [0, 2],
+                blur: 4,
+                spread: 0,
+                color: Color32::from_black_alpha(40),
+            })
+            .inner_margin(Margin::same(12))
+            .show(ui, |ui| {
+                ui.set_min_width(width - 24.0);
+                ui.set_max_width(width - 24.0);
+                ui.set_min_height(card_height - 24.0);
+                
+                // Titolo
+                ui.label(RichText::new("Metadati EXIF").size(13.0).color(self.theme.text_primary).strong());
+                ui.add_space(4.0);
+                ui.separator();
+                ui.add_space(4.0);
+                
+                // Area scrollabile per i metadati
+                let scroll_height = (card_height - 60.0).max(100.0);
+                egui::ScrollArea::vertical()
+                    .max_height(scroll_height)
+                    .auto_shrink([false, false]) // Impedisce lo shrink automatico
+                    .show(ui, |ui| {
+                        for (key, value) in exif_data {
+                            let formatted_key = key.replace("(", "").replace(")", "");
+                            ui.horizontal(|ui| {
+                                // Usa una larghezza fissa per la chiave per allineamento
+                                ui.with_layout(egui::Layout::left_to_right(egui::Align::Min), |ui| {
+                                    ui.set_min_width(150.0);
+                                    ui.label(RichText::new(format!("{}:", formatted_key))
+                                        .size(11.0)
+                                        .color(self.theme.text_secondary));
+                                });
+                                ui.label(RichText::new(value)
+                                    .size(11.0)
+                                    .color(self.theme.text_primary));
+                            });
+                        }
+                        
+                        // Aggiungi un po' di spazio alla fine per miglior leggibilità
+                        ui.add_space(10.0);
+                    });
+            });
+    }
+    
+    // Tabella unica dei metadati: in verde i campi presenti in una sola immagine, in giallo
+    // quelli con valori diversi
+    fn show_metadata_diff_card(&self, ui: &mut egui::Ui, exif_data1: &[(String, String)], exif_data2: &[(String, String)], width: f32) {
+        let card_height = ui.available_height().max(210.0) - 10.0;
+        let rows = Self::metadata_diff_rows(exif_data1, exif_data2);
+        let differing = rows.iter().filter(|(_, value1, value2)| value1 != value2).count();
+        
+        Frame::NONE
+            .fill(self.theme.card_bg)
+            .corner_radius(CornerRadius::same(12))
+            .stroke(Stroke::new(1.0, self.theme.border))
+            .inner_margin(Margin::same(12))
+            .show(ui, |ui| {
+                ui.set_min_width(width - 24.0);
+                ui.set_max_width(width - 24.0);
+                ui.set_min_height(card_height - 24.0);
+                
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(format!("Metadati EXIF: {} campi diversi (M)", differing)).size(13.0).color(self.theme.text_primary).strong());
+                    ui.label(RichText::new("solo in un'immagine").size(11.0).color(self.theme.accent_green));
+                    ui.label(RichText::new("valori diversi").size(11.0).color(self.theme.gold_star));
+                });
+                ui.add_space(4.0);
+                ui.separator();
+                ui.add_space(4.0);
+                
+                let value_width = (width - 24.0 - 170.0) / 2.0 - 10.0;
+                egui::ScrollArea::vertical()
+                    .max_height((card_height - 60.0).max(100.0))
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        egui::Grid::new("metadata_diff").num_columns(3).striped(true).show(ui, |ui| {
+                            ui.label(RichText::new("Tag").size(11.0).color(self.theme.text_secondary).strong());
+                            ui.label(RichText::new("Immagine 1").size(11.0).color(self.theme.accent_blue).strong());
+                            ui.label(RichText::new("Immagine 2").size(11.0).color(self.theme.accent_orange).strong());
+                            ui.end_row();
+                            
+                            for (key, value1, value2) in &rows {
+                                let color = match (value1, value2) {
+                                    (Some(_), None) | (None, Some(_)) => self.theme.accent_green,
+                                    (Some(value1), Some(value2)) if value1 != value2 => self.theme.gold_star,
+                                    _ => self.theme.text_secondary,
+                                };
+                                ui.label(RichText::new(key.replace("(", "").replace(")", "")).size(11.0).color(self.theme.text_secondary));
+                                for value in [value1, value2] {
+                                    ui.add_sized([value_width, 14.0], egui::Label::new(
+                                        RichText::new(value.as_deref().unwrap_or("—")).size(11.0).color(color)
+                                    ).truncate());
+                                }
+                                ui.end_row();
+                            }
+                        });
+                        ui.add_space(10.0);
+                    });
+            });
+    }
+    
+    // Campi EXIF delle due immagini allineati per tag, nell'ordine in cui compaiono. Lo stesso tag
+    // può comparire più volte (es. nell'IFD della miniatura): si abbinano le occorrenze in ordine
+    fn metadata_diff_rows(exif_data1: &[(String, String)], exif_data2: &[(String, String)]) -> Vec<(String, Option<String>, Option<String>)> {
+        let mut rows: Vec<(String, Option<String>, Option<String>)> = Vec::new();
+        for (key, value) in exif_data1 {
+            rows.push((key.clone(), Some(value.clone()), None));
+        }
+        for (key, value) in exif_data2 {
+            match rows.iter_mut().find(|(row_key, _, value2)| row_key == key && value2.is_none()) {
+                Some(row) => row.2 = Some(value.clone()),
+                None => rows.push((key.clone(), None, Some(value.clone()))),
+            }
+        }
+        rows
+    }
+    
+    fn show_modern_controls(&mut self, ui: &mut egui::Ui) {
+        if let Some(frontier) = self.review_return {
+            self.show_review_controls(ui, frontier);
+            return;
+        }
+        
+        // In modalità touch i pulsanti grandi vanno a capo invece di uscire dalla finestra
+        let touch_mode = self.touch_mode;
+        let controls = |ui: &mut egui::Ui| {
+            // Pulsanti principali compatti
+            let btn_size = self.button_size();
+            let keys = self.keys;
+            
+            if self.modern_button(ui, &format!("{} Prima ({})", regular::ARROW_LEFT, KeyBindings::label(keys.choose_left)), self.theme.accent_blue, btn_size) {
+                self.make_choice(1);
+            }
+            
+            if self.modern_button(ui, &format!("{} Previous ({})", regular::ARROW_U_UP_LEFT, KeyBindings::label(keys.undo)), self.theme.text_secondary, btn_size) {
+                self.go_to_previous();
+            }
+            
+            if self.modern_button(ui, &format!("{} Seconda ({})", regular::ARROW_RIGHT, KeyBindings::label(keys.choose_right)), self.theme.accent_orange, btn_size) {
+                self.make_choice(2);
+            }
+            
+            if self.modern_button(ui, &format!("{} Salta ({})", regular::ARROW_DOWN, KeyBindings::label(keys.skip)), self.theme.text_secondary, btn_size) {
+                self.skip_current();
+            }
+            
+            if self.modern_button(ui, &format!("{} Rimanda ({})", regular::HOURGLASS, KeyBindings::label(keys.defer)), self.theme.text_secondary, btn_size) {
+                self.defer_current();
+            }
+            
+            if self.modern_button(ui, &format!("{} Meta ({})", regular::ARROW_UP, KeyBindings::label(keys.transfer_meta)), self.theme.accent_green, btn_size) {
+                self.transfer_metadata();
+            }
+            
+            if self.modern_button(ui, &format!("{} Entrambe (B)", regular::COPY), self.theme.gold_star, btn_size) {
+                self.keep_both();
+            }
+            
+            if self.modern_button(ui, &format!("{} Inverti (X)", regular::ARROWS_LEFT_RIGHT), self.theme.text_secondary, btn_size) {
+                self.swap_folders();
+            }
+            
+            if touch_mode {
+                if self.modern_button(ui, &format!("{} Esci", regular::X), self.theme.danger_red, btn_size) {
+                    self.request_exit();
+                }
+                return;
+            }
+            
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if self.modern_button(ui, &format!("{} Esci", regular::X), self.theme.danger_red, btn_size) {
+                    self.request_exit();
+                }
+                
+                // Shortcuts help compatto
+                let shortcuts = format!(
+                    "{}, {}, {}, {}, {}, Maiusc+{}/{}, B, X, {}, ←/→, G, N, Ctrl+1-3, C/V, Maiusc+C/V, Q/E, Maiusc+E, Maiusc+I, L, F, H, K, M, J, O, T, PagSu/PagGiù, +/-, F11, Ctrl+rotella, Maiusc+trascina, F1 tutti i tasti, {}",
+                    KeyBindings::label(keys.choose_left),
+                    KeyBindings::label(keys.choose_right),
+                    KeyBindings::label(keys.skip),
+                    KeyBindings::label(keys.defer),
+                    KeyBindings::label(keys.transfer_meta),
+                    KeyBindings::label(keys.choose_left),
+                    KeyBindings::label(keys.choose_right),
+                    KeyBindings::label(keys.undo),
+                    KeyBindings::label(keys.exit),
+                );
+                ui.label(RichText::new(format!("{} {}", regular::KEYBOARD, shortcuts)).size(12.0).color(self.theme.text_secondary));
+            });
+        };
+        if touch_mode {
+            ui.horizontal_wrapped(controls);
+        } else {
+            ui.horizontal(controls);
+        }
+    }
+    
+    fn show_review_controls(&mut self, ui: &mut egui::Ui, frontier: usize) {
+        ui.horizontal(|ui| {
+            let btn_size = self.button_size();
+            
+            if self.modern_button(ui, &format!("{} Indietro (←)", regular::CARET_LEFT), self.theme.text_secondary, btn_size) {
+                self.review_step(-1);
+            }
+            
+            if self.modern_button(ui, &format!("{} Avanti (→)", regular::CARET_RIGHT), self.theme.text_secondary, btn_size) {
+                self.review_step(1);
+            }
+            
+            if self.modern_button(ui, &format!("{} Torna alla coppia {}", regular::ARROW_LINE_RIGHT, frontier + 1), self.theme.accent_blue, Vec2::new(240.0, btn_size.y)) {
+                self.review_to(frontier);
+            }
+            
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if self.modern_button(ui, &format!("{} Esci", regular::X), self.theme.danger_red, btn_size) {
+                    self.request_exit();
+                }
+                
+                let shortcuts = format!("←/→, C/V, Maiusc+C/V, Q/E, L, F, H, K, M, O, T, PagSu/PagGiù, F11, {}", KeyBindings::label(self.keys.exit));
+                ui.label(RichText::new(format!("{} {}", regular::KEYBOARD, shortcuts)).size(12.0).color(self.theme.text_secondary));
+            });
+        });
+    }
+    
+    fn modern_button(&self, ui: &mut egui::Ui, text: &str, color: Color32, size: Vec2) -> bool {
+        let button = egui::Button::new(RichText::new(text).size(if self.touch_mode { 22.0 } else { 18.0 }))
+            .min_size(size)
+            .fill(color.gamma_multiply(0.2))
+            .stroke(Stroke::new(1.0, color));
+        
+        let response = ui.add(button);
+        
+        if response.hovered() {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+        }
+        
+        response.clicked()
+    }
+    
+    // Arco rotante come ui.spinner(), che però chiede un nuovo frame a ogni disegno: questo
+    // segue animation_time e si aggiorna con i frame del controllo del caricamento
+    fn show_loading_indicator(&self, ui: &mut egui::Ui, size: f32) {
+        let (rect, _) = ui.allocate_exact_size(Vec2::splat(size), egui::Sense::hover());
+        let radius = rect.height() / 2.0 - 2.0;
+        let start_angle = self.animation_time * std::f32::consts::TAU;
+        let end_angle = start_angle + 240f32.to_radians() * self.animation_time.sin();
+        let points: Vec<egui::Pos2> = (0..=30)
+            .map(|step| {
+                let angle = start_angle + (end_angle - start_angle) * step as f32 / 30.0;
+                rect.center() + radius * Vec2::angled(angle)
+            })
+            .collect();
+        ui.painter().add(egui::Shape::line(points, Stroke::new(3.0, self.theme.text_secondary)));
+    }
+    
+    fn show_loading_ui(&self, ctx: &Context, message: &str) {
+        let thumbnails = self.loading_thumbnails.as_ref()
+            .filter(|(index, _)| *index == self.current_index)
+            .map(|(_, thumbnails)| thumbnails);
+        
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                let available_height = ui.available_height();
+                if let Some(thumbnails) = thumbnails {
+                    // Miniature incorporate nei file: un'anteprima immediata mentre si decodificano
+                    ui.add_space(20.0);
+                    let slot = Vec2::new((ui.available_width() - 60.0) / 2.0, (available_height - 260.0).max(100.0));
                     ui.horizontal(|ui| {
-                        let color = if num == 1 { ACCENT_BLUE } else { ACCENT_ORANGE };
-                        
-                        // Ottieni il nome del file
-                        let filename = Path::new(&analysis.file_path)
-                            .file_name()
-                            .unwrap_or_default()
-                            .to_string_lossy();
-                        
-                        // Tronca il nome se troppo lungo
-                        let max_chars = 30;
-                        let display_name = if filename.len() > max_chars {
-                            format!("...{}", &filename[filename.len().saturating_sub(max_chars-3)..])
+                        ui.add_space(20.0);
+                        for texture in thumbnails {
+                            let (rect, _) = ui.allocate_exact_size(slot, egui::Sense::hover());
+                            ui.painter().rect_filled(rect, CornerRadius::same(8), self.theme.image_bg);
+                            if let Some(texture) = texture {
+                                let size = texture.size_vec2();
+                                let scale = (rect.width() / size.x).min(rect.height() / size.y);
+                                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                                ui.painter().image(texture.id(), egui::Rect::from_center_size(rect.center(), size * scale), uv, Color32::WHITE);
+                            }
+                        }
+                    });
+                    ui.label(RichText::new("Miniature EXIF, l'immagine completa è in caricamento")
+                        .size(14.0)
+                        .color(self.theme.text_secondary));
+                    ui.add_space(20.0);
+                } else {
+                    ui.add_space(available_height / 2.0 - 100.0);
+                }
+                
+                self.show_loading_indicator(ui, 20.0);
+                ui.add_space(30.0);
+                
+                ui.heading(RichText::new(message).size(24.0).color(self.theme.text_primary));
+                
+                ui.add_space(20.0);
+                
+                // Progress info
+                Frame::NONE
+                    .fill(self.theme.card_bg)
+                    .corner_radius(CornerRadius::same(8))
+                    .inner_margin(Margin::symmetric(20, 12))
+                    .show(ui, |ui| {
+                        ui.label(RichText::new(format!("{} File {}/{}", regular::FILE, 
+                            self.current_index + 1, self.all_pairs.len()))
+                            .size(18.0)
+                            .color(self.theme.text_secondary));
+                    });
+            });
+        });
+    }
+    
+    fn show_group_ui(&mut self, ctx: &Context) {
+        egui::TopBottomPanel::top("header").show(ctx, |ui| {
+            ui.add_space(3.0);
+            self.show_modern_header(ui);
+            ui.add_space(3.0);
+        });
+        
+        let Some(view) = &self.group_view else {
+            return;
+        };
+        let keys = self.keys;
+        let mut chosen = None;
+        let mut skip = false;
+        let mut previous = false;
+        let mut exit = false;
+        
+        egui::TopBottomPanel::bottom("controls").show(ctx, |ui| {
+            ui.add_space(3.0);
+            ui.horizontal(|ui| {
+                let btn_size = Vec2::new(120.0, 35.0);
+                if let Some(best) = view.best
+                    && self.modern_button(ui, &format!("{} Tieni il migliore (Invio)", regular::STAR), self.theme.accent_green, Vec2::new(220.0, 35.0))
+                {
+                    chosen = Some(best);
+                }
+                if self.modern_button(ui, &format!("{} Previous ({})", regular::ARROW_U_UP_LEFT, KeyBindings::label(keys.undo)), self.theme.text_secondary, btn_size) {
+                    previous = true;
+                }
+                if self.modern_button(ui, &format!("{} Salta gruppo ({})", regular::ARROW_DOWN, KeyBindings::label(keys.skip)), self.theme.text_secondary, Vec2::new(180.0, 35.0)) {
+                    skip = true;
+                }
+                
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if self.modern_button(ui, &format!("{} Esci", regular::X), self.theme.danger_red, btn_size) {
+                        exit = true;
+                    }
+                    let shortcuts = format!("1-9, Invio, {}, {}, T, {}",
+                        KeyBindings::label(keys.skip),
+                        KeyBindings::label(keys.undo),
+                        KeyBindings::label(keys.exit));
+                    ui.label(RichText::new(format!("{} {}", regular::KEYBOARD, shortcuts)).size(12.0).color(self.theme.text_secondary));
+                });
+            });
+            ui.add_space(3.0);
+        });
+        
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.label(RichText::new(format!("{} Gruppo di {} duplicati: clicca il file da tenere, gli altri non vengono copiati", regular::COPY, view.members.len()))
+                .size(18.0)
+                .color(self.theme.text_primary)
+                .strong());
+            ui.add_space(8.0);
+            
+            let card_width = ((ui.available_width() - 16.0 * view.members.len() as f32) / view.members.len() as f32).clamp(200.0, 340.0);
+            let thumbnail_height = (ui.available_height() - 140.0).clamp(120.0, card_width);
+            let alpha = self.transition_progress();
+            if alpha < 1.0 {
+                ui.ctx().request_repaint();
+            }
+            
+            egui::ScrollArea::horizontal().auto_shrink([false, false]).show(ui, |ui| {
+                ui.horizontal_top(|ui| {
+                    for (index, (member, texture)) in view.members.iter().enumerate() {
+                        let is_best = view.best == Some(index);
+                        let stroke = if is_best {
+                            Stroke::new(3.0, self.theme.gold_star)
                         } else {
-                            filename.to_string()
+                            Stroke::new(1.0, self.theme.border)
                         };
                         
-                        // Crea il label con troncamento
-                        let label_text = format!("[{}] {}", num, display_name);
-                        let label = egui::Label::new(
-                            RichText::new(label_text)
-                                .size(14.0)
-                                .color(color)
-                        ).truncate();
-                        
-                        let response = ui.add(label);
-                        
-                        // Mostra tooltip con nome completo se troncato
-                        if filename.len() > max_chars {
-                            response.on_hover_text(filename.to_string());
-                        }
-                        
-                        // Check if this image is the metadata source
-                        let is_metadata_source = self.metadata_transfer_pending && 
-                            self.metadata_transfer_source.as_ref()
-                                .map(|p| p == Path::new(&analysis.file_path))
-                                .unwrap_or(false);
-                        
-                        if is_metadata_source {
-                            ui.label(RichText::new(format!(" {} META SORGENTE", regular::DATABASE)).color(ACCENT_GREEN).strong());
-                        } else if is_best {
-                            ui.label(RichText::new(format!(" {} MIGLIORE", regular::STAR)).color(ACCENT_GREEN).strong());
-                        }
-                    });
-                    
-                    // Info compatte su una riga con dimensioni e percentuale qualità
-                    ui.label(RichText::new(format!("{}×{} | {:.1}MP | {:.1}MB | {} ({}%) {}",
-                        analysis.width,
-                        analysis.height,
-                        analysis.megapixels,
-                        analysis.file_size_mb,
-                        analysis.get_quality_stars(),
-                        analysis.quality_score,
-                        if analysis.metadata_count > 0 { format!("| {} meta", analysis.metadata_count) } else { String::new() }
-                    )).size(12.0).color(TEXT_SECONDARY));
-                    
-                    ui.add_space(4.0);
-                    
-                    // Area immagine - altezza fissa per tutte
-                    let image_height = 600.0;
-                    // Consideriamo i margini interni della card (16px * 2) e del frame immagine (8px * 2)
-                    let image_width = width - 32.0 - 16.0;
-                    
-                    Frame::NONE
-                        .fill(Color32::from_gray(20))
-                        .corner_radius(CornerRadius::same(8))
-                        .inner_margin(Margin::same(8))
-                        .show(ui, |ui| {
-                            ui.set_min_height(image_height);
-                            ui.set_min_width(image_width);
-                            
-                            if let Some(texture) = texture {
-                                let size = texture.size_vec2();
-                                let scale_x = image_width / size.x;
-                                let scale_y = image_height / size.y;
-                                let scale = scale_x.min(scale_y);
-                                let scaled_size = Vec2::new(size.x * scale, size.y * scale);
+                        let response = Frame::NONE
+                            .fill(self.theme.card_bg)
+                            .stroke(stroke)
+                            .corner_radius(CornerRadius::same(10))
+                            .inner_margin(Margin::same(10))
+                            .show(ui, |ui| {
+                                ui.set_width(card_width);
+                                ui.set_opacity(alpha);
                                 
-                                // Centra l'immagine nell'area disponibile
-                                let x_offset = (image_width - scaled_size.x) / 2.0;
-                                let y_offset = (image_height - scaled_size.y) / 2.0;
+                                let (rect, _) = ui.allocate_exact_size(Vec2::new(card_width, thumbnail_height), egui::Sense::hover());
+                                ui.painter().rect_filled(rect, CornerRadius::same(6), self.theme.image_bg);
+                                if let Some(texture) = texture {
+                                    let size = texture.size_vec2();
+                                    let scale = (rect.width() / size.x).min(rect.height() / size.y);
+                                    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                                    ui.painter().image(texture.id(), egui::Rect::from_center_size(rect.center(), size * scale), uv, Color32::WHITE);
+                                }
                                 
-                                ui.add_space(y_offset.max(0.0));
-                                ui.horizontal(|ui| {
-                                    ui.add_space(x_offset.max(0.0));
-                                    let response = ui.image((texture.id(), scaled_size));
-                                    
-                                    if num == 1 {
-                                        self.hover_image1 = response.hovered();
-                                    } else {
-                                        self.hover_image2 = response.hovered();
+                                let name = member.path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+                                ui.label(RichText::new(format!("{}. {}", index + 1, name)).size(14.0).color(self.theme.text_primary).strong())
+                                    .on_hover_text(member.path.display().to_string());
+                                
+                                match &member.analysis {
+                                    Ok(analysis) => {
+                                        let score_color = if is_best { self.theme.gold_star } else { self.theme.text_primary };
+                                        let star = if is_best { format!("{} ", regular::STAR) } else { String::new() };
+                                        ui.label(RichText::new(format!("{}Punteggio {}", star, analysis.quality_score)).size(16.0).color(score_color).strong());
+                                        ui.label(RichText::new(format!("{}×{} · {:.1} MP · {:.2} MB", analysis.width, analysis.height, analysis.megapixels, analysis.file_size_mb))
+                                            .size(13.0)
+                                            .color(self.theme.text_secondary));
                                     }
-                                });
-                            } else {
-                                // Mostra spinner centrato
-                                ui.add_space(image_height / 2.0 - 20.0);
-                                ui.horizontal(|ui| {
-                                    ui.add_space(image_width / 2.0 - 20.0);
-                                    ui.spinner();
-                                });
+                                    Err(damage) => {
+                                        ui.label(RichText::new(format!("{} File {}", regular::WARNING, damage.label())).size(14.0).color(self.theme.danger_red));
+                                    }
+                                }
+                            })
+                            .response;
+                        
+                        if member.analysis.is_ok() {
+                            let response = response.interact(egui::Sense::click()).on_hover_cursor(egui::CursorIcon::PointingHand);
+                            if response.clicked() {
+                                chosen = Some(index);
                             }
-                        });
+                        }
+                    }
+                });
+            });
+        });
+        
+        if let Some(index) = chosen {
+            self.choose_group_keeper(index);
+        } else if skip {
+            self.skip_group();
+        } else if previous {
+            self.go_to_previous();
+        } else if exit {
+            self.request_exit();
+        }
+    }
+    
+    fn handle_group_input(&mut self, ctx: &Context) {
+        let keys = self.keys;
+        let numbers = [
+            egui::Key::Num1, egui::Key::Num2, egui::Key::Num3, egui::Key::Num4, egui::Key::Num5,
+            egui::Key::Num6, egui::Key::Num7, egui::Key::Num8, egui::Key::Num9,
+        ];
+        if let Some(member) = numbers.iter().position(|&key| ctx.input(|i| i.key_pressed(key))) {
+            self.choose_group_keeper(member);
+        } else if ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
+            if let Some(best) = self.group_view.as_ref().and_then(|view| view.best) {
+                self.choose_group_keeper(best);
+            }
+        } else if ctx.input(|i| i.key_pressed(keys.skip)) {
+            self.skip_group();
+        } else if ctx.input(|i| i.key_pressed(keys.undo)) {
+            self.go_to_previous();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::T)) {
+            self.theme.cycle();
+        }
+        if ctx.input(|i| i.key_pressed(keys.exit)) {
+            self.request_exit();
+        }
+    }
+    
+    fn show_summary_ui(&mut self, ctx: &Context) {
+        let output_folder = std::path::absolute(&self.file_manager.output_folder)
+            .unwrap_or_else(|_| self.file_manager.output_folder.clone());
+        let report_path = report::csv_path(&output_folder);
+        let elapsed = self.started_at.elapsed().as_secs();
+        let damaged = self.damaged_files.lock().unwrap().len();
+        
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                let available_height = ui.available_height();
+                ui.add_space(available_height / 2.0 - 220.0);
+                
+                ui.label(RichText::new(regular::CHECK_CIRCLE).size(56.0).color(self.theme.accent_green));
+                ui.add_space(12.0);
+                ui.heading(RichText::new("Confronto completato").size(28.0).color(self.theme.text_primary));
+                ui.add_space(20.0);
+                
+                Frame::NONE
+                    .fill(self.theme.card_bg)
+                    .corner_radius(CornerRadius::same(12))
+                    .inner_margin(Margin::symmetric(32, 20))
+                    .show(ui, |ui| {
+                        let row = |ui: &mut egui::Ui, icon: &str, label: &str, value: String, color: Color32| {
+                            ui.horizontal(|ui| {
+                                ui.label(RichText::new(format!("{} {}", icon, label)).size(16.0).color(self.theme.text_secondary));
+                                ui.label(RichText::new(value).size(16.0).color(color).strong());
+                            });
+                        };
+                        row(ui, regular::CHECK, "File selezionati:", self.selected_count.lock().unwrap().to_string(), self.theme.accent_green);
+                        row(ui, regular::ARROW_RIGHT, "Coppie saltate:", self.skipped_count.lock().unwrap().to_string(), self.theme.accent_orange);
+                        row(ui, regular::COPY, "Coppie tenute entrambe:", self.kept_both_count.lock().unwrap().to_string(), self.theme.gold_star);
+                        let identical = self.identical_pairs.lock().unwrap().len();
+                        if identical > 0 {
+                            row(ui, regular::COPY_SIMPLE, "Coppie identiche risolte in automatico:", identical.to_string(), self.theme.accent_green);
+                        }
+                        let same_size = self.same_size_pairs.lock().unwrap().len();
+                        if same_size > 0 {
+                            row(ui, regular::COPY_SIMPLE, "Coppie con stesso peso e dimensioni:", same_size.to_string(), self.theme.accent_green);
+                        }
+                        if damaged > 0 {
+                            row(ui, regular::WARNING, "File non leggibili:", damaged.to_string(), self.theme.danger_red);
+                        }
+                        row(ui, regular::TIMER, "Durata:", format!("{} min {:02} s", elapsed / 60, elapsed % 60), self.theme.text_primary);
+                        row(ui, regular::FOLDER, "Output:", output_folder.display().to_string(), self.theme.text_primary);
+                    });
+                ui.add_space(24.0);
+                
+                ui.horizontal(|ui| {
+                    let btn_size = Vec2::new(200.0, 44.0);
+                    ui.add_space((ui.available_width() - 3.0 * btn_size.x - 2.0 * ui.spacing().item_spacing.x) / 2.0);
+                    if self.modern_button(ui, &format!("{} Apri cartella", regular::FOLDER_OPEN), self.theme.accent_blue, btn_size) {
+                        open_with_system(&output_folder);
+                    }
+                    if self.modern_button(ui, &format!("{} Apri report", regular::FILE_CSV), self.theme.accent_green, btn_size) {
+                        open_with_system(&report_path);
+                    }
+                    if self.modern_button(ui, &format!("{} Chiudi ({})", regular::X, KeyBindings::label(self.keys.exit)), self.theme.danger_red, btn_size) {
+                        self.exit_program = true;
+                    }
+                });
+            });
+        });
+        
+        if ctx.input(|i| i.key_pressed(self.keys.exit) || i.key_pressed(egui::Key::Enter)) {
+            self.exit_program = true;
+        }
+    }
+    
+    fn show_error_ui(&mut self, ctx: &Context, message: &str) {
+        let mut retry = false;
+        let mut skip = ctx.input(|i| i.key_pressed(self.keys.skip));
+        
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                let available_height = ui.available_height();
+                ui.add_space(available_height / 2.0 - 140.0);
+                
+                ui.label(RichText::new(regular::WARNING).size(48.0).color(self.theme.danger_red));
+                ui.add_space(20.0);
+                ui.heading(RichText::new(format!("Impossibile caricare la coppia {}/{}", self.current_index + 1, self.all_pairs.len()))
+                    .size(24.0)
+                    .color(self.theme.text_primary));
+                ui.add_space(12.0);
+                
+                Frame::NONE
+                    .fill(self.theme.card_bg)
+                    .corner_radius(CornerRadius::same(8))
+                    .inner_margin(Margin::symmetric(20, 12))
+                    .show(ui, |ui| {
+                        ui.label(RichText::new(message).size(14.0).monospace().color(self.theme.text_secondary));
+                    });
+                ui.add_space(24.0);
+                
+                // Immagini oltre --max-image-mb: si possono comunque guardare fuori dall'app
+                for path in &self.oversized_files {
+                    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+                    if ui.button(format!("{} Apri {} con l'app di sistema", regular::ARROW_SQUARE_OUT, name)).clicked() {
+                        open_with_system(path);
+                    }
+                }
+                if !self.oversized_files.is_empty() {
+                    ui.add_space(12.0);
+                }
+                
+                ui.horizontal(|ui| {
+                    // Centra i pulsanti nella colonna
+                    ui.add_space((ui.available_width() - 2.0 * 180.0 - ui.spacing().item_spacing.x) / 2.0);
+                    if self.modern_button(ui, &format!("{} Riprova", regular::ARROW_CLOCKWISE), self.theme.accent_blue, Vec2::new(180.0, 44.0)) {
+                        retry = true;
+                    }
+                    if self.modern_button(ui, &format!("{} Salta ({})", regular::ARROW_DOWN, KeyBindings::label(self.keys.skip)), self.theme.text_secondary, Vec2::new(180.0, 44.0)) {
+                        skip = true;
+                    }
                 });
+            });
+        });
+        
+        if skip || retry {
+            self.oversized_files.clear();
+        }
+        if skip && self.slideshow.is_some() {
+            self.advance_slide(1);
+        } else if let Some(frontier) = self.review_return
+            && skip
+        {
+            self.review_to(frontier);
+        } else if skip {
+            self.auto_skip_current("Salto la coppia...");
+        } else if retry {
+            self.state = AppState::Loading(format!("Caricamento coppia {}...", self.current_index + 1));
+            self.load_current_pair();
+        }
+    }
+    
+    fn show_slideshow_controls(&mut self, ui: &mut egui::Ui) {
+        let Some(slideshow) = &self.slideshow else {
+            return;
+        };
+        let paused = slideshow.paused;
+        let remaining = (slideshow.interval - (self.animation_time - slideshow.shown_at)).max(0.0);
+        
+        ui.horizontal(|ui| {
+            let btn_size = Vec2::new(150.0, 35.0);
+            
+            if self.modern_button(ui, &format!("{} Precedente (←)", regular::ARROW_LEFT), self.theme.text_secondary, btn_size) {
+                self.advance_slide(-1);
+            }
+            
+            let (icon, label, color) = if paused {
+                (regular::PLAY, "Riprendi", self.theme.accent_green)
+            } else {
+                (regular::PAUSE, "Pausa", self.theme.accent_orange)
+            };
+            if self.modern_button(ui, &format!("{} {} (Spazio)", icon, label), color, btn_size) {
+                self.toggle_slideshow_pause();
+            }
+            
+            if self.modern_button(ui, &format!("{} Successiva (→)", regular::ARROW_RIGHT), self.theme.text_secondary, btn_size) {
+                self.advance_slide(1);
+            }
+            
+            let status = if paused {
+                "In pausa".to_string()
+            } else if matches!(self.state, AppState::ShowingImages) {
+                format!("Prossima tra {:.0}s", remaining.ceil())
+            } else {
+                "Caricamento...".to_string()
+            };
+            ui.label(RichText::new(format!("{} {}", regular::TIMER, status)).size(14.0).color(self.theme.text_secondary));
+            
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if self.modern_button(ui, &format!("{} Esci", regular::X), self.theme.danger_red, Vec2::new(120.0, 35.0)) {
+                    self.exit_program = true;
+                }
+            });
+        });
+    }
+    
+    fn handle_slideshow_input(&mut self, ctx: &Context) {
+        if ctx.input(|i| i.key_pressed(egui::Key::Space)) {
+            self.toggle_slideshow_pause();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
+            self.advance_slide(1);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
+            self.advance_slide(-1);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::F)) {
+            self.toggle_full_resolution(ctx);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::L)) {
+            self.loupe_enabled = !self.loupe_enabled;
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::T)) {
+            self.theme.cycle();
+        }
+        if ctx.input(|i| i.key_pressed(self.keys.exit)) {
+            self.exit_program = true;
+        }
+    }
+    
+    fn toggle_slideshow_pause(&mut self) {
+        if let Some(slideshow) = &mut self.slideshow {
+            slideshow.paused = !slideshow.paused;
+            // Alla ripresa la slide corrente resta visibile per un intervallo intero
+            slideshow.shown_at = self.animation_time;
+        }
+    }
+    
+    // Presentazione dei file in output: ogni "coppia" è lo stesso file ripetuto
+    fn is_single_image(&self) -> bool {
+        self.slideshow.is_some()
+            && self.all_pairs.get(self.current_index).is_some_and(|(path1, path2)| path1 == path2)
+    }
+    
+    // Passa alla slide precedente o successiva usando quella già caricata se disponibile.
+    // Restituisce false se non ce ne sono altre in quella direzione
+    fn advance_slide(&mut self, step: isize) -> bool {
+        let Some(index) = self.current_index.checked_add_signed(step).filter(|&index| index < self.all_pairs.len()) else {
+            return false;
+        };
+        
+        self.current_index = index;
+        self.state = AppState::Loading(format!("Caricamento {}/{}...", index + 1, self.all_pairs.len()));
+        
+        let prefetched = self.prefetched.lock().unwrap().take();
+        match prefetched {
+            Some((prefetched_index, result)) if prefetched_index == index => {
+                *self.next_data.lock().unwrap() = Some((index, result));
+            }
+            _ => self.load_current_pair(),
+        }
+        true
+    }
+    
+    fn prefetch_next_slide(&self) {
+        let index = self.current_index + 1;
+        let Some((path1, path2)) = self.pair_at(index) else {
+            return;
+        };
+        let prefetched = self.prefetched.clone();
+        let weights = self.scoring_weights;
+        
+        thread::spawn(move || {
+            let result = Self::load_pair(&path1, &path2, &weights, false);
+            *prefetched.lock().unwrap() = Some((index, result));
         });
     }
     
+    fn handle_keyboard_input(&mut self, ctx: &Context) {
+        let keys = self.keys;
+        if ctx.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
+            self.review_step(-1);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
+            self.review_step(1);
+        }
+        
+        // In revisione restano solo i comandi di visualizzazione
+        if self.review_return.is_none() {
+            self.handle_decision_input(ctx);
+        }
+        
+        // Maiusc+C/V mostrano il file nel file manager, C/V da soli ne copiano il percorso
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, egui::Key::C)) {
+            self.reveal_file(1);
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, egui::Key::V)) {
+            self.reveal_file(2);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::C)) {
+            self.copy_path(ctx, 1);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::V)) {
+            self.copy_path(ctx, 2);
+        }
+        
+        // Q/E ruotano l'immagine sotto il cursore
+        let hovered = if self.hover_image1 { Some(1) } else if self.hover_image2 { Some(2) } else { None };
+        if let Some(num) = hovered {
+            if ctx.input(|i| i.key_pressed(egui::Key::Q)) {
+                self.rotate_image(ctx, num, false);
+            }
+            if ctx.input(|i| i.key_pressed(egui::Key::E)) {
+                self.rotate_image(ctx, num, true);
+            }
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::F)) {
+            self.toggle_full_resolution(ctx);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::L)) {
+            self.loupe_enabled = !self.loupe_enabled;
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::H)) {
+            self.toggle_exposure_normalization(ctx);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::K)) {
+            self.focus_peaking = !self.focus_peaking;
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::T)) {
+            self.theme.cycle();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::M)) {
+            self.metadata_diff = !self.metadata_diff;
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::J)) {
+            self.history_panel = !self.history_panel;
+        }
+        // +/- ingrandiscono o riducono l'area immagini; Ctrl+/- resta lo zoom dell'interfaccia
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Plus) || i.consume_key(egui::Modifiers::NONE, egui::Key::Equals)) {
+            self.adjust_image_height(true);
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Minus)) {
+            self.adjust_image_height(false);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::O)) {
+            self.weights_editor = true;
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::F1) || i.key_pressed(egui::Key::Questionmark)) {
+            self.shortcut_help = true;
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::PageDown)) {
+            self.cycle_pages(true);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::PageUp)) {
+            self.cycle_pages(false);
+        }
+        if ctx.input(|i| i.key_pressed(keys.exit)) {
+            self.request_exit();
+        }
+    }
+    
+    // Scelte, note e salti: tutto ciò che modifica le decisioni o la posizione nella sessione
+    fn handle_decision_input(&mut self, ctx: &Context) {
+        let keys = self.keys;
+        // Maiusc con i tasti di scelta indica la sorgente dei metadati: va letto per primo
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, keys.choose_left)) {
+            self.set_metadata_source(1);
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, keys.choose_right)) {
+            self.set_metadata_source(2);
+        }
+        if ctx.input(|i| i.key_pressed(keys.choose_left)) {
+            self.make_choice(1);
+        }
+        if ctx.input(|i| i.key_pressed(keys.choose_right)) {
+            self.make_choice(2);
+        }
+        if ctx.input(|i| i.key_pressed(keys.skip)) {
+            self.skip_current();
+        }
+        if ctx.input(|i| i.key_pressed(keys.defer)) {
+            self.defer_current();
+        }
+        if ctx.input(|i| i.key_pressed(keys.undo)) {
+            self.go_to_previous();
+        }
+        if ctx.input(|i| i.key_pressed(keys.transfer_meta)) {
+            self.transfer_metadata();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::B)) {
+            self.keep_both();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::X)) {
+            self.swap_folders();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::N)) {
+            let note = self.annotations.get(&self.current_index).map(|a| a.note.clone()).unwrap_or_default();
+            self.note_input = Some(note);
+        }
+        for (key, tag) in [egui::Key::Num1, egui::Key::Num2, egui::Key::Num3].into_iter().zip(QUICK_TAGS) {
+            if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, key)) {
+                self.toggle_tag(tag);
+            }
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::G)) {
+            self.jump_input = Some(String::new());
+        }
+        // Maiusc+E: E da sola ruota l'immagine sotto il cursore
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, egui::Key::E)) {
+            self.open_exif_editor(if self.hover_image2 { 2 } else { 1 });
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, egui::Key::I)) {
+            self.start_identical_run();
+        }
+    }
+    
+    // Passa alla pagina successiva o precedente di ogni TIFF multipagina della coppia,
+    // decodificandola in background come le coppie
+    fn cycle_pages(&mut self, forward: bool) {
+        for num in [1, 2] {
+            let (analysis, page) = if num == 1 {
+                (&self.current_analysis1, self.page1)
+            } else {
+                (&self.current_analysis2, self.page2)
+            };
+            let Some(analysis) = analysis else {
+                continue;
+            };
+            let count = analysis.frame_count;
+            if count < 2 || !image_analyzer::is_tiff_path(&analysis.file_path) {
+                continue;
+            }
+            
+            let page = if forward { (page + 1) % count } else { (page + count - 1) % count };
+            let path = analysis.file_path.clone();
+            let index = self.current_index;
+            let page_data = self.page_data.clone();
+            
+            thread::spawn(move || {
+                let result = image_analyzer::open_tiff_page(&path, page).map(Self::with_preview);
+                page_data.lock().unwrap().push((index, num, page, result));
+            });
+        }
+    }
     
-    fn show_metadata_card(&self, ui: &mut egui::Ui, exif_data: &Vec<(String, String)>, width: f32) {
-        // Calcola l'altezza disponibile
-        let available_height = ui.available_height();
-        
-        // Usa un'altezza fissa se lo spazio disponibile è troppo piccolo
-        let card_height = if available_height > 200.0 {
-            available_height - 10.0
-        } else {
-            200.0 // Altezza minima garantita
-        };
+    // Immagini intere delle anteprime prese dalla cache: si decodificano solo quando servono
+    // a lente, piena risoluzione, regione di fuoco o focus peaking. Arrivano come la prima
+    // pagina di un TIFF, che è proprio ciò che decodifica load_and_resize_image
+    fn load_missing_full_images(&mut self) {
+        let needed = self.full_resolution || self.loupe_enabled || self.focus_region.is_some() || self.focus_peaking;
+        if !needed || !matches!(self.state, AppState::ShowingImages) || self.full_requested == Some(self.current_index) {
+            return;
+        }
+        self.full_requested = Some(self.current_index);
         
-        Frame::NONE
-            .fill(CARD_BG)
-            .corner_radius(CornerRadius::same(12))
-            .stroke(Stroke::new(1.0, Color32::from_gray(50)))
-            .shadow(egui::epaint::Shadow {
-                offset: [0, 2],
-                blur: 4,
-                spread: 0,
-                color: Color32::from_black_alpha(40),
-            })
-            .inner_margin(Margin::same(12))
-            .show(ui, |ui| {
-                ui.set_min_width(width - 24.0);
-                ui.set_max_width(width - 24.0);
-                ui.set_min_height(card_height - 24.0);
-                
-                // Titolo
-                ui.label(RichText::new("Metadati EXIF").size(13.0).color(TEXT_PRIMARY).strong());
-                ui.add_space(4.0);
-                ui.separator();
-                ui.add_space(4.0);
-                
-                // Area scrollabile per i metadati
-                let scroll_height = (card_height - 60.0).max(100.0);
-                egui::ScrollArea::vertical()
-                    .max_height(scroll_height)
-                    .auto_shrink([false, false]) // Impedisce lo shrink automatico
-                    .show(ui, |ui| {
-                        for (key, value) in exif_data {
-                            let formatted_key = key.replace("(", "").replace(")", "");
-                            ui.horizontal(|ui| {
-                                // Usa una larghezza fissa per la chiave per allineamento
-                                ui.with_layout(egui::Layout::left_to_right(egui::Align::Min), |ui| {
-                                    ui.set_min_width(150.0);
-                                    ui.label(RichText::new(format!("{}:", formatted_key))
-                                        .size(11.0)
-                                        .color(TEXT_SECONDARY));
-                                });
-                                ui.label(RichText::new(value)
-                                    .size(11.0)
-                                    .color(TEXT_PRIMARY));
-                            });
-                        }
-                        
-                        // Aggiungi un po' di spazio alla fine per miglior leggibilità
-                        ui.add_space(10.0);
-                    });
+        for num in [1, 2] {
+            let (analysis, full) = if num == 1 {
+                (&self.current_analysis1, &self.full1)
+            } else {
+                (&self.current_analysis2, &self.full2)
+            };
+            let (Some(analysis), None) = (analysis, full) else {
+                continue;
+            };
+            
+            let path = analysis.file_path.clone();
+            let index = self.current_index;
+            let page_data = self.page_data.clone();
+            thread::spawn(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| Self::load_and_resize_image(&path)))
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("Decoder panicked on {:?}", path)));
+                page_data.lock().unwrap().push((index, num, 0, result));
             });
+        }
     }
     
-    fn show_modern_controls(&mut self, ui: &mut egui::Ui) {
-        ui.horizontal(|ui| {
-            // Pulsanti principali compatti
-            let btn_size = Vec2::new(120.0, 35.0);
-            
-            if self.modern_button(ui, &format!("{} Prima (A)", regular::ARROW_LEFT), ACCENT_BLUE, btn_size) {
-                self.make_choice(1);
-            }
-            
-            if self.modern_button(ui, &format!("{} Previous (P)", regular::ARROW_U_UP_LEFT), TEXT_SECONDARY, btn_size) {
-                self.go_to_previous();
-            }
-            
-            if self.modern_button(ui, &format!("{} Seconda (D)", regular::ARROW_RIGHT), ACCENT_ORANGE, btn_size) {
-                self.make_choice(2);
+    // Focus peaking sulla parte visibile dell'immagine intera, una richiesta alla volta per
+    // immagine: durante uno spostamento si calcola l'ultima posizione appena finita la precedente
+    fn update_focus_peaking(&mut self, ctx: &Context) {
+        let results = std::mem::take(&mut *self.peaking_data.lock().unwrap());
+        for (num, request, covered, mask) in results {
+            let slot = num as usize - 1;
+            self.peaking_pending[slot] = None;
+            let tiles = Self::image_to_tiles(ctx, &mask, &format!("peaking{}", num));
+            self.peaking_overlays[slot] = Some((request, covered, tiles));
+        }
+        
+        if !self.focus_peaking || !matches!(self.state, AppState::ShowingImages) {
+            return;
+        }
+        for num in [1, 2] {
+            let slot = num as usize - 1;
+            let Some(request) = self.peaking_request(num) else {
+                continue;
+            };
+            if self.peaking_pending[slot].is_some() || self.peaking_overlays[slot].as_ref().is_some_and(|(shown, _, _)| *shown == request) {
+                continue;
             }
+            let full = if num == 1 { &self.full1 } else { &self.full2 };
+            let Some(full) = full else {
+                continue;
+            };
             
-            if self.modern_button(ui, &format!("{} Salta (S)", regular::ARROW_DOWN), TEXT_SECONDARY, btn_size) {
-                self.skip_current();
-            }
+            // Si ritaglia subito la parte visibile, senza copiare l'immagine intera nel thread
+            let corners = [request.region.min, request.region.max]
+                .map(|corner| Self::source_point(corner.to_vec2(), request.rotation));
+            let (width, height) = (full.width() as f32, full.height() as f32);
+            let x0 = ((corners[0].x.min(corners[1].x) * width).floor() as u32).min(full.width() - 1);
+            let y0 = ((corners[0].y.min(corners[1].y) * height).floor() as u32).min(full.height() - 1);
+            let x1 = ((corners[0].x.max(corners[1].x) * width).ceil() as u32).clamp(x0 + 1, full.width());
+            let y1 = ((corners[0].y.max(corners[1].y) * height).ceil() as u32).clamp(y0 + 1, full.height());
+            let crop = full.crop_imm(x0, y0, x1 - x0, y1 - y0);
             
-            if self.modern_button(ui, &format!("{} Meta (W)", regular::ARROW_UP), ACCENT_GREEN, btn_size) {
-                self.transfer_metadata();
-            }
+            let source = egui::Rect::from_min_max(
+                egui::pos2(x0 as f32 / width, y0 as f32 / height),
+                egui::pos2(x1 as f32 / width, y1 as f32 / height),
+            );
+            let inverse = (4 - request.rotation) % 4;
+            let shown = [source.min, source.max].map(|corner| Self::source_point(corner.to_vec2(), inverse).to_pos2());
+            let covered = egui::Rect::from_two_pos(shown[0], shown[1]);
             
-            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                if self.modern_button(ui, &format!("{} Esci", regular::X), DANGER_RED, btn_size) {
-                    self.exit_program = true;
+            self.peaking_pending[slot] = Some(request.clone());
+            let peaking_data = self.peaking_data.clone();
+            thread::spawn(move || {
+                let mut image = image_analyzer::rotate_clockwise(&crop, request.rotation);
+                let [max_width, max_height] = request.max_size;
+                if image.width() > max_width.max(1) || image.height() > max_height.max(1) {
+                    image = image.resize(max_width.max(1), max_height.max(1), FilterType::Triangle);
                 }
-                
-                // Shortcuts help compatto
-                ui.label(RichText::new(format!("{} A, D, S, W, P, ESC", regular::KEYBOARD)).size(12.0).color(TEXT_SECONDARY));
+                // Calcolata dopo la normalizzazione, così la soglia vale per ciò che si vede
+                if let Some(gain) = request.gain {
+                    image = image_analyzer::apply_exposure_gain(&image, gain);
+                }
+                let mask = image_analyzer::focus_peaking(&image, PEAKING_COLOR);
+                peaking_data.lock().unwrap().push((num, request, covered, mask));
             });
-        });
+        }
     }
     
-    fn modern_button(&self, ui: &mut egui::Ui, text: &str, color: Color32, size: Vec2) -> bool {
-        let button = egui::Button::new(RichText::new(text).size(18.0))
-            .min_size(size)
-            .fill(color.gamma_multiply(0.2))
-            .stroke(Stroke::new(1.0, color));
+    // Richiesta per lo stato attuale dell'immagine `num`, se è già stata disegnata
+    fn peaking_request(&self, num: u8) -> Option<PeakingRequest> {
+        let slot = num as usize - 1;
+        let (region, max_size) = self.peaking_visible[slot]?;
+        let analysis = if num == 1 { &self.current_analysis1 } else { &self.current_analysis2 };
+        Some(PeakingRequest {
+            path: analysis.as_ref()?.file_path.clone(),
+            page: if num == 1 { self.page1 } else { self.page2 },
+            rotation: if num == 1 { self.rotation1 } else { self.rotation2 },
+            gain: self.exposure_gains.map(|gains| gains[slot]),
+            region,
+            max_size,
+        })
+    }
+    
+    // Maschera del focus peaking sopra l'immagine, se calcolata per ciò che è mostrato ora. Durante
+    // uno spostamento resta quella della posizione precedente, nel punto dell'immagine a cui si riferisce
+    fn paint_focus_peaking(&self, painter: &egui::Painter, num: u8, view: egui::Rect, tint: Color32) {
+        if !self.focus_peaking {
+            return;
+        }
+        let slot = num as usize - 1;
+        let (Some((request, covered, tiles)), Some(current)) = (&self.peaking_overlays[slot], self.peaking_request(num)) else {
+            return;
+        };
+        if (&request.path, request.page, request.rotation, request.gain) != (&current.path, current.page, current.rotation, current.gain) {
+            return;
+        }
         
-        let response = ui.add(button);
+        let region = egui::Rect::from_min_max(
+            view.min + covered.min.to_vec2() * view.size(),
+            view.min + covered.max.to_vec2() * view.size(),
+        );
+        let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+        for (tile, tile_rect) in &tiles.tiles {
+            let tile_rect = egui::Rect::from_min_max(
+                region.min + tile_rect.min.to_vec2() * region.size(),
+                region.min + tile_rect.max.to_vec2() * region.size(),
+            );
+            painter.image(tile.id(), tile_rect, uv, tint);
+        }
+    }
+    
+    // Scambia cartella 1 e 2 per le coppie ancora da decidere. Le coppie già decise e i file
+    // in output restano come sono; la coppia corrente viene mostrata con le colonne invertite.
+    fn swap_folders(&mut self) {
+        // Nella deduplicazione c'è una sola cartella
+        if self.pair_groups.is_some() {
+            return;
+        }
         
-        if response.hovered() {
-            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+        let file_manager = &mut self.file_manager;
+        std::mem::swap(&mut file_manager.folder1, &mut file_manager.folder2);
+        
+        let mut current_swapped = false;
+        for (index, (path1, path2)) in self.all_pairs.iter_mut().enumerate() {
+            if self.decisions.get(index).is_none_or(|decision| decision.is_none()) {
+                std::mem::swap(path1, path2);
+                current_swapped |= index == self.current_index;
+                if let Some(labels) = self.compression_labels.get_mut(&index) {
+                    labels.swap(0, 1);
+                }
+            }
         }
         
-        response.clicked()
+        if current_swapped {
+            std::mem::swap(&mut self.current_analysis1, &mut self.current_analysis2);
+            std::mem::swap(&mut self.preview1, &mut self.preview2);
+            std::mem::swap(&mut self.full1, &mut self.full2);
+            std::mem::swap(&mut self.rotation1, &mut self.rotation2);
+            std::mem::swap(&mut self.page1, &mut self.page2);
+            self.exif_edits.swap(0, 1);
+            if let Some(sharpness) = &mut self.focus_sharpness {
+                sharpness.swap(0, 1);
+            }
+            if let Some(gains) = &mut self.exposure_gains {
+                gains.swap(0, 1);
+            }
+            std::mem::swap(&mut self.texture1, &mut self.texture2);
+            std::mem::swap(&mut self.loupe_texture1, &mut self.loupe_texture2);
+            std::mem::swap(&mut self.image_rect1, &mut self.image_rect2);
+            std::mem::swap(&mut self.hover_image1, &mut self.hover_image2);
+            self.cropped = self.cropped.map(|num| 3 - num);
+        }
+        
+        self.save_session();
     }
     
-    fn show_loading_ui(&self, ctx: &Context, message: &str) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical_centered(|ui| {
-                let available_height = ui.available_height();
-                ui.add_space(available_height / 2.0 - 100.0);
-                
-                // Animated spinner
-                ui.spinner();
-                ui.add_space(30.0);
-                
-                ui.heading(RichText::new(message).size(24.0).color(TEXT_PRIMARY));
-                
-                ui.add_space(20.0);
-                
-                // Progress info
-                Frame::NONE
-                    .fill(CARD_BG)
-                    .corner_radius(CornerRadius::same(8))
-                    .inner_margin(Margin::symmetric(20, 12))
-                    .show(ui, |ui| {
-                        ui.label(RichText::new(format!("{} File {}/{}", regular::FILE, 
-                            self.current_index + 1, self.all_pairs.len()))
-                            .size(18.0)
-                            .color(TEXT_SECONDARY));
-                    });
-            });
-        });
+    // Ruota solo l'anteprima; il file viene ruotato solo quando la scelta è confermata
+    fn rotate_image(&mut self, ctx: &Context, num: u8, clockwise: bool) {
+        let rotation = if num == 1 { &mut self.rotation1 } else { &mut self.rotation2 };
+        *rotation = if clockwise { (*rotation + 1) % 4 } else { (*rotation + 3) % 4 };
+        // La zona è tracciata sull'immagine com'era mostrata: ruotata non coprirebbe più lo stesso soggetto
+        self.clear_focus_region();
+        self.refresh_texture(ctx, num);
     }
     
-    fn handle_keyboard_input(&mut self, ctx: &Context) {
-        if ctx.input(|i| i.key_pressed(egui::Key::A)) {
-            self.make_choice(1);
+    fn toggle_full_resolution(&mut self, ctx: &Context) {
+        self.full_resolution = !self.full_resolution;
+        self.refresh_texture(ctx, 1);
+        self.refresh_texture(ctx, 2);
+    }
+    
+    // Utile con i bracketing: le differenze di luminosità volute non coprono composizione e nitidezza
+    fn toggle_exposure_normalization(&mut self, ctx: &Context) {
+        self.normalize_exposure = !self.normalize_exposure;
+        self.update_exposure_gains();
+        self.refresh_texture(ctx, 1);
+        self.refresh_texture(ctx, 2);
+    }
+    
+    fn update_exposure_gains(&mut self) {
+        self.exposure_gains = match (&self.preview1, &self.preview2) {
+            (Some(preview1), Some(preview2)) if self.normalize_exposure => {
+                Some(image_analyzer::exposure_gains(preview1, preview2))
+            }
+            _ => None,
+        };
+    }
+    
+    // Ricrea la texture dall'anteprima o dall'immagine intera, applicando la rotazione scelta
+    fn refresh_texture(&mut self, ctx: &Context, num: u8) {
+        let (preview, full, rotation) = if num == 1 {
+            (&self.preview1, &self.full1, self.rotation1)
+        } else {
+            (&self.preview2, &self.full2, self.rotation2)
+        };
+        let source = if self.full_resolution { full } else { preview };
+        let Some(source) = source else {
+            return;
+        };
+        
+        let rotated;
+        let image = if rotation == 0 {
+            source
+        } else {
+            rotated = image_analyzer::rotate_clockwise(source, rotation);
+            &rotated
+        };
+        let normalized;
+        let image = match self.exposure_gains {
+            Some(gains) => {
+                normalized = image_analyzer::apply_exposure_gain(image, gains[num as usize - 1]);
+                &normalized
+            }
+            None => image,
+        };
+        let tiles = Self::image_to_tiles(ctx, image, &format!("img{}", num));
+        
+        if num == 1 {
+            self.texture1 = Some(tiles);
+        } else {
+            self.texture2 = Some(tiles);
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::D)) {
-            self.make_choice(2);
+    }
+    
+    fn make_choice(&mut self, choice: u8) {
+        // Una coppia raggiunta con G potrebbe essere già stata decisa
+        self.undo_decision(self.current_index);
+        
+        if let Some((path1, path2)) = self.pair_at(self.current_index) {
+            // Save current index to history before moving forward
+            self.navigation_history.push(self.current_index);
+            let path = if choice == 1 { path1 } else { path2 };
+            self.state = AppState::ProcessingChoice(choice, path);
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::S)) {
-            self.skip_current();
+    }
+    
+    fn skip_current(&mut self) {
+        self.remember_decision([false, false]);
+        self.undo_decision(self.current_index);
+        
+        // Save current index to history before skipping
+        self.navigation_history.push(self.current_index);
+        self.record_decision(PairDecision::Skipped);
+        
+        *self.skipped_count.lock().unwrap() += 1;
+        self.move_to_next();
+    }
+    
+    // Rimanda la coppia: resta da decidere, non conta tra le saltate e ricompare dopo le altre
+    fn defer_current(&mut self) {
+        // Nella deduplicazione il file scelto passa alla coppia successiva del gruppo
+        if self.pair_groups.is_some() {
+            self.toast = Some(("Nella ricerca duplicati le coppie non si possono rimandare".to_string(), self.animation_time));
+            return;
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::P)) {
-            self.go_to_previous();
+        
+        let index = self.current_index;
+        self.deferred.retain(|&deferred| deferred != index);
+        if self.next_index() >= self.all_pairs.len() {
+            self.toast = Some(("Non restano altre coppie da decidere".to_string(), self.animation_time));
+            return;
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::W)) {
-            self.transfer_metadata();
+        
+        self.undo_decision(index);
+        self.deferred.push(index);
+        self.navigation_history.push(index);
+        info!("Coppia {} rimandata", index + 1);
+        self.pending_toast = Some(format!("Coppia {} rimandata: verrà riproposta alla fine", index + 1));
+        self.move_to_next();
+    }
+    
+    fn keep_both(&mut self) {
+        self.undo_decision(self.current_index);
+        
+        if let Some((path1, path2)) = self.pair_at(self.current_index) {
+            // La rinomina anti-collisione di copy_to_output evita che le due copie si sovrascrivano
+            let file_manager = self.file_manager.clone();
+            let mut copied: Vec<PathBuf> = Vec::new();
+            for (num, path, rotation) in [(1, &path1, self.rotation1), (2, &path2, self.rotation2)] {
+                let Some(dest_path) = self.copy_or_pause(&file_manager, path, None, rotation) else {
+                    // Metà decisione non vale: la prima copia si toglie e la coppia resta da decidere
+                    for dest_path in &copied {
+                        if let Err(e) = file_manager.delete_from_output(dest_path) {
+                            warn!("Impossibile eliminare la copia {}: {}", dest_path.display(), e);
+                        }
+                    }
+                    return;
+                };
+                self.apply_exif_edits(num, path, &dest_path);
+                copied.push(dest_path);
+            }
+            
+            self.remember_decision([true, true]);
+            self.navigation_history.push(self.current_index);
+            self.record_decision(PairDecision::KeptBoth(copied));
+            
+            // Il trasferimento metadati non si applica quando si tengono entrambe
+            self.metadata_transfer_pending = false;
+            self.metadata_transfer_source = None;
+            
+            *self.kept_both_count.lock().unwrap() += 1;
+            self.move_to_next();
         }
-        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
-            self.exit_program = true;
+    }
+    
+    fn is_below_min_score(&self, analysis1: &ImageAnalysis, analysis2: &ImageAnalysis) -> bool {
+        self.min_score
+            .map(|min| analysis1.quality_score < min && analysis2.quality_score < min)
+            .unwrap_or(false)
+    }
+    
+    // Salta la coppia senza mostrarla; non entra nella cronologia di P
+    fn auto_skip_current(&mut self, message: &str) {
+        self.record_decision(PairDecision::Skipped);
+        *self.skipped_count.lock().unwrap() += 1;
+        
+        self.state = AppState::Loading(message.to_string());
+        self.move_to_next();
+        if !matches!(self.state, AppState::Summary) {
+            self.load_current_pair();
+        }
+    }
+    
+    // --identical vale solo quando si decide: non nella presentazione, in revisione o con un
+    // errore di scrittura in sospeso
+    fn resolves_identical(&self) -> bool {
+        self.identical_action.is_some()
+            && self.write_error.is_none()
+            && self.slideshow.is_none()
+            && self.review_return.is_none()
+    }
+    
+    // File identici: nessuna scelta da fare. Il file copiato è sempre il primo; nella
+    // deduplicazione il primo passa al confronto successivo del gruppo
+    fn auto_resolve_identical(&mut self) {
+        let Some((path1, _)) = self.pair_at(self.current_index) else {
+            return;
+        };
+        
+        if self.same_group(self.current_index, self.current_index + 1) {
+            self.identical_pairs.lock().unwrap().insert(self.current_index);
+            self.advance_winner(path1);
+            return;
+        }
+        
+        if !self.record_identical(&path1, self.identical_action == Some(IdenticalAction::Copy)) {
+            // Copia non riuscita: con l'errore in sospeso la coppia si mostra, per una scelta manuale
+            self.load_current_pair();
+            return;
+        }
+        self.identical_pairs.lock().unwrap().insert(self.current_index);
+        
+        self.state = AppState::Loading("File identici, passo alla coppia successiva...".to_string());
+        self.move_to_next();
+        if !matches!(self.state, AppState::Summary) {
+            self.load_current_pair();
+        }
+    }
+    
+    // Falso se la copia non è riuscita: la coppia resta da decidere
+    fn record_identical(&mut self, path1: &Path, copy: bool) -> bool {
+        if copy {
+            let file_manager = self.file_manager.clone();
+            let Some(dest_path) = self.copy_or_pause(&file_manager, path1, None, 0) else {
+                return false;
+            };
+            self.record_decision(PairDecision::Selected(Some(dest_path)));
+        } else {
+            self.record_decision(PairDecision::Skipped);
+        }
+        true
+    }
+    
+    // Copia in output il file scelto. Se la scrittura fallisce l'errore resta in un banner,
+    // con il file e il motivo dato dal sistema, e chi chiama non deve passare alla coppia dopo
+    fn copy_or_pause(&mut self, file_manager: &FileManager, path: &Path, metadata_source: Option<&Path>, rotation: u8) -> Option<PathBuf> {
+        match file_manager.copy_to_output_with_metadata(path, metadata_source, rotation) {
+            Ok(dest_path) => {
+                self.write_error = None;
+                Some(dest_path)
+            }
+            Err(e) => {
+                error!("Impossibile copiare {} in {}: {:#}", path.display(), file_manager.output_folder.display(), e);
+                self.write_error = Some(format!("{} → {}: {:#}", path.display(), file_manager.output_folder.display(), e));
+                None
+            }
+        }
+    }
+    
+    fn show_write_error_banner(&mut self, ctx: &Context) {
+        let Some(message) = &self.write_error else {
+            return;
+        };
+        let mut dismissed = false;
+        egui::TopBottomPanel::top("write_error_banner")
+            .frame(Frame::NONE.fill(self.theme.danger_red.gamma_multiply(0.3)).inner_margin(Margin::symmetric(16, 8)))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(format!("{} COPIA NON RIUSCITA, sessione in pausa: {}", regular::WARNING, message))
+                        .size(14.0)
+                        .color(self.theme.text_primary)
+                        .strong());
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        dismissed = ui.button(format!("{} Chiudi", regular::X)).clicked();
+                    });
+                });
+                ui.label(RichText::new("Libera spazio o controlla i permessi della cartella di output, poi ripeti la scelta: la coppia non è stata registrata")
+                    .size(12.0)
+                    .color(self.theme.text_secondary));
+            });
+        if dismissed {
+            self.write_error = None;
+        }
+    }
+    
+    // Confronta in background gli hash delle coppie successive, fino alla prima con file diversi.
+    // I gruppi di tre o più duplicati interrompono la serie: si decidono dalla loro schermata
+    fn start_identical_run(&mut self) {
+        let current_identical = matches!((&self.current_analysis1, &self.current_analysis2),
+            (Some(a1), Some(a2)) if a1.is_file_identical(a2));
+        if !current_identical
+            || self.write_error.is_some()
+            || !matches!(self.state, AppState::ShowingImages)
+            || self.review_return.is_some()
+            || self.slideshow.is_some()
+        {
+            return;
         }
+        
+        let start = self.current_index;
+        let following: Vec<(PathBuf, PathBuf)> = (start + 1..self.all_pairs.len())
+            .map_while(|index| self.group_span(index).is_none().then(|| self.pair_at(index)).flatten())
+            .collect();
+        let identical_run = self.identical_run.clone();
+        let identical_progress = self.identical_progress.clone();
+        *identical_progress.lock().unwrap() = Some((0, following.len()));
+        
+        self.state = AppState::Loading("Cerco le coppie identiche successive...".to_string());
+        thread::spawn(move || {
+            let identical = following.iter()
+                .take_while(|(path1, path2)| ImageAnalysis::files_identical(path1, path2))
+                .inspect(|_| {
+                    if let Some((checked, _)) = identical_progress.lock().unwrap().as_mut() {
+                        *checked += 1;
+                    }
+                })
+                .count();
+            *identical_run.lock().unwrap() = Some((start, 1 + identical));
+            *identical_progress.lock().unwrap() = None;
+        });
     }
     
-    fn make_choice(&mut self, choice: u8) {
-        if let Some((path1, path2)) = self.all_pairs.get(self.current_index) {
-            // Save current index to history before moving forward
+    // Registra le coppie della serie come farebbe --identical (saltate se non indicato) e
+    // passa alla prima coppia con file diversi. Ogni coppia resta annullabile una alla volta
+    fn resolve_identical_run(&mut self, count: usize) {
+        let copy = self.identical_action == Some(IdenticalAction::Copy);
+        for _ in 0..count {
+            let Some((path1, _)) = self.pair_at(self.current_index) else {
+                break;
+            };
+            self.undo_decision(self.current_index);
+            if !self.record_identical(&path1, copy) {
+                // Copia non riuscita: ci si ferma sulla coppia, che resta da decidere
+                self.load_current_pair();
+                return;
+            }
             self.navigation_history.push(self.current_index);
-            let path = if choice == 1 { path1.clone() } else { path2.clone() };
-            self.state = AppState::ProcessingChoice(choice, path);
+            self.identical_pairs.lock().unwrap().insert(self.current_index);
+            self.current_index += 1;
+        }
+        
+        let action = if copy { "copiate" } else { "saltate" };
+        info!("{} coppie identiche consecutive {}", count, action);
+        self.pending_toast = Some(format!("{} coppie identiche {}", count, action));
+        
+        // move_to_next avanza di una coppia: si riparte dall'ultima risolta
+        self.current_index -= 1;
+        self.move_to_next();
+        if !matches!(self.state, AppState::Summary) {
+            self.load_current_pair();
         }
     }
     
-    fn skip_current(&mut self) {
-        // Save current index to history before skipping
-        self.navigation_history.push(self.current_index);
-        // Ensure copied_files is properly sized and mark as skipped (None)
-        while self.copied_files.len() <= self.current_index {
-            self.copied_files.push(None);
+    // Salta la coppia e annota i file per l'elenco di fine sessione
+    fn skip_damaged_pair(&mut self, damaged: Vec<(PathBuf, FileDamage)>) {
+        self.note_damaged(damaged);
+        self.auto_skip_current("File vuoto o danneggiato, salto la coppia...");
+    }
+    
+    // Un file troppo grande non è rovinato: invece di saltarlo in silenzio si lascia scegliere
+    // se aprirlo con l'app di sistema o saltare la coppia
+    fn show_oversized_pair(&mut self, damaged: Vec<(PathBuf, FileDamage)>) {
+        self.oversized_files = damaged.iter()
+            .filter(|(_, damage)| *damage == FileDamage::TooLarge)
+            .map(|(path, _)| path.clone())
+            .collect();
+        let files: Vec<String> = damaged.iter()
+            .map(|(path, damage)| format!("File {}: {}", damage.label(), path.display()))
+            .collect();
+        self.note_damaged(damaged);
+        self.state = AppState::Error(files.join("\n"));
+    }
+    
+    fn note_damaged(&mut self, damaged: Vec<(PathBuf, FileDamage)>) {
+        let mut damaged_files = self.damaged_files.lock().unwrap();
+        for (path, damage) in damaged {
+            warn!("File {}: {}", damage.label(), path.display());
+            if !damaged_files.iter().any(|(known, _)| *known == path) {
+                damaged_files.push((path, damage));
+            }
+        }
+    }
+    
+    // Miniature della coppia a schermo per la cronologia, prima che arrivi la successiva. Senza
+    // immagini mostrate (errore, gruppi, risoluzione automatica) la coppia non compare
+    fn remember_decision(&mut self, chosen: [bool; 2]) {
+        if !matches!(self.state, AppState::ShowingImages | AppState::ProcessingChoice(_, _)) {
+            return;
         }
-        self.copied_files[self.current_index] = None;
+        let (Some(preview1), Some(preview2)) = (&self.preview1, &self.preview2) else {
+            return;
+        };
+        let thumbnail = |preview: &DynamicImage, rotation| {
+            image_analyzer::rotate_clockwise(&preview.thumbnail(HISTORY_THUMBNAIL_SIZE, HISTORY_THUMBNAIL_SIZE), rotation)
+        };
+        let thumbnails = [thumbnail(preview1, self.rotation1), thumbnail(preview2, self.rotation2)];
         
-        *self.skipped_count.lock().unwrap() += 1;
-        self.move_to_next();
+        let index = self.current_index;
+        self.recent_decisions.retain(|recent| recent.index != index);
+        self.recent_decisions.push_front(RecentDecision { index, chosen, thumbnails, textures: None });
+        self.recent_decisions.truncate(HISTORY_LENGTH);
     }
     
-    fn process_choice(&mut self, _choice: u8, path: PathBuf) {
+    fn record_decision(&mut self, decision: PairDecision) {
+        // Ensure decisions is properly sized and store the result
+        while self.decisions.len() <= self.current_index {
+            self.decisions.push(None);
+        }
+        self.decisions[self.current_index] = Some(decision);
+        let index = self.current_index;
+        self.deferred.retain(|&deferred| deferred != index);
+    }
+    
+    fn process_choice(&mut self, choice: u8, path: PathBuf) {
+        if self.same_group(self.current_index, self.current_index + 1) {
+            self.advance_winner(path);
+            return;
+        }
+        
         let file_manager = self.file_manager.clone();
+        let rotation = if choice == 1 { self.rotation1 } else { self.rotation2 };
         let next_data = self.next_data.clone();
-        let pairs = self.all_pairs.clone();
-        let next_index = self.current_index + 1;
+        // Un gruppo di duplicati non si carica come coppia e con --same-size la coppia può non
+        // andare caricata affatto: ci pensa load_current_pair
+        let next_index = self.next_index();
+        let next_group = self.group_span(next_index).is_some() || self.same_size_action.is_some();
+        let next_pair = if next_group { None } else { self.pair_at(next_index) };
+        let weights = self.scoring_weights;
+        let identical = self.resolves_identical();
         
-        // Check if there's pending metadata transfer
+        // Check if there's pending metadata transfer (scegliere la sorgente stessa non trasferisce nulla)
         let metadata_source = if self.metadata_transfer_pending {
-            self.metadata_transfer_source.clone()
+            self.metadata_transfer_source.clone().filter(|source| *source != path)
         } else {
             None
         };
         
+        // Copy file synchronously first to get the destination path
+        let Some(dest_path) = self.copy_or_pause(&file_manager, &path, metadata_source.as_deref(), rotation) else {
+            // Copia non riuscita: la coppia resta da decidere e il trasferimento metadati in attesa
+            self.navigation_history.pop();
+            self.state = AppState::ShowingImages;
+            return;
+        };
+        self.remember_decision([choice == 1, choice == 2]);
+        debug!("File copiato con successo all'indice {}: {:?}", self.current_index, dest_path);
+        self.apply_exif_edits(choice, &path, &dest_path);
+        
         // Clear metadata transfer state after using it
         self.metadata_transfer_pending = false;
         self.metadata_transfer_source = None;
         
-        // Copy file synchronously first to get the destination path
-        let copied_file_path = if let Ok(dest_path) = file_manager.copy_to_output_with_metadata(&path, metadata_source.as_deref()) {
-            println!("DEBUG: File copiato con successo all'indice {}: {:?}", self.current_index, dest_path);
-            
-            // Force filesystem sync to ensure file is written
-            if let Err(e) = std::process::Command::new("sync").output() {
-                println!("DEBUG: Impossibile eseguire sync: {}", e);
-            } else {
-                println!("DEBUG: Sync filesystem completato");
-            }
-            
-            // Additional verification that file exists
-            if dest_path.exists() {
-                println!("DEBUG: Verifica esistenza file post-sync: OK");
-            } else {
-                println!("DEBUG: ATTENZIONE: File non trovato dopo sync!");
-            }
-            
-            Some(dest_path)
+        // Force filesystem sync to ensure file is written
+        if let Err(e) = std::process::Command::new("sync").output() {
+            debug!("Impossibile eseguire sync: {}", e);
         } else {
-            println!("DEBUG: Errore nella copia del file all'indice {}", self.current_index);
-            None
-        };
+            debug!("Sync filesystem completato");
+        }
         
-        // Ensure copied_files is properly sized and store the result
-        while self.copied_files.len() <= self.current_index {
-            self.copied_files.push(None);
+        // Additional verification that file exists
+        if dest_path.exists() {
+            debug!("Verifica esistenza file post-sync: OK");
+        } else {
+            debug!("ATTENZIONE: File non trovato dopo sync!");
         }
-        self.copied_files[self.current_index] = copied_file_path.clone();
+        let copied_file_path = Some(dest_path);
+        
+        self.record_decision(PairDecision::Selected(copied_file_path.clone()));
         
-        println!("DEBUG: Salvato in copied_files[{}]: {:?}", self.current_index, copied_file_path);
+        debug!("Salvato in decisions[{}]: {:?}", self.current_index, copied_file_path);
+        
+        if let Some((path1, path2)) = &next_pair {
+            self.load_thumbnails(next_index, path1.clone(), path2.clone());
+        }
         
         thread::spawn(move || {
             
-            if next_index < pairs.len() {
-                let (path1, path2) = &pairs[next_index];
-                if let (Ok(a1), Ok(a2)) = (
-                    ImageAnalysis::analyze_image(path1),
-                    ImageAnalysis::analyze_image(path2)
-                ) {
-                    if let (Ok(img1), Ok(img2)) = (
-                        Self::load_and_resize_image(path1),
-                        Self::load_and_resize_image(path2)
-                    ) {
-                        *next_data.lock().unwrap() = Some((a1, a2, img1, img2));
-                    }
-                }
+            if let Some((path1, path2)) = next_pair {
+                *next_data.lock().unwrap() = Some((next_index, Self::load_pair(&path1, &path2, &weights, identical)));
             }
         });
         
         *self.selected_count.lock().unwrap() += 1;
         self.state = AppState::Loading("Preparazione prossima coppia...".to_string());
         self.move_to_next();
+        if next_group && !matches!(self.state, AppState::Summary) {
+            self.load_current_pair();
+        }
+    }
+    
+    // Deduplicazione: il file scelto non viene copiato ma affronta il prossimo file del gruppo
+    fn advance_winner(&mut self, path: PathBuf) {
+        self.record_decision(PairDecision::Advanced(path));
+        
+        self.metadata_transfer_pending = false;
+        self.metadata_transfer_source = None;
+        
+        self.state = AppState::Loading("Prossimo confronto del gruppo...".to_string());
+        self.move_to_next();
+        if !matches!(self.state, AppState::Summary) {
+            self.load_current_pair();
+        }
+    }
+    
+    // Coppie del gruppo di duplicati di `index` quando il gruppo ha almeno tre file e si decide
+    // quindi in un'unica schermata. In revisione e nella presentazione si scorrono le coppie
+    fn group_span(&self, index: usize) -> Option<Range<usize>> {
+        if self.review_return.is_some() || self.slideshow.is_some() {
+            return None;
+        }
+        let groups = self.pair_groups.as_ref()?;
+        let group = *groups.get(index)?;
+        let start = groups[..index].iter().rposition(|&other| other != group).map_or(0, |position| position + 1);
+        let end = groups[index..].iter().position(|&other| other != group).map_or(groups.len(), |position| index + position);
+        (end - start >= 2).then_some(start..end)
+    }
+    
+    // Il gruppo si decide tutto insieme: si riparte sempre dalla sua prima coppia
+    fn load_group(&mut self, pairs: Range<usize>) {
+        let files: Vec<PathBuf> = std::iter::once(self.all_pairs[pairs.start].0.clone())
+            .chain(self.all_pairs[pairs.clone()].iter().map(|(_, second)| second.clone()))
+            .collect();
+        self.current_index = pairs.start;
+        self.group_view = None;
+        self.state = AppState::Loading(format!("Caricamento gruppo di {} duplicati...", files.len()));
+        
+        let group_data = self.group_data.clone();
+        let weights = self.scoring_weights;
+        thread::spawn(move || {
+            let members = files.into_iter()
+                .map(|path| Self::load_group_member(path, &weights))
+                .collect();
+            *group_data.lock().unwrap() = Some((pairs.start, members));
+        });
+    }
+    
+    fn load_group_member(path: PathBuf, weights: &ScoringWeights) -> GroupMember {
+        let analysis = ImageAnalysis::analyze_image(&path, weights)
+            .map_err(|error| FileDamage::classify(&path, &error));
+        // Un panic del decoder rende illeggibile solo questo file, non l'intero gruppo
+        let thumbnail = analysis.as_ref()
+            .ok()
+            .and_then(|analysis| panic::catch_unwind(AssertUnwindSafe(|| Self::load_preview(&path, &analysis.hash))).ok())
+            .and_then(|result| result.ok())
+            .map(|(_, preview)| preview.thumbnail(GROUP_THUMBNAIL_SIZE, GROUP_THUMBNAIL_SIZE));
+        GroupMember { path, analysis, thumbnail }
+    }
+    
+    fn open_group_view(&mut self, ctx: &Context, members: Vec<GroupMember>) {
+        let Some(pairs) = self.group_span(self.current_index) else {
+            return;
+        };
+        
+        {
+            let mut damaged_files = self.damaged_files.lock().unwrap();
+            for member in &members {
+                if let Err(damage) = &member.analysis
+                    && !damaged_files.iter().any(|(known, _)| *known == member.path)
+                {
+                    warn!("File {}: {}", damage.label(), member.path.display());
+                    damaged_files.push((member.path.clone(), *damage));
+                }
+            }
+        }
+        
+        // A parità di punteggio vince il primo, come nel confronto a coppie
+        let best = members.iter()
+            .enumerate()
+            .filter_map(|(index, member)| member.analysis.as_ref().ok().map(|analysis| (index, analysis)))
+            .reduce(|best, other| if best.1.is_preferred_over(other.1, &self.scoring_weights) { best } else { other })
+            .map(|(index, _)| index);
+        
+        let members = members.into_iter()
+            .enumerate()
+            .map(|(index, member)| {
+                let texture = member.thumbnail.as_ref().map(|thumbnail| {
+                    let rgba = thumbnail.to_rgba8();
+                    let color_image = ColorImage::from_rgba_unmultiplied([rgba.width() as usize, rgba.height() as usize], rgba.as_raw());
+                    ctx.load_texture(format!("group{}", index), color_image, egui::TextureOptions::default())
+                });
+                (member, texture)
+            })
+            .collect();
+        
+        self.group_view = Some(GroupView { pairs, members, best });
+        self.transition_start = self.animation_time;
+        self.state = AppState::ShowingGroup;
+    }
+    
+    // Il file tenuto vince tutti i confronti del gruppo: le coppie vengono registrate come se
+    // fossero state decise una alla volta, così revisione, annullamento e report non cambiano
+    fn choose_group_keeper(&mut self, member: usize) {
+        let keeper = self.group_view.as_ref()
+            .and_then(|view| view.members.get(member))
+            .filter(|(member, _)| member.analysis.is_ok())
+            .map(|(member, _)| member.path.clone());
+        let Some(keeper) = keeper else {
+            return;
+        };
+        // La copia viene prima di tutto: se non riesce il gruppo resta da decidere
+        let file_manager = self.file_manager.clone();
+        let Some(copied) = self.copy_or_pause(&file_manager, &keeper, None, 0) else {
+            return;
+        };
+        let Some(view) = self.group_view.take() else {
+            return;
+        };
+        
+        for index in view.pairs.clone() {
+            self.undo_decision(index);
+        }
+        self.navigation_history.push(view.pairs.start);
+        
+        let mut winner = self.all_pairs[view.pairs.start].0.clone();
+        for index in view.pairs.clone() {
+            self.current_index = index;
+            if self.all_pairs[index].1 == keeper {
+                winner = keeper.clone();
+            }
+            if index + 1 < view.pairs.end {
+                self.record_decision(PairDecision::Advanced(winner.clone()));
+                continue;
+            }
+            
+            self.record_decision(PairDecision::Selected(Some(copied.clone())));
+        }
+        
+        *self.selected_count.lock().unwrap() += 1;
+        self.finish_group();
+    }
+    
+    fn skip_group(&mut self) {
+        let Some(view) = self.group_view.take() else {
+            return;
+        };
+        
+        for index in view.pairs.clone() {
+            self.undo_decision(index);
+        }
+        self.navigation_history.push(view.pairs.start);
+        
+        for index in view.pairs {
+            self.current_index = index;
+            self.record_decision(PairDecision::Skipped);
+            *self.skipped_count.lock().unwrap() += 1;
+        }
+        self.finish_group();
+    }
+    
+    // current_index è sull'ultima coppia del gruppo
+    fn finish_group(&mut self) {
+        self.state = AppState::Loading("Caricamento...".to_string());
+        self.move_to_next();
+        if !matches!(self.state, AppState::Summary) {
+            self.load_current_pair();
+        }
+    }
+    
+    fn same_group(&self, index1: usize, index2: usize) -> bool {
+        self.pair_groups.as_ref()
+            .is_some_and(|groups| groups.get(index1).is_some() && groups.get(index1) == groups.get(index2))
+    }
+    
+    // Coppia effettiva all'indice: nella deduplicazione il primo file è il vincitore dei
+    // confronti precedenti dello stesso gruppo (se un confronto è stato saltato resta il precedente)
+    fn pair_at(&self, index: usize) -> Option<(PathBuf, PathBuf)> {
+        let (first, second) = self.all_pairs.get(index)?.clone();
+        if index == 0 || !self.same_group(index - 1, index) {
+            return Some((first, second));
+        }
+        
+        let first = match self.decisions.get(index - 1) {
+            Some(Some(PairDecision::Advanced(winner))) => winner.clone(),
+            _ => self.pair_at(index - 1)?.0,
+        };
+        Some((first, second))
     }
     
     fn move_to_next(&mut self) {
-        self.current_index += 1;
+        self.current_index = self.next_index();
         
         if self.current_index >= self.all_pairs.len() {
-            self.exit_program = true;
+            SessionState::clear(&self.file_manager);
+            self.write_report();
+            self.unsaved_decisions = 0;
+            self.state = AppState::Summary;
             return;
         }
         
+        self.unsaved_decisions += 1;
+        self.save_session_if_due();
+        
         if matches!(self.state, AppState::ShowingImages) {
             self.state = AppState::Loading("Caricamento...".to_string());
             self.load_current_pair();
         }
     }
     
+    // Coppia che segue la corrente: la successiva finché ce ne sono da decidere dopo di lei,
+    // poi quelle scavalcate con un salto (G), infine le rimandate nell'ordine in cui sono
+    // state rimandate
+    fn next_index(&self) -> usize {
+        let next = self.current_index + 1;
+        let pending = |index: usize| index != self.current_index && self.decisions.get(index).is_none_or(|decision| decision.is_none());
+        let undecided = |index: &usize| pending(*index) && !self.deferred.contains(index);
+        if (next..self.all_pairs.len()).any(|index| undecided(&index)) {
+            return next;
+        }
+        if let Some(skipped) = (0..next).find(undecided) {
+            return skipped;
+        }
+        self.deferred.iter().copied().find(|&index| pending(index)).unwrap_or(next)
+    }
+    
+    fn save_session(&mut self) {
+        // In revisione si riparte dalla coppia da decidere, non da quella rivista
+        let resume_index = self.review_return.unwrap_or(self.current_index);
+        let session = SessionState::new(&self.file_manager, &self.all_pairs, resume_index, &self.decisions, &self.annotations, &self.deferred, &self.compression_labels);
+        if let Err(e) = session.save(&self.file_manager) {
+            error!("Errore nel salvataggio della sessione: {}", e);
+        }
+        self.write_report();
+        self.unsaved_decisions = 0;
+        self.last_save = Instant::now();
+    }
+    
+    // Dopo una decisione si salva secondo --autosave-every e --autosave-interval
+    fn save_session_if_due(&mut self) {
+        if self.autosave.is_due(self.unsaved_decisions, self.last_save.elapsed()) {
+            self.save_session();
+        }
+    }
+    
+    fn write_report(&self) {
+        let pairs: Vec<(PathBuf, PathBuf)> = (0..self.all_pairs.len())
+            .filter_map(|index| self.pair_at(index))
+            .collect();
+        let entries = ReportEntry::from_session(&pairs, &self.decisions, &self.annotations, &self.compression_labels);
+        if let Err(e) = report::write_report(&self.file_manager.output_folder, &entries) {
+            error!("Errore nella scrittura del report: {}", e);
+        }
+    }
+    
     fn load_current_pair(&mut self) {
-        if let Some((path1, path2)) = self.all_pairs.get(self.current_index) {
-            let path1 = path1.clone();
-            let path2 = path2.clone();
+        // Un ciclo e non una ricorsione: le coppie da risolvere possono essere migliaia di fila
+        while self.resolve_same_size() {
+            if matches!(self.state, AppState::Summary) {
+                return;
+            }
+        }
+        if let Some(pairs) = self.group_span(self.current_index) {
+            self.load_group(pairs);
+            return;
+        }
+        if let Some((path1, path2)) = self.pair_at(self.current_index) {
+            self.load_thumbnails(self.current_index, path1.clone(), path2.clone());
             let next_data = self.next_data.clone();
+            let weights = self.scoring_weights;
+            let index = self.current_index;
+            let identical = self.resolves_identical();
             
             thread::spawn(move || {
-                if let (Ok(a1), Ok(a2)) = (
-                    ImageAnalysis::analyze_image(&path1),
-                    ImageAnalysis::analyze_image(&path2)
-                ) {
-                    if let (Ok(img1), Ok(img2)) = (
-                        Self::load_and_resize_image(&path1),
-                        Self::load_and_resize_image(&path2)
-                    ) {
-                        *next_data.lock().unwrap() = Some((a1, a2, img1, img2));
-                    }
-                }
+                *next_data.lock().unwrap() = Some((index, Self::load_pair(&path1, &path2, &weights, identical)));
             });
         }
     }
     
-    fn load_and_resize_image(path: &Path) -> Result<DynamicImage> {
-        let mut img = image::open(path)?;
-        let (width, height) = img.dimensions();
-        if width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
+    // Le miniature EXIF si leggono in un thread a parte: arrivano molto prima della decodifica
+    // completa, che sui file da 100MP richiede secondi
+    fn load_thumbnails(&self, index: usize, path1: PathBuf, path2: PathBuf) {
+        let thumbnail_data = self.thumbnail_data.clone();
+        thread::spawn(move || {
+            let thumbnails = (index, ImageAnalysis::read_exif_thumbnail(&path1), ImageAnalysis::read_exif_thumbnail(&path2));
+            *thumbnail_data.lock().unwrap() = Some(thumbnails);
+        });
+    }
+    
+    // Analisi e decodifica di una coppia, eseguite nei thread di lavoro. Un panic viene
+    // intercettato qui: altrimenti si perderebbe con il thread e la GUI resterebbe in caricamento
+    // Con `identical` i file si confrontano prima per hash: se coincidono non si decodificano
+    fn load_pair(path1: &Path, path2: &Path, weights: &ScoringWeights, identical: bool) -> PairLoadResult {
+        if identical && ImageAnalysis::files_identical(path1, path2) {
+            return Err(PairLoadError::Identical);
+        }
+        match panic::catch_unwind(AssertUnwindSafe(|| Self::analyze_and_decode_pair(path1, path2, weights))) {
+            Ok(result) => result.map_err(PairLoadError::Damaged),
+            Err(payload) => {
+                let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "errore sconosciuto".to_string());
+                Err(PairLoadError::Panicked(message))
+            }
+        }
+    }
+    
+    fn analyze_and_decode_pair(path1: &Path, path2: &Path, weights: &ScoringWeights) -> Result<PairData, Vec<(PathBuf, FileDamage)>> {
+        let classify = |path: &Path, error: anyhow::Error| (path.to_path_buf(), FileDamage::classify(path, &error));
+        
+        // Presentazione dei file scelti: lo stesso file su entrambi i lati si analizza e
+        // decodifica una volta sola
+        if path1 == path2 {
+            let analysis = ImageAnalysis::analyze_image(path1, weights).map_err(|error| vec![classify(path1, error)])?;
+            let (full, image) = Self::load_preview(path1, &analysis.hash).map_err(|error| vec![classify(path1, error)])?;
+            return Ok(PairData {
+                analysis1: analysis.clone(),
+                analysis2: analysis,
+                image1: image.clone(),
+                image2: image,
+                full1: full.clone(),
+                full2: full,
+                cropped: None,
+                ssim: Some(1.0),
+            });
+        }
+        
+        // Si analizzano entrambi i file anche se il primo fallisce, per segnalarli tutti
+        let (analysis1, analysis2) = match (
+            ImageAnalysis::analyze_image(path1, weights),
+            ImageAnalysis::analyze_image(path2, weights),
+        ) {
+            (Ok(analysis1), Ok(analysis2)) => (analysis1, analysis2),
+            (result1, result2) => {
+                let mut damaged = Vec::new();
+                if let Err(error) = result1 {
+                    damaged.push(classify(path1, error));
+                }
+                if let Err(error) = result2 {
+                    damaged.push(classify(path2, error));
+                }
+                return Err(damaged);
+            }
+        };
+        let (full1, image1) = Self::load_preview(path1, &analysis1.hash).map_err(|error| vec![classify(path1, error)])?;
+        let (full2, image2) = Self::load_preview(path2, &analysis2.hash).map_err(|error| vec![classify(path2, error)])?;
+        let cropped = image_analyzer::detect_crop(&image1, &image2);
+        let ssim = image_analyzer::structural_similarity(&image1, &image2);
+        
+        Ok(PairData {
+            analysis1,
+            analysis2,
+            image1,
+            image2,
+            full1,
+            full2,
+            cropped,
+            ssim,
+        })
+    }
+    
+    // Anteprima dalla cache su disco se il file è già stato visto, altrimenti decodifica completa.
+    // Si salvano solo le anteprime ridotte: per le immagini piccole decodificare costa poco
+    fn load_preview(path: &Path, hash: &str) -> Result<(Option<DynamicImage>, DynamicImage)> {
+        if let Some(preview) = thumbnail_cache::load(hash, MAX_TEXTURE_SIZE) {
+            return Ok((None, preview));
+        }
+        let (full, preview) = Self::load_and_resize_image(path)?;
+        if full.dimensions() != preview.dimensions() {
+            thumbnail_cache::store(hash, MAX_TEXTURE_SIZE, &preview);
+        }
+        Ok((Some(full), preview))
+    }
+    
+    // Restituisce l'immagine a piena risoluzione e quella ridotta per la texture, entrambe in sRGB
+    fn load_and_resize_image(path: &Path) -> Result<(DynamicImage, DynamicImage)> {
+        let (mut full, icc_profile) = image_analyzer::retry_transient(|| image_analyzer::open_with_icc_profile(path))?;
+        
+        // egui tratta i pixel come sRGB: i profili ad ampia gamma vanno convertiti
+        if let Some(icc_profile) = icc_profile
+            && image_analyzer::non_srgb_profile_name(&icc_profile).is_some()
+        {
+            full = image_analyzer::convert_to_srgb(full, &icc_profile);
+        }
+        
+        Ok(Self::with_preview(full))
+    }
+    
+    // Affianca all'immagine intera l'anteprima limitata a MAX_TEXTURE_SIZE
+    fn with_preview(full: DynamicImage) -> (DynamicImage, DynamicImage) {
+        let (width, height) = full.dimensions();
+        let img = if width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
             let ratio = (MAX_TEXTURE_SIZE as f32 / width.max(height) as f32).min(1.0);
             let new_width = (width as f32 * ratio) as u32;
             let new_height = (height as f32 * ratio) as u32;
-            img = img.resize(new_width, new_height, FilterType::Lanczos3);
-        }
-        Ok(img)
+            full.resize(new_width, new_height, PREVIEW_FILTER)
+        } else {
+            full.clone()
+        };
+        (full, img)
     }
     
-    fn image_to_texture(&self, ctx: &Context, img: DynamicImage, name: &str) -> Option<TextureHandle> {
-        let size = [img.width() as usize, img.height() as usize];
-        let img_rgba = img.to_rgba8();
-        let pixels = img_rgba.as_flat_samples();
-        let color_image = ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
+    fn image_to_tiles(ctx: &Context, img: &DynamicImage, name: &str) -> ImageTiles {
+        let max_side = ctx.input(|i| i.max_texture_side).max(1) as u32;
+        let (width, height) = img.dimensions();
+        let mut tiles = Vec::new();
+        
+        for y in (0..height).step_by(max_side as usize) {
+            for x in (0..width).step_by(max_side as usize) {
+                let tile_width = max_side.min(width - x);
+                let tile_height = max_side.min(height - y);
+                let tile_rgba = img.crop_imm(x, y, tile_width, tile_height).to_rgba8();
+                let color_image = ColorImage::from_rgba_unmultiplied(
+                    [tile_width as usize, tile_height as usize],
+                    tile_rgba.as_raw(),
+                );
+                
+                let texture = ctx.load_texture(
+                    format!("{}_{}_{}", name, x, y),
+                    color_image,
+                    egui::TextureOptions::default()
+                );
+                let rect = egui::Rect::from_min_size(
+                    egui::pos2(x as f32 / width as f32, y as f32 / height as f32),
+                    Vec2::new(tile_width as f32 / width as f32, tile_height as f32 / height as f32),
+                );
+                tiles.push((texture, rect));
+            }
+        }
         
-        Some(ctx.load_texture(
-            name,
-            color_image,
-            egui::TextureOptions::default()
-        ))
+        ImageTiles {
+            tiles,
+            size: Vec2::new(width as f32, height as f32),
+        }
     }
     
+    // W: sorgente dei metadati scelta in automatico, l'immagine con più tag EXIF
     fn transfer_metadata(&mut self) {
-        // Get the current pair of files
-        if let Some((path1, path2)) = self.all_pairs.get(self.current_index) {
-            // Determine which image has more metadata
-            let metadata_count_1 = self.current_analysis1.as_ref().map(|a| a.metadata_count).unwrap_or(0);
-            let metadata_count_2 = self.current_analysis2.as_ref().map(|a| a.metadata_count).unwrap_or(0);
-            
-            if metadata_count_1 > metadata_count_2 {
-                self.metadata_transfer_source = Some(path1.clone());
-                self.metadata_transfer_pending = true;
-                self.state = AppState::Loading(format!(
-                    "Metadati marcati per trasferimento: immagine 1 ({} meta) → immagine selezionata", 
-                    metadata_count_1
-                ));
-            } else if metadata_count_2 > metadata_count_1 {
-                self.metadata_transfer_source = Some(path2.clone());
-                self.metadata_transfer_pending = true;
-                self.state = AppState::Loading(format!(
-                    "Metadati marcati per trasferimento: immagine 2 ({} meta) → immagine selezionata", 
-                    metadata_count_2
-                ));
-            } else if metadata_count_1 > 0 {
-                // If both have same metadata count (and not zero), don't transfer
-                self.state = AppState::Loading("Entrambe le immagini hanno già lo stesso numero di metadati".to_string());
-                self.metadata_transfer_pending = false;
-                self.metadata_transfer_source = None;
+        let metadata_count_1 = self.current_analysis1.as_ref().map(|a| a.metadata_count).unwrap_or(0);
+        let metadata_count_2 = self.current_analysis2.as_ref().map(|a| a.metadata_count).unwrap_or(0);
+        
+        if metadata_count_1 > metadata_count_2 {
+            self.set_metadata_source(1);
+        } else if metadata_count_2 > metadata_count_1 {
+            self.set_metadata_source(2);
+        } else {
+            self.metadata_transfer_pending = false;
+            self.metadata_transfer_source = None;
+            let message = if metadata_count_1 > 0 {
+                "Entrambe le immagini hanno già lo stesso numero di metadati: Maiusc+A/D per scegliere la sorgente"
             } else {
-                // Both have no metadata
-                self.state = AppState::Loading("Nessuna immagine ha metadati da trasferire".to_string());
-                self.metadata_transfer_pending = false;
-                self.metadata_transfer_source = None;
+                "Nessuna immagine ha metadati da trasferire"
+            };
+            self.toast = Some((message.to_string(), self.animation_time));
+        }
+    }
+    
+    // Maiusc+A/D: l'immagine indicata diventa la sorgente dei metadati, segnata sulla card fino
+    // alla scelta. Ripetere il tasto sulla stessa immagine annulla il trasferimento
+    fn set_metadata_source(&mut self, num: u8) {
+        let Some((path1, path2)) = self.pair_at(self.current_index) else {
+            return;
+        };
+        let (path, analysis) = if num == 1 { (path1, &self.current_analysis1) } else { (path2, &self.current_analysis2) };
+        let metadata_count = analysis.as_ref().map(|a| a.metadata_count).unwrap_or(0);
+        
+        let message = if self.metadata_transfer_pending && self.metadata_transfer_source.as_ref() == Some(&path) {
+            self.metadata_transfer_pending = false;
+            self.metadata_transfer_source = None;
+            "Trasferimento metadati annullato".to_string()
+        } else if metadata_count == 0 {
+            format!("L'immagine {} non ha metadati da trasferire", num)
+        } else {
+            self.metadata_transfer_source = Some(path);
+            self.metadata_transfer_pending = true;
+            format!("Metadati dell'immagine {} ({} meta) marcati per il trasferimento → immagine scelta", num, metadata_count)
+        };
+        self.toast = Some((message, self.animation_time));
+    }
+    
+    // --same-size: la coppia corrente ha stesso peso e dimensioni e viene registrata come una
+    // coppia identica, senza decodificarla. Nella presentazione e in revisione non si decide nulla
+    fn resolve_same_size(&mut self) -> bool {
+        let Some(action) = self.same_size_action else {
+            return false;
+        };
+        if self.slideshow.is_some() || self.review_return.is_some() || self.write_error.is_some() || self.pair_groups.is_some() {
+            return false;
+        }
+        let Some((path1, path2)) = self.pair_at(self.current_index) else {
+            return false;
+        };
+        if !FileManager::same_size(&path1, &path2) {
+            return false;
+        }
+        // Copia non riuscita: con l'errore in sospeso la coppia si mostra, per una scelta manuale
+        if !self.record_identical(&path1, action == IdenticalAction::Copy) {
+            return false;
+        }
+        self.same_size_pairs.lock().unwrap().insert(self.current_index);
+        
+        self.state = AppState::Loading("Stesso peso e dimensioni, passo alla coppia successiva...".to_string());
+        self.move_to_next();
+        true
+    }
+    
+    // Annulla la decisione presa per una coppia: elimina i file copiati e aggiorna i conteggi
+    fn undo_decision(&mut self, index: usize) {
+        // Le coppie risolte in automatico non sono contate tra le selezionate né tra le saltate
+        let counted = !(self.same_size_pairs.lock().unwrap().remove(&index) | self.identical_pairs.lock().unwrap().remove(&index));
+        match self.decisions.get_mut(index).and_then(|d| d.take()) {
+            Some(PairDecision::Selected(copied_file_path)) => {
+                if let Some(copied_file_path) = copied_file_path {
+                    debug!("Tentativo di cancellazione file: {:?}", copied_file_path);
+                    
+                    // Delete the file from output
+                    if let Err(e) = self.file_manager.delete_from_output(&copied_file_path) {
+                        error!("Errore durante la cancellazione del file: {}", e);
+                    } else {
+                        debug!("File cancellato con successo");
+                    }
+                }
+                
+                // Decrease selected count since we undid a selection
+                if counted {
+                    let mut selected = self.selected_count.lock().unwrap();
+                    *selected = selected.saturating_sub(1);
+                }
             }
-            
-            // Show the state briefly, then return to showing images
-            let next_data = self.next_data.clone();
-            let pairs = self.all_pairs.clone();
-            let current_index = self.current_index;
-            
-            thread::spawn(move || {
-                // Wait a bit to show the message
-                std::thread::sleep(std::time::Duration::from_millis(1500));
-                
-                // Reload current pair to go back to showing images
-                if let Some((path1, path2)) = pairs.get(current_index) {
-                    if let (Ok(a1), Ok(a2)) = (
-                        ImageAnalysis::analyze_image(path1),
-                        ImageAnalysis::analyze_image(path2)
-                    ) {
-                        if let (Ok(img1), Ok(img2)) = (
-                            PhotoComparisonApp::load_and_resize_image(path1),
-                            PhotoComparisonApp::load_and_resize_image(path2)
-                        ) {
-                            *next_data.lock().unwrap() = Some((a1, a2, img1, img2));
-                        }
+            Some(PairDecision::KeptBoth(copied_files)) => {
+                for copied_file_path in &copied_files {
+                    if let Err(e) = self.file_manager.delete_from_output(copied_file_path) {
+                        error!("Errore durante la cancellazione del file: {}", e);
                     }
                 }
-            });
+                
+                let mut kept_both = self.kept_both_count.lock().unwrap();
+                *kept_both = kept_both.saturating_sub(1);
+            }
+            // Il vincitore non era stato copiato: il confronto successivo torna al file precedente
+            Some(PairDecision::Advanced(_)) => {}
+            Some(PairDecision::Skipped) => {
+                debug!("decisions[{}] era uno skip", index);
+                // This was a skip, decrease skip count
+                if counted {
+                    let mut skipped = self.skipped_count.lock().unwrap();
+                    *skipped = skipped.saturating_sub(1);
+                }
+            }
+            // Nessuna decisione da annullare
+            None => {}
         }
     }
     
     fn go_to_previous(&mut self) {
-        println!("DEBUG: go_to_previous chiamato");
+        debug!("go_to_previous chiamato");
         
         // Check if we have history to go back to
         if let Some(previous_index) = self.navigation_history.pop() {
-            println!("DEBUG: Going back from index {} to index {}", self.current_index, previous_index);
-            println!("DEBUG: copied_files.len() = {}", self.copied_files.len());
+            debug!("Going back from index {} to index {}", self.current_index, previous_index);
+            debug!("decisions.len() = {}", self.decisions.len());
             
-            // Check if there was a file copied from the previous index that needs to be deleted
-            if previous_index < self.copied_files.len() {
-                println!("DEBUG: Controllo copied_files[{}] (previous_index)", previous_index);
-                
-                if let Some(copied_file_path) = &self.copied_files[previous_index] {
-                    println!("DEBUG: Tentativo di cancellazione file: {:?}", copied_file_path);
-                    
-                    // Delete the file from output
-                    if let Err(e) = self.file_manager.delete_from_output(copied_file_path) {
-                        eprintln!("Errore durante la cancellazione del file: {}", e);
-                    } else {
-                        println!("DEBUG: File cancellato con successo");
-                    }
-                    // Mark as no longer copied
-                    self.copied_files[previous_index] = None;
-                    
-                    // Decrease selected count since we undid a selection
-                    let selected = self.selected_count.lock().unwrap();
-                    if *selected > 0 {
-                        drop(selected);
-                        *self.selected_count.lock().unwrap() -= 1;
-                    }
-                } else {
-                    println!("DEBUG: copied_files[{}] è None (era uno skip)", previous_index);
-                    // This was a skip, decrease skip count
-                    let skipped = self.skipped_count.lock().unwrap();
-                    if *skipped > 0 {
-                        drop(skipped);
-                        *self.skipped_count.lock().unwrap() -= 1;
-                    }
-                }
-            } else {
-                println!("DEBUG: previous_index {} >= copied_files.len() {}, nessun controllo possibile", 
-                    previous_index, self.copied_files.len());
+            // Undo the decision taken at the previous index, deleting any copied file.
+            // Un gruppo deciso in un'unica schermata si annulla tutto insieme
+            let undo = self.group_span(previous_index).unwrap_or(previous_index..previous_index + 1);
+            for index in undo.clone() {
+                self.undo_decision(index);
+            }
+            while self.navigation_history.last().is_some_and(|index| undo.contains(index)) {
+                self.navigation_history.pop();
             }
             
             // Update the current index
-            self.current_index = previous_index;
+            self.current_index = undo.start;
+            self.save_session();
             
             // Clear any pending metadata transfer
             self.metadata_transfer_pending = false;
@@ -920,7 +4531,47 @@ impl PhotoComparisonApp {
             self.state = AppState::Loading("Caricamento coppia precedente...".to_string());
             self.load_current_pair();
         } else {
-            println!("DEBUG: Nessuna storia disponibile per tornare indietro");
+            debug!("Nessuna storia disponibile per tornare indietro");
         }
     }
+}
+
+// Apre un file o una cartella con l'applicazione predefinita del sistema
+fn open_with_system(path: &Path) {
+    let program = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    
+    if let Err(e) = std::process::Command::new(program).arg(path).spawn() {
+        warn!("Impossibile aprire {:?} con {}: {}", path, program, e);
+    }
+}
+
+// Mostra un file nel file manager del sistema. Windows e macOS lo selezionano nella sua
+// cartella; altrove non c'è un comando standard e si apre solo la cartella
+fn reveal_in_file_manager(path: &Path) {
+    let mut command = if cfg!(target_os = "windows") {
+        // explorer vuole "/select,<percorso>" come unico argomento
+        let mut select = OsString::from("/select,");
+        select.push(path);
+        let mut command = std::process::Command::new("explorer");
+        command.arg(select);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = std::process::Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    } else {
+        let mut command = std::process::Command::new("xdg-open");
+        command.arg(path.parent().unwrap_or(path));
+        command
+    };
+    
+    if let Err(e) = command.spawn() {
+        warn!("Impossibile mostrare {:?} nel file manager: {}", path, e);
+    }
 }
\ No newline at end of file
@@ -1,97 +1,733 @@
 use anyhow::{Context, Result};
-use image::{DynamicImage, GenericImageView};
-use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
+use image::imageops::FilterType;
+use image::{AnimationDecoder, DynamicImage, GenericImageView, GrayImage, ImageBuffer, ImageDecoder, ImageFormat};
+use log::warn;
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::archive;
+use crate::live_photo;
+use crate::xmp::XmpSidecar;
 use sha2::{Sha256, Digest};
 use std::io::Read;
 
-#[derive(Debug, Clone)]
+// Lato massimo dell'immagine ridotta usata per cercare i ritagli
+const CROP_MATCH_SIZE: u32 = 96;
+// Correlazione minima perché una regione venga considerata lo stesso contenuto
+const CROP_MIN_CORRELATION: f64 = 0.9;
+// Lato massimo delle immagini ridotte su cui si calcola l'SSIM, finestre e passo con cui si scorrono
+const SSIM_SIZE: u32 = 256;
+const SSIM_WINDOW: usize = 8;
+const SSIM_STEP: usize = 4;
+// Blockiness oltre cui il punteggio di compressione viene ridotto e valore a cui la riduzione è massima.
+// Ricomprimendo lo stesso JPEG si passa da ~1,0 (qualità 95) a ~1,2 (50) e ~1,75 (15).
+const BLOCKINESS_THRESHOLD: f64 = 1.15;
+const BLOCKINESS_SATURATION: f64 = 2.0;
+// Frazione massima del punteggio di compressione tolta da artefatti a blocchi evidenti
+const BLOCKINESS_MAX_PENALTY: f64 = 0.5;
+// Energia della derivata seconda rispetto alla prima tra pixel adiacenti: le foto alla
+// risoluzione nativa stanno da ~0,7 in su, le stesse ingrandite 2x o più tra ~0,2 e ~0,55,
+// perché variano in modo regolare da un pixel all'altro. Anche una foto molto sfocata scende
+// sotto soglia: è solo un indizio
+const UPSCALE_DETAIL_THRESHOLD: f64 = 0.55;
+// Sotto questa dimensione l'ingrandimento non fa vincere sulla risoluzione: non si segnala
+const UPSCALE_MIN_MEGAPIXELS: f64 = 4.0;
+// Un'immagine sospetta ha il punteggio risoluzione di una con un quarto dei pixel (metà per lato)
+const UPSCALE_RESOLUTION_DIVISOR: f64 = 4.0;
+// A parità di qualità percepita un WebP lossy pesa circa il 25-35% meno di un JPEG: i suoi
+// byte per pixel vengono moltiplicati per questo fattore prima di usare la scala dei JPEG
+const WEBP_JPEG_EQUIVALENCE: f64 = 1.4;
+// Varianza del Laplaciano di una foto con dettaglio normale e riduzione massima delle soglie
+// di compressione per le immagini con poco dettaglio (cielo, studio, sfocato), che a parità
+// di qualità JPEG occupano molti meno byte (--adaptive-compression)
+const DETAIL_REFERENCE: f64 = 300.0;
+const MIN_DETAIL_SCALE: f64 = 0.3;
+// Qualità JPEG indicativa di ciascun gradino della scala di compressione, e oltre l'ultimo
+const LADDER_QUALITY: [&str; 12] = ["~100%", "~95%", "~90%", "~85%", "~75%", "~70%", "~60%", "~50%", "~40%", "~35%", "~30%", "<30%"];
+// Differenza relativa oltre cui due proporzioni sono considerate diverse: assorbe gli
+// arrotondamenti di qualche pixel dei ridimensionamenti (es. 4000x3000 contro 1333x1000)
+const ASPECT_RATIO_TOLERANCE: f64 = 0.01;
+
+// Lato massimo dell'immagine ridotta su cui si contano i pixel saturati
+const CLIPPING_SAMPLE_SIZE: u32 = 512;
+// Percentuale di pixel saturati in un canale oltre cui il clipping viene segnalato
+const CLIPPING_WARNING_PERCENT: f64 = 1.0;
+// Guadagno massimo della normalizzazione dell'esposizione: oltre, un'immagine quasi nera
+// diventerebbe solo rumore amplificato
+const MAX_EXPOSURE_GAIN: f64 = 8.0;
+// Modulo del gradiente di Sobel oltre cui un bordo viene evidenziato dal focus peaking: un salto
+// netto di ~50 livelli lo supera, lo stesso bordo sfocato su qualche pixel no
+const PEAKING_THRESHOLD: f32 = 200.0;
+
+// Tentativi di lettura in caso di errori di I/O transitori (condivisioni SMB/NFS) e attesa
+// prima del secondo tentativo, raddoppiata a ogni ripetizione
+const IO_RETRY_ATTEMPTS: u32 = 3;
+const IO_RETRY_INITIAL_DELAY: Duration = Duration::from_millis(200);
+
+// Memoria massima per un'immagine decodificata se non indicata con --max-image-mb. Oltre, il
+// file viene rifiutato prima di allocare invece di rischiare di far chiudere il processo
+const DEFAULT_MAX_IMAGE_MB: u64 = 1024;
+static MAX_IMAGE_MB: OnceLock<u64> = OnceLock::new();
+
+/// Percentuale di pixel a 0 (ombre chiuse) e a 255 (alte luci bruciate) per canale R, G, B.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ClippingStats {
+    pub shadows: [f64; 3],
+    pub highlights: [f64; 3],
+}
+
+impl ClippingStats {
+    fn measure(img: &DynamicImage) -> ClippingStats {
+        let sample = img.thumbnail(CLIPPING_SAMPLE_SIZE, CLIPPING_SAMPLE_SIZE).to_rgb8();
+        let total = (sample.width() as f64 * sample.height() as f64).max(1.0);
+        let mut shadows = [0u32; 3];
+        let mut highlights = [0u32; 3];
+        
+        for pixel in sample.pixels() {
+            for (channel, &value) in pixel.0.iter().enumerate() {
+                match value {
+                    0 => shadows[channel] += 1,
+                    255 => highlights[channel] += 1,
+                    _ => {}
+                }
+            }
+        }
+        
+        ClippingStats {
+            shadows: shadows.map(|count| count as f64 * 100.0 / total),
+            highlights: highlights.map(|count| count as f64 * 100.0 / total),
+        }
+    }
+    
+    /// Canali con alte luci bruciate oltre la soglia, es. "R 3.2% G 1.5%".
+    pub fn highlights_warning(&self) -> Option<String> {
+        Self::warning(&self.highlights)
+    }
+    
+    /// Canali con ombre chiuse oltre la soglia.
+    pub fn shadows_warning(&self) -> Option<String> {
+        Self::warning(&self.shadows)
+    }
+    
+    fn warning(percentages: &[f64; 3]) -> Option<String> {
+        let channels: Vec<String> = ["R", "G", "B"].iter()
+            .zip(percentages)
+            .filter(|(_, percent)| **percent >= CLIPPING_WARNING_PERCENT)
+            .map(|(name, percent)| format!("{} {:.1}%", name, percent))
+            .collect();
+        (!channels.is_empty()).then(|| channels.join(" "))
+    }
+}
+
+/// Cosa conta di più nella scelta tra due immagini (--prefer).
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Preference {
+    // Punteggio qualità
+    #[default]
+    Quality,
+    // Numero di tag EXIF, poi punteggio qualità: per chi ha catalogato con cura una sola copia
+    Metadata,
+}
+
+// Tag EXIF (nome, valore), numero di tag, DPI e dimensioni dichiarate, letti in un solo passaggio
+type ExifSummary = (Vec<(String, String)>, usize, Option<(f64, f64)>, Option<(u32, u32)>);
+
+/// Peso (in punti su 100) di ciascuna componente del punteggio qualità.
+/// La somma deve essere 100; il default riproduce lo schema 40% risoluzione / 60% compressione.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoringWeights {
+    pub resolution: u8,
+    pub compression: u8,
+    pub sharpness: u8,
+    pub noise: u8,
+    // A parità di punteggio e di dimensioni in pixel preferisce l'immagine con DPI maggiori (--prefer-dpi)
+    pub prefer_dpi: bool,
+    // Analisi rapida (--fast): nessuna decodifica, solo dimensione del file e dimensioni in
+    // pixel lette dall'intestazione. L'EXIF si legge solo se serve alla scelta (metadati, DPI)
+    pub fast: bool,
+    // Soglie in byte per pixel dei punteggi di compressione JPEG (--jpeg-thresholds)
+    pub ladder: CompressionLadder,
+    // Abbassa le soglie per le immagini con poco dettaglio (--adaptive-compression)
+    pub adaptive_compression: bool,
+    // Criterio principale della scelta (--prefer)
+    pub prefer: Preference,
+}
+
+impl Default for ScoringWeights {
+    fn default() -> Self {
+        ScoringWeights {
+            resolution: 40,
+            compression: 60,
+            sharpness: 0,
+            noise: 0,
+            prefer_dpi: false,
+            fast: false,
+            ladder: CompressionLadder::default(),
+            adaptive_compression: false,
+            prefer: Preference::Quality,
+        }
+    }
+}
+
+// Formato "risoluzione,compressione,nitidezza,rumore", es. "50,30,20,0"
+impl FromStr for ScoringWeights {
+    type Err = String;
+    
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let values = s.split(',')
+            .map(|v| v.trim().parse::<u8>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| format!("peso non valido: {}", e))?;
+        
+        let [resolution, compression, sharpness, noise] = values[..] else {
+            return Err("servono 4 pesi: risoluzione,compressione,nitidezza,rumore".to_string());
+        };
+        
+        let total: u32 = values.iter().map(|&v| v as u32).sum();
+        if total != 100 {
+            return Err(format!("la somma dei pesi deve essere 100 (attuale: {})", total));
+        }
+        
+        Ok(ScoringWeights { resolution, compression, sharpness, noise, ..ScoringWeights::default() })
+    }
+}
+
+// Lo stesso formato accettato da --weights, per riusare i pesi provati nella GUI
+impl std::fmt::Display for ScoringWeights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{},{}", self.resolution, self.compression, self.sharpness, self.noise)
+    }
+}
+
+impl ScoringWeights {
+    /// Pesi nell'ordine risoluzione, compressione, nitidezza, rumore.
+    pub fn components(&self) -> [u8; 4] {
+        [self.resolution, self.compression, self.sharpness, self.noise]
+    }
+    
+    /// Pesi predefiniti, mantenendo le altre opzioni (DPI, analisi rapida, soglie).
+    pub fn with_default_components(&self) -> ScoringWeights {
+        let defaults = ScoringWeights::default();
+        ScoringWeights {
+            resolution: defaults.resolution,
+            compression: defaults.compression,
+            sharpness: defaults.sharpness,
+            noise: defaults.noise,
+            ..*self
+        }
+    }
+    
+    /// Imposta una componente (indice come in `components`) e ridistribuisce il resto tra le
+    /// altre in proporzione ai pesi attuali, così la somma resta 100.
+    pub fn with_component(&self, component: usize, value: u8) -> ScoringWeights {
+        let mut values = self.components().map(u32::from);
+        let value = u32::from(value.min(100));
+        let others: Vec<usize> = (0..values.len()).filter(|&index| index != component).collect();
+        let others_total: u32 = others.iter().map(|&index| values[index]).sum();
+        let remaining = 100 - value;
+        
+        for &index in &others {
+            values[index] = (values[index] * remaining).checked_div(others_total).unwrap_or(remaining / others.len() as u32);
+        }
+        // Lo scarto dell'arrotondamento va alla componente più pesante
+        let assigned: u32 = others.iter().map(|&index| values[index]).sum();
+        if let Some(&largest) = others.iter().max_by_key(|&&index| values[index]) {
+            values[largest] += remaining - assigned;
+        }
+        values[component] = value;
+        
+        let [resolution, compression, sharpness, noise] = values.map(|value| value as u8);
+        ScoringWeights { resolution, compression, sharpness, noise, ..*self }
+    }
+    
+    // Senza decodifica nitidezza e rumore non sono misurati: il loro peso passa a risoluzione e
+    // compressione, in proporzione. Se contavano solo quelli si torna allo schema predefinito
+    fn without_pixel_measures(&self) -> ScoringWeights {
+        let total = self.resolution as u32 + self.compression as u32;
+        if total == 0 {
+            return self.with_default_components();
+        }
+        let resolution = (self.resolution as u32 * 100 + total / 2) / total;
+        ScoringWeights {
+            resolution: resolution as u8,
+            compression: (100 - resolution) as u8,
+            sharpness: 0,
+            noise: 0,
+            ..*self
+        }
+    }
+}
+
+/// Scala byte per pixel → punteggio di compressione dei formati con perdite: chi raggiunge la
+/// prima soglia prende 60 punti, ogni soglia successiva 5 in meno, sotto l'ultima 5 punti.
+/// Le soglie predefinite sono tarate su foto di fotocamera con dettaglio medio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressionLadder(pub [f64; 11]);
+
+impl Default for CompressionLadder {
+    fn default() -> Self {
+        CompressionLadder([4.0, 3.0, 2.5, 2.0, 1.5, 1.2, 1.0, 0.7, 0.5, 0.4, 0.3])
+    }
+}
+
+// 11 soglie decrescenti separate da virgole, es. "4,3,2.5,2,1.5,1.2,1,0.7,0.5,0.4,0.3"
+impl FromStr for CompressionLadder {
+    type Err = String;
+    
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let values = s.split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| format!("soglia non valida: {}", e))?;
+        
+        let thresholds: [f64; 11] = values.try_into()
+            .map_err(|values: Vec<f64>| format!("servono 11 soglie in byte per pixel (attuali: {})", values.len()))?;
+        if thresholds.iter().any(|threshold| !threshold.is_finite() || *threshold <= 0.0) {
+            return Err("le soglie devono essere numeri positivi".to_string());
+        }
+        if thresholds.windows(2).any(|pair| pair[0] <= pair[1]) {
+            return Err("le soglie devono essere in ordine decrescente".to_string());
+        }
+        Ok(CompressionLadder(thresholds))
+    }
+}
+
+impl std::fmt::Display for CompressionLadder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let thresholds: Vec<String> = self.0.iter().map(|threshold| threshold.to_string()).collect();
+        write!(f, "{}", thresholds.join(","))
+    }
+}
+
+impl CompressionLadder {
+    // Gradino raggiunto (0 = il migliore, 11 = sotto l'ultima soglia) con le soglie moltiplicate per `scale`
+    fn step(&self, bytes_per_pixel: f64, scale: f64) -> usize {
+        self.0.iter()
+            .position(|threshold| bytes_per_pixel >= threshold * scale)
+            .unwrap_or(self.0.len())
+    }
+}
+
+/// Come è stato stimato il punteggio di compressione di un file con perdite, per il report.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressionEstimate {
+    // Byte per pixel del file, già convertiti nell'equivalente JPEG per i WebP
+    pub bytes_per_pixel: f64,
+    // Fattore applicato alle soglie (1 = scala fissa, meno di 1 = immagine con poco dettaglio)
+    pub scale: f64,
+    pub points: u8,
+}
+
+impl std::fmt::Display for CompressionEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let step = (60 - self.points as usize) / 5;
+        write!(f, "qualità {} ({:.2} B/px, {}/60)", LADDER_QUALITY[step], self.bytes_per_pixel, self.points)?;
+        if self.scale < 1.0 {
+            write!(f, ", soglie ×{:.2} per poco dettaglio", self.scale)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageAnalysis {
-    pub file_path: String,
+    pub file_path: PathBuf,
     pub file_size_mb: f64,
     pub width: u32,
     pub height: u32,
     pub megapixels: f64,
     pub metadata_count: usize,
+    // Coppie (tag, valore) nell'ordine del file; in JSON diventano oggetti {"tag", "value"}
+    #[serde(with = "exif_entries")]
     pub exif_data: Vec<(String, String)>,
+    // Risoluzione di stampa orizzontale e verticale in punti per pollice (EXIF XResolution/YResolution)
+    pub dpi: Option<(f64, f64)>,
+    // Dimensioni in pixel dichiarate nell'EXIF (PixelXDimension/PixelYDimension)
+    #[serde(default)]
+    pub exif_dimensions: Option<(u32, u32)>,
+    pub is_lossless: bool,
+    // JPEG secondo il contenuto del file, non l'estensione (un PNG rinominato .jpg non lo è)
+    #[serde(default)]
+    pub is_jpeg: bool,
+    // WebP lossy (VP8); i WebP lossless (VP8L) hanno is_lossless
+    #[serde(default)]
+    pub is_lossy_webp: bool,
+    // Varianza del Laplaciano sulla luminanza (più alta = più nitida)
+    pub sharpness: f64,
+    // Deviazione standard stimata del rumore, in livelli di grigio
+    pub noise_level: f64,
+    // Rapporto tra i salti di luminanza sui bordi dei blocchi 8x8 e quelli interni (1 = nessun blocco)
+    pub blockiness: f64,
+    // Poco dettaglio tra pixel adiacenti per la risoluzione: probabile copia ingrandita
+    #[serde(default)]
+    pub upscale_suspected: bool,
+    pub clipping: ClippingStats,
+    // Nome del profilo ICC incorporato se non è sRGB (es. Adobe RGB, ProPhoto)
+    pub color_profile: Option<String>,
+    // Marca e modello della fotocamera (EXIF Make + Model)
+    pub camera: Option<String>,
+    // Fotogrammi (GIF/WebP/APNG animati) o pagine (TIFF); si confronta il primo, le pagine TIFF si possono scorrere
+    pub frame_count: usize,
+    // Livelli del PSD (0 per gli altri formati); viene confrontato il composito
+    pub layer_count: usize,
     pub quality_score: u8,
     pub hash: String,
+    // Hash dei pixel decodificati (RGBA), indipendente da metadati e contenitore
+    pub pixel_hash: u64,
+    // Analisi rapida (--fast): misure sui pixel e hash non calcolati, EXIF solo se serve alla scelta
+    #[serde(default)]
+    pub header_only: bool,
+    // Sidecar XMP accanto al file, con la valutazione data in Lightroom o simili
+    #[serde(default)]
+    pub xmp: Option<XmpSidecar>,
+    // File con lo stesso nome che fanno parte della stessa foto (video .mov di una Live Photo, HEIC)
+    #[serde(default)]
+    pub live_companions: Vec<PathBuf>,
 }
 
 impl ImageAnalysis {
-    pub fn analyze_image(path: &Path) -> Result<Self> {
-        let file_path = path.to_string_lossy().to_string();
+    pub fn analyze_image(path: &Path, weights: &ScoringWeights) -> Result<Self> {
+        let file_path = path.to_path_buf();
         
-        let metadata = std::fs::metadata(path)
+        let length = retry_transient(|| Ok(archive::len(path)?))
             .with_context(|| format!("Failed to read metadata for {:?}", path))?;
-        let file_size_mb = metadata.len() as f64 / (1024.0 * 1024.0);
+        let file_size_mb = length as f64 / (1024.0 * 1024.0);
+        
+        // Segnaposto dei servizi cloud non ancora scaricati
+        if length == 0 {
+            anyhow::bail!("File is empty (0 bytes): {:?}", path);
+        }
         
-        let img = image::open(path)
-            .with_context(|| format!("Failed to open image {:?}", path))?;
+        if weights.fast {
+            return Ok(Self::analyze_header(path, file_size_mb, weights));
+        }
+        
+        // Formato dai primi byte: l'estensione può mentire e falserebbe il punteggio di compressione
+        let format = detect_format(path);
+        let is_jpeg = format == Some(ImageFormat::Jpeg);
+        let is_lossless = is_lossless_format(format, path);
+        let is_lossy_webp = format == Some(ImageFormat::WebP) && !is_lossless;
+        
+        // Il decoder JPEG completa in silenzio le immagini troncate: serve un controllo esplicito
+        if is_jpeg {
+            let data = retry_transient(|| Ok(archive::read(path)?))
+                .with_context(|| format!("Failed to read {:?}", path))?;
+            if is_truncated_jpeg(&data) {
+                anyhow::bail!("Truncated JPEG file (end of image marker missing): {:?}", path);
+            }
+        }
+        
+        // Dei PSD il composito e i livelli vengono dalla stessa lettura del file
+        let (img, icc_profile, layer_count) = if is_psd_path(path) {
+            retry_transient(|| open_psd(path)).map(|(img, psd)| (img, None, psd.layers().len()))
+        } else {
+            retry_transient(|| open_with_icc_profile(path)).map(|(img, icc_profile)| (img, icc_profile, 0))
+        }
+        .with_context(|| format!("Failed to open image {:?}", path))?;
         let (width, height) = img.dimensions();
+        let color_profile = icc_profile.as_deref().and_then(non_srgb_profile_name);
+        let frame_count = count_frames(path);
         let megapixels = (width as f64 * height as f64) / 1_000_000.0;
         
-        let (exif_data, metadata_count) = Self::extract_exif_data(path);
+        let (exif_data, metadata_count, dpi, exif_dimensions) = Self::extract_exif_data(path);
+        let camera = Self::read_camera(path);
         
-        let is_lossless = path.extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| matches!(ext.to_lowercase().as_str(), "png" | "tiff" | "tif" | "bmp"))
-            .unwrap_or(false);
-            
-        let quality_score = Self::calculate_quality_score(
+        let luma = img.to_luma8();
+        let (sharpness, noise_level) = Self::measure_sharpness_and_noise(&luma);
+        let blockiness = Self::measure_blockiness(&luma);
+        let upscale_suspected = megapixels >= UPSCALE_MIN_MEGAPIXELS
+            && Self::measure_fine_detail(&luma) < UPSCALE_DETAIL_THRESHOLD;
+        let clipping = ClippingStats::measure(&img);
+        
+        let hash = retry_transient(|| Self::calculate_file_hash(path))?;
+        let pixel_hash = Self::calculate_pixel_hash(&img);
+        
+        let mut analysis = ImageAnalysis {
+            file_path,
             file_size_mb,
+            width,
+            height,
             megapixels,
             metadata_count,
-            &img,
+            exif_data,
+            dpi,
+            exif_dimensions,
             is_lossless,
-            path
-        );
+            is_jpeg,
+            is_lossy_webp,
+            sharpness,
+            noise_level,
+            blockiness,
+            upscale_suspected,
+            clipping,
+            color_profile,
+            camera,
+            frame_count,
+            layer_count,
+            quality_score: 0,
+            hash,
+            pixel_hash,
+            header_only: false,
+            xmp: XmpSidecar::read(path),
+            live_companions: live_photo::companions(path),
+        };
+        analysis.quality_score = analysis.calculate_quality_score(weights);
         
-        let hash = Self::calculate_file_hash(path)?;
+        Ok(analysis)
+    }
+    
+    // Analisi rapida: le dimensioni vengono dall'intestazione, senza decodificare l'immagine.
+    // I formati che il crate image non riconosce restano a 0x0 e vengono valutati sul peso del file.
+    // Con --prefer metadata o --prefer-dpi l'EXIF si legge comunque: costa poco e decide la scelta
+    fn analyze_header(path: &Path, file_size_mb: f64, weights: &ScoringWeights) -> ImageAnalysis {
+        let (exif_data, metadata_count, dpi, exif_dimensions) = if weights.prefer == Preference::Metadata || weights.prefer_dpi {
+            Self::extract_exif_data(path)
+        } else {
+            (Vec::new(), 0, None, None)
+        };
+        let reader = archive::image_reader(path).ok();
+        let format = reader.as_ref().and_then(|reader| reader.format());
+        let (width, height) = reader
+            .and_then(|reader| reader.into_dimensions().ok())
+            .unwrap_or((0, 0));
         
-        Ok(ImageAnalysis {
-            file_path,
+        let is_lossless = is_lossless_format(format, path);
+        let mut analysis = ImageAnalysis {
+            file_path: path.to_path_buf(),
             file_size_mb,
             width,
             height,
-            megapixels,
+            megapixels: (width as f64 * height as f64) / 1_000_000.0,
             metadata_count,
             exif_data,
-            quality_score,
-            hash,
-        })
+            dpi,
+            exif_dimensions,
+            is_lossless,
+            is_jpeg: format == Some(ImageFormat::Jpeg),
+            is_lossy_webp: format == Some(ImageFormat::WebP) && !is_lossless,
+            sharpness: 0.0,
+            noise_level: 0.0,
+            blockiness: 1.0,
+            upscale_suspected: false,
+            clipping: ClippingStats::default(),
+            color_profile: None,
+            camera: None,
+            frame_count: 1,
+            layer_count: 0,
+            quality_score: 0,
+            hash: String::new(),
+            pixel_hash: 0,
+            header_only: true,
+            xmp: XmpSidecar::read(path),
+            live_companions: live_photo::companions(path),
+        };
+        analysis.quality_score = analysis.calculate_quality_score(weights);
+        analysis
     }
     
-    fn extract_exif_data(path: &Path) -> (Vec<(String, String)>, usize) {
+    fn extract_exif_data(path: &Path) -> ExifSummary {
         let mut exif_data = Vec::new();
         let mut count = 0;
+        let mut dpi = None;
+        let mut dimensions = None;
         
-        if let Ok(file) = File::open(path) {
-            let mut bufreader = BufReader::new(file);
-            let exif_reader = exif::Reader::new();
-            if let Ok(exif) = exif_reader.read_from_container(&mut bufreader) {
-                for field in exif.fields() {
-                    count += 1;
-                    let tag_name = format!("{:?}", field.tag);
-                    let value = field.display_value().to_string();
-                    exif_data.push((tag_name, value));
+        if let Some(exif) = Self::read_exif(path) {
+            for field in exif.fields() {
+                count += 1;
+                // Nome leggibile del tag (es. "Make"), "Tag(Tiff, n)" solo per i tag sconosciuti
+                let tag_name = field.tag.to_string();
+                let value = field.display_value().to_string();
+                exif_data.push((tag_name, value));
+            }
+            dpi = Self::read_dpi(&exif);
+            let read_dimension = |tag: exif::Tag| exif.get_field(tag, exif::In::PRIMARY)?.value.get_uint(0);
+            dimensions = read_dimension(exif::Tag::PixelXDimension).zip(read_dimension(exif::Tag::PixelYDimension));
+        }
+        
+        (exif_data, count, dpi, dimensions)
+    }
+    
+    /// Dimensioni dichiarate nell'EXIF, se diverse da quelle reali dell'immagine decodificata:
+    /// il file è stato ridimensionato o ritagliato da un programma che non ha aggiornato i
+    /// metadati. Lati scambiati (foto ruotata) non contano come differenza.
+    pub fn exif_dimensions_mismatch(&self) -> Option<(u32, u32)> {
+        let (width, height) = self.exif_dimensions?;
+        let matches = (width, height) == (self.width, self.height) || (height, width) == (self.width, self.height);
+        (!matches && width > 0 && height > 0).then_some((width, height))
+    }
+    
+    // XResolution/YResolution sono espresse nell'unità di ResolutionUnit: 2 = pollici (default), 3 = centimetri
+    fn read_dpi(exif: &exif::Exif) -> Option<(f64, f64)> {
+        let read_rational = |tag: exif::Tag| -> Option<f64> {
+            match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+                exif::Value::Rational(values) => values.first().map(|r| r.to_f64()).filter(|v| v.is_finite() && *v > 0.0),
+                _ => None,
+            }
+        };
+        let per_inch = match exif.get_field(exif::Tag::ResolutionUnit, exif::In::PRIMARY).and_then(|f| f.value.get_uint(0)) {
+            Some(3) => 2.54,
+            _ => 1.0,
+        };
+        
+        let x = read_rational(exif::Tag::XResolution)?;
+        let y = read_rational(exif::Tag::YResolution).unwrap_or(x);
+        Some((x * per_inch, y * per_inch))
+    }
+    
+    /// DPI in forma leggibile: "300 DPI", oppure "300×600 DPI" se diversi sui due assi.
+    pub fn dpi_label(&self) -> Option<String> {
+        let (x, y) = self.dpi?;
+        if (x - y).abs() < 0.5 {
+            Some(format!("{:.0} DPI", x))
+        } else {
+            Some(format!("{:.0}×{:.0} DPI", x, y))
+        }
+    }
+    
+    /// Vero se questa immagine va preferita all'altra: punteggio maggiore o uguale. A parità di
+    /// punteggio, con `prefer_dpi` e stesse dimensioni in pixel, decidono i DPI maggiori (es.
+    /// documenti riscansionati).
+    /// Prima di tutto conta la valutazione in stelle dei sidecar XMP, data da chi le ha già viste;
+    /// con `Preference::Metadata` subito dopo il numero di tag EXIF.
+    pub fn is_preferred_over(&self, other: &ImageAnalysis, weights: &ScoringWeights) -> bool {
+        let (rating, other_rating) = (XmpSidecar::score(self.xmp.as_ref()), XmpSidecar::score(other.xmp.as_ref()));
+        if rating != other_rating {
+            return rating > other_rating;
+        }
+        if weights.prefer == Preference::Metadata && self.metadata_count != other.metadata_count {
+            return self.metadata_count > other.metadata_count;
+        }
+        if weights.prefer_dpi
+            && self.quality_score == other.quality_score
+            && (self.width, self.height) == (other.width, other.height)
+            && let (Some((x1, y1)), Some((x2, y2))) = (self.dpi, other.dpi)
+            && x1 * y1 != x2 * y2
+        {
+            return x1 * y1 > x2 * y2;
+        }
+        self.quality_score >= other.quality_score
+    }
+    
+    fn read_exif(path: &Path) -> Option<exif::Exif> {
+        let mut source = archive::open(path).ok()?;
+        exif::Reader::new().read_from_container(&mut source).ok()
+    }
+    
+    /// Testo dei tag ASCII indicati, letti dall'IFD principale nello stesso ordine (None se mancano).
+    pub fn read_ascii_tags<const N: usize>(path: &Path, tags: [exif::Tag; N]) -> [Option<String>; N] {
+        let exif = Self::read_exif(path);
+        tags.map(|tag| {
+            match &exif.as_ref()?.get_field(tag, exif::In::PRIMARY)?.value {
+                exif::Value::Ascii(values) => values.first().map(|value| String::from_utf8_lossy(value).trim_end_matches('\0').to_string()),
+                _ => None,
+            }
+        })
+    }
+    
+    /// Miniatura JPEG incorporata nell'EXIF (IFD1), se presente. Si decodifica in pochi
+    /// millisecondi anche quando l'immagine intera richiede secondi.
+    pub fn read_exif_thumbnail(path: &Path) -> Option<DynamicImage> {
+        let exif = Self::read_exif(path)?;
+        let read_uint = |tag: exif::Tag| -> Option<usize> {
+            exif.get_field(tag, exif::In::THUMBNAIL)?.value.get_uint(0).map(|value| value as usize)
+        };
+        
+        // L'offset è relativo all'inizio dei dati TIFF, che è proprio il buffer dell'EXIF
+        let offset = read_uint(exif::Tag::JPEGInterchangeFormat)?;
+        let length = read_uint(exif::Tag::JPEGInterchangeFormatLength)?;
+        let data = exif.buf().get(offset..offset.checked_add(length)?)?;
+        image::load_from_memory_with_format(data, ImageFormat::Jpeg).ok()
+    }
+    
+    // Marca e modello solo se entrambi presenti, per evitare falsi allarmi
+    pub fn read_camera(path: &Path) -> Option<String> {
+        let exif = Self::read_exif(path)?;
+        let read_ascii = |tag: exif::Tag| -> Option<String> {
+            match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+                exif::Value::Ascii(values) => {
+                    let text = String::from_utf8_lossy(values.first()?).trim_matches(char::from(0)).trim().to_string();
+                    (!text.is_empty()).then_some(text)
                 }
+                _ => None,
             }
+        };
+        
+        let make = read_ascii(exif::Tag::Make)?;
+        let model = read_ascii(exif::Tag::Model)?;
+        // Molti modelli ripetono già la marca (es. "Canon EOS R5")
+        if model.to_lowercase().starts_with(&make.to_lowercase()) {
+            Some(model)
+        } else {
+            Some(format!("{} {}", make, model))
+        }
+    }
+    
+    /// Le due fotocamere, se entrambe note e diverse
+    pub fn camera_mismatch<'a>(&'a self, other: &'a ImageAnalysis) -> Option<(&'a str, &'a str)> {
+        match (&self.camera, &other.camera) {
+            (Some(camera1), Some(camera2)) if !camera1.eq_ignore_ascii_case(camera2) => Some((camera1, camera2)),
+            _ => None,
+        }
+    }
+    
+    /// Rapporto tra lato lungo e lato corto, indipendente dall'orientamento.
+    pub fn aspect_ratio(&self) -> f64 {
+        let (long, short) = (self.width.max(self.height), self.width.min(self.height));
+        if short == 0 {
+            return 0.0;
+        }
+        long as f64 / short as f64
+    }
+    
+    /// Le due proporzioni, se differiscono oltre la tolleranza (probabile reinquadratura).
+    /// Una foto ruotata di 90° non conta come differenza.
+    pub fn aspect_ratio_mismatch(&self, other: &ImageAnalysis) -> Option<(f64, f64)> {
+        let (ratio1, ratio2) = (self.aspect_ratio(), other.aspect_ratio());
+        if ratio1 == 0.0 || ratio2 == 0.0 {
+            return None;
+        }
+        ((ratio1 - ratio2).abs() / ratio1.min(ratio2) > ASPECT_RATIO_TOLERANCE).then_some((ratio1, ratio2))
+    }
+    
+    /// Data e ora di scatto (DateTimeOriginal, con i sottosecondi se presenti).
+    /// Ricade su DateTime quando l'originale manca.
+    pub fn read_capture_datetime(path: &Path) -> Option<exif::DateTime> {
+        let exif = Self::read_exif(path)?;
+        let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)
+            .or_else(|| exif.get_field(exif::Tag::DateTime, exif::In::PRIMARY))?;
+        
+        let mut datetime = match &field.value {
+            exif::Value::Ascii(values) => exif::DateTime::from_ascii(values.first()?).ok()?,
+            _ => return None,
+        };
+        
+        if let Some(subsec) = exif.get_field(exif::Tag::SubSecTimeOriginal, exif::In::PRIMARY)
+            && let exif::Value::Ascii(values) = &subsec.value
+            && let Some(value) = values.first()
+        {
+            datetime.parse_subsec(value).ok();
         }
         
-        (exif_data, count)
+        Some(datetime)
+    }
+    
+    /// Istante di scatto in secondi (ora locale della fotocamera, fuso ignorato).
+    pub fn read_capture_timestamp(path: &Path) -> Option<f64> {
+        Self::read_capture_datetime(path).map(|dt| datetime_to_seconds(&dt))
     }
     
-    fn calculate_quality_score(
-        file_size_mb: f64,
-        megapixels: f64,
-        _metadata_count: usize,
-        _img: &DynamicImage,
-        is_lossless: bool,
-        path: &Path
-    ) -> u8 {
-        // PESO 40%: Punteggio risoluzione (0-40 punti)
+    /// Punteggio 0-100: ogni componente vale da 0 a 1 e viene moltiplicata per il suo peso.
+    pub fn calculate_quality_score(&self, weights: &ScoringWeights) -> u8 {
+        let weights = if self.header_only { weights.without_pixel_measures() } else { *weights };
+        // I pixel di una copia ingrandita non aggiungono dettaglio
+        let megapixels = if self.upscale_suspected { self.megapixels / UPSCALE_RESOLUTION_DIVISOR } else { self.megapixels };
+        
+        // Punteggio risoluzione (0-40 punti nella scala storica)
         let resolution_score = if megapixels >= 48.0 {
             40  // 48+ MP (8K e oltre)
         } else if megapixels >= 24.0 {
@@ -110,59 +746,207 @@ impl ImageAnalysis {
             5   // <1 MP
         };
         
-        // PESO 60%: Punteggio qualità/compressione (0-60 punti)
-        let compression_score = if is_lossless {
-            60  // Formato lossless (PNG/TIFF/BMP): massima qualità
+        // Punteggio qualità/compressione (0-60 punti nella scala storica)
+        let compression_score = if self.is_lossless {
+            60  // Formato lossless (PNG/TIFF/BMP/WebP lossless): massima qualità
+        } else if let Some(estimate) = self.compression_estimate(&weights) {
+            estimate.points
         } else {
-            let extension = path.extension()
-                .and_then(|ext| ext.to_str())
-                .map(|ext| ext.to_lowercase())
-                .unwrap_or_default();
+            // Altri formati: punteggio medio
+            30
+        };
+        
+        // Artefatti a blocchi: copie ricompresse o condivise più volte perdono parte del punteggio di compressione
+        let blockiness_penalty = ((self.blockiness - BLOCKINESS_THRESHOLD) / (BLOCKINESS_SATURATION - BLOCKINESS_THRESHOLD)).clamp(0.0, 1.0);
+        let compression_fraction = compression_score as f64 / 60.0 * (1.0 - BLOCKINESS_MAX_PENALTY * blockiness_penalty);
+        
+        // Nitidezza: la varianza del Laplaciano satura verso 1 per immagini molto definite
+        let sharpness_fraction = self.sharpness / (self.sharpness + 300.0);
+        // Rumore: 0 livelli = pulita, 20+ livelli = molto rumorosa
+        let noise_fraction = (1.0 - self.noise_level / 20.0).clamp(0.0, 1.0);
+        
+        let score = weights.resolution as f64 * (resolution_score as f64 / 40.0)
+            + weights.compression as f64 * compression_fraction
+            + weights.sharpness as f64 * sharpness_fraction
+            + weights.noise as f64 * noise_fraction;
+        
+        score.round().min(100.0) as u8
+    }
+    
+    /// Stima della compressione di JPEG e WebP lossy dai byte per pixel, secondo la scala di
+    /// `weights`. None per gli altri formati e per i file senza dimensioni.
+    pub fn compression_estimate(&self, weights: &ScoringWeights) -> Option<CompressionEstimate> {
+        if !(self.is_jpeg || self.is_lossy_webp) || self.megapixels <= 0.0 {
+            return None;
+        }
+        let total_pixels = self.megapixels * 1_000_000.0;
+        let total_bytes = self.file_size_mb * 1_024.0 * 1_024.0;
+        let equivalence = if self.is_lossy_webp { WEBP_JPEG_EQUIVALENCE } else { 1.0 };
+        let bytes_per_pixel = total_bytes / total_pixels * equivalence;
+        
+        // Nell'analisi rapida la nitidezza non è misurata: si usa la scala fissa
+        let scale = if weights.adaptive_compression && !self.header_only {
+            (self.sharpness / DETAIL_REFERENCE).sqrt().clamp(MIN_DETAIL_SCALE, 1.0)
+        } else {
+            1.0
+        };
+        let step = weights.ladder.step(bytes_per_pixel, scale);
+        Some(CompressionEstimate { bytes_per_pixel, scale, points: 60 - 5 * step as u8 })
+    }
+    
+    /// Descrizione della valutazione della compressione, come compare nel report.
+    pub fn compression_label(&self, weights: &ScoringWeights) -> String {
+        if self.is_lossless {
+            "senza perdite (60/60)".to_string()
+        } else if let Some(estimate) = self.compression_estimate(weights) {
+            estimate.to_string()
+        } else {
+            "formato non valutato (30/60)".to_string()
+        }
+    }
+    
+    // Varianza del Laplaciano e stima del rumore (metodo di Immerkær) sulla luminanza
+    fn measure_sharpness_and_noise(luma: &GrayImage) -> (f64, f64) {
+        let (width, height) = luma.dimensions();
+        if width < 3 || height < 3 {
+            return (0.0, 0.0);
+        }
+        
+        let data = luma.as_raw();
+        let stride = width as usize;
+        let mut laplacian_sum = 0.0;
+        let mut laplacian_sq_sum = 0.0;
+        let mut noise_sum = 0.0;
+        
+        for y in 1..height as usize - 1 {
+            let above = &data[(y - 1) * stride..y * stride];
+            let row = &data[y * stride..(y + 1) * stride];
+            let below = &data[(y + 1) * stride..(y + 2) * stride];
+            
+            for x in 1..stride - 1 {
+                let center = row[x] as f64;
+                let cross = (row[x - 1] as u32 + row[x + 1] as u32 + above[x] as u32 + below[x] as u32) as f64;
+                let diagonal = (above[x - 1] as u32 + above[x + 1] as u32 + below[x - 1] as u32 + below[x + 1] as u32) as f64;
                 
-            if extension == "jpg" || extension == "jpeg" {
-                // Calcola bytes per pixel per stimare compressione JPEG
-                let total_pixels = megapixels * 1_000_000.0;
-                let total_bytes = file_size_mb * 1_024.0 * 1_024.0;
-                let bytes_per_pixel = total_bytes / total_pixels;
+                let laplacian = cross - 4.0 * center;
+                laplacian_sum += laplacian;
+                laplacian_sq_sum += laplacian * laplacian;
                 
-                // Mappa bytes/pixel a punteggio 0-60
-                if bytes_per_pixel >= 4.0 {
-                    60  // JPEG qualità ~100%
-                } else if bytes_per_pixel >= 3.0 {
-                    55  // JPEG qualità ~95%
-                } else if bytes_per_pixel >= 2.5 {
-                    50  // JPEG qualità ~90%
-                } else if bytes_per_pixel >= 2.0 {
-                    45  // JPEG qualità ~85%
-                } else if bytes_per_pixel >= 1.5 {
-                    40  // JPEG qualità ~75%
-                } else if bytes_per_pixel >= 1.2 {
-                    35  // JPEG qualità ~70%
-                } else if bytes_per_pixel >= 1.0 {
-                    30  // JPEG qualità ~60%
-                } else if bytes_per_pixel >= 0.7 {
-                    25  // JPEG qualità ~50%
-                } else if bytes_per_pixel >= 0.5 {
-                    20  // JPEG qualità ~40%
-                } else if bytes_per_pixel >= 0.4 {
-                    15  // JPEG qualità ~35%
-                } else if bytes_per_pixel >= 0.3 {
-                    10  // JPEG qualità ~30%
-                } else {
-                    5   // JPEG qualità <30%
+                // Maschera [1 -2 1; -2 4 -2; 1 -2 1]
+                noise_sum += (diagonal - 2.0 * cross + 4.0 * center).abs();
+            }
+        }
+        
+        let count = ((width - 2) as f64) * ((height - 2) as f64);
+        let mean = laplacian_sum / count;
+        let sharpness = laplacian_sq_sum / count - mean * mean;
+        let noise_level = noise_sum * (std::f64::consts::PI / 2.0).sqrt() / (6.0 * count);
+        
+        (sharpness, noise_level)
+    }
+    
+    // Energia della derivata seconda divisa per quella della prima, in orizzontale e in verticale
+    // (vedi UPSCALE_DETAIL_THRESHOLD). Si usano solo terne di pixel interne ai blocchi 8x8: i salti
+    // sui bordi di una copia ingrandita e salvata in JPEG sembrerebbero dettaglio. Un'immagine
+    // uniforme non ha dettaglio da misurare: 1
+    fn measure_fine_detail(luma: &GrayImage) -> f64 {
+        let (width, height) = luma.dimensions();
+        if width < 3 || height < 3 {
+            return 1.0;
+        }
+        
+        let data = luma.as_raw();
+        let stride = width as usize;
+        let mut first_sum = 0.0;
+        let mut second_sum = 0.0;
+        let mut count = 0u64;
+        let inside_block = |i: usize| !matches!(i % 8, 0 | 7);
+        
+        for y in 1..height as usize - 1 {
+            let above = &data[(y - 1) * stride..y * stride];
+            let row = &data[y * stride..(y + 1) * stride];
+            let below = &data[(y + 1) * stride..(y + 2) * stride];
+            
+            for x in 1..stride - 1 {
+                let center = row[x] as f64;
+                let (left, right) = (row[x - 1] as f64, row[x + 1] as f64);
+                let (up, down) = (above[x] as f64, below[x] as f64);
+                
+                if inside_block(x) {
+                    first_sum += (right - center).powi(2);
+                    second_sum += (left - 2.0 * center + right).powi(2);
+                    count += 1;
+                }
+                if inside_block(y) {
+                    first_sum += (down - center).powi(2);
+                    second_sum += (up - 2.0 * center + down).powi(2);
+                    count += 1;
                 }
-            } else {
-                // Altri formati: punteggio medio
-                30
             }
-        };
+        }
         
-        // Punteggio totale: 40% risoluzione + 60% qualità/compressione
-        (resolution_score + compression_score).min(100)
+        // Meno di un livello di grigio di variazione media tra pixel vicini
+        if first_sum < count as f64 {
+            return 1.0;
+        }
+        second_sum / first_sum
     }
     
-    fn calculate_file_hash(path: &Path) -> Result<String> {
-        let mut file = File::open(path)?;
+    // Salto medio di luminanza tra pixel adiacenti sui bordi della griglia 8x8 JPEG,
+    // diviso per quello all'interno dei blocchi
+    fn measure_blockiness(luma: &GrayImage) -> f64 {
+        let (width, height) = luma.dimensions();
+        if width < 16 || height < 16 {
+            return 1.0;
+        }
+        
+        let data = luma.as_raw();
+        let stride = width as usize;
+        let mut edge_sum = 0u64;
+        let mut edge_count = 0u64;
+        let mut inner_sum = 0u64;
+        let mut inner_count = 0u64;
+        
+        for y in 0..height as usize {
+            let row = &data[y * stride..(y + 1) * stride];
+            let above = if y > 0 { Some(&data[(y - 1) * stride..y * stride]) } else { None };
+            
+            for x in 0..stride {
+                if x > 0 {
+                    let step = row[x].abs_diff(row[x - 1]) as u64;
+                    if x.is_multiple_of(8) {
+                        edge_sum += step;
+                        edge_count += 1;
+                    } else {
+                        inner_sum += step;
+                        inner_count += 1;
+                    }
+                }
+                if let Some(above) = above {
+                    let step = row[x].abs_diff(above[x]) as u64;
+                    if y.is_multiple_of(8) {
+                        edge_sum += step;
+                        edge_count += 1;
+                    } else {
+                        inner_sum += step;
+                        inner_count += 1;
+                    }
+                }
+            }
+        }
+        
+        let edge_mean = edge_sum as f64 / edge_count as f64;
+        let inner_mean = inner_sum as f64 / inner_count as f64;
+        // Immagine uniforme: nessun artefatto misurabile
+        if inner_mean < 0.01 {
+            return 1.0;
+        }
+        
+        edge_mean / inner_mean
+    }
+    
+    pub fn calculate_file_hash(path: &Path) -> Result<String> {
+        let mut file = archive::open(path)?;
         let mut hasher = Sha256::new();
         let mut buffer = [0; 8192];
         
@@ -177,6 +961,45 @@ impl ImageAnalysis {
         Ok(format!("{:x}", hasher.finalize()))
     }
     
+    fn calculate_pixel_hash(img: &DynamicImage) -> u64 {
+        let rgba = img.to_rgba8();
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u32(rgba.width());
+        hasher.write_u32(rgba.height());
+        hasher.write(rgba.as_raw());
+        hasher.finish()
+    }
+    
+    /// Come `is_file_identical`, ma dai soli file e senza decodificarli. Un file che non si
+    /// legge non è identico a nessun altro.
+    pub fn files_identical(path1: &Path, path2: &Path) -> bool {
+        let hash = |path: &Path| retry_transient(|| Self::calculate_file_hash(path)).ok();
+        hash(path1).is_some_and(|hash1| Some(hash1) == hash(path2))
+    }
+    
+    /// Vero se i due file sono identici byte per byte (stesso SHA256)
+    pub fn is_file_identical(&self, other: &ImageAnalysis) -> bool {
+        !self.hash.is_empty() && self.hash == other.hash
+    }
+    
+    /// Vero se le due immagini hanno gli stessi pixel, anche se file e metadati differiscono
+    pub fn is_pixel_identical(&self, other: &ImageAnalysis) -> bool {
+        !self.header_only
+            && !other.header_only
+            && self.width == other.width
+            && self.height == other.height
+            && self.pixel_hash == other.pixel_hash
+    }
+    
+    /// Nome del file da mostrare; il percorso reale resta in `file_path`
+    pub fn display_name(&self) -> String {
+        self.file_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    }
+    
     pub fn get_quality_stars(&self) -> String {
         // Converti da scala 0-100 a 0-5 stelle
         let stars = ((self.quality_score as f32 / 100.0) * 5.0).round() as usize;
@@ -202,4 +1025,741 @@ impl ImageAnalysis {
         
         result
     }
-}
\ No newline at end of file
+}
+
+/// Esegue `operation` ripetendola, con attese crescenti, se fallisce per un errore di I/O
+/// transitorio. Gli errori di decodifica e i file troncati vengono restituiti subito.
+pub fn retry_transient<T>(mut operation: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut delay = IO_RETRY_INITIAL_DELAY;
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Err(e) if attempt < IO_RETRY_ATTEMPTS && is_transient_io_error(&e) => {
+                warn!("Errore di I/O transitorio (tentativo {}/{}), riprovo tra {:?}: {}", attempt, IO_RETRY_ATTEMPTS, delay, e);
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Errori di rete o di un file system remoto momentaneamente non disponibile, anche quando
+// arrivano incapsulati in un errore del decoder
+fn is_transient_io_error(error: &anyhow::Error) -> bool {
+    use std::io::ErrorKind;
+    
+    error.chain().any(|cause| {
+        let io_error = cause.downcast_ref::<std::io::Error>().or_else(|| match cause.downcast_ref::<image::ImageError>() {
+            Some(image::ImageError::IoError(io_error)) => Some(io_error),
+            _ => None,
+        });
+        io_error.is_some_and(|io_error| matches!(io_error.kind(),
+            ErrorKind::Interrupted
+                | ErrorKind::TimedOut
+                | ErrorKind::WouldBlock
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::NotConnected
+                | ErrorKind::BrokenPipe
+                | ErrorKind::NetworkDown
+                | ErrorKind::NetworkUnreachable
+                | ErrorKind::HostUnreachable
+                | ErrorKind::StaleNetworkFileHandle
+                | ErrorKind::ResourceBusy
+        ))
+    })
+}
+
+// Frammenti dei messaggi con cui i decoder segnalano la fine inattesa dei dati
+const TRUNCATION_MESSAGES: [&str; 5] = ["unexpected end", "unexpected eof", "exhausted data", "no more bytes", "truncated"];
+
+/// Motivo per cui un file non è stato analizzato, per l'elenco dei file da risincronizzare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileDamage {
+    // File di 0 byte, tipico segnaposto di una sincronizzazione cloud
+    Empty,
+    // I dati finiscono prima della fine dell'immagine (download interrotto)
+    Truncated,
+    // Decodificato supererebbe la memoria consentita (--max-image-mb)
+    TooLarge,
+    // Qualsiasi altro errore di lettura o decodifica
+    Unreadable,
+}
+
+impl FileDamage {
+    /// Classifica l'errore ottenuto analizzando o decodificando `path`.
+    pub fn classify(path: &Path, error: &anyhow::Error) -> FileDamage {
+        if archive::len(path).is_ok_and(|length| length == 0) {
+            return FileDamage::Empty;
+        }
+        
+        let too_large = error.chain().any(|cause| {
+            cause.is::<ImageTooLarge>() || matches!(cause.downcast_ref::<image::ImageError>(), Some(image::ImageError::Limits(_)))
+        });
+        if too_large {
+            return FileDamage::TooLarge;
+        }
+        
+        let truncated = error.chain().any(|cause| {
+            if let Some(io_error) = cause.downcast_ref::<std::io::Error>()
+                && io_error.kind() == std::io::ErrorKind::UnexpectedEof
+            {
+                return true;
+            }
+            let message = cause.to_string().to_lowercase();
+            TRUNCATION_MESSAGES.iter().any(|fragment| message.contains(fragment))
+        });
+        
+        if truncated { FileDamage::Truncated } else { FileDamage::Unreadable }
+    }
+    
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileDamage::Empty => "vuoto (0 byte)",
+            FileDamage::Truncated => "troncato",
+            FileDamage::TooLarge => "troppo grande, oltre --max-image-mb",
+            FileDamage::Unreadable => "illeggibile",
+        }
+    }
+}
+
+// I campi EXIF possono ripetersi (IFD principale e miniatura), quindi restano una lista
+// ordinata anziché una mappa
+mod exif_entries {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    
+    #[derive(Serialize, Deserialize)]
+    struct ExifEntry {
+        tag: String,
+        value: String,
+    }
+    
+    pub fn serialize<S: Serializer>(entries: &[(String, String)], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(entries.iter().map(|(tag, value)| ExifEntry {
+            tag: tag.clone(),
+            value: value.clone(),
+        }))
+    }
+    
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, String)>, D::Error> {
+        let entries = Vec::<ExifEntry>::deserialize(deserializer)?;
+        Ok(entries.into_iter().map(|entry| (entry.tag, entry.value)).collect())
+    }
+}
+
+/// Converte una data EXIF in secondi dall'epoch, senza considerare il fuso orario.
+pub fn datetime_to_seconds(datetime: &exif::DateTime) -> f64 {
+    let days = days_from_civil(datetime.year as i64, datetime.month as i64, datetime.day as i64);
+    let seconds = days * 86_400
+        + datetime.hour as i64 * 3_600
+        + datetime.minute as i64 * 60
+        + datetime.second as i64;
+    seconds as f64 + datetime.nanosecond.unwrap_or(0) as f64 / 1_000_000_000.0
+}
+
+/// Giorni dal 1970-01-01 per una data del calendario gregoriano.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Ruota l'immagine di `quarter_turns` quarti di giro in senso orario.
+pub fn rotate_clockwise(img: &DynamicImage, quarter_turns: u8) -> DynamicImage {
+    match quarter_turns % 4 {
+        1 => img.rotate90(),
+        2 => img.rotate180(),
+        3 => img.rotate270(),
+        _ => img.clone(),
+    }
+}
+
+/// Guadagno di ciascuna immagine che porta la sua luminanza media alla media comune delle due,
+/// pareggiando l'esposizione di un bracketing. Un solo guadagno per immagine: i colori, e con
+/// loro il bilanciamento del bianco da confrontare, restano quelli dello scatto.
+pub fn exposure_gains(img1: &DynamicImage, img2: &DynamicImage) -> [f64; 2] {
+    let luma_mean = |img: &DynamicImage| {
+        let sample = img.thumbnail(CLIPPING_SAMPLE_SIZE, CLIPPING_SAMPLE_SIZE).to_luma8();
+        let total = (sample.width() as f64 * sample.height() as f64).max(1.0);
+        sample.pixels().map(|pixel| pixel.0[0] as f64).sum::<f64>() / total
+    };
+    
+    let means = [luma_mean(img1), luma_mean(img2)];
+    let target = (means[0] + means[1]) / 2.0;
+    means.map(|mean| (target / mean.max(1.0)).min(MAX_EXPOSURE_GAIN))
+}
+
+/// Moltiplica i canali R, G, B per lo stesso guadagno, saturando a 255. Usata solo per la
+/// visualizzazione.
+pub fn apply_exposure_gain(img: &DynamicImage, gain: f64) -> DynamicImage {
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        for value in &mut pixel.0[..3] {
+            *value = (*value as f64 * gain).round().min(255.0) as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Focus peaking: maschera da sovrapporre all'immagine, con `color` sui pixel dei bordi netti
+/// (gradiente di Sobel sopra PEAKING_THRESHOLD) e trasparente altrove. La soglia è assoluta,
+/// così le due immagini sono confrontabili.
+pub fn focus_peaking(img: &DynamicImage, color: [u8; 3]) -> DynamicImage {
+    let luma = img.to_luma8();
+    let (width, height) = luma.dimensions();
+    let mut rgba = image::RgbaImage::new(width, height);
+    if width < 3 || height < 3 {
+        return DynamicImage::ImageRgba8(rgba);
+    }
+    
+    let width = width as usize;
+    let pixels = luma.as_raw();
+    let at = |x: usize, y: usize| pixels[y * width + x] as f32;
+    
+    for y in 1..height as usize - 1 {
+        for x in 1..width - 1 {
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1) - 2.0 * at(x - 1, y) - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1) - 2.0 * at(x, y - 1) - at(x + 1, y - 1);
+            if gx * gx + gy * gy >= PEAKING_THRESHOLD * PEAKING_THRESHOLD {
+                let pixel = rgba.get_pixel_mut(x as u32, y as u32);
+                pixel.0 = [color[0], color[1], color[2], 255];
+            }
+        }
+    }
+    
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Decodifica l'immagine restituendo anche l'eventuale profilo ICC incorporato.
+/// Dei PSD si usa il composito salvato da Photoshop, senza profilo.
+pub fn open_with_icc_profile(path: &Path) -> Result<(DynamicImage, Option<Vec<u8>>)> {
+    if is_psd_path(path) {
+        return Ok((open_psd(path)?.0, None));
+    }
+    
+    let mut reader = archive::image_reader(path)?;
+    // Il limite del crate vale anche per le allocazioni che la stima non prevede
+    let mut limits = image::Limits::default();
+    limits.max_alloc = Some(max_image_bytes());
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    check_decoded_size(width, height, decoder.total_bytes())?;
+    let icc_profile = decoder.icc_profile().ok().flatten();
+    let img = DynamicImage::from_decoder(decoder)?;
+    Ok((img, icc_profile))
+}
+
+/// Imposta la memoria massima, in MB, che può occupare un'immagine decodificata (--max-image-mb).
+pub fn set_max_image_mb(megabytes: u64) {
+    let _ = MAX_IMAGE_MB.set(megabytes);
+}
+
+fn max_image_bytes() -> u64 {
+    MAX_IMAGE_MB.get().copied().unwrap_or(DEFAULT_MAX_IMAGE_MB).saturating_mul(1024 * 1024)
+}
+
+/// Immagine che decodificata supererebbe la memoria consentita, rifiutata leggendo solo l'intestazione.
+#[derive(Debug)]
+pub struct ImageTooLarge {
+    pub width: u32,
+    pub height: u32,
+    pub estimated_mb: u64,
+}
+
+impl std::fmt::Display for ImageTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Image is {}x{} and would need about {} MB once decoded, over the {} MB limit (--max-image-mb)",
+            self.width, self.height, self.estimated_mb, max_image_bytes() / (1024 * 1024))
+    }
+}
+
+impl std::error::Error for ImageTooLarge {}
+
+fn check_decoded_size(width: u32, height: u32, bytes: u64) -> Result<()> {
+    if bytes > max_image_bytes() {
+        return Err(ImageTooLarge { width, height, estimated_mb: bytes.div_ceil(1024 * 1024) }.into());
+    }
+    Ok(())
+}
+
+fn open_psd(path: &Path) -> Result<(DynamicImage, psd::Psd)> {
+    // Intestazione PSD: altezza e larghezza big-endian ai byte 14 e 18. Il composito è RGBA a 8 bit
+    let mut header = [0u8; 22];
+    archive::open(path)?.read_exact(&mut header)?;
+    let height = u32::from_be_bytes([header[14], header[15], header[16], header[17]]);
+    let width = u32::from_be_bytes([header[18], header[19], header[20], header[21]]);
+    check_decoded_size(width, height, width as u64 * height as u64 * 4)?;
+    
+    let bytes = archive::read(path)?;
+    let psd = psd::Psd::from_bytes(&bytes)
+        .map_err(|e| anyhow::anyhow!("Invalid PSD file: {}", e))?;
+    let composite = image::RgbaImage::from_raw(psd.width(), psd.height(), psd.rgba())
+        .context("PSD composite does not match the image size")?;
+    Ok((DynamicImage::ImageRgba8(composite), psd))
+}
+
+/// Decodifica la pagina `page` (da 0) di un TIFF multipagina. La prima pagina passa dal
+/// decoder di `image`, come nell'analisi; le altre sono supportate a 8 o 16 bit.
+pub fn open_tiff_page(path: &Path, page: usize) -> Result<DynamicImage> {
+    if page == 0 {
+        return Ok(open_with_icc_profile(path)?.0);
+    }
+    
+    let mut decoder = tiff::decoder::Decoder::new(archive::open(path)?)?;
+    decoder.seek_to_image(page)?;
+    let (width, height) = decoder.dimensions()?;
+    let color = decoder.colortype()?;
+    
+    use tiff::ColorType;
+    use tiff::decoder::DecodingResult;
+    let img = match (color, decoder.read_image()?) {
+        (ColorType::Gray(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8),
+        (ColorType::Gray(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16),
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8),
+        (ColorType::GrayA(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA16),
+        (ColorType::RGB(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8),
+        (ColorType::RGB(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16),
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8),
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16),
+        (color, _) => anyhow::bail!("Unsupported color type {:?} in TIFF page {}", color, page + 1),
+    };
+    img.with_context(|| format!("TIFF page {} does not match its declared size", page + 1))
+}
+
+pub fn is_tiff_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| matches!(ext.to_lowercase().as_str(), "tif" | "tiff"))
+        .unwrap_or(false)
+}
+
+fn is_psd_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("psd"))
+}
+
+/// Nome del profilo se diverso da sRGB, `None` per i profili sRGB.
+pub fn non_srgb_profile_name(icc_profile: &[u8]) -> Option<String> {
+    match icc_description(icc_profile) {
+        Some(name) if name.to_lowercase().contains("srgb") => None,
+        Some(name) if !name.is_empty() => Some(name),
+        _ => Some("Profilo ICC".to_string()),
+    }
+}
+
+/// Converte i pixel in sRGB usando il profilo ICC incorporato. Se il profilo non è
+/// utilizzabile l'immagine viene restituita invariata.
+pub fn convert_to_srgb(img: DynamicImage, icc_profile: &[u8]) -> DynamicImage {
+    let Some(input) = qcms::Profile::new_from_slice(icc_profile, false) else {
+        return img;
+    };
+    let output = qcms::Profile::new_sRGB();
+    let Some(transform) = qcms::Transform::new(&input, &output, qcms::DataType::RGBA8, qcms::Intent::default()) else {
+        return img;
+    };
+    
+    let mut rgba = img.to_rgba8();
+    transform.apply(&mut rgba);
+    DynamicImage::ImageRgba8(rgba)
+}
+
+// Legge il tag 'desc' del profilo (formato v2 'desc' o v4 'mluc')
+fn icc_description(icc_profile: &[u8]) -> Option<String> {
+    let read_u32 = |at: usize| {
+        icc_profile.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    
+    let tag_count = read_u32(128)?;
+    for index in 0..tag_count.min(256) {
+        let entry = 132 + index * 12;
+        if icc_profile.get(entry..entry + 4)? != b"desc" {
+            continue;
+        }
+        
+        let offset = read_u32(entry + 4)?;
+        let text = match icc_profile.get(offset..offset + 4)? {
+            b"desc" => {
+                let length = read_u32(offset + 8)?;
+                String::from_utf8_lossy(icc_profile.get(offset + 12..offset + 12 + length)?).to_string()
+            }
+            b"mluc" => {
+                // Primo record: lingua, paese, lunghezza e offset della stringa UTF-16BE
+                let length = read_u32(offset + 20)?;
+                let start = offset + read_u32(offset + 24)?;
+                let units: Vec<u16> = icc_profile.get(start..start + length)?
+                    .chunks_exact(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect();
+                String::from_utf16_lossy(&units)
+            }
+            _ => return None,
+        };
+        return Some(text.trim_end_matches('\0').trim().to_string());
+    }
+    
+    None
+}
+
+/// Hash percettivo a 64 bit (dHash): confronta la luminosità di pixel adiacenti in una
+/// miniatura 9×8, quindi resiste a ridimensionamenti, ricompressioni e piccoli ritocchi.
+pub fn perceptual_hash(path: &Path) -> Option<u64> {
+    let (img, _) = open_with_icc_profile(path).ok()?;
+    let small = img.thumbnail_exact(9, 8).to_luma8();
+    
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+    Some(hash)
+}
+
+// Formato riconosciuto dai primi byte del file, o dall'estensione se il contenuto non basta.
+// None per i formati che image non gestisce, come il PSD
+fn detect_format(path: &Path) -> Option<ImageFormat> {
+    archive::image_reader(path).ok()?.format()
+}
+
+fn is_lossless_format(format: Option<ImageFormat>, path: &Path) -> bool {
+    match format {
+        Some(ImageFormat::WebP) => is_lossless_webp(path),
+        Some(format) => matches!(format, ImageFormat::Png | ImageFormat::Tiff | ImageFormat::Bmp),
+        None => is_psd_path(path),
+    }
+}
+
+/// Vero se il WebP è codificato senza perdite: il primo blocco immagine è VP8L invece di VP8.
+/// Nei WebP estesi (VP8X) si saltano i blocchi di metadati; negli animati conta il primo
+/// fotogramma (ANMF), i cui dati iniziano dopo 16 byte di posizione, dimensioni e durata.
+fn is_lossless_webp(path: &Path) -> bool {
+    let Ok(mut file) = archive::open(path) else {
+        return false;
+    };
+    let mut header = [0u8; 12];
+    if file.read_exact(&mut header).is_err() || &header[..4] != b"RIFF" || &header[8..] != b"WEBP" {
+        return false;
+    }
+    
+    loop {
+        let mut chunk = [0u8; 8];
+        if file.read_exact(&mut chunk).is_err() {
+            return false;
+        }
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as i64;
+        match &chunk[..4] {
+            b"VP8L" => return true,
+            b"VP8 " => return false,
+            b"ANMF" => {
+                if file.seek(SeekFrom::Current(16)).is_err() {
+                    return false;
+                }
+            }
+            // I blocchi hanno lunghezza pari: quelli dispari hanno un byte di riempimento
+            _ => {
+                if file.seek(SeekFrom::Current(size + (size & 1))).is_err() {
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+/// Segue i segmenti del JPEG fino al marker di fine immagine (EOI). Il file è troncato
+/// se i dati finiscono prima; i dati accodati dopo l'EOI (es. video delle motion photo)
+/// non vengono considerati. Un file che non rispetta la struttura non viene segnalato:
+/// ci pensa il decoder.
+pub fn is_truncated_jpeg(data: &[u8]) -> bool {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return false;
+    }
+    
+    let mut pos = 2;
+    loop {
+        // Byte di riempimento 0xFF prima del marker
+        while data.get(pos) == Some(&0xFF) && data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        
+        let marker = match (data.get(pos), data.get(pos + 1)) {
+            (Some(0xFF), Some(&marker)) => marker,
+            (Some(_), Some(_)) => return false,
+            _ => return true,
+        };
+        pos += 2;
+        
+        match marker {
+            0xD9 => return false,
+            // Marker senza segmento
+            0x01 | 0xD0..=0xD7 => continue,
+            _ => {}
+        }
+        
+        let Some(length) = data.get(pos..pos + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize) else {
+            return true;
+        };
+        pos += length;
+        if pos > data.len() {
+            return true;
+        }
+        
+        // Dopo l'intestazione dello scan seguono i dati compressi, che terminano al primo
+        // marker diverso da 0xFF00 (byte 0xFF letterale) e dai marker di restart
+        if marker == 0xDA {
+            loop {
+                let Some(offset) = data[pos..].iter().position(|&byte| byte == 0xFF) else {
+                    return true;
+                };
+                pos += offset;
+                match data.get(pos + 1) {
+                    None => return true,
+                    Some(0x00 | 0xD0..=0xD7) => pos += 2,
+                    Some(0xFF) => pos += 1,
+                    Some(_) => break,
+                }
+            }
+        }
+    }
+}
+
+/// Numero di fotogrammi o pagine del file; 1 per le immagini statiche o illeggibili.
+pub fn count_frames(path: &Path) -> usize {
+    let format = archive::image_reader(path)
+        .ok()
+        .and_then(|reader| reader.format());
+    let Ok(reader) = archive::open(path) else {
+        return 1;
+    };
+    
+    let count = match format {
+        Some(ImageFormat::Gif) => GifDecoder::new(reader)
+            .ok()
+            .map(|decoder| decoder.into_frames().count()),
+        Some(ImageFormat::WebP) => WebPDecoder::new(reader)
+            .ok()
+            .filter(|decoder| decoder.has_animation())
+            .map(|decoder| decoder.into_frames().count()),
+        Some(ImageFormat::Png) => PngDecoder::new(reader)
+            .ok()
+            .filter(|decoder| decoder.is_apng().unwrap_or(false))
+            .and_then(|decoder| decoder.apng().ok())
+            .map(|decoder| decoder.into_frames().count()),
+        Some(ImageFormat::Tiff) => tiff::decoder::Decoder::new(reader)
+            .ok()
+            .map(|mut decoder| {
+                let mut pages = 1;
+                while decoder.more_images() && decoder.next_image().is_ok() {
+                    pages += 1;
+                }
+                pages
+            }),
+        _ => None,
+    };
+    
+    count.unwrap_or(1).max(1)
+}
+
+/// Rileva se una delle due immagini è un ritaglio dell'altra, cercando la più piccola
+/// (a varie scale) dentro una versione ridotta in scala di grigi della più grande.
+/// Restituisce il numero (1 o 2) dell'immagine ritagliata.
+pub fn detect_crop(image1: &DynamicImage, image2: &DynamicImage) -> Option<u8> {
+    let area1 = image1.width() as u64 * image1.height() as u64;
+    let area2 = image2.width() as u64 * image2.height() as u64;
+    let (full, part, part_number) = if area1 >= area2 { (image1, image2, 2) } else { (image2, image1, 1) };
+    if part.width() == 0 || part.height() == 0 {
+        return None;
+    }
+    
+    let full_small = full.resize(CROP_MATCH_SIZE, CROP_MATCH_SIZE, FilterType::Triangle).to_luma8();
+    let (full_width, full_height) = full_small.dimensions();
+    
+    // Se l'immagine intera combacia, è la stessa inquadratura solo ridimensionata
+    let whole = part.resize_exact(full_width, full_height, FilterType::Triangle).to_luma8();
+    let whole_correlation = correlation(&full_small, &whole, 0, 0);
+    
+    let mut best = f64::MIN;
+    // La regione cercata occupa dal 30% al 100% della larghezza dell'immagine intera
+    for step in 6..=20 {
+        let template_width = (full_width as f64 * step as f64 * 0.05).round() as u32;
+        let template_height = (template_width as f64 * part.height() as f64 / part.width() as f64).round() as u32;
+        let is_whole_frame = template_width + 1 >= full_width && template_height + 1 >= full_height;
+        if template_width < 8 || template_height < 8 || template_height > full_height || is_whole_frame {
+            continue;
+        }
+        
+        let template = part.resize_exact(template_width, template_height, FilterType::Triangle).to_luma8();
+        for y in 0..=full_height - template_height {
+            for x in 0..=full_width - template_width {
+                best = best.max(correlation(&full_small, &template, x, y));
+            }
+        }
+    }
+    
+    (best >= CROP_MIN_CORRELATION && best > whole_correlation + 0.05).then_some(part_number)
+}
+
+/// Nitidezza (varianza del Laplaciano) di due regioni corrispondenti, la più grande ricampionata
+/// alle dimensioni della più piccola: a risoluzioni diverse i valori non sarebbero confrontabili.
+pub fn region_sharpness(region1: &DynamicImage, region2: &DynamicImage) -> Option<[f64; 2]> {
+    let width = region1.width().min(region2.width());
+    let height = region1.height().min(region2.height());
+    if width < 3 || height < 3 {
+        return None;
+    }
+    let measure = |region: &DynamicImage| {
+        let luma = if region.dimensions() == (width, height) {
+            region.to_luma8()
+        } else {
+            region.resize_exact(width, height, FilterType::Triangle).to_luma8()
+        };
+        ImageAnalysis::measure_sharpness_and_noise(&luma).0
+    };
+    Some([measure(region1), measure(region2)])
+}
+
+/// Somiglianza strutturale (SSIM, da 0 a 1) delle due immagini ridotte alla stessa dimensione
+/// in scala di grigi. Vicina a 1 per la stessa foto a qualità diverse, bassa per foto diverse.
+pub fn structural_similarity(image1: &DynamicImage, image2: &DynamicImage) -> Option<f64> {
+    if image1.width() == 0 || image1.height() == 0 || image2.width() == 0 || image2.height() == 0 {
+        return None;
+    }
+    let small1 = image1.resize(SSIM_SIZE, SSIM_SIZE, FilterType::Triangle).to_luma8();
+    let (width, height) = small1.dimensions();
+    let small2 = image2.resize_exact(width, height, FilterType::Triangle).to_luma8();
+    let (width, height) = (width as usize, height as usize);
+    if width < SSIM_WINDOW || height < SSIM_WINDOW {
+        return None;
+    }
+    
+    // Costanti di stabilizzazione standard per valori a 8 bit
+    let c1 = (0.01 * 255.0_f64).powi(2);
+    let c2 = (0.03 * 255.0_f64).powi(2);
+    let count = (SSIM_WINDOW * SSIM_WINDOW) as f64;
+    let (mut total, mut windows) = (0.0, 0);
+    
+    for y in (0..=height - SSIM_WINDOW).step_by(SSIM_STEP) {
+        for x in (0..=width - SSIM_WINDOW).step_by(SSIM_STEP) {
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for wy in y..y + SSIM_WINDOW {
+                let row = wy * width + x..wy * width + x + SSIM_WINDOW;
+                for (&a, &b) in small1.as_raw()[row.clone()].iter().zip(&small2.as_raw()[row]) {
+                    let (a, b) = (a as f64, b as f64);
+                    sum_a += a;
+                    sum_b += b;
+                    sum_aa += a * a;
+                    sum_bb += b * b;
+                    sum_ab += a * b;
+                }
+            }
+            
+            let (mean_a, mean_b) = (sum_a / count, sum_b / count);
+            let variance_a = sum_aa / count - mean_a * mean_a;
+            let variance_b = sum_bb / count - mean_b * mean_b;
+            let covariance = sum_ab / count - mean_a * mean_b;
+            total += ((2.0 * mean_a * mean_b + c1) * (2.0 * covariance + c2))
+                / ((mean_a * mean_a + mean_b * mean_b + c1) * (variance_a + variance_b + c2));
+            windows += 1;
+        }
+    }
+    
+    Some(total / windows as f64)
+}
+
+// Correlazione incrociata normalizzata del modello posto in (x, y) sull'immagine
+fn correlation(image: &GrayImage, template: &GrayImage, x: u32, y: u32) -> f64 {
+    let (width, height) = template.dimensions();
+    let image_width = image.width() as usize;
+    let count = (width * height) as f64;
+    let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    
+    for ty in 0..height as usize {
+        let image_start = (y as usize + ty) * image_width + x as usize;
+        let image_row = &image.as_raw()[image_start..image_start + width as usize];
+        let template_row = &template.as_raw()[ty * width as usize..(ty + 1) * width as usize];
+        
+        for (&a, &b) in image_row.iter().zip(template_row) {
+            let (a, b) = (a as f64, b as f64);
+            sum_a += a;
+            sum_b += b;
+            sum_aa += a * a;
+            sum_bb += b * b;
+            sum_ab += a * b;
+        }
+    }
+    
+    let variance_a = sum_aa - sum_a * sum_a / count;
+    let variance_b = sum_bb - sum_b * sum_b / count;
+    if variance_a <= 0.0 || variance_b <= 0.0 {
+        return 0.0;
+    }
+    (sum_ab - sum_a * sum_b / count) / (variance_a * variance_b).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageEncoder, RgbImage};
+    use image::codecs::{jpeg::JpegEncoder, png::PngEncoder};
+    
+    // Pesi che misurano solo la compressione: il punteggio è la quota di compressione in centesimi
+    fn compression_only() -> ScoringWeights {
+        ScoringWeights { resolution: 0, compression: 100, ..ScoringWeights::default() }
+    }
+    
+    fn sample_image() -> RgbImage {
+        RgbImage::from_fn(64, 64, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, ((x * y) % 256) as u8]))
+    }
+    
+    fn temp_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("photoscope-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+    
+    #[test]
+    fn png_with_jpg_extension_is_scored_as_lossless() {
+        let path = temp_file("png-saved-as.jpg");
+        let img = sample_image();
+        let file = std::fs::File::create(&path).unwrap();
+        PngEncoder::new(file).write_image(&img, img.width(), img.height(), image::ExtendedColorType::Rgb8).unwrap();
+        
+        let weights = compression_only();
+        let analysis = ImageAnalysis::analyze_image(&path, &weights).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        
+        assert!(analysis.is_lossless);
+        assert!(!analysis.is_jpeg);
+        assert!(analysis.compression_estimate(&weights).is_none());
+        assert_eq!(analysis.compression_label(&weights), "senza perdite (60/60)");
+        assert_eq!(analysis.calculate_quality_score(&weights), 100);
+    }
+    
+    #[test]
+    fn jpeg_with_png_extension_is_scored_as_jpeg() {
+        let path = temp_file("jpeg-saved-as.png");
+        let img = sample_image();
+        let file = std::fs::File::create(&path).unwrap();
+        JpegEncoder::new_with_quality(file, 50).write_image(&img, img.width(), img.height(), image::ExtendedColorType::Rgb8).unwrap();
+        
+        let weights = compression_only();
+        let analysis = ImageAnalysis::analyze_image(&path, &weights).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        
+        assert!(analysis.is_jpeg);
+        assert!(!analysis.is_lossless);
+        let estimate = analysis.compression_estimate(&weights).expect("stima della compressione JPEG");
+        assert_eq!(analysis.compression_label(&weights), estimate.to_string());
+        assert!(analysis.calculate_quality_score(&weights) < 100);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::theme::APP_ID;
+use eframe::egui::Key;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// File modificabile a mano, accanto al tema; viene creato con i tasti predefiniti al primo avvio
+const KEYS_FILE: &str = "keys.json";
+
+// Tasti fissi della schermata di confronto (strumenti, zoom, navigazione, guida): non si possono
+// assegnare alle azioni personalizzabili
+const RESERVED_KEYS: [Key; 30] = [
+    Key::B, Key::X, Key::C, Key::V, Key::Q, Key::E, Key::F, Key::L, Key::H, Key::K,
+    Key::T, Key::G, Key::N, Key::M, Key::J, Key::O, Key::I,
+    Key::Num1, Key::Num2, Key::Num3,
+    Key::ArrowLeft, Key::ArrowRight, Key::PageUp, Key::PageDown,
+    Key::F1, Key::F11, Key::Questionmark, Key::Plus, Key::Equals, Key::Minus,
+];
+
+/// Tasti delle azioni principali del confronto, personalizzabili per layout non QWERTY
+/// o per chi usa la mano sinistra sul mouse.
+#[derive(Clone, Copy, Debug)]
+pub struct KeyBindings {
+    pub choose_left: Key,
+    pub choose_right: Key,
+    pub skip: Key,
+    pub transfer_meta: Key,
+    pub exit: Key,
+    // Torna alla coppia precedente annullandone la decisione
+    pub undo: Key,
+    // Rimanda la coppia in fondo alla coda, da decidere dopo tutte le altre
+    pub defer: Key,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            choose_left: Key::A,
+            choose_right: Key::D,
+            skip: Key::S,
+            transfer_meta: Key::W,
+            exit: Key::Escape,
+            undo: Key::P,
+            defer: Key::R,
+        }
+    }
+}
+
+// Forma su file: nomi dei tasti come li riconosce egui (es. "A", "ArrowLeft", "Escape")
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct KeyNames {
+    choose_left: String,
+    choose_right: String,
+    skip: String,
+    transfer_meta: String,
+    exit: String,
+    undo: String,
+    defer: String,
+}
+
+impl Default for KeyNames {
+    fn default() -> Self {
+        let defaults = KeyBindings::default();
+        KeyNames {
+            choose_left: defaults.choose_left.name().to_string(),
+            choose_right: defaults.choose_right.name().to_string(),
+            skip: defaults.skip.name().to_string(),
+            transfer_meta: defaults.transfer_meta.name().to_string(),
+            exit: defaults.exit.name().to_string(),
+            undo: defaults.undo.name().to_string(),
+            defer: defaults.defer.name().to_string(),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Tasti letti dal file delle impostazioni. I nomi non riconosciuti mantengono il tasto
+    /// predefinito; se il file manca viene scritto con i valori predefiniti. Un file che assegna
+    /// lo stesso tasto a più azioni, o un tasto fisso della schermata, vale come non valido.
+    pub fn load() -> KeyBindings {
+        let Some(path) = Self::path() else {
+            return KeyBindings::default();
+        };
+        
+        let names = match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<KeyNames>(&content) {
+                Ok(names) => names,
+                Err(e) => {
+                    warn!("File dei tasti {:?} non valido, uso i tasti predefiniti: {}", path, e);
+                    return KeyBindings::default();
+                }
+            },
+            Err(_) => {
+                Self::write_defaults(&path);
+                return KeyBindings::default();
+            }
+        };
+        
+        let defaults = KeyBindings::default();
+        let parse = |action: &str, name: &str, default: Key| {
+            Key::from_name(name).unwrap_or_else(|| {
+                warn!("Tasto {:?} non riconosciuto per {}, uso {}", name, action, default.name());
+                default
+            })
+        };
+        let bindings = KeyBindings {
+            choose_left: parse("choose_left", &names.choose_left, defaults.choose_left),
+            choose_right: parse("choose_right", &names.choose_right, defaults.choose_right),
+            skip: parse("skip", &names.skip, defaults.skip),
+            transfer_meta: parse("transfer_meta", &names.transfer_meta, defaults.transfer_meta),
+            exit: parse("exit", &names.exit, defaults.exit),
+            undo: parse("undo", &names.undo, defaults.undo),
+            defer: parse("defer", &names.defer, defaults.defer),
+        };
+        
+        let keys = bindings.all();
+        if keys.iter().enumerate().any(|(i, key)| keys[..i].contains(key)) {
+            warn!("File dei tasti {:?} non valido, uso i tasti predefiniti: lo stesso tasto è assegnato a più azioni", path);
+            return KeyBindings::default();
+        }
+        if let Some(key) = keys.iter().find(|key| RESERVED_KEYS.contains(key)) {
+            warn!("File dei tasti {:?} non valido, uso i tasti predefiniti: {} è un tasto fisso della schermata di confronto", path, key.name());
+            return KeyBindings::default();
+        }
+        
+        bindings
+    }
+    
+    /// Nome breve del tasto per le etichette dei pulsanti (es. "A", "ESC").
+    pub fn label(key: Key) -> &'static str {
+        match key {
+            Key::Escape => "ESC",
+            _ => key.symbol_or_name(),
+        }
+    }
+    
+    fn all(&self) -> [Key; 7] {
+        [self.choose_left, self.choose_right, self.skip, self.transfer_meta, self.exit, self.undo, self.defer]
+    }
+    
+    fn write_defaults(path: &Path) {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        if let Ok(content) = serde_json::to_string_pretty(&KeyNames::default())
+            && let Err(e) = fs::write(path, content)
+        {
+            warn!("Impossibile creare il file dei tasti {:?}: {}", path, e);
+        }
+    }
+    
+    fn path() -> Option<PathBuf> {
+        eframe::storage_dir(APP_ID).map(|dir| dir.join(KEYS_FILE))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+use std::path::{Path, PathBuf};
+
+// Estensioni dei file che accompagnano una Live Photo: il video e l'originale HEIC
+const COMPANION_EXTENSIONS: [&str; 2] = ["mov", "heic"];
+
+/// File con lo stesso nome dell'immagine che ne fanno parte, come il `.mov` di una Live Photo
+/// di iPhone (`IMG_0001.JPG` + `IMG_0001.MOV`) o l'originale `.heic`. L'immagine stessa è esclusa.
+pub fn companions(image: &Path) -> Vec<PathBuf> {
+    let Some(stem) = image.file_stem() else {
+        return Vec::new();
+    };
+    let own_extension = image.extension().map(|ext| ext.to_ascii_lowercase());
+    COMPANION_EXTENSIONS.iter()
+        .filter(|extension| own_extension.as_deref() != Some(extension.as_ref()))
+        .filter_map(|extension| {
+            // Le fotocamere scrivono le estensioni maiuscole, i programmi di importazione spesso minuscole
+            [extension.to_string(), extension.to_uppercase()].into_iter()
+                .map(|extension| {
+                    let mut name = stem.to_os_string();
+                    name.push(".");
+                    name.push(extension);
+                    image.with_file_name(name)
+                })
+                .find(|path| path.is_file())
+        })
+        .collect()
+}
+
+/// Vero se tra i file affiancati c'è il video di una Live Photo.
+pub fn has_video(companions: &[PathBuf]) -> bool {
+    companions.iter().any(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mov")))
+}
\ No newline at end of file
@@ -23,7 +23,7 @@ impl LoadingWindow {
         
         let message = self.message.clone();
         
-        eframe::run_simple_native("PhotoScope Loading", options, move |ctx, _frame| {
+        crate::monitor::run_simple_native("PhotoScope Loading", options, move |ctx, _frame| {
             egui::CentralPanel::default().show(ctx, |ui| {
                 ui.vertical_centered(|ui| {
                     ui.add_space(50.0);
@@ -67,7 +67,7 @@ pub fn show_loading_spinner(message: &str) {
     let msg = message.to_string();
     let start_time = std::time::Instant::now();
     
-    let _ = eframe::run_simple_native("PhotoScope Loading", options, move |ctx, _frame| {
+    let _ = crate::monitor::run_simple_native("PhotoScope Loading", options, move |ctx, _frame| {
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical_centered(|ui| {
                 ui.add_space(30.0);
@@ -1,34 +1,41 @@
 use anyhow::Result;
 use eframe::egui;
+use log::warn;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
+/// Avanzamento condiviso con il thread di lavoro: (file elaborati, file totali).
+/// Finché il totale è 0 la finestra mostra solo lo spinner.
+pub type Progress = Arc<Mutex<(usize, usize)>>;
+
 pub struct LoadingGui {
     message: String,
     is_complete: Arc<Mutex<bool>>,
+    progress: Progress,
     start_time: Instant,
 }
 
 impl LoadingGui {
-    pub fn new(message: String, is_complete: Arc<Mutex<bool>>) -> Self {
+    pub fn new(message: String, is_complete: Arc<Mutex<bool>>, progress: Progress) -> Self {
         LoadingGui {
             message,
             is_complete,
+            progress,
             start_time: Instant::now(),
         }
     }
     
-    pub fn show(mut self) -> Result<()> {
+    pub fn show(self) -> Result<()> {
         let options = eframe::NativeOptions {
             viewport: egui::ViewportBuilder::default()
-                .with_inner_size([450.0, 200.0])
+                .with_inner_size([450.0, 240.0])
                 .with_title("PhotoScope - Caricamento")
                 .with_resizable(false)
                 .with_always_on_top(),
             ..Default::default()
         };
         
-        eframe::run_simple_native("PhotoScope Loading", options, move |ctx, _frame| {
+        crate::monitor::run_simple_native("PhotoScope Loading", options, move |ctx, _frame| {
             // Controlla se il caricamento è completato
             if *self.is_complete.lock().unwrap() {
                 ctx.send_viewport_cmd(egui::ViewportCommand::Close);
@@ -39,8 +46,17 @@ impl LoadingGui {
                 ui.vertical_centered(|ui| {
                     ui.add_space(40.0);
                     
-                    // Spinner animato grande
-                    ui.add(egui::Spinner::new().size(60.0));
+                    let (done, total) = *self.progress.lock().unwrap();
+                    let elapsed = self.start_time.elapsed().as_secs_f32();
+                    
+                    // Spinner finché il totale non è noto, poi barra determinata
+                    if total == 0 || done == 0 {
+                        ui.add(egui::Spinner::new().size(60.0));
+                    } else {
+                        ui.add(egui::ProgressBar::new(done as f32 / total as f32)
+                            .desired_width(380.0)
+                            .show_percentage());
+                    }
                     
                     ui.add_space(20.0);
                     
@@ -49,9 +65,27 @@ impl LoadingGui {
                     
                     ui.add_space(10.0);
                     
-                    // Tempo trascorso
-                    let elapsed = self.start_time.elapsed();
-                    ui.label(format!("Tempo: {:.1}s", elapsed.as_secs_f32()));
+                    // Velocità e tempo stimato
+                    if total > 0 {
+                        let counted = if done > 0 { done } else { total };
+                        let rate = if elapsed > 0.0 { counted as f32 / elapsed } else { 0.0 };
+                        
+                        if done > 0 {
+                            ui.label(format!("{}/{} file — {:.0} file/s", done, total, rate));
+                            if rate > 0.0 && done < total {
+                                let eta = (total - done) as f32 / rate;
+                                ui.label(format!("Tempo: {:.1}s — Rimanente: {}", elapsed, format_eta(eta)));
+                            } else {
+                                ui.label(format!("Tempo: {:.1}s", elapsed));
+                            }
+                        } else {
+                            ui.label(format!("{} file trovati — {:.0} file/s", total, rate));
+                            ui.label(format!("Tempo: {:.1}s", elapsed));
+                        }
+                    } else {
+                        // Tempo trascorso
+                        ui.label(format!("Tempo: {:.1}s", elapsed));
+                    }
                     
                     ui.add_space(10.0);
                     
@@ -68,8 +102,20 @@ impl LoadingGui {
     }
 }
 
-// Funzione helper per eseguire un'operazione con GUI di loading
-pub fn run_with_loading_gui<F, T>(message: &str, operation: F) -> Result<T>
+// Formatta i secondi rimanenti come "12s" o "3m 05s"
+fn format_eta(seconds: f32) -> String {
+    let seconds = seconds.round() as u64;
+    if seconds >= 60 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+// Funzione helper per eseguire un'operazione con GUI di loading.
+// L'operazione gira in un thread separato e aggiorna `progress`; la finestra
+// resta sul thread chiamante perché l'event loop deve vivere sul thread principale.
+pub fn run_with_loading_gui<F, T>(message: &str, progress: Progress, operation: F) -> Result<T>
 where
     F: FnOnce() -> Result<T> + Send + 'static,
     T: Send + 'static,
@@ -84,29 +130,23 @@ where
     let (tx, rx) = mpsc::channel();
     
     // Avvia il thread per l'operazione pesante
-    thread::spawn(move || {
+    let worker = thread::spawn(move || {
         let result = operation();
         tx.send(result).ok();
         *is_complete_clone.lock().unwrap() = true;
     });
     
-    // Mostra la GUI di loading
-    let loading_gui = LoadingGui::new(message.to_string(), is_complete.clone());
-    
-    // Avvia la GUI in un thread separato
-    let gui_thread = thread::spawn(move || {
-        loading_gui.show().ok();
-    });
+    // Mostra la GUI di loading finché il lavoro non è completato
+    let loading_gui = LoadingGui::new(message.to_string(), is_complete.clone(), progress);
+    if let Err(e) = loading_gui.show() {
+        warn!("Impossibile mostrare la finestra di caricamento: {}", e);
+    }
     
-    // Aspetta il risultato
+    // Aspetta il risultato (anche se la finestra è stata chiusa prima)
     let result = rx.recv()
         .map_err(|_| anyhow::anyhow!("Failed to receive result from worker thread"))?;
     
-    // Assicurati che la GUI si chiuda
-    *is_complete.lock().unwrap() = true;
-    
-    // Aspetta che il thread GUI finisca
-    gui_thread.join().ok();
+    worker.join().ok();
     
     result
 }
\ No newline at end of file
@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// Livello dei messaggi diagnostici mostrati sul terminale.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Off => LevelFilter::Off,
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+// Vero mentre la modalità batch tiene una riga di avanzamento in fondo al terminale
+static PROGRESS_LINE: AtomicBool = AtomicBool::new(false);
+
+/// Segnala che su stderr c'è una riga di avanzamento riscritta con \r: i messaggi la
+/// cancellano prima di essere stampati, e la riga ricompare al successivo aggiornamento.
+pub fn set_progress_line(active: bool) {
+    PROGRESS_LINE.store(active, Ordering::Relaxed);
+}
+
+// I messaggi vanno sempre su stderr: in modalità stdio stdout è riservato alle risposte JSON.
+// Il file di log, pensato per le segnalazioni di bug, riceve anche i messaggi di debug
+struct Logger {
+    terminal_level: LevelFilter,
+    file: Option<Mutex<File>>,
+    started_at: Instant,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Le librerie (eframe, wgpu, winit) registrano molti messaggi: di loro solo avvisi ed errori
+        if !metadata.target().starts_with(env!("CARGO_CRATE_NAME")) && metadata.level() > Level::Warn {
+            return false;
+        }
+        metadata.level() <= self.terminal_level || (self.file.is_some() && metadata.level() <= Level::Debug)
+    }
+    
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        
+        if record.level() <= self.terminal_level {
+            if PROGRESS_LINE.load(Ordering::Relaxed) {
+                eprint!("\r\x1b[2K");
+            }
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+        if let Some(file) = &self.file {
+            let elapsed = self.started_at.elapsed().as_secs_f64();
+            let mut file = file.lock().unwrap();
+            let _ = writeln!(file, "{:>10.3}s {:<5} {}: {}", elapsed, record.level(), record.target(), record.args());
+        }
+    }
+    
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Installa il logger globale. Con `log_file` i messaggi fino al livello debug vengono
+/// scritti anche nel file indicato, che viene sovrascritto.
+pub fn init(level: LogLevel, log_file: Option<&Path>) -> Result<()> {
+    let file = log_file
+        .map(|path| File::create(path).with_context(|| format!("Failed to create log file {:?}", path)))
+        .transpose()?;
+    
+    let terminal_level = level.filter();
+    let max_level = if file.is_some() { terminal_level.max(LevelFilter::Debug) } else { terminal_level };
+    
+    log::set_boxed_logger(Box::new(Logger {
+        terminal_level,
+        file: file.map(Mutex::new),
+        started_at: Instant::now(),
+    }))?;
+    log::set_max_level(max_level);
+    Ok(())
+}
\ No newline at end of file
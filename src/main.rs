@@ -1,95 +1,802 @@
+mod archive;
+mod batch;
 mod file_manager;
 mod folder_selector;
-mod gui;
+mod glob;
 mod gui_v2;
 mod image_analyzer;
+mod keymap;
+mod live_photo;
 mod loading;
+mod logging;
+mod monitor;
+mod overview;
+mod overview_gui;
 mod loading_gui;
+mod pairing_gui;
+mod report;
+mod scan_index;
+mod session;
+mod stdio;
+mod theme;
+mod thumbnail_cache;
+mod xmp;
 
 use anyhow::Result;
 use clap::Parser;
 use colored::*;
-use file_manager::FileManager;
-use std::path::PathBuf;
+use file_manager::{Conversion, FileManager, MatchStrategy, MetadataMode, NameTemplate, OutputFormat, PairSuffixes};
+use glob::GlobPattern;
+use image_analyzer::{CompressionLadder, Preference, ScoringWeights};
+use overview::Overview;
+use overview_gui::{OverviewApp, OverviewChoice};
+use report::AnalysisEntry;
+use session::{AutosavePolicy, SessionState};
+use std::io::{IsTerminal, Write};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
 
-#[derive(Parser, Debug)]
+// --quiet: niente banner né passaggi intermedi, solo i risultati e gli errori
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+// Passaggi intermedi, nascosti da --quiet
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
+// Avvisi ed esiti negativi: con --quiet vanno su stderr, così stdout contiene solo i risultati
+macro_rules! notice {
+    ($($arg:tt)*) => {
+        if QUIET.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum MatchBy {
+    Name,
+    RelativePath,
+    Similarity,
+    Timestamp,
+    Content,
+}
+
+#[derive(Parser, Clone, Debug)]
 #[command(name = "PhotoScope")]
 #[command(about = "Confronta immagini duplicate e seleziona la migliore qualità", long_about = None)]
 struct Args {
     #[arg(help = "Prima cartella da analizzare (opzionale se vuoi usare la GUI)")]
     folder1: Option<PathBuf>,
     
-    #[arg(help = "Seconda cartella da analizzare (opzionale se vuoi usare la GUI)")]
+    #[arg(help = "Seconda cartella da analizzare, anche un archivio .zip (opzionale se vuoi usare la GUI)")]
     folder2: Option<PathBuf>,
     
-    #[arg(short, long, help = "Modalità batch (salta conferma per ogni file)")]
+    #[arg(short, long, help = "Modalità batch: sceglie da sola l'immagine migliore di ogni coppia, senza aprire finestre")]
     batch: bool,
     
+    #[arg(long, help = "Non aprire finestre e usa la modalità batch (automatico se non c'è un display)")]
+    no_gui: bool,
+    
+    #[arg(long, help = "A fine sessione ricalcola lo SHA256 di ogni file copiato senza modifiche e lo confronta con l'originale")]
+    verify: bool,
+    
+    #[arg(long, help = "Con l'immagine scelta copia in output anche il video .mov e l'HEIC con lo stesso nome (Live Photo di iPhone)")]
+    copy_live_video: bool,
+    
+    #[arg(long, help = "Abbina manualmente i file con nomi diversi prima del confronto")]
+    manual_pairing: bool,
+    
+    #[arg(long, value_enum, default_value = "name", help = "Criterio di abbinamento: nome file, percorso relativo, somiglianza visiva, orario di scatto EXIF o contenuto identico (SHA256)")]
+    match_by: MatchBy,
+    
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(0..=file_manager::MAX_SIMILARITY_DISTANCE as i64), help = "Distanza massima tra gli hash percettivi per l'abbinamento per somiglianza (0 = identiche)")]
+    similarity_threshold: u32,
+    
+    #[arg(long, default_value_t = 1.0, help = "Tolleranza in secondi per l'abbinamento per orario di scatto")]
+    time_tolerance: f64,
+    
+    #[arg(long, default_value = "40,60,0,0", help = "Pesi del punteggio qualità: risoluzione,compressione,nitidezza,rumore (somma 100)")]
+    weights: ScoringWeights,
+    
+    #[arg(long, help = "A parità di dimensioni in pixel preferisci l'immagine con DPI (risoluzione di stampa) maggiori")]
+    prefer_dpi: bool,
+    
+    #[arg(long, value_enum, default_value = "quality", help = "Criterio principale della scelta: quality il punteggio qualità, metadata l'immagine con più metadati EXIF (a parità, il punteggio qualità)")]
+    prefer: Preference,
+    
+    #[arg(long, value_name = "SOGLIE", default_value = "4,3,2.5,2,1.5,1.2,1,0.7,0.5,0.4,0.3", help = "Soglie in byte per pixel del punteggio di compressione JPEG/WebP: 11 valori decrescenti, dal 60/60 (qualità ~100%) al 10/60 (~30%)")]
+    jpeg_thresholds: CompressionLadder,
+    
+    #[arg(long, help = "Abbassa le soglie di compressione per le immagini con poco dettaglio (cieli, sfondi lisci), che a parità di qualità occupano meno byte")]
+    adaptive_compression: bool,
+    
+    #[arg(long, help = "Analisi rapida: confronta solo peso del file e dimensioni in pixel, senza decodificare le immagini né leggere l'EXIF (letto solo con --prefer metadata o --prefer-dpi)")]
+    fast: bool,
+    
+    #[arg(long, value_name = "MB", default_value_t = 1024, help = "Memoria massima per un'immagine decodificata: i file più grandi vengono segnalati invece di aprirli")]
+    max_image_mb: u64,
+    
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(0..=100), help = "Salta automaticamente le coppie in cui entrambe le immagini hanno punteggio inferiore a N")]
+    min_score: Option<u8>,
+    
+    #[arg(long, value_enum, value_name = "AZIONE", help = "Risolvi senza mostrarle le coppie di file identici (SHA256): copy copia il primo file in output, skip le salta")]
+    identical: Option<gui_v2::IdenticalAction>,
+    
+    #[arg(long, value_enum, value_name = "AZIONE", conflicts_with = "dedupe", help = "Risolvi senza mostrarle né decodificarle le coppie con stesso peso e stesse dimensioni in pixel, registrate come identiche: copy copia il primo file in output, skip le salta")]
+    same_size: Option<gui_v2::IdenticalAction>,
+    
+    #[arg(long, help = "Organizza l'output in sottocartelle AAAA/MM secondo la data di scatto EXIF")]
+    organize_by_date: bool,
+    
+    #[arg(long, value_enum, default_value = "merge", help = "Trasferimento metadati (W): merge aggiunge solo i tag mancanti, overwrite sostituisce tutti i tag")]
+    metadata_mode: MetadataMode,
+    
+    #[arg(long, value_enum, value_name = "FORMATO", help = "Ricodifica i file scelti nel formato indicato invece di copiarli (i file già in quel formato vengono copiati così come sono)")]
+    convert: Option<OutputFormat>,
+    
+    #[arg(long, value_name = "N", default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100), requires = "convert", help = "Qualità JPEG per --convert jpeg (1-100)")]
+    quality: u8,
+    
+    #[arg(long, value_name = "MODELLO", help = "Rinomina i file in output, es. \"{date}_{camera}_{origname}\" (segnaposto: {origname}, {date}, {time}, {camera})")]
+    name_template: Option<NameTemplate>,
+    
+    #[arg(long, value_name = "AAAA-MM-GG", value_parser = parse_since, help = "Confronta solo le coppie con almeno un file modificato dopo questa data")]
+    since: Option<SystemTime>,
+    
+    #[arg(long, value_name = "FILE", conflicts_with = "dedupe", help = "Confronta solo i file i cui nomi sono elencati nel file indicato (uno per riga)")]
+    manifest: Option<PathBuf>,
+    
+    #[arg(long, value_name = "GLOB", help = "Ignora i file che corrispondono al modello, ripetibile (es. '*_thumb.*' per nome, '**/cache/**' per percorso)")]
+    exclude: Vec<GlobPattern>,
+    
+    #[arg(long, help = "Segui i collegamenti simbolici durante la scansione delle cartelle (i cicli vengono saltati e ogni file compare una volta sola)")]
+    follow_symlinks: bool,
+    
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["dedupe", "manual_pairing"], help = "Confronta ogni file della cartella 1 con un unico file di riferimento della cartella 2 (in alternativa passa direttamente il file come seconda cartella)")]
+    reference: Option<PathBuf>,
+    
+    #[arg(long, value_name = "CARTELLA", conflicts_with_all = ["folder1", "folder2", "manual_pairing"], help = "Cerca i duplicati all'interno di una sola cartella e scegli quale tenere per ogni gruppo")]
+    dedupe: Option<PathBuf>,
+    
+    #[arg(long, value_name = "CARTELLA", conflicts_with_all = ["folder1", "folder2", "dedupe", "slideshow", "stdio", "dump_json"], help = "Confronta a due a due le sottocartelle della cartella indicata (con --pair-suffixes o --pair-map), una coppia dopo l'altra, con l'output di ciascuna in output/<nome coppia>")]
+    pairs_in: Option<PathBuf>,
+    
+    #[arg(long, value_name = "SUFFISSI", requires = "pairs_in", conflicts_with = "pair_map", help = "Suffissi delle due sottocartelle di ogni coppia, es. _phone,_camera abbina viaggio_phone a viaggio_camera")]
+    pair_suffixes: Option<PairSuffixes>,
+    
+    #[arg(long, value_name = "FILE", requires = "pairs_in", help = "File con le coppie di sottocartelle da confrontare, una per riga: cartella1 => cartella2")]
+    pair_map: Option<PathBuf>,
+    
+    #[arg(long, value_name = "DURATA", value_parser = parse_interval, conflicts_with = "manual_pairing", help = "Presentazione automatica delle coppie, una ogni DURATA (es. 3s, 1.5s, 800ms); Spazio mette in pausa")]
+    slideshow: Option<Duration>,
+    
+    #[arg(long, requires = "slideshow", help = "Nella presentazione mostra i file scelti nella cartella di output invece delle coppie")]
+    slideshow_output: bool,
+    
+    #[arg(long, value_name = "N", default_value_t = 1, help = "Salva sessione e report ogni N decisioni, per riprendere dopo un crash (0 = solo a tempo, con --autosave-interval)")]
+    autosave_every: usize,
+    
+    #[arg(long, value_name = "DURATA", value_parser = parse_interval, required_if_eq("autosave_every", "0"), help = "Salva sessione e report, se ci sono decisioni nuove, almeno ogni DURATA (es. 30s)")]
+    autosave_interval: Option<Duration>,
+    
+    #[arg(long, value_name = "N", help = "Apri le finestre sul monitor indicato (numerati da 1 nell'ordine del sistema)")]
+    monitor: Option<usize>,
+    
+    #[arg(long, help = "Modalità script: comandi JSON su stdin, risultati JSON su stdout (nessuna GUI)")]
+    stdio: bool,
+    
+    #[arg(long, help = "Analizza tutte le immagini delle cartelle indicate e stampa il risultato in JSON su stdout")]
+    dump_json: bool,
+    
+    #[arg(long, conflicts_with_all = ["slideshow", "same_size", "verify", "manual_pairing"], help = "Solo analisi: scrive in output un report CSV/JSON di ogni file delle coppie (o di una sola cartella) senza decidere né copiare nulla")]
+    analyze_only: bool,
+    
+    #[arg(long, conflicts_with_all = ["dedupe", "slideshow", "analyze_only"], help = "Prima del confronto mostra una panoramica rapida delle coppie (identiche, differenze di punteggio, spazio occupato, scelte senza dubbi); nella GUI si sceglie poi se proseguire coppia per coppia o in modalità batch")]
+    stats: bool,
+    
+    #[arg(long, value_name = "FILE", help = "Scrivi i messaggi diagnostici, inclusi quelli di debug, nel file indicato (utile per le segnalazioni di bug)")]
+    log_file: Option<PathBuf>,
+    
+    #[arg(long, value_enum, default_value = "info", help = "Livello dei messaggi diagnostici mostrati sul terminale")]
+    log_level: logging::LogLevel,
+    
+    #[arg(short, long, help = "Niente banner, colori né passaggi intermedi: solo il riepilogo finale (righe chiave=valore), gli avvisi e gli errori")]
+    quiet: bool,
+    
+    #[arg(long, help = "Riepilogo finale in JSON su stdout, senza altri messaggi (implica --quiet)")]
+    json: bool,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    let quiet = args.quiet || args.json;
+    // I messaggi informativi sono passaggi intermedi anche loro: restano avvisi ed errori
+    let log_level = match args.log_level {
+        logging::LogLevel::Info if quiet => logging::LogLevel::Warn,
+        level => level,
+    };
+    logging::init(log_level, args.log_file.as_deref())?;
+    if quiet {
+        QUIET.store(true, Ordering::Relaxed);
+        colored::control::set_override(false);
+    }
     
-    let (folder1, folder2, from_cli) = if args.folder1.is_none() || args.folder2.is_none() {
-        println!("{}", "╔══════════════════════════════════════╗".bright_cyan());
-        println!("{}", "║         PhotoScope v0.1.0            ║".bright_cyan());
-        println!("{}", "║   Confronto e Selezione Immagini     ║".bright_cyan());
-        println!("{}", "╚══════════════════════════════════════╝".bright_cyan());
-        println!();
-        println!("{} Apertura interfaccia di selezione cartelle...", "→".bright_green());
+    if let Some(parent) = args.pairs_in.clone() {
+        return run_folder_pairs(args, &parent);
+    }
+    run(args, PathBuf::from(file_manager::OUTPUT_FOLDER))
+}
+
+// Tutto quello che segue la lettura delle opzioni, con i file scelti, il report e la sessione
+// in `output_folder`
+fn run(args: Args, output_folder: PathBuf) -> Result<()> {
+    let conversion = args.convert.map(|format| Conversion { format, quality: args.quality });
+    let weights = ScoringWeights {
+        prefer_dpi: args.prefer_dpi,
+        prefer: args.prefer,
+        fast: args.fast,
+        ladder: args.jpeg_thresholds,
+        adaptive_compression: args.adaptive_compression,
+        ..args.weights
+    };
+    image_analyzer::set_max_image_mb(args.max_image_mb);
+    scan_index::set_follow_symlinks(args.follow_symlinks);
+    
+    // In modalità stdio stdout è riservato alle risposte JSON: niente banner
+    if args.stdio {
+        return stdio::run(weights, args.organize_by_date, args.metadata_mode, conversion, args.name_template);
+    }
+    
+    if args.dump_json {
+        let folders: Vec<PathBuf> = args.folder1.into_iter().chain(args.folder2).collect();
+        if folders.is_empty() {
+            anyhow::bail!("--dump-json requires at least one folder");
+        }
+        return stdio::dump_analysis(&folders, &weights);
+    }
+    
+    // Inventario di una sola cartella: nessun abbinamento
+    if args.analyze_only
+        && args.folder2.is_none()
+        && args.dedupe.is_none()
+    {
+        let Some(folder) = args.folder1.clone() else {
+            anyhow::bail!("--analyze-only requires at least one folder");
+        };
+        let file_manager = FileManager::with_output(folder.clone(), folder.clone(), output_folder)?;
+        let manifest = args.manifest.as_deref().map(FileManager::read_manifest).transpose()?;
+        let mut files = FileManager::collect_listed_files(&folder, manifest.as_ref(), &args.exclude, None)?;
+        files.sort();
+        status!("{} Analisi di {} file in {}...", "→".bright_green(), files.len().to_string().bright_yellow(), folder.display());
+        return write_analysis(batch::analyze_files(&files, &weights), &file_manager, args.json);
+    }
+    
+    // Senza display (CI, sessioni SSH) le finestre non si aprono: si passa alla modalità batch
+    let batch = if args.batch || args.no_gui || args.analyze_only {
+        true
+    } else if !monitor::display_available() {
+        notice!("{} Nessun display disponibile: modalità batch senza GUI.", "⚠".bright_yellow());
+        true
+    } else {
+        false
+    };
+    
+    if batch && args.slideshow.is_some() {
+        anyhow::bail!("--slideshow requires a display");
+    }
+    
+    if let Some(number) = args.monitor
+        && !batch
+    {
+        monitor::select(number)?;
+    }
+    
+    // Presentazione dei file già scelti: le cartelle sorgente non servono
+    if let Some(interval) = args.slideshow
+        && args.slideshow_output
+    {
+        let file_manager = FileManager::with_output(PathBuf::from("."), PathBuf::from("."), output_folder)?;
+        let mut files = FileManager::collect_image_files(&file_manager.output_folder, None)?;
+        if files.is_empty() {
+            notice!("{} Nessuna immagine in {}.", "✗".bright_red(), file_manager.output_folder.display());
+            return Ok(());
+        }
+        files.sort();
+        let slides = files.into_iter().map(|path| (path.clone(), path)).collect();
+        return run_slideshow(slides, file_manager, weights, interval);
+    }
+    
+    let match_strategy = match args.match_by {
+        MatchBy::Name => MatchStrategy::Name,
+        MatchBy::RelativePath => MatchStrategy::RelativePath,
+        MatchBy::Similarity => MatchStrategy::Similarity { max_distance: args.similarity_threshold },
+        MatchBy::Timestamp => MatchStrategy::Timestamp { tolerance_secs: args.time_tolerance },
+        MatchBy::Content => MatchStrategy::Content,
+    };
+    
+    let (folder1, folder2, match_strategy, from_cli) = if let Some(folder) = &args.dedupe {
+        (folder.clone(), folder.clone(), match_strategy, true)
+    } else if batch && (args.folder1.is_none() || args.folder2.is_none()) {
+        anyhow::bail!("Without a GUI both folders must be given on the command line (or use --dedupe)");
+    } else if args.folder1.is_none() || args.folder2.is_none() {
+        print_banner();
+        status!("{} Apertura interfaccia di selezione cartelle...", "→".bright_green());
         
-        let selector = folder_selector::FolderSelectorApp::new();
+        let mut selector = folder_selector::FolderSelectorApp::new(match_strategy);
+        selector.exclude = args.exclude.clone();
         match selector.run()? {
-            Some((f1, f2)) => (f1, f2, false),
+            Some((f1, f2, strategy)) => (f1, f2, strategy, false),
             None => {
-                println!("{} Operazione annullata dall'utente.", "✗".bright_red());
-                return Ok(());
+                notice!("{} Operazione annullata dall'utente.", "✗".bright_red());
+                return print_no_results(&output_folder, args.json);
             }
         }
     } else {
         let f1 = args.folder1.unwrap();
         let f2 = args.folder2.unwrap();
-        (f1, f2, true)
+        (f1, f2, match_strategy, true)
     };
     
-    if from_cli {
-        println!("{}", "╔══════════════════════════════════════╗".bright_cyan());
-        println!("{}", "║         PhotoScope v0.1.0            ║".bright_cyan());
-        println!("{}", "║   Confronto e Selezione Immagini     ║".bright_cyan());
-        println!("{}", "╚══════════════════════════════════════╝".bright_cyan());
-        println!();
+    // Con --pairs-in il banner compare una volta sola, prima di tutte le coppie
+    if from_cli && args.pairs_in.is_none() {
+        print_banner();
     }
     
-    let file_manager = FileManager::new(folder1.clone(), folder2.clone())?;
+    // Riferimento unico: la seconda "cartella" può essere direttamente il file (un archivio ZIP
+    // vale invece come cartella)
+    let (folder2, reference) = if folder2.is_file() && !archive::is_archive(&folder2) {
+        let parent = folder2.parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("."));
+        (parent, Some(folder2))
+    } else {
+        let reference = args.reference.as_ref().map(|name| folder2.join(name));
+        (folder2, reference)
+    };
+    if let Some(reference) = &reference
+        && !reference.is_file()
+    {
+        anyhow::bail!("Reference file does not exist: {:?}", reference);
+    }
     
-    println!("{} Ricerca file con lo stesso nome...", "→".bright_green());
-    let matching_files = file_manager.find_matching_files()?;
+    let mut file_manager = FileManager::with_output(folder1.clone(), folder2.clone(), output_folder)?;
+    file_manager.match_strategy = match_strategy;
+    file_manager.organize_by_date = args.organize_by_date;
+    file_manager.metadata_mode = args.metadata_mode;
+    file_manager.modified_since = args.since;
+    file_manager.conversion = conversion;
+    file_manager.reference = reference;
+    file_manager.name_template = args.name_template;
+    file_manager.verify = args.verify;
+    file_manager.copy_live_companions = args.copy_live_video;
+    if let Some(manifest) = &args.manifest {
+        file_manager.manifest = Some(FileManager::read_manifest(manifest)?);
+    }
+    file_manager.exclude = args.exclude.clone();
     
-    if matching_files.is_empty() {
-        println!("{} Nessun file con lo stesso nome trovato nelle due cartelle.", "✗".bright_red());
-        return Ok(());
+    if args.manual_pairing && batch {
+        notice!("{} Abbinamento manuale non disponibile senza GUI: proseguo con le coppie trovate.", "⚠".bright_yellow());
+    } else if args.manual_pairing {
+        let (unmatched1, unmatched2) = file_manager.find_unmatched_files()?;
+        
+        if unmatched1.is_empty() || unmatched2.is_empty() {
+            status!("{} Nessun file senza corrispondenza da abbinare manualmente.", "→".bright_green());
+        } else {
+            status!("{} Apertura abbinamento manuale ({} + {} file senza corrispondenza)...",
+                "→".bright_green(),
+                unmatched1.len(),
+                unmatched2.len());
+            
+            let pairing = pairing_gui::ManualPairingApp::new(unmatched1, unmatched2, &file_manager);
+            file_manager.manual_pairs = pairing.run()?;
+            
+            status!("{} Coppie abbinate manualmente: {}",
+                "✓".bright_green(),
+                file_manager.manual_pairs.len().to_string().bright_yellow());
+        }
     }
     
-    println!("{} Trovate {} coppie di file da confrontare", 
+    let progress = Arc::new(Mutex::new((0, 0)));
+    let scan_progress = progress.clone();
+    let (matching_files, pair_groups) = if let Some(folder) = args.dedupe.clone() {
+        status!("{} Ricerca duplicati in {}...", "→".bright_green(), folder.display());
+        let max_distance = args.similarity_threshold;
+        let exclude = args.exclude.clone();
+        let groups = run_scan(batch, "Ricerca duplicati...", progress, move || {
+            FileManager::find_duplicate_groups(&folder, max_distance, &exclude, Some(&scan_progress))
+        })?;
+        
+        if groups.is_empty() {
+            notice!("{} Nessun duplicato trovato.", "✗".bright_red());
+            return print_no_results(&file_manager.output_folder, args.json);
+        }
+        status!("{} Trovati {} gruppi di duplicati", "✓".bright_green(), groups.len().to_string().bright_yellow());
+        
+        let (pairs, pair_groups) = FileManager::duplicate_pairs(&groups);
+        (pairs, Some(pair_groups))
+    } else {
+        if let Some(reference) = &file_manager.reference {
+            status!("{} Confronto con il riferimento {}...", "→".bright_green(), reference.display());
+        } else {
+            status!("{} Ricerca file con {}...", "→".bright_green(), file_manager.match_strategy.label());
+        }
+        let scan_manager = file_manager.clone();
+        let pairs = run_scan(batch, "Scansione cartelle...", progress, move || {
+            scan_manager.find_matching_files(Some(&scan_progress))
+        })?;
+        
+        if pairs.is_empty() {
+            notice!("{} Nessun file con {} trovato nelle due cartelle.", "✗".bright_red(), file_manager.match_strategy.label());
+            return print_no_results(&file_manager.output_folder, args.json);
+        }
+        (pairs, None)
+    };
+    
+    status!("{} Trovate {} coppie di file da confrontare", 
         "✓".bright_green(), 
         matching_files.len().to_string().bright_yellow());
-    println!();
+    status!();
     
-    // Usa la nuova GUI unificata
-    println!("{} Avvio interfaccia grafica...", "→".bright_green());
+    if let Some(interval) = args.slideshow {
+        return run_slideshow(matching_files, file_manager, weights, interval);
+    }
     
-    let app = gui_v2::PhotoComparisonApp::new(
-        matching_files,
-        file_manager,
-    );
+    if args.analyze_only {
+        status!("{} Analisi delle coppie, senza decisioni né copie...", "→".bright_green());
+        return write_analysis(batch::analyze_pairs(&matching_files, &weights), &file_manager, args.json);
+    }
     
-    let (selected_count, skipped_count) = app.run()?;
+    let batch = if args.stats {
+        match run_overview(batch, &matching_files, &file_manager, weights)? {
+            Some(batch) => batch,
+            None => {
+                notice!("{} Operazione annullata dall'utente.", "✗".bright_red());
+                return print_no_results(&file_manager.output_folder, args.json);
+            }
+        }
+    } else {
+        batch
+    };
+    
+    // La GUI prende il FileManager: il clone condivide l'elenco delle copie da verificare
+    let verifier = file_manager.clone();
+    let stats = if batch {
+        status!("{} Selezione automatica dell'immagine migliore di ogni coppia...", "→".bright_green());
+        batch::run(&matching_files, pair_groups.as_deref(), &file_manager, &weights, args.min_score, args.identical, args.same_size)
+    } else {
+        // Una sessione interrotta sulle stesse coppie può essere ripresa
+        let session = SessionState::load(&file_manager, &matching_files)
+            .filter(|session| ask_resume(session.current_index, matching_files.len()));
+        
+        // Usa la nuova GUI unificata
+        status!("{} Avvio interfaccia grafica...", "→".bright_green());
+        
+        let mut app = gui_v2::PhotoComparisonApp::new(
+            matching_files,
+            file_manager,
+            weights,
+        );
+        app.min_score = args.min_score;
+        app.identical_action = args.identical;
+        app.same_size_action = args.same_size;
+        app.autosave = AutosavePolicy { decisions: args.autosave_every, interval: args.autosave_interval };
+        app.pair_groups = pair_groups;
+        if let Some(session) = session {
+            app.resume(session);
+        }
+        
+        app.run()?
+    };
+    
+    if QUIET.load(Ordering::Relaxed) {
+        let verification = args.verify.then(|| verifier.verify_copies());
+        return print_results(&stats, &verifier.output_folder, verification, args.json);
+    }
     
     println!("{}", "════════════════════════════════════════".bright_cyan());
     println!("{} Processo completato!", "✓".bright_green());
-    println!("  {} File selezionati: {}", "•".bright_cyan(), selected_count.to_string().bright_green());
-    println!("  {} File saltati: {}", "•".bright_cyan(), skipped_count.to_string().bright_yellow());
-    println!("  {} Output salvato in: {}", "•".bright_cyan(), "output/".bright_white());
+    println!("  {} File selezionati: {}", "•".bright_cyan(), stats.selected.to_string().bright_green());
+    println!("  {} File saltati: {}", "•".bright_cyan(), stats.skipped.to_string().bright_yellow());
+    println!("  {} Coppie tenute entrambe: {}", "•".bright_cyan(), stats.kept_both.to_string().bright_green());
+    if stats.auto_identical > 0 {
+        println!("  {} Coppie identiche risolte in automatico: {}", "•".bright_cyan(), stats.auto_identical.to_string().bright_green());
+    }
+    if stats.auto_same_size > 0 {
+        println!("  {} Coppie con stesso peso e dimensioni: {}", "•".bright_cyan(), stats.auto_same_size.to_string().bright_green());
+    }
+    println!("  {} Output salvato in: {}", "•".bright_cyan(), verifier.output_folder.display().to_string().bright_white());
+    println!("  {} Report: {}", "•".bright_cyan(), format!("{}, .json", report::csv_path(&verifier.output_folder).display()).bright_white());
+    
+    if !stats.damaged_files.is_empty() {
+        println!();
+        println!("{} File non leggibili (coppie saltate, da risincronizzare se vuoti o troncati):", "⚠".bright_yellow());
+        for (path, damage) in &stats.damaged_files {
+            println!("  {} {} ({})", "•".bright_red(), path.display(), damage.label().bright_yellow());
+        }
+    }
     
+    if args.verify {
+        println!();
+        println!("{} Verifica delle copie in output...", "→".bright_green());
+        let (checked, failures) = verifier.verify_copies();
+        if !failures.is_empty() {
+            println!("{} Copie non valide ({} su {}):", "✗".bright_red(), failures.len(), checked);
+            for (path, reason) in &failures {
+                println!("  {} {} ({})", "•".bright_red(), path.display(), reason.bright_yellow());
+            }
+            anyhow::bail!("{} of {} copied files failed verification", failures.len(), checked);
+        }
+        println!("{} {} copie verificate, tutte identiche agli originali", "✓".bright_green(), checked.to_string().bright_yellow());
+    }
+    
+    Ok(())
+}
+
+// --pairs-in: ogni coppia di sottocartelle segue lo stesso percorso di due cartelle indicate
+// a mano, una dopo l'altra. Una coppia non riuscita non ferma le successive
+fn run_folder_pairs(args: Args, parent: &Path) -> Result<()> {
+    let pairs = match (&args.pair_suffixes, &args.pair_map) {
+        (_, Some(map)) => FileManager::read_folder_map(parent, map)?,
+        (Some(suffixes), None) => FileManager::discover_folder_pairs(parent, suffixes)?,
+        (None, None) => anyhow::bail!("--pairs-in requires --pair-suffixes or --pair-map"),
+    };
+    print_banner();
+    if pairs.is_empty() {
+        notice!("{} Nessuna coppia di sottocartelle trovata in {}.", "✗".bright_red(), parent.display());
+        return print_no_results(Path::new(file_manager::OUTPUT_FOLDER), args.json);
+    }
+    status!("{} {} coppie di sottocartelle in {}", "✓".bright_green(), pairs.len().to_string().bright_yellow(), parent.display());
+    
+    let mut failed = Vec::new();
+    for (index, pair) in pairs.iter().enumerate() {
+        status!();
+        status!("{} Coppia di cartelle {}/{}: {} ↔ {}",
+            "▶".bright_cyan(),
+            index + 1,
+            pairs.len(),
+            pair.folder1.display(),
+            pair.folder2.display());
+        
+        let mut pair_args = args.clone();
+        pair_args.folder1 = Some(pair.folder1.clone());
+        pair_args.folder2 = Some(pair.folder2.clone());
+        if let Err(e) = run(pair_args, Path::new(file_manager::OUTPUT_FOLDER).join(&pair.name)) {
+            notice!("{} Coppia {} non completata: {:#}", "✗".bright_red(), pair.name, e);
+            failed.push(pair.name.as_str());
+        }
+    }
+    
+    if !failed.is_empty() {
+        anyhow::bail!("{} of {} folder pairs failed: {}", failed.len(), pairs.len(), failed.join(", "));
+    }
+    Ok(())
+}
+
+fn print_banner() {
+    status!("{}", "╔══════════════════════════════════════╗".bright_cyan());
+    status!("{}", "║         PhotoScope v0.1.0            ║".bright_cyan());
+    status!("{}", "║   Confronto e Selezione Immagini     ║".bright_cyan());
+    status!("{}", "╚══════════════════════════════════════╝".bright_cyan());
+    status!();
+}
+
+// Uscita anticipata (nessuna coppia, operazione annullata): chi legge il riepilogo lo trova
+// comunque, con tutti i conteggi a zero
+fn print_no_results(output: &Path, json: bool) -> Result<()> {
+    if QUIET.load(Ordering::Relaxed) {
+        print_results(&gui_v2::SessionStats::default(), output, None, json)?;
+    }
     Ok(())
 }
+
+// Riepilogo per gli script (--quiet, --json): una riga chiave=valore per dato, oppure un
+// oggetto JSON. I file non leggibili e le copie non valide hanno una riga ciascuno
+fn print_results(stats: &gui_v2::SessionStats, output: &Path, verification: Option<(usize, Vec<(PathBuf, String)>)>, json: bool) -> Result<()> {
+    if json {
+        let damaged: Vec<_> = stats.damaged_files.iter()
+            .map(|(path, damage)| json!({ "path": path, "reason": damage.label() }))
+            .collect();
+        let verification = verification.as_ref().map(|(checked, failures)| {
+            let failures: Vec<_> = failures.iter().map(|(path, reason)| json!({ "path": path, "reason": reason })).collect();
+            json!({ "checked": checked, "failures": failures })
+        });
+        println!("{}", json!({
+            "selected": stats.selected,
+            "skipped": stats.skipped,
+            "kept_both": stats.kept_both,
+            "auto_identical": stats.auto_identical,
+            "auto_same_size": stats.auto_same_size,
+            "output": output,
+            "damaged_files": damaged,
+            "verification": verification,
+        }));
+    } else {
+        println!("selected={}", stats.selected);
+        println!("skipped={}", stats.skipped);
+        println!("kept_both={}", stats.kept_both);
+        println!("auto_identical={}", stats.auto_identical);
+        println!("auto_same_size={}", stats.auto_same_size);
+        println!("output={}", output.display());
+        for (path, damage) in &stats.damaged_files {
+            println!("damaged={}\t{}", path.display(), damage.label());
+        }
+        if let Some((checked, failures)) = &verification {
+            println!("verified={}", checked);
+            for (path, reason) in failures {
+                println!("verify_failed={}\t{}", path.display(), reason);
+            }
+        }
+    }
+    
+    match verification {
+        Some((checked, failures)) if !failures.is_empty() => {
+            anyhow::bail!("{} of {} copied files failed verification", failures.len(), checked)
+        }
+        _ => Ok(()),
+    }
+}
+
+// --analyze-only: report di inventario in output e riepilogo sul terminale
+fn write_analysis(entries: Vec<AnalysisEntry>, file_manager: &FileManager, json: bool) -> Result<()> {
+    report::write_analysis_report(&file_manager.output_folder, &entries)?;
+    
+    let failed = entries.iter().filter(|entry| entry.error.is_some()).count();
+    let analyzed = entries.len() - failed;
+    let report = file_manager.output_folder.join(report::ANALYSIS_NAME).with_extension("csv");
+    if json {
+        println!("{}", json!({ "analyzed": analyzed, "failed": failed, "report": report }));
+        return Ok(());
+    }
+    if QUIET.load(Ordering::Relaxed) {
+        println!("analyzed={}", analyzed);
+        println!("failed={}", failed);
+        println!("report={}", report.display());
+        return Ok(());
+    }
+    println!("{} {} file analizzati", "✓".bright_green(), analyzed.to_string().bright_yellow());
+    if failed > 0 {
+        println!("  {} File non leggibili: {}", "•".bright_cyan(), failed.to_string().bright_red());
+    }
+    println!("  {} Report: {}", "•".bright_cyan(), format!("{}, .json", report.display()).bright_white());
+    Ok(())
+}
+
+// In modalità batch la scansione gira senza la finestra di avanzamento
+fn run_scan<F, T>(batch: bool, message: &str, progress: loading_gui::Progress, operation: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    if batch {
+        operation()
+    } else {
+        loading_gui::run_with_loading_gui(message, progress, operation)
+    }
+}
+
+// --stats: panoramica sul terminale e, con la GUI, scelta tra confronto coppia per coppia e
+// modalità batch. None se l'utente chiude senza scegliere
+fn run_overview(batch: bool, pairs: &[(PathBuf, PathBuf)], file_manager: &FileManager, weights: ScoringWeights) -> Result<Option<bool>> {
+    status!("{} Panoramica delle coppie (analisi rapida)...", "→".bright_green());
+    let progress = Arc::new(Mutex::new((0, 0)));
+    let overview_progress = progress.clone();
+    let overview_pairs = pairs.to_vec();
+    let overview_manager = file_manager.clone();
+    let overview = run_scan(batch, "Panoramica delle coppie...", progress, move || {
+        Ok(Overview::compute(&overview_pairs, &overview_manager, &weights, Some(&overview_progress)))
+    })?;
+    
+    status!("  {} Coppie: {} ({} identiche byte per byte, {} con un file non leggibile)",
+        "•".bright_cyan(),
+        overview.pairs.to_string().bright_yellow(),
+        overview.identical.to_string().bright_green(),
+        overview.unreadable.to_string().bright_red());
+    for (index, (files, bytes)) in overview.footprint.iter().enumerate() {
+        status!("  {} Cartella {}: {} immagini, {}", "•".bright_cyan(), index + 1, files, Overview::size_label(*bytes).bright_white());
+    }
+    status!("  {} Differenza di punteggio:", "•".bright_cyan());
+    for (index, count) in overview.score_differences.iter().enumerate() {
+        status!("      {:<18} {}", Overview::score_difference_label(index), count.to_string().bright_yellow());
+    }
+    status!("  {} Decise senza dubbi: {} su {} ({:.0}%): {}",
+        "•".bright_cyan(),
+        overview.clear_decisions().to_string().bright_green(),
+        overview.pairs - overview.unreadable,
+        overview.clear_percentage(),
+        overview.advice().label());
+    status!();
+    
+    if batch {
+        return Ok(Some(true));
+    }
+    let folders = [&file_manager.folder1, &file_manager.folder2].map(|folder| folder.display().to_string());
+    Ok(match OverviewApp::new(overview, folders).run()? {
+        OverviewChoice::Compare => Some(false),
+        OverviewChoice::Batch => Some(true),
+        OverviewChoice::Cancel => None,
+    })
+}
+
+// Sola visualizzazione: nessuna decisione, sessione o report
+fn run_slideshow(slides: Vec<(PathBuf, PathBuf)>, file_manager: FileManager, weights: ScoringWeights, interval: Duration) -> Result<()> {
+    status!("{} Avvio presentazione: {} elementi, uno ogni {:.1}s",
+        "→".bright_green(),
+        slides.len().to_string().bright_yellow(),
+        interval.as_secs_f32());
+    
+    let mut app = gui_v2::PhotoComparisonApp::new(slides, file_manager, weights);
+    app.slideshow = Some(gui_v2::Slideshow::new(interval));
+    app.run()?;
+    Ok(())
+}
+
+// Secondi ("3", "3s", "1.5s") o millisecondi ("800ms")
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let seconds = if let Some(millis) = value.strip_suffix("ms") {
+        millis.trim().parse::<f64>().map(|ms| ms / 1000.0)
+    } else {
+        value.strip_suffix('s').unwrap_or(value).trim().parse::<f64>()
+    };
+    
+    match seconds {
+        Ok(seconds) if seconds.is_finite() && seconds > 0.0 => Ok(Duration::from_secs_f64(seconds)),
+        _ => Err(format!("durata non valida '{}': usa ad esempio 3s o 800ms", value)),
+    }
+}
+
+// Mezzanotte UTC del giorno indicato
+fn parse_since(value: &str) -> Result<SystemTime, String> {
+    let parts: Vec<&str> = value.trim().split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(format!("data non valida '{}': usa il formato AAAA-MM-GG", value));
+    };
+    
+    let parse = |part: &str| part.parse::<i64>().map_err(|_| format!("data non valida '{}': usa il formato AAAA-MM-GG", value));
+    let (year, month, day) = (parse(year)?, parse(month)?, parse(day)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(format!("data non valida '{}'", value));
+    }
+    
+    let days = image_analyzer::days_from_civil(year, month, day);
+    if days < 0 {
+        return Err(format!("la data '{}' precede il 1970", value));
+    }
+    Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(days as u64 * 86_400))
+}
+
+// Senza un terminale su stdin nessuno risponde: vale la risposta predefinita. Con --quiet la
+// domanda va su stderr, come gli avvisi
+fn ask_resume(current_index: usize, total: usize) -> bool {
+    if !std::io::stdin().is_terminal() {
+        return true;
+    }
+    let question = format!("{} Trovata una sessione interrotta alla coppia {}/{}. Riprendere? [S/n] ",
+        "?".bright_yellow(),
+        current_index + 1,
+        total);
+    if QUIET.load(Ordering::Relaxed) {
+        eprint!("{}", question);
+        let _ = std::io::stderr().flush();
+    } else {
+        print!("{}", question);
+        let _ = std::io::stdout().flush();
+    }
+    
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return true;
+    }
+    !matches!(answer.trim().to_lowercase().as_str(), "n" | "no")
+}
\ No newline at end of file
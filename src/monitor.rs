@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use eframe::egui::{self, Pos2, Vec2};
+use eframe::UserEvent;
+use log::{debug, info};
+use std::cell::RefCell;
+use std::sync::OnceLock;
+use winit::application::ApplicationHandler;
+use winit::event::{StartCause, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::platform::run_on_demand::EventLoopExtRunOnDemand;
+use winit::window::WindowId;
+
+/// Un monitor collegato, con posizione e dimensioni in punti logici come li usa egui.
+#[derive(Clone, Debug)]
+pub struct Monitor {
+    pub name: String,
+    pub origin: Pos2,
+    pub size: Vec2,
+}
+
+// Monitor scelto con --monitor: tutte le finestre vengono centrate lì
+static TARGET: OnceLock<Monitor> = OnceLock::new();
+
+// winit ammette un solo event loop per processo e eframe non espone il suo: lo creiamo noi
+// e lo riusiamo per tutte le finestre, così da poter elencare i monitor prima di aprirle
+thread_local!(static EVENT_LOOP: RefCell<Option<EventLoop<UserEvent>>> = const { RefCell::new(None) });
+
+fn with_event_loop<R>(f: impl FnOnce(&mut EventLoop<UserEvent>) -> eframe::Result<R>) -> eframe::Result<R> {
+    EVENT_LOOP.with(|cell| {
+        let mut slot = cell.borrow_mut();
+        let event_loop = match &mut *slot {
+            Some(event_loop) => event_loop,
+            None => slot.insert(EventLoop::with_user_event().build()?),
+        };
+        f(event_loop)
+    })
+}
+
+// Gira l'event loop un solo ciclo, il tempo di leggere l'elenco dei monitor
+#[derive(Default)]
+struct MonitorProbe {
+    monitors: Vec<Monitor>,
+}
+
+impl ApplicationHandler<UserEvent> for MonitorProbe {
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
+        if cause != StartCause::Init {
+            return;
+        }
+        self.monitors = event_loop.available_monitors()
+            .map(|handle| {
+                let scale = handle.scale_factor();
+                let position = handle.position().to_logical::<f32>(scale);
+                let size = handle.size().to_logical::<f32>(scale);
+                Monitor {
+                    name: handle.name().unwrap_or_else(|| "senza nome".to_string()),
+                    origin: egui::pos2(position.x, position.y),
+                    size: egui::vec2(size.width, size.height),
+                }
+            })
+            .collect();
+        event_loop.exit();
+    }
+    
+    fn resumed(&mut self, _event_loop: &ActiveEventLoop) {}
+    
+    fn window_event(&mut self, _event_loop: &ActiveEventLoop, _window_id: WindowId, _event: WindowEvent) {}
+}
+
+/// Monitor collegati, nell'ordine riportato dal sistema.
+pub fn available() -> Result<Vec<Monitor>> {
+    let mut probe = MonitorProbe::default();
+    with_event_loop(|event_loop| Ok(event_loop.run_app_on_demand(&mut probe)?))
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .context("Failed to list the monitors")?;
+    Ok(probe.monitors)
+}
+
+/// Vero se c'è un display su cui aprire le finestre (su Linux serve DISPLAY o WAYLAND_DISPLAY).
+/// L'event loop creato per la verifica resta a disposizione delle finestre successive.
+pub fn display_available() -> bool {
+    match with_event_loop(|_| Ok(())) {
+        Ok(()) => true,
+        Err(e) => {
+            debug!("Nessun display disponibile: {}", e);
+            false
+        }
+    }
+}
+
+/// Sceglie il monitor (numerato da 1) su cui aprire tutte le finestre successive.
+pub fn select(number: usize) -> Result<()> {
+    let monitors = available()?;
+    for (index, monitor) in monitors.iter().enumerate() {
+        debug!("Monitor {}: {} {:?} a {:?}", index + 1, monitor.name, monitor.size, monitor.origin);
+    }
+    
+    let Some(monitor) = number.checked_sub(1).and_then(|index| monitors.get(index)) else {
+        let names: Vec<String> = monitors.iter()
+            .enumerate()
+            .map(|(index, monitor)| format!("{}: {}", index + 1, monitor.name))
+            .collect();
+        anyhow::bail!("Monitor {} not found, available monitors: {}", number, names.join(", "));
+    };
+    
+    info!("Finestre sul monitor {} ({})", number, monitor.name);
+    let _ = TARGET.set(monitor.clone());
+    Ok(())
+}
+
+/// Come `eframe::run_simple_native`, ma sull'event loop condiviso e centrando la finestra
+/// sul monitor scelto con `select`.
+pub fn run_simple_native(
+    app_name: &str,
+    mut options: eframe::NativeOptions,
+    update_fun: impl FnMut(&egui::Context, &mut eframe::Frame) + 'static,
+) -> eframe::Result<()> {
+    struct SimpleApp<U> {
+        update_fun: U,
+    }
+    
+    impl<U: FnMut(&egui::Context, &mut eframe::Frame) + 'static> eframe::App for SimpleApp<U> {
+        fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+            (self.update_fun)(ctx, frame);
+        }
+    }
+    
+    if let Some(monitor) = TARGET.get() {
+        let size = options.viewport.inner_size.unwrap_or(egui::vec2(800.0, 600.0)).min(monitor.size);
+        options.viewport = options.viewport
+            .with_inner_size(size)
+            .with_position(monitor.origin + (monitor.size - size) / 2.0);
+        // La geometria salvata riporterebbe la finestra sul monitor dell'ultima esecuzione
+        options.persist_window = false;
+    }
+    
+    with_event_loop(|event_loop| {
+        let mut app = eframe::create_native(
+            app_name,
+            options,
+            Box::new(|_cc| Ok(Box::new(SimpleApp { update_fun }))),
+            event_loop,
+        );
+        Ok(event_loop.run_app_on_demand(&mut app)?)
+    })
+}
\ No newline at end of file
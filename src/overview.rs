@@ -0,0 +1,149 @@
+use crate::archive;
+use crate::file_manager::FileManager;
+use crate::image_analyzer::{ImageAnalysis, ScoringWeights};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+// Estremi superiori delle fasce di differenza di punteggio; l'ultima fascia è "oltre 20"
+const SCORE_DIFFERENCE_LIMITS: [u8; 4] = [0, 5, 10, 20];
+// Percentuali di coppie decise senza dubbi oltre cui si consiglia la modalità batch o si
+// segnala che ne risolve la maggior parte
+const BATCH_ADVICE_PERCENTAGE: f64 = 90.0;
+const MIXED_ADVICE_PERCENTAGE: f64 = 50.0;
+
+/// Consiglio su come proseguire, secondo la quota di coppie decise senza dubbi.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Advice {
+    Batch,
+    Mixed,
+    PairByPair,
+}
+
+impl Advice {
+    pub fn label(self) -> &'static str {
+        match self {
+            Advice::Batch => "la modalità batch può decidere quasi tutto da sola",
+            Advice::Mixed => "la modalità batch risolve la maggior parte delle coppie, ma conviene controllare le altre",
+            Advice::PairByPair => "molte coppie sono incerte: conviene il confronto coppia per coppia",
+        }
+    }
+}
+
+/// Panoramica delle coppie prima del confronto (--stats), da un'analisi rapida: dimensioni
+/// dall'intestazione e peso del file, senza decodificare le immagini.
+#[derive(Clone, Debug, Default)]
+pub struct Overview {
+    pub pairs: usize,
+    // Coppie identiche byte per byte: l'hash si calcola solo se i pesi coincidono
+    pub identical: usize,
+    // Coppie non identiche in cui risoluzione e compressione indicano lo stesso file
+    pub unanimous: usize,
+    // Coppie con almeno un file non leggibile
+    pub unreadable: usize,
+    // Coppie per fascia di differenza di punteggio (vedi `score_difference_label`)
+    pub score_differences: [usize; SCORE_DIFFERENCE_LIMITS.len() + 1],
+    // Numero di immagini e byte occupati in ciascuna cartella
+    pub footprint: [(usize, u64); 2],
+}
+
+impl Overview {
+    /// Calcola la panoramica delle coppie. Il progresso avanza di una coppia alla volta.
+    pub fn compute(pairs: &[(PathBuf, PathBuf)], file_manager: &FileManager, weights: &ScoringWeights, progress: Option<&Arc<Mutex<(usize, usize)>>>) -> Overview {
+        let mut overview = Overview { pairs: pairs.len(), ..Overview::default() };
+        if let Some(progress) = progress {
+            *progress.lock().unwrap() = (0, pairs.len());
+        }
+        
+        let quick = ScoringWeights { fast: true, ..*weights };
+        // Un solo criterio alla volta: se entrambi preferiscono lo stesso file la scelta non
+        // dipende dai pesi
+        let criteria = [quick.with_component(0, 100), quick.with_component(1, 100)];
+        
+        for (path1, path2) in pairs {
+            let analyses = (ImageAnalysis::analyze_image(path1, &quick), ImageAnalysis::analyze_image(path2, &quick));
+            if let (Ok(analysis1), Ok(analysis2)) = analyses {
+                let difference = analysis1.quality_score.abs_diff(analysis2.quality_score);
+                let bucket = SCORE_DIFFERENCE_LIMITS.iter()
+                    .position(|&limit| difference <= limit)
+                    .unwrap_or(SCORE_DIFFERENCE_LIMITS.len());
+                overview.score_differences[bucket] += 1;
+                
+                if Self::same_content(&analysis1, &analysis2) {
+                    overview.identical += 1;
+                } else {
+                    let preferences: Vec<_> = criteria.iter()
+                        .map(|criterion| analysis1.calculate_quality_score(criterion).cmp(&analysis2.calculate_quality_score(criterion)))
+                        .filter(|ordering| ordering.is_ne())
+                        .collect();
+                    if preferences.first().is_some_and(|first| preferences.iter().all(|ordering| ordering == first)) {
+                        overview.unanimous += 1;
+                    }
+                }
+            } else {
+                overview.unreadable += 1;
+            }
+            
+            if let Some(progress) = progress {
+                progress.lock().unwrap().0 += 1;
+            }
+        }
+        
+        for (index, folder) in [&file_manager.folder1, &file_manager.folder2].into_iter().enumerate() {
+            let files = FileManager::collect_listed_files(folder, file_manager.manifest.as_ref(), &file_manager.exclude, None).unwrap_or_default();
+            let bytes = files.iter().filter_map(|path| archive::len(path).ok()).sum();
+            overview.footprint[index] = (files.len(), bytes);
+        }
+        
+        overview
+    }
+    
+    // L'analisi rapida non calcola l'hash: si confronta solo quando i pesi coincidono
+    fn same_content(analysis1: &ImageAnalysis, analysis2: &ImageAnalysis) -> bool {
+        let length = |analysis: &ImageAnalysis| archive::len(&analysis.file_path).ok();
+        length(analysis1).is_some_and(|length1| Some(length1) == length(analysis2))
+            && ImageAnalysis::calculate_file_hash(&analysis1.file_path)
+                .is_ok_and(|hash| ImageAnalysis::calculate_file_hash(&analysis2.file_path).is_ok_and(|other| hash == other))
+    }
+    
+    /// Coppie che la modalità batch deciderebbe senza dubbi: identiche o con criteri concordi.
+    pub fn clear_decisions(&self) -> usize {
+        self.identical + self.unanimous
+    }
+    
+    /// Percentuale di coppie leggibili decise senza dubbi.
+    pub fn clear_percentage(&self) -> f64 {
+        let readable = self.pairs - self.unreadable;
+        if readable == 0 {
+            return 0.0;
+        }
+        self.clear_decisions() as f64 * 100.0 / readable as f64
+    }
+    
+    pub fn advice(&self) -> Advice {
+        match self.clear_percentage() {
+            percentage if percentage >= BATCH_ADVICE_PERCENTAGE => Advice::Batch,
+            percentage if percentage >= MIXED_ADVICE_PERCENTAGE => Advice::Mixed,
+            _ => Advice::PairByPair,
+        }
+    }
+    
+    /// Etichetta della fascia `index` di `score_differences`, es. "6-10 punti".
+    pub fn score_difference_label(index: usize) -> String {
+        match (index.checked_sub(1).map(|previous| SCORE_DIFFERENCE_LIMITS[previous]), SCORE_DIFFERENCE_LIMITS.get(index)) {
+            (None, Some(0)) => "stesso punteggio".to_string(),
+            (Some(previous), Some(limit)) => format!("{}-{} punti", previous + 1, limit),
+            (Some(previous), None) => format!("oltre {} punti", previous),
+            _ => String::new(),
+        }
+    }
+    
+    /// Byte in forma leggibile: MB sotto il gigabyte, altrimenti GB.
+    pub fn size_label(bytes: u64) -> String {
+        let megabytes = bytes as f64 / (1024.0 * 1024.0);
+        if megabytes < 1024.0 {
+            format!("{:.1} MB", megabytes)
+        } else {
+            format!("{:.2} GB", megabytes / 1024.0)
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,164 @@
+use crate::overview::{Advice, Overview};
+use crate::theme::{Theme, ThemeKind};
+use anyhow::Result;
+use eframe::egui;
+use egui::{Color32, RichText, Stroke, Vec2};
+use egui_phosphor::regular;
+use std::sync::{Arc, Mutex};
+
+// Larghezza massima della barra di una fascia di differenza di punteggio
+const BAR_WIDTH: f32 = 260.0;
+
+/// Come proseguire dopo la panoramica.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverviewChoice {
+    // Confronto coppia per coppia nella GUI
+    Compare,
+    // Scelta automatica di ogni coppia, come con --batch
+    Batch,
+    // Finestra chiusa senza scegliere
+    Cancel,
+}
+
+pub struct OverviewApp {
+    overview: Overview,
+    folders: [String; 2],
+    choice: Option<OverviewChoice>,
+    theme: Theme,
+}
+
+impl OverviewApp {
+    pub fn new(overview: Overview, folders: [String; 2]) -> Self {
+        OverviewApp {
+            overview,
+            folders,
+            choice: None,
+            theme: Theme::from_kind(ThemeKind::load()),
+        }
+    }
+    
+    /// Mostra la panoramica finché l'utente non sceglie come proseguire.
+    pub fn run(mut self) -> Result<OverviewChoice> {
+        let options = eframe::NativeOptions {
+            viewport: egui::ViewportBuilder::default()
+                .with_inner_size([640.0, 620.0])
+                .with_title("PhotoScope Pro - Panoramica"),
+            ..Default::default()
+        };
+        
+        let result = Arc::new(Mutex::new(OverviewChoice::Cancel));
+        let result_clone = result.clone();
+        
+        crate::monitor::run_simple_native("PhotoScope Overview", options, move |ctx, _frame| {
+            self.setup_style(ctx);
+            self.update(ctx);
+            
+            if let Some(choice) = self.choice {
+                *result_clone.lock().unwrap() = choice;
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+        }).map_err(|e| anyhow::anyhow!("GUI error: {}", e))?;
+        
+        let choice = *result.lock().unwrap();
+        Ok(choice)
+    }
+    
+    fn setup_style(&self, ctx: &egui::Context) {
+        let mut fonts = egui::FontDefinitions::default();
+        egui_phosphor::add_to_fonts(&mut fonts, egui_phosphor::Variant::Regular);
+        ctx.set_fonts(fonts);
+        
+        let mut style = (*ctx.style()).clone();
+        style.spacing.item_spacing = Vec2::new(12.0, 8.0);
+        style.spacing.button_padding = Vec2::new(16.0, 10.0);
+        style.visuals = self.theme.visuals();
+        ctx.set_style(style);
+    }
+    
+    fn update(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("overview_actions").show(ctx, |ui| {
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if self.action_button(ui, &format!("{} Confronta coppia per coppia", regular::COLUMNS), self.theme.accent_blue) {
+                    self.choice = Some(OverviewChoice::Compare);
+                }
+                if self.action_button(ui, &format!("{} Modalità batch", regular::LIGHTNING), self.theme.accent_green) {
+                    self.choice = Some(OverviewChoice::Batch);
+                }
+                if self.action_button(ui, &format!("{} Esci", regular::X), self.theme.text_secondary) {
+                    self.choice = Some(OverviewChoice::Cancel);
+                }
+            });
+            ui.add_space(8.0);
+        });
+        
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let overview = &self.overview;
+            ui.add_space(8.0);
+            ui.heading(RichText::new(format!("{} Panoramica delle coppie", regular::CHART_BAR)).color(self.theme.text_primary));
+            ui.label(RichText::new("Analisi rapida: dimensioni e peso dei file, senza decodificare le immagini")
+                .size(13.0)
+                .color(self.theme.text_secondary));
+            ui.add_space(12.0);
+            
+            egui::Grid::new("overview_counts").num_columns(2).spacing([24.0, 6.0]).show(ui, |ui| {
+                let rows = [
+                    ("Coppie da confrontare", overview.pairs.to_string()),
+                    ("Identiche byte per byte", overview.identical.to_string()),
+                    ("Risoluzione e compressione concordi", overview.unanimous.to_string()),
+                    ("Con un file non leggibile", overview.unreadable.to_string()),
+                ];
+                for (label, value) in rows {
+                    ui.label(RichText::new(label).color(self.theme.text_secondary));
+                    ui.label(RichText::new(value).strong().color(self.theme.text_primary));
+                    ui.end_row();
+                }
+                for (index, folder) in self.folders.iter().enumerate() {
+                    let (files, bytes) = overview.footprint[index];
+                    ui.label(RichText::new(format!("Cartella {}", index + 1)).color(self.theme.text_secondary))
+                        .on_hover_text(folder);
+                    ui.label(RichText::new(format!("{} immagini, {}", files, Overview::size_label(bytes))).strong().color(self.theme.text_primary));
+                    ui.end_row();
+                }
+            });
+            
+            ui.add_space(16.0);
+            ui.label(RichText::new("Differenza di punteggio tra i due file").strong().color(self.theme.text_primary));
+            ui.add_space(4.0);
+            let largest = overview.score_differences.iter().copied().max().unwrap_or(0).max(1);
+            egui::Grid::new("overview_differences").num_columns(3).spacing([12.0, 4.0]).show(ui, |ui| {
+                for (index, &count) in overview.score_differences.iter().enumerate() {
+                    ui.label(RichText::new(Overview::score_difference_label(index)).color(self.theme.text_secondary));
+                    let (rect, _) = ui.allocate_exact_size(Vec2::new(BAR_WIDTH, 14.0), egui::Sense::hover());
+                    let filled = egui::Rect::from_min_size(rect.min, Vec2::new(BAR_WIDTH * count as f32 / largest as f32, rect.height()));
+                    ui.painter().rect_filled(rect, 3.0, self.theme.card_bg);
+                    ui.painter().rect_filled(filled, 3.0, self.theme.accent_blue);
+                    ui.label(RichText::new(count.to_string()).color(self.theme.text_primary));
+                    ui.end_row();
+                }
+            });
+            
+            ui.add_space(16.0);
+            let advice = overview.advice();
+            let color = match advice {
+                Advice::Batch => self.theme.accent_green,
+                Advice::Mixed => self.theme.warning,
+                Advice::PairByPair => self.theme.accent_orange,
+            };
+            ui.label(RichText::new(format!("{} {} coppie su {} ({:.0}%) decise senza dubbi: {}",
+                regular::LIGHTBULB,
+                overview.clear_decisions(),
+                overview.pairs - overview.unreadable,
+                overview.clear_percentage(),
+                advice.label())).color(color));
+        });
+    }
+    
+    fn action_button(&self, ui: &mut egui::Ui, text: &str, color: Color32) -> bool {
+        let button = egui::Button::new(RichText::new(text).size(16.0))
+            .min_size(Vec2::new(180.0, 40.0))
+            .fill(color.gamma_multiply(0.2))
+            .stroke(Stroke::new(1.0, color));
+        ui.add(button).clicked()
+    }
+}
\ No newline at end of file
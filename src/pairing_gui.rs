@@ -0,0 +1,289 @@
+use crate::file_manager::FileManager;
+use crate::theme::{Theme, ThemeKind};
+use anyhow::Result;
+use eframe::egui;
+use egui::{Color32, CornerRadius, FontId, Frame, Margin, RichText, Stroke, Vec2};
+use egui_phosphor::regular;
+use std::path::PathBuf;
+
+
+// Un file non abbinato con l'etichetta da mostrare
+struct UnmatchedFile {
+    path: PathBuf,
+    label: String,
+}
+
+pub struct ManualPairingApp {
+    unmatched1: Vec<UnmatchedFile>,
+    unmatched2: Vec<UnmatchedFile>,
+    // Coppie create: (file cartella 1, file cartella 2)
+    pairs: Vec<(UnmatchedFile, UnmatchedFile)>,
+    selected1: Option<usize>,
+    selected2: Option<usize>,
+    confirmed: bool,
+    skipped: bool,
+    theme: Theme,
+}
+
+impl ManualPairingApp {
+    pub fn new(unmatched1: Vec<PathBuf>, unmatched2: Vec<PathBuf>, file_manager: &FileManager) -> Self {
+        let to_entries = |paths: Vec<PathBuf>| -> Vec<UnmatchedFile> {
+            paths.into_iter()
+                .map(|path| UnmatchedFile {
+                    label: file_manager.get_relative_path(&path),
+                    path,
+                })
+                .collect()
+        };
+        
+        ManualPairingApp {
+            unmatched1: to_entries(unmatched1),
+            unmatched2: to_entries(unmatched2),
+            pairs: Vec::new(),
+            selected1: None,
+            selected2: None,
+            confirmed: false,
+            skipped: false,
+            theme: Theme::from_kind(ThemeKind::load()),
+        }
+    }
+    
+    /// Mostra la schermata di abbinamento e restituisce le coppie create.
+    /// Se l'utente salta o chiude la finestra restituisce una lista vuota.
+    pub fn run(mut self) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let options = eframe::NativeOptions {
+            viewport: egui::ViewportBuilder::default()
+                .with_inner_size([1200.0, 800.0])
+                .with_title("PhotoScope Pro - Abbinamento manuale"),
+            ..Default::default()
+        };
+        
+        let result = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let result_clone = result.clone();
+        
+        crate::monitor::run_simple_native("PhotoScope Pairing", options, move |ctx, _frame| {
+            self.setup_custom_style(ctx, &self.theme);
+            self.update(ctx);
+            
+            if self.confirmed {
+                *result_clone.lock().unwrap() = self.pairs.iter()
+                    .map(|(a, b)| (a.path.clone(), b.path.clone()))
+                    .collect();
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            } else if self.skipped {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+        }).map_err(|e| anyhow::anyhow!("GUI error: {}", e))?;
+        
+        let pairs = result.lock().unwrap().clone();
+        Ok(pairs)
+    }
+    
+    fn setup_custom_style(&self, ctx: &egui::Context, theme: &Theme) {
+        // Initialize Phosphor fonts
+        let mut fonts = egui::FontDefinitions::default();
+        egui_phosphor::add_to_fonts(&mut fonts, egui_phosphor::Variant::Regular);
+        ctx.set_fonts(fonts);
+        
+        let mut style = (*ctx.style()).clone();
+        
+        // Font sizes
+        style.text_styles.insert(
+            egui::TextStyle::Heading,
+            FontId::proportional(24.0),
+        );
+        style.text_styles.insert(
+            egui::TextStyle::Body,
+            FontId::proportional(16.0),
+        );
+        style.text_styles.insert(
+            egui::TextStyle::Button,
+            FontId::proportional(18.0),
+        );
+        
+        // Spacing
+        style.spacing.item_spacing = Vec2::new(12.0, 8.0);
+        style.spacing.button_padding = Vec2::new(16.0, 10.0);
+        
+        // Visual tweaks
+        style.visuals = theme.visuals();
+        
+        ctx.set_style(style);
+    }
+    
+    fn update(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::top("pairing_header").show(ctx, |ui| {
+            ui.add_space(8.0);
+            ui.heading(RichText::new(format!("{} Abbinamento manuale", regular::LINK)).color(self.theme.text_primary));
+            ui.label(RichText::new("Trascina un file della cartella 1 su uno della cartella 2, oppure clicca un file per lato")
+                .size(14.0)
+                .color(self.theme.text_secondary));
+            ui.add_space(8.0);
+        });
+        
+        egui::TopBottomPanel::bottom("pairing_actions").show(ctx, |ui| {
+            ui.add_space(8.0);
+            self.show_pairs(ui);
+            ui.add_space(8.0);
+            self.show_actions(ui);
+            ui.add_space(8.0);
+        });
+        
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let column_width = (ui.available_width() - 20.0) / 2.0;
+            
+            ui.horizontal_top(|ui| {
+                ui.vertical(|ui| {
+                    ui.set_width(column_width);
+                    self.show_column(ui, 1);
+                });
+                
+                ui.add_space(20.0);
+                
+                ui.vertical(|ui| {
+                    ui.set_width(column_width);
+                    self.show_column(ui, 2);
+                });
+            });
+        });
+        
+        // Click su entrambi i lati: crea la coppia
+        if let (Some(i1), Some(i2)) = (self.selected1, self.selected2) {
+            self.create_pair(i1, i2);
+        }
+    }
+    
+    fn show_column(&mut self, ui: &mut egui::Ui, num: u8) {
+        let color = if num == 1 { self.theme.accent_blue } else { self.theme.accent_orange };
+        let count = if num == 1 { self.unmatched1.len() } else { self.unmatched2.len() };
+        
+        ui.label(RichText::new(format!("{} Cartella {} ({} non abbinati)", regular::FOLDER, num, count))
+            .size(18.0)
+            .color(color)
+            .strong());
+        ui.add_space(4.0);
+        
+        let mut dropped: Option<(usize, usize)> = None;
+        
+        egui::ScrollArea::vertical()
+            .id_salt(("unmatched_column", num))
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                let files = if num == 1 { &self.unmatched1 } else { &self.unmatched2 };
+                let selected = if num == 1 { self.selected1 } else { self.selected2 };
+                
+                for (index, file) in files.iter().enumerate() {
+                    let text = RichText::new(&file.label).size(14.0).monospace();
+                    
+                    if num == 1 {
+                        // I file della cartella 1 si trascinano sui file della cartella 2
+                        let response = ui.dnd_drag_source(egui::Id::new(("pair_source", index)), index, |ui| {
+                            ui.selectable_label(selected == Some(index), text)
+                        });
+                        if response.inner.clicked() {
+                            self.selected1 = if selected == Some(index) { None } else { Some(index) };
+                        }
+                    } else {
+                        let response = ui.selectable_label(selected == Some(index), text);
+                        if let Some(source) = response.dnd_release_payload::<usize>() {
+                            dropped = Some((*source, index));
+                        } else if response.clicked() {
+                            self.selected2 = if selected == Some(index) { None } else { Some(index) };
+                        }
+                    }
+                }
+            });
+        
+        if let Some((i1, i2)) = dropped {
+            self.create_pair(i1, i2);
+        }
+    }
+    
+    fn show_pairs(&mut self, ui: &mut egui::Ui) {
+        ui.label(RichText::new(format!("{} Coppie create: {}", regular::LINK_SIMPLE, self.pairs.len()))
+            .size(16.0)
+            .color(self.theme.text_primary)
+            .strong());
+        
+        let mut to_remove = None;
+        
+        egui::ScrollArea::vertical()
+            .id_salt("manual_pairs")
+            .max_height(150.0)
+            .show(ui, |ui| {
+                for (index, (file1, file2)) in self.pairs.iter().enumerate() {
+                    Frame::NONE
+                        .fill(self.theme.card_bg)
+                        .corner_radius(CornerRadius::same(6))
+                        .inner_margin(Margin::symmetric(12, 4))
+                        .show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label(RichText::new(&file1.label).size(13.0).color(self.theme.accent_blue));
+                                ui.label(RichText::new(regular::ARROWS_LEFT_RIGHT).color(self.theme.text_secondary));
+                                ui.label(RichText::new(&file2.label).size(13.0).color(self.theme.accent_orange));
+                                
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    if ui.small_button(RichText::new(regular::X).color(self.theme.danger_red)).clicked() {
+                                        to_remove = Some(index);
+                                    }
+                                });
+                            });
+                        });
+                }
+            });
+        
+        // Un abbinamento rimosso rimette i file nelle rispettive liste
+        if let Some(index) = to_remove {
+            let (file1, file2) = self.pairs.remove(index);
+            self.unmatched1.push(file1);
+            self.unmatched2.push(file2);
+            self.unmatched1.sort_by(|a, b| a.path.cmp(&b.path));
+            self.unmatched2.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+    }
+    
+    fn show_actions(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let has_pairs = !self.pairs.is_empty();
+            
+            ui.add_enabled_ui(has_pairs, |ui| {
+                let btn_color = if has_pairs { self.theme.accent_green } else { self.theme.disabled };
+                if self.modern_button(ui, &format!("{} Conferma {} coppie", regular::CHECK, self.pairs.len()), btn_color, Vec2::new(200.0, 40.0)) {
+                    self.confirmed = true;
+                }
+            });
+            
+            if self.modern_button(ui, &format!("{} Salta", regular::ARROW_RIGHT), self.theme.text_secondary, Vec2::new(150.0, 40.0)) {
+                self.skipped = true;
+            }
+        });
+    }
+    
+    fn create_pair(&mut self, index1: usize, index2: usize) {
+        if index1 >= self.unmatched1.len() || index2 >= self.unmatched2.len() {
+            return;
+        }
+        
+        let file1 = self.unmatched1.remove(index1);
+        let file2 = self.unmatched2.remove(index2);
+        self.pairs.push((file1, file2));
+        
+        self.selected1 = None;
+        self.selected2 = None;
+    }
+    
+    fn modern_button(&self, ui: &mut egui::Ui, text: &str, color: Color32, size: Vec2) -> bool {
+        let button = egui::Button::new(RichText::new(text).size(16.0))
+            .min_size(size)
+            .fill(color.gamma_multiply(0.2))
+            .stroke(Stroke::new(1.0, color));
+        
+        let response = ui.add(button);
+        
+        if response.hovered() {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+        }
+        
+        response.clicked()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,186 @@
+use crate::image_analyzer::{ImageAnalysis, ScoringWeights};
+use crate::session::{self, PairAnnotation, PairDecision};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const REPORT_NAME: &str = "photoscope_report";
+pub const ANALYSIS_NAME: &str = "photoscope_analysis";
+
+/// Una riga del report: la coppia, la decisione presa e le eventuali note.
+#[derive(Debug, Serialize)]
+pub struct ReportEntry {
+    // Numero della coppia come mostrato nella GUI (da 1)
+    pub pair: usize,
+    pub file1: PathBuf,
+    pub file2: PathBuf,
+    pub decision: &'static str,
+    pub output: Vec<PathBuf>,
+    pub note: String,
+    pub tags: Vec<String>,
+    // Come è stata valutata la compressione di file1 e file2 (None se la coppia non è stata analizzata)
+    pub compression: Option<[String; 2]>,
+}
+
+impl ReportEntry {
+    pub fn from_session(
+        pairs: &[(PathBuf, PathBuf)],
+        decisions: &[Option<PairDecision>],
+        annotations: &BTreeMap<usize, PairAnnotation>,
+        compression: &BTreeMap<usize, [String; 2]>,
+    ) -> Vec<ReportEntry> {
+        pairs.iter()
+            .enumerate()
+            .map(|(index, (file1, file2))| {
+                let decision = decisions.get(index).and_then(|d| d.as_ref());
+                let output = match decision {
+                    Some(PairDecision::Selected(copied)) => copied.iter().cloned().collect(),
+                    Some(PairDecision::KeptBoth(copied)) => copied.clone(),
+                    _ => Vec::new(),
+                };
+                let annotation = annotations.get(&index).cloned().unwrap_or_default();
+                
+                ReportEntry {
+                    pair: index + 1,
+                    file1: file1.clone(),
+                    file2: file2.clone(),
+                    decision: decision.map(PairDecision::label).unwrap_or("da decidere"),
+                    output,
+                    note: annotation.note,
+                    tags: annotation.tags,
+                    compression: compression.get(&index).cloned(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Una riga del report di inventario (--analyze-only): un file con la sua analisi o l'errore.
+#[derive(Debug, Serialize)]
+pub struct AnalysisEntry {
+    // Numero della coppia (da 1); assente quando si analizza una sola cartella
+    pub pair: Option<usize>,
+    pub file: PathBuf,
+    // Nelle coppie, vero per il file che la modalità batch sceglierebbe
+    pub preferred: Option<bool>,
+    // Come è stata valutata la compressione, come nel report delle decisioni
+    pub compression: Option<String>,
+    pub analysis: Option<ImageAnalysis>,
+    pub error: Option<String>,
+}
+
+impl AnalysisEntry {
+    pub fn new(pair: Option<usize>, file: &Path, preferred: Option<bool>, result: Result<ImageAnalysis>, weights: &ScoringWeights) -> AnalysisEntry {
+        let (analysis, error) = match result {
+            Ok(analysis) => (Some(analysis), None),
+            Err(e) => (None, Some(format!("{:#}", e))),
+        };
+        AnalysisEntry {
+            pair,
+            file: file.to_path_buf(),
+            preferred,
+            compression: analysis.as_ref().map(|analysis| analysis.compression_label(weights)),
+            analysis,
+            error,
+        }
+    }
+}
+
+/// Percorso del report CSV, il formato più comodo da aprire in un foglio di calcolo.
+pub fn csv_path(output_folder: &Path) -> PathBuf {
+    output_folder.join(format!("{}.csv", REPORT_NAME))
+}
+
+/// Scrive il report in formato CSV e JSON nella cartella di output. Viene riscritto durante
+/// la sessione: ogni file è sostituito per intero, senza passare da una versione troncata.
+pub fn write_report(output_folder: &Path, entries: &[ReportEntry]) -> Result<()> {
+    let json_path = output_folder.join(format!("{}.json", REPORT_NAME));
+    session::write_atomic(&json_path, &serde_json::to_string_pretty(entries)?)
+        .with_context(|| format!("Failed to write report {:?}", json_path))?;
+    
+    let mut csv = String::from("coppia,file1,file2,decisione,output,nota,tag,compressione1,compressione2\n");
+    for entry in entries {
+        let output: Vec<String> = entry.output.iter().map(|p| p.display().to_string()).collect();
+        let [compression1, compression2] = entry.compression.clone().unwrap_or_default();
+        let fields = [
+            entry.pair.to_string(),
+            entry.file1.display().to_string(),
+            entry.file2.display().to_string(),
+            entry.decision.to_string(),
+            output.join(";"),
+            entry.note.clone(),
+            entry.tags.join(";"),
+            compression1,
+            compression2,
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    
+    let csv_path = csv_path(output_folder);
+    session::write_atomic(&csv_path, &csv)
+        .with_context(|| format!("Failed to write report {:?}", csv_path))?;
+    
+    Ok(())
+}
+
+/// Scrive il report di inventario in CSV e JSON nella cartella di output. Il JSON contiene
+/// l'analisi completa; il CSV le misure principali, una riga per file.
+pub fn write_analysis_report(output_folder: &Path, entries: &[AnalysisEntry]) -> Result<()> {
+    let json_path = output_folder.join(format!("{}.json", ANALYSIS_NAME));
+    fs::write(&json_path, serde_json::to_string_pretty(entries)?)
+        .with_context(|| format!("Failed to write report {:?}", json_path))?;
+    
+    let mut csv = String::from("coppia,file,consigliato,punteggio,larghezza,altezza,megapixel,peso_mb,senza_perdite,nitidezza,rumore,blocchi,ingrandita,dimensioni_exif,fotocamera,profilo_colore,compressione,sha256,errore\n");
+    for entry in entries {
+        let mut fields = vec![
+            entry.pair.map(|pair| pair.to_string()).unwrap_or_default(),
+            entry.file.display().to_string(),
+            entry.preferred.map(|preferred| if preferred { "sì" } else { "no" }.to_string()).unwrap_or_default(),
+        ];
+        match &entry.analysis {
+            Some(analysis) => fields.extend([
+                analysis.quality_score.to_string(),
+                analysis.width.to_string(),
+                analysis.height.to_string(),
+                format!("{:.2}", analysis.megapixels),
+                format!("{:.2}", analysis.file_size_mb),
+                if analysis.is_lossless { "sì" } else { "no" }.to_string(),
+                format!("{:.1}", analysis.sharpness),
+                format!("{:.2}", analysis.noise_level),
+                format!("{:.2}", analysis.blockiness),
+                if analysis.upscale_suspected { "sospetta" } else { "" }.to_string(),
+                // Solo se diverse dalle reali
+                analysis.exif_dimensions_mismatch().map(|(width, height)| format!("{}x{}", width, height)).unwrap_or_default(),
+                analysis.camera.clone().unwrap_or_default(),
+                analysis.color_profile.clone().unwrap_or_default(),
+                entry.compression.clone().unwrap_or_default(),
+                analysis.hash.clone(),
+            ]),
+            None => fields.extend(std::iter::repeat_n(String::new(), 15)),
+        }
+        fields.push(entry.error.clone().unwrap_or_default());
+        
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    
+    let csv_path = output_folder.join(format!("{}.csv", ANALYSIS_NAME));
+    fs::write(&csv_path, csv)
+        .with_context(|| format!("Failed to write report {:?}", csv_path))?;
+    
+    Ok(())
+}
+
+// Virgolette solo quando servono, raddoppiando quelle interne
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
\ No newline at end of file
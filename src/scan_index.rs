@@ -0,0 +1,346 @@
+use crate::theme::APP_ID;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+// Indici delle scansioni, uno per cartella scandita, nella cartella dati di eframe accanto al tema
+const INDEX_DIR: &str = "scan_index";
+// Indici unici di tutte le cartelle delle versioni precedenti, da cancellare
+const LEGACY_INDEX_FILES: [&str; 2] = ["scan_index.json", "scan_index_v2.json"];
+// Gli indici non usati da così tanto tempo vengono cancellati, come quelli delle cartelle sparite
+const INDEX_MAX_AGE: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+// Una cartella modificata poco prima della scansione precedente può essere cambiata ancora
+// senza che la data cambi: FAT la registra a 2 secondi e le condivisioni SMB la tengono in
+// cache per qualche secondo. In quel margine l'elenco salvato non vale e la cartella si rilegge
+const MODIFIED_MARGIN: Duration = Duration::from_secs(10);
+
+// Segue i collegamenti simbolici (--follow-symlinks); altrimenti si elencano solo quelli a file
+static FOLLOW_SYMLINKS: AtomicBool = AtomicBool::new(false);
+// Gli indici superati si cercano una volta sola per esecuzione
+static PRUNED: AtomicBool = AtomicBool::new(false);
+
+// File con dimensione e data di modifica al momento della lettura della cartella
+#[derive(Serialize, Deserialize)]
+struct CachedFile {
+    name: String,
+    size: u64,
+    modified: SystemTime,
+}
+
+// Contenuto di una cartella alla data di modifica indicata. Aggiungere, togliere o rinominare
+// un file cambia la data della cartella, quindi finché coincide l'elenco è ancora valido
+#[derive(Serialize, Deserialize)]
+struct CachedDir {
+    modified: SystemTime,
+    files: Vec<CachedFile>,
+    subdirs: Vec<String>,
+    // Collegamenti simbolici, a file o cartelle: dove puntano si decide a ogni scansione
+    links: Vec<String>,
+}
+
+// Contenuto di una cartella letto dal disco o dall'indice; i nomi possono non essere UTF-8
+struct DirListing {
+    modified: SystemTime,
+    files: Vec<(OsString, u64, SystemTime)>,
+    subdirs: Vec<OsString>,
+    links: Vec<OsString>,
+}
+
+// Indice di una cartella scandita. Chiave: percorso di ogni sottocartella relativo a `root`
+// ("" per la cartella stessa)
+#[derive(Default, Serialize, Deserialize)]
+struct ScanIndex {
+    root: PathBuf,
+    // Inizio della scansione che ha scritto l'indice
+    scanned: Option<SystemTime>,
+    dirs: HashMap<String, CachedDir>,
+}
+
+// Basta la cartella indicizzata per decidere se l'indice serve ancora
+#[derive(Deserialize)]
+struct IndexRoot {
+    root: PathBuf,
+}
+
+#[derive(Default)]
+struct ScanStats {
+    cached: usize,
+    read: usize,
+    files: usize,
+    // Collegamenti ignorati (non seguiti, interrotti o che riportano a un percorso già visto)
+    skipped_links: usize,
+}
+
+// Stato di una scansione: cartelle visitate (per ripulire l'indice), percorsi reali delle
+// cartelle e dei file già elencati (per non entrare due volte nella stessa cartella seguendo
+// un collegamento, anche ciclico, né elencare due volte lo stesso file). I percorsi reali si
+// ricavano da quello della cartella: si risolvono solo i collegamenti
+struct Scan<'a> {
+    // Le cartelle con una data precedente valgono come nell'indice
+    trusted_before: Option<SystemTime>,
+    visited: HashSet<String>,
+    real_dirs: HashSet<PathBuf>,
+    real_files: HashSet<PathBuf>,
+    stats: ScanStats,
+    on_file: &'a mut dyn FnMut(PathBuf),
+}
+
+/// Decide se le scansioni successive seguono i collegamenti simbolici.
+pub fn set_follow_symlinks(follow: bool) {
+    FOLLOW_SYMLINKS.store(follow, Ordering::Relaxed);
+}
+
+/// Passa a `on_file`, man mano che li trova, tutti i file (non cartelle) contenuti in `folder`
+/// e nelle sue sottocartelle, in ordine di cartella e di nome. Le cartelle non modificate
+/// dall'ultima scansione non vengono rilette; delle altre si rileggono dimensione e data dei file.
+/// I collegamenti simbolici a file sono elencati come file; quelli a cartelle vengono ignorati,
+/// a meno di `set_follow_symlinks(true)`: in quel caso si seguono saltando i cicli, e un file
+/// raggiunto per più strade compare una volta sola.
+pub fn list_files(folder: &Path, on_file: &mut dyn FnMut(PathBuf)) {
+    let root = std::path::absolute(folder).unwrap_or_else(|_| folder.to_path_buf());
+    let path = index_path(&root);
+    let mut index = path.as_deref()
+        .map(load)
+        .filter(|index| index.root == root)
+        .unwrap_or_default();
+    let started = SystemTime::now();
+    
+    let mut scan = Scan {
+        trusted_before: index.scanned.and_then(|scanned| scanned.checked_sub(MODIFIED_MARGIN)),
+        visited: HashSet::new(),
+        real_dirs: HashSet::new(),
+        real_files: HashSet::new(),
+        stats: ScanStats::default(),
+        on_file,
+    };
+    let real = fs::canonicalize(folder).ok();
+    if let Some(real) = &real {
+        scan.real_dirs.insert(real.clone());
+    }
+    scan_dir(folder, real.as_deref(), Path::new(""), &mut index, &mut scan);
+    
+    // Le sottocartelle non più raggiunte sono state cancellate o spostate
+    index.dirs.retain(|dir, _| scan.visited.contains(dir));
+    index.root = root;
+    index.scanned = Some(started);
+    
+    let stats = &scan.stats;
+    debug!("Scansione di {:?}: {} cartelle dall'indice, {} rilette, {} file, {} collegamenti ignorati",
+        folder, stats.cached, stats.read, stats.files, stats.skipped_links);
+    if let Some(path) = path {
+        save(&path, &index);
+        if !PRUNED.swap(true, Ordering::Relaxed)
+            && let Some(dir) = path.parent()
+        {
+            prune(dir, &path);
+            if let Some(data_dir) = dir.parent() {
+                for name in LEGACY_INDEX_FILES {
+                    let _ = fs::remove_file(data_dir.join(name));
+                }
+            }
+        }
+    }
+}
+
+// `real_dir` è il percorso reale di `dir`, se noto
+fn scan_dir(dir: &Path, real_dir: Option<&Path>, relative: &Path, index: &mut ScanIndex, scan: &mut Scan) {
+    // La data va letta prima dell'elenco: una modifica durante la lettura invalida l'indice
+    let Ok(modified) = fs::metadata(dir).and_then(|metadata| metadata.modified()) else {
+        return;
+    };
+    
+    // I nomi non UTF-8 non si possono salvare nell'indice: la cartella verrà riletta
+    let key = relative.to_str().map(str::to_string);
+    let trusted = scan.trusted_before.is_some_and(|before| modified < before);
+    let cached = key.as_ref()
+        .and_then(|key| index.dirs.remove(key))
+        .filter(|cached| trusted && cached.modified == modified);
+    let listing = match cached {
+        Some(cached) => {
+            scan.stats.cached += 1;
+            DirListing::from(cached)
+        }
+        None => {
+            scan.stats.read += 1;
+            let Some(listing) = read_dir(dir, modified) else {
+                return;
+            };
+            listing
+        }
+    };
+    
+    let DirListing { files, subdirs, links, .. } = &listing;
+    let follow = FOLLOW_SYMLINKS.load(Ordering::Relaxed);
+    for (name, _, _) in files {
+        let real = real_dir.map(|real_dir| real_dir.join(name));
+        add_file(dir.join(name), follow.then_some(real).flatten(), scan);
+    }
+    
+    let mut subdirs: Vec<(OsString, Option<PathBuf>)> = subdirs.iter()
+        .map(|name| (name.clone(), real_dir.map(|real_dir| real_dir.join(name))))
+        .collect();
+    for name in links {
+        let link = dir.join(name);
+        // fs::metadata segue il collegamento: un collegamento interrotto dà errore
+        match fs::metadata(&link) {
+            Ok(target) if follow && target.is_dir() => subdirs.push((name.clone(), fs::canonicalize(&link).ok())),
+            Ok(target) if target.is_file() => {
+                let real = if follow { fs::canonicalize(&link).ok() } else { None };
+                add_file(link, real, scan);
+            }
+            _ => {
+                debug!("Collegamento simbolico ignorato: {:?}", link);
+                scan.stats.skipped_links += 1;
+            }
+        }
+    }
+    subdirs.sort();
+    
+    if let Some(key) = key
+        && let Some(cached) = listing.into_cached()
+    {
+        scan.visited.insert(key.clone());
+        index.dirs.insert(key, cached);
+    }
+    
+    for (name, real) in subdirs {
+        let subdir = dir.join(&name);
+        let real = real.or_else(|| if follow { fs::canonicalize(&subdir).ok() } else { None });
+        // Una cartella già visitata (collegamento ciclico o doppio) non viene riletta
+        if follow && !real.clone().is_some_and(|real| scan.real_dirs.insert(real)) {
+            debug!("Cartella già visitata o irraggiungibile: {:?}", subdir);
+            scan.stats.skipped_links += 1;
+            continue;
+        }
+        scan_dir(&subdir, real.as_deref(), &relative.join(&name), index, scan);
+    }
+}
+
+// Seguendo i collegamenti lo stesso file può essere raggiunto da più percorsi: vale il primo.
+// `real` è il percorso reale del file, presente solo quando si seguono i collegamenti
+fn add_file(path: PathBuf, real: Option<PathBuf>, scan: &mut Scan) {
+    if let Some(real) = real
+        && !scan.real_files.insert(real)
+    {
+        debug!("File già elencato con un altro percorso: {:?}", path);
+        return;
+    }
+    scan.stats.files += 1;
+    (scan.on_file)(path);
+}
+
+// I collegamenti simbolici restano a parte, senza stabilire dove puntano
+fn read_dir(dir: &Path, modified: SystemTime) -> Option<DirListing> {
+    let mut files = Vec::new();
+    let mut subdirs = Vec::new();
+    let mut links = Vec::new();
+    for entry in fs::read_dir(dir).ok()?.filter_map(|e| e.ok()) {
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_symlink() => links.push(entry.file_name()),
+            Ok(file_type) if file_type.is_dir() => subdirs.push(entry.file_name()),
+            Ok(_) => {
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                let file_modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                files.push((entry.file_name(), metadata.len(), file_modified));
+            }
+            Err(_) => {}
+        }
+    }
+    files.sort_by(|(name1, _, _), (name2, _, _)| name1.cmp(name2));
+    subdirs.sort();
+    links.sort();
+    Some(DirListing { modified, files, subdirs, links })
+}
+
+impl From<CachedDir> for DirListing {
+    fn from(cached: CachedDir) -> Self {
+        let to_names = |names: Vec<String>| names.into_iter().map(OsString::from).collect();
+        DirListing {
+            modified: cached.modified,
+            files: cached.files.into_iter()
+                .map(|file| (OsString::from(file.name), file.size, file.modified))
+                .collect(),
+            subdirs: to_names(cached.subdirs),
+            links: to_names(cached.links),
+        }
+    }
+}
+
+impl DirListing {
+    // None se qualche nome non è UTF-8
+    fn into_cached(self) -> Option<CachedDir> {
+        let to_strings = |names: Vec<OsString>| names.into_iter().map(|name| name.into_string().ok()).collect::<Option<Vec<_>>>();
+        let files = self.files.into_iter()
+            .map(|(name, size, modified)| Some(CachedFile { name: name.into_string().ok()?, size, modified }))
+            .collect::<Option<Vec<_>>>()?;
+        Some(CachedDir {
+            modified: self.modified,
+            files,
+            subdirs: to_strings(self.subdirs)?,
+            links: to_strings(self.links)?,
+        })
+    }
+}
+
+fn load(path: &Path) -> ScanIndex {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// Scrittura su file temporaneo e rinomina: un'interruzione non lascia un indice troncato.
+// Il nome temporaneo è per thread, perché la stessa cartella può essere scandita due volte insieme
+fn save(path: &Path, index: &ScanIndex) {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let temp = path.with_extension(format!("{:?}.tmp", std::thread::current().id()));
+    let result = serde_json::to_string(index)
+        .map_err(std::io::Error::from)
+        .and_then(|content| fs::write(&temp, content))
+        .and_then(|_| fs::rename(&temp, path));
+    if let Err(e) = result {
+        warn!("Impossibile salvare l'indice delle scansioni in {:?}: {}", path, e);
+        let _ = fs::remove_file(&temp);
+    }
+}
+
+// Cancella gli indici di cartelle che non esistono più o non scandite da INDEX_MAX_AGE
+fn prune(dir: &Path, current: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|entry| entry.path()) {
+        if path == current {
+            continue;
+        }
+        let age = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok());
+        let stale = age.is_none_or(|age| age > INDEX_MAX_AGE)
+            || fs::read_to_string(&path).ok()
+                .and_then(|content| serde_json::from_str::<IndexRoot>(&content).ok())
+                .is_none_or(|index| !index.root.is_dir());
+        if stale {
+            debug!("Indice delle scansioni superato: {:?}", path);
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+// Un file per cartella, dal nome derivato dal suo percorso assoluto
+fn index_path(root: &Path) -> Option<PathBuf> {
+    let hash = Sha256::digest(root.as_os_str().as_encoded_bytes());
+    let name: String = hash.iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+    eframe::storage_dir(APP_ID).map(|dir| dir.join(INDEX_DIR).join(format!("{}.json", name)))
+}
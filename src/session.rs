@@ -0,0 +1,184 @@
+use crate::file_manager::FileManager;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const SESSION_FILE: &str = ".photoscope_session.json";
+
+// Decisione presa per una coppia, con i file effettivamente copiati in output
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum PairDecision {
+    Skipped,
+    Selected(Option<PathBuf>),
+    KeptBoth(Vec<PathBuf>),
+    // Deduplicazione: il file scelto passa al confronto successivo del gruppo, senza copia
+    Advanced(PathBuf),
+}
+
+impl PairDecision {
+    /// Nome della decisione come compare nel report e nella revisione.
+    pub fn label(&self) -> &'static str {
+        match self {
+            PairDecision::Selected(_) => "selezionata",
+            PairDecision::KeptBoth(_) => "entrambe",
+            PairDecision::Skipped => "saltata",
+            PairDecision::Advanced(_) => "avanza",
+        }
+    }
+}
+
+// Nota libera e tag rapidi associati a una coppia
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PairAnnotation {
+    pub note: String,
+    pub tags: Vec<String>,
+}
+
+impl PairAnnotation {
+    pub fn is_empty(&self) -> bool {
+        self.note.is_empty() && self.tags.is_empty()
+    }
+}
+
+/// Frequenza del salvataggio automatico di sessione e report dopo le decisioni
+/// (--autosave-every, --autosave-interval). Note, salti, annullamenti e uscita salvano subito.
+#[derive(Clone, Copy, Debug)]
+pub struct AutosavePolicy {
+    // Decisioni dopo cui si salva; 0 = solo a tempo
+    pub decisions: usize,
+    // Tempo massimo con decisioni non ancora salvate
+    pub interval: Option<Duration>,
+}
+
+impl Default for AutosavePolicy {
+    fn default() -> Self {
+        AutosavePolicy { decisions: 1, interval: None }
+    }
+}
+
+impl AutosavePolicy {
+    /// Vero se è ora di salvare, con `unsaved` decisioni prese dall'ultimo salvataggio,
+    /// avvenuto `elapsed` fa.
+    pub fn is_due(&self, unsaved: usize, elapsed: Duration) -> bool {
+        unsaved > 0
+            && ((self.decisions > 0 && unsaved >= self.decisions)
+                || self.interval.is_some_and(|interval| elapsed >= interval))
+    }
+}
+
+/// Stato di una sessione di confronto interrotta, salvato nella cartella di output
+/// per poter riprendere dalla coppia in cui ci si era fermati.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionState {
+    pub folder1: PathBuf,
+    pub folder2: PathBuf,
+    pub pairs: Vec<(PathBuf, PathBuf)>,
+    pub current_index: usize,
+    pub decisions: Vec<Option<PairDecision>>,
+    // Note e tag per indice di coppia
+    #[serde(default)]
+    pub annotations: BTreeMap<usize, PairAnnotation>,
+    // Coppie rimandate e non ancora decise, nell'ordine in cui verranno riproposte
+    #[serde(default)]
+    pub deferred: Vec<usize>,
+    // Valutazione della compressione delle coppie già mostrate, per il report
+    #[serde(default)]
+    pub compression: BTreeMap<usize, [String; 2]>,
+}
+
+impl SessionState {
+    pub fn new(
+        file_manager: &FileManager,
+        pairs: &[(PathBuf, PathBuf)],
+        current_index: usize,
+        decisions: &[Option<PairDecision>],
+        annotations: &BTreeMap<usize, PairAnnotation>,
+        deferred: &[usize],
+        compression: &BTreeMap<usize, [String; 2]>,
+    ) -> Self {
+        SessionState {
+            folder1: canonical(&file_manager.folder1),
+            folder2: canonical(&file_manager.folder2),
+            pairs: pairs.to_vec(),
+            current_index,
+            decisions: decisions.to_vec(),
+            annotations: annotations.clone(),
+            deferred: deferred.to_vec(),
+            compression: compression.clone(),
+        }
+    }
+    
+    /// Carica la sessione in corso solo se riguarda le stesse cartelle e le stesse coppie
+    /// e non era già terminata. Le cartelle e le coppie possono essere state invertite
+    /// durante la sessione: in quel caso vale l'ordine salvato.
+    pub fn load(file_manager: &FileManager, pairs: &[(PathBuf, PathBuf)]) -> Option<Self> {
+        let content = fs::read_to_string(Self::path(file_manager)).ok()?;
+        let session: SessionState = serde_json::from_str(&content).ok()?;
+        
+        let same_folders = session.folders_swapped(file_manager)
+            || (session.folder1 == canonical(&file_manager.folder1) && session.folder2 == canonical(&file_manager.folder2));
+        // La coppia corrente può essere la prima anche a sessione avanzata (rimandate, revisione)
+        let started = session.decisions.iter().any(Option::is_some) || !session.deferred.is_empty();
+        let in_progress = started && session.current_index < pairs.len();
+        let same_pairs = session.pairs.len() == pairs.len()
+            && session.pairs.iter().zip(pairs).all(|((a1, b1), (a2, b2))| {
+                (a1 == a2 && b1 == b2) || (a1 == b2 && b1 == a2)
+            });
+        
+        if same_folders && in_progress && same_pairs {
+            Some(session)
+        } else {
+            None
+        }
+    }
+    
+    /// Vero se la sessione è stata salvata dopo aver invertito le due cartelle
+    pub fn folders_swapped(&self, file_manager: &FileManager) -> bool {
+        self.folder1 == canonical(&file_manager.folder2) && self.folder2 == canonical(&file_manager.folder1)
+    }
+    
+    pub fn save(&self, file_manager: &FileManager) -> Result<()> {
+        let path = Self::path(file_manager);
+        let content = serde_json::to_string_pretty(self)?;
+        write_atomic(&path, &content)
+            .with_context(|| format!("Failed to write session file {:?}", path))?;
+        Ok(())
+    }
+    
+    /// Rimuove il file di sessione quando tutte le coppie sono state decise
+    pub fn clear(file_manager: &FileManager) {
+        let _ = fs::remove_file(Self::path(file_manager));
+    }
+    
+    fn path(file_manager: &FileManager) -> PathBuf {
+        file_manager.output_folder.join(SESSION_FILE)
+    }
+}
+
+/// Scrive in un file temporaneo accanto a `path` e lo rinomina: un crash o un disco pieno
+/// a metà scrittura lasciano la versione precedente intatta invece di un file troncato. Il
+/// contenuto arriva su disco prima della rinomina: dopo un'interruzione di corrente il file non
+/// può risultare rinominato ma vuoto.
+pub fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let result = fs::File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
\ No newline at end of file
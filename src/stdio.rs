@@ -0,0 +1,130 @@
+use crate::archive;
+use crate::file_manager::{Conversion, FileManager, MetadataMode, NameTemplate};
+use crate::image_analyzer::{self, ImageAnalysis, ScoringWeights};
+use anyhow::Result;
+use log::warn;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Comando letto da stdin, una riga JSON per comando.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum Command {
+    Analyze {
+        path: PathBuf,
+    },
+    Compare {
+        path1: PathBuf,
+        path2: PathBuf,
+    },
+    Copy {
+        path: PathBuf,
+        #[serde(default)]
+        metadata_source: Option<PathBuf>,
+        #[serde(default)]
+        rotation: u8,
+    },
+}
+
+/// Modalità senza GUI: legge comandi JSON da stdin e scrive un risultato JSON per riga su stdout.
+/// Ogni risposta è `{"ok": true, "result": ...}` oppure `{"ok": false, "error": "..."}`.
+pub fn run(weights: ScoringWeights, organize_by_date: bool, metadata_mode: MetadataMode, conversion: Option<Conversion>, name_template: Option<NameTemplate>) -> Result<()> {
+    let mut file_manager = FileManager::new(PathBuf::from("."), PathBuf::from("."))?;
+    file_manager.organize_by_date = organize_by_date;
+    file_manager.metadata_mode = metadata_mode;
+    file_manager.conversion = conversion;
+    file_manager.name_template = name_template;
+    
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        
+        let response = match serde_json::from_str::<Command>(&line) {
+            Ok(command) => match execute(command, &weights, &file_manager) {
+                Ok(result) => json!({ "ok": true, "result": result }),
+                Err(e) => json!({ "ok": false, "error": format!("{:#}", e) }),
+            },
+            Err(e) => json!({ "ok": false, "error": format!("Invalid command: {}", e) }),
+        };
+        
+        writeln!(stdout, "{}", response)?;
+        stdout.flush()?;
+    }
+    
+    Ok(())
+}
+
+/// Analizza tutte le immagini delle cartelle e stampa l'elenco delle analisi in JSON su stdout.
+/// I file che non si riescono ad analizzare vengono segnalati su stderr e saltati.
+pub fn dump_analysis(folders: &[PathBuf], weights: &ScoringWeights) -> Result<()> {
+    let mut analyses = Vec::new();
+    
+    for folder in folders {
+        if !folder.is_dir() && !archive::is_archive(folder) {
+            anyhow::bail!("Folder does not exist: {:?}", folder);
+        }
+        
+        let mut files = FileManager::collect_image_files(folder, None)?;
+        files.sort();
+        analyses.extend(files.iter().filter_map(|path| analyze_or_report(path, weights)));
+    }
+    
+    let mut stdout = std::io::stdout();
+    serde_json::to_writer_pretty(&mut stdout, &analyses)?;
+    writeln!(stdout)?;
+    
+    Ok(())
+}
+
+fn analyze_or_report(path: &Path, weights: &ScoringWeights) -> Option<ImageAnalysis> {
+    match ImageAnalysis::analyze_image(path, weights) {
+        Ok(analysis) => Some(analysis),
+        Err(e) => {
+            warn!("Errore analisi {:?}: {:#}", path, e);
+            None
+        }
+    }
+}
+
+fn execute(command: Command, weights: &ScoringWeights, file_manager: &FileManager) -> Result<Value> {
+    match command {
+        Command::Analyze { path } => {
+            let analysis = ImageAnalysis::analyze_image(&path, weights)?;
+            Ok(serde_json::to_value(analysis)?)
+        }
+        Command::Compare { path1, path2 } => {
+            let analysis1 = ImageAnalysis::analyze_image(&path1, weights)?;
+            let analysis2 = ImageAnalysis::analyze_image(&path2, weights)?;
+            let (image1, _) = image_analyzer::open_with_icc_profile(&path1)?;
+            let (image2, _) = image_analyzer::open_with_icc_profile(&path2)?;
+            
+            // A parità di punteggio vince la prima, come nella GUI
+            let winner = if analysis1.is_preferred_over(&analysis2, weights) { 1 } else { 2 };
+            
+            Ok(json!({
+                "winner": winner,
+                "pixel_identical": analysis1.is_pixel_identical(&analysis2),
+                "cropped": image_analyzer::detect_crop(&image1, &image2),
+                "camera_mismatch": analysis1.camera_mismatch(&analysis2).is_some(),
+                "aspect_ratio_mismatch": analysis1.aspect_ratio_mismatch(&analysis2).is_some(),
+                "exif_dimensions_mismatch": [
+                    analysis1.exif_dimensions_mismatch().is_some(),
+                    analysis2.exif_dimensions_mismatch().is_some(),
+                ],
+                "analysis1": analysis1,
+                "analysis2": analysis2,
+            }))
+        }
+        Command::Copy { path, metadata_source, rotation } => {
+            let output = file_manager.copy_to_output_with_metadata(&path, metadata_source.as_deref(), rotation)?;
+            Ok(json!({ "output": output }))
+        }
+    }
+}
\ No newline at end of file
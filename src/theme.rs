@@ -0,0 +1,179 @@
+use eframe::egui::{Color32, Stroke, Visuals};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+// Il tema scelto viene salvato nella cartella dati di eframe, accanto alla geometria delle finestre
+pub const APP_ID: &str = "PhotoScope Pro";
+const THEME_FILE: &str = "theme.json";
+
+/// Temi disponibili, selezionabili a runtime con T.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ThemeKind {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+}
+
+impl ThemeKind {
+    pub fn next(self) -> ThemeKind {
+        match self {
+            ThemeKind::Dark => ThemeKind::Light,
+            ThemeKind::Light => ThemeKind::HighContrast,
+            ThemeKind::HighContrast => ThemeKind::Dark,
+        }
+    }
+    
+    pub fn label(self) -> &'static str {
+        match self {
+            ThemeKind::Dark => "Scuro",
+            ThemeKind::Light => "Chiaro",
+            ThemeKind::HighContrast => "Alto contrasto",
+        }
+    }
+    
+    /// Tema scelto nell'ultima esecuzione; scuro se non è mai stato cambiato.
+    pub fn load() -> ThemeKind {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+    
+    pub fn save(self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        if let Ok(content) = serde_json::to_string(&self)
+            && let Err(e) = fs::write(&path, content)
+        {
+            warn!("Impossibile salvare il tema in {:?}: {}", path, e);
+        }
+    }
+    
+    fn path() -> Option<PathBuf> {
+        eframe::storage_dir(APP_ID).map(|dir| dir.join(THEME_FILE))
+    }
+}
+
+/// Palette dell'interfaccia, condivisa da tutte le finestre.
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    pub kind: ThemeKind,
+    pub bg: Color32,
+    pub card_bg: Color32,
+    pub card_hover: Color32,
+    pub accent_blue: Color32,
+    pub accent_green: Color32,
+    pub accent_orange: Color32,
+    pub danger_red: Color32,
+    pub warning: Color32,
+    pub text_primary: Color32,
+    pub text_secondary: Color32,
+    pub gold_star: Color32,
+    // Bordo delle card non evidenziate
+    pub border: Color32,
+    // Sfondo dietro le immagini e i percorsi
+    pub image_bg: Color32,
+    // Pulsanti disabilitati
+    pub disabled: Color32,
+}
+
+impl Theme {
+    pub const DARK: Theme = Theme {
+        kind: ThemeKind::Dark,
+        bg: Color32::from_rgb(24, 26, 31),
+        card_bg: Color32::from_rgb(32, 34, 41),
+        card_hover: Color32::from_rgb(38, 40, 48),
+        accent_blue: Color32::from_rgb(59, 130, 246),
+        accent_green: Color32::from_rgb(34, 197, 94),
+        accent_orange: Color32::from_rgb(251, 146, 60),
+        danger_red: Color32::from_rgb(239, 68, 68),
+        warning: Color32::from_rgb(251, 146, 60),
+        text_primary: Color32::from_rgb(229, 231, 235),
+        text_secondary: Color32::from_rgb(148, 163, 184),
+        gold_star: Color32::from_rgb(250, 204, 21),
+        border: Color32::from_gray(50),
+        image_bg: Color32::from_gray(20),
+        disabled: Color32::from_gray(80),
+    };
+    
+    pub const LIGHT: Theme = Theme {
+        kind: ThemeKind::Light,
+        bg: Color32::from_rgb(243, 244, 246),
+        card_bg: Color32::from_rgb(255, 255, 255),
+        card_hover: Color32::from_rgb(235, 238, 243),
+        accent_blue: Color32::from_rgb(37, 99, 235),
+        accent_green: Color32::from_rgb(22, 163, 74),
+        accent_orange: Color32::from_rgb(234, 88, 12),
+        danger_red: Color32::from_rgb(220, 38, 38),
+        warning: Color32::from_rgb(217, 119, 6),
+        text_primary: Color32::from_rgb(17, 24, 39),
+        text_secondary: Color32::from_rgb(75, 85, 99),
+        gold_star: Color32::from_rgb(161, 98, 7),
+        border: Color32::from_gray(209),
+        image_bg: Color32::from_gray(229),
+        disabled: Color32::from_gray(170),
+    };
+    
+    // Colori saturi su nero e bordi bianchi, per chi ha difficoltà a distinguere i toni scuri
+    pub const HIGH_CONTRAST: Theme = Theme {
+        kind: ThemeKind::HighContrast,
+        bg: Color32::BLACK,
+        card_bg: Color32::from_gray(8),
+        card_hover: Color32::from_gray(40),
+        accent_blue: Color32::from_rgb(96, 190, 255),
+        accent_green: Color32::from_rgb(0, 255, 128),
+        accent_orange: Color32::from_rgb(255, 170, 0),
+        danger_red: Color32::from_rgb(255, 90, 90),
+        warning: Color32::from_rgb(255, 210, 0),
+        text_primary: Color32::WHITE,
+        text_secondary: Color32::from_gray(230),
+        gold_star: Color32::from_rgb(255, 235, 0),
+        border: Color32::WHITE,
+        image_bg: Color32::BLACK,
+        disabled: Color32::from_gray(120),
+    };
+    
+    pub fn from_kind(kind: ThemeKind) -> Theme {
+        match kind {
+            ThemeKind::Dark => Theme::DARK,
+            ThemeKind::Light => Theme::LIGHT,
+            ThemeKind::HighContrast => Theme::HIGH_CONTRAST,
+        }
+    }
+    
+    /// Passa al tema successivo e lo ricorda per i prossimi avvii.
+    pub fn cycle(&mut self) {
+        *self = Theme::from_kind(self.kind.next());
+        self.kind.save();
+    }
+    
+    /// Visuals di egui con i colori del tema applicati a pannelli e widget.
+    pub fn visuals(&self) -> Visuals {
+        let mut visuals = if self.kind == ThemeKind::Light { Visuals::light() } else { Visuals::dark() };
+        visuals.window_fill = self.bg;
+        visuals.panel_fill = self.bg;
+        visuals.extreme_bg_color = self.card_bg;
+        visuals.widgets.noninteractive.bg_fill = self.card_bg;
+        visuals.widgets.inactive.bg_fill = self.card_bg;
+        visuals.widgets.hovered.bg_fill = self.card_hover;
+        visuals.widgets.active.bg_fill = self.accent_blue;
+        visuals.selection.bg_fill = self.accent_blue;
+        
+        if self.kind == ThemeKind::HighContrast {
+            visuals.override_text_color = Some(self.text_primary);
+            visuals.widgets.noninteractive.bg_stroke = Stroke::new(1.0, self.border);
+            visuals.widgets.inactive.bg_stroke = Stroke::new(1.0, self.border);
+            visuals.widgets.hovered.bg_stroke = Stroke::new(2.0, self.warning);
+            visuals.selection.stroke = Stroke::new(2.0, self.text_primary);
+        }
+        
+        visuals
+    }
+}
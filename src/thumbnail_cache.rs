@@ -0,0 +1,83 @@
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{DynamicImage, ImageFormat};
+use log::{debug, warn};
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+// Cartella dell'applicazione nella cache di sistema: il contenuto si può cancellare in ogni momento
+const CACHE_DIR: &str = "photoscope";
+const THUMBNAILS_DIR: &str = "thumbnails";
+
+/// Anteprima già ridimensionata di un file, cercata per hash del contenuto e lato massimo.
+/// Un file modificato ha un altro hash, quindi non si legge mai un'anteprima superata.
+pub fn load(hash: &str, size: u32) -> Option<DynamicImage> {
+    let path = entry_path(hash, size)?;
+    if !path.is_file() {
+        return None;
+    }
+    match image::open(&path) {
+        Ok(image) => {
+            debug!("Anteprima dalla cache: {:?}", path);
+            Some(image)
+        }
+        Err(e) => {
+            // Voce troncata o rovinata: verrà riscritta dopo la decodifica
+            debug!("Anteprima in cache non leggibile {:?}: {}", path, e);
+            let _ = fs::remove_file(&path);
+            None
+        }
+    }
+}
+
+/// Salva l'anteprima per le sessioni successive. PNG con compressione rapida: senza perdite,
+/// così confronti e SSIM danno lo stesso risultato con o senza cache.
+pub fn store(hash: &str, size: u32, preview: &DynamicImage) {
+    let Some(path) = entry_path(hash, size) else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    
+    // Scrittura su file temporaneo e rinomina: un'interruzione non lascia un PNG troncato
+    let temp = path.with_extension("tmp");
+    let result = File::create(&temp)
+        .map_err(image::ImageError::IoError)
+        .and_then(|file| {
+            let encoder = PngEncoder::new_with_quality(BufWriter::new(file), CompressionType::Fast, FilterType::Adaptive);
+            preview.write_with_encoder(encoder)
+        })
+        .and_then(|_| fs::rename(&temp, &path).map_err(image::ImageError::IoError));
+    if let Err(e) = result {
+        warn!("Impossibile salvare l'anteprima in cache {:?}: {}", path, e);
+        let _ = fs::remove_file(&temp);
+    }
+}
+
+fn entry_path(hash: &str, size: u32) -> Option<PathBuf> {
+    // Senza hash (analisi rapida) non c'è una chiave affidabile
+    if hash.is_empty() {
+        return None;
+    }
+    let name = format!("{}_{}.{}", hash, size, ImageFormat::Png.extensions_str()[0]);
+    // Sottocartelle per i primi due caratteri, per non avere decine di migliaia di file in una
+    Some(cache_dir()?.join(THUMBNAILS_DIR).join(hash.get(..2)?).join(name))
+}
+
+// Cartella cache della piattaforma: XDG_CACHE_HOME (o ~/.cache) su Linux, Library/Caches su macOS,
+// LOCALAPPDATA su Windows
+fn cache_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::home_dir().map(|home| home.join("Library").join("Caches"))
+    } else {
+        env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| env::home_dir().map(|home| home.join(".cache")))
+    };
+    base.map(|base| base.join(CACHE_DIR))
+}
\ No newline at end of file
@@ -0,0 +1,182 @@
+use log::debug;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Metadati di un file XMP affiancato all'immagine (Lightroom, Capture One, darktable...).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct XmpSidecar {
+    pub path: PathBuf,
+    // Stelle da 0 a 5, -1 per le foto scartate (xmp:Rating)
+    pub rating: Option<i8>,
+    // Etichetta colore, es. "Red" (xmp:Label)
+    pub label: Option<String>,
+    // Parole chiave (dc:subject)
+    pub keywords: Vec<String>,
+    // Cronologia delle modifiche (xmpMM:History), dalla più vecchia
+    pub history: Vec<String>,
+}
+
+impl XmpSidecar {
+    /// Sidecar dell'immagine, se esiste ed è leggibile. Si cercano sia `IMG_0001.xmp`
+    /// (Lightroom) sia `IMG_0001.CR2.xmp` (darktable).
+    pub fn read(image: &Path) -> Option<XmpSidecar> {
+        let path = sidecar_path(image)?;
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                debug!("Sidecar {:?} non leggibile: {}", path, e);
+                return None;
+            }
+        };
+        Some(XmpSidecar { path, ..parse(&content) })
+    }
+    
+    /// Valutazione per i confronti: le foto senza stelle contano 0, le scartate -1.
+    pub fn score(sidecar: Option<&XmpSidecar>) -> i8 {
+        sidecar.and_then(|sidecar| sidecar.rating).unwrap_or(0)
+    }
+    
+    /// Valutazione in forma leggibile: "★★★☆☆" oppure "Scartata".
+    pub fn rating_label(&self) -> Option<String> {
+        match self.rating? {
+            rating if rating < 0 => Some("Scartata".to_string()),
+            rating => Some(format!("{}{}", "★".repeat(rating as usize), "☆".repeat(5 - rating as usize))),
+        }
+    }
+}
+
+fn sidecar_path(image: &Path) -> Option<PathBuf> {
+    let name = image.file_name()?.to_str()?;
+    let stem = image.file_stem()?.to_str()?;
+    [stem, name].iter()
+        .flat_map(|base| ["xmp", "XMP"].map(|extension| image.with_file_name(format!("{}.{}", base, extension))))
+        .find(|path| path.is_file())
+}
+
+// Evento della cronologia, scritto come attributi di rdf:li oppure come elementi figli
+#[derive(Default)]
+struct HistoryEvent {
+    action: Option<String>,
+    when: Option<String>,
+    software: Option<String>,
+}
+
+impl HistoryEvent {
+    fn from_attributes(element: &BytesStart) -> HistoryEvent {
+        let mut event = HistoryEvent::default();
+        for (name, value) in attributes(element) {
+            event.set(&name, value);
+        }
+        event
+    }
+    
+    fn set(&mut self, name: &[u8], value: String) {
+        match name {
+            b"stEvt:action" => self.action = Some(value),
+            b"stEvt:when" => self.when = Some(value),
+            b"stEvt:softwareAgent" => self.software = Some(value),
+            _ => {}
+        }
+    }
+    
+    // "2023-05-01T10:00:00 saved (Adobe Photoshop Lightroom Classic)"
+    fn describe(self) -> Option<String> {
+        let mut parts: Vec<String> = [self.when, self.action].into_iter().flatten().collect();
+        if let Some(software) = self.software {
+            parts.push(format!("({})", software));
+        }
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+}
+
+// Si riconoscono i prefissi standard usati da tutti i programmi Adobe e compatibili, senza
+// risolvere gli spazi dei nomi. Un file malformato restituisce quanto letto fino all'errore
+fn parse(content: &str) -> XmpSidecar {
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+    
+    let mut sidecar = XmpSidecar::default();
+    let mut stack: Vec<Vec<u8>> = Vec::new();
+    let mut event: Option<HistoryEvent> = None;
+    
+    loop {
+        let in_history = stack.iter().any(|name| name == b"xmpMM:History");
+        match reader.read_event() {
+            Ok(Event::Start(element)) => {
+                read_attributes(&element, &mut sidecar);
+                if in_history && element.name().as_ref() == b"rdf:li" {
+                    event = Some(HistoryEvent::from_attributes(&element));
+                }
+                stack.push(element.name().as_ref().to_vec());
+            }
+            Ok(Event::Empty(element)) => {
+                read_attributes(&element, &mut sidecar);
+                if in_history && element.name().as_ref() == b"rdf:li" {
+                    sidecar.history.extend(HistoryEvent::from_attributes(&element).describe());
+                }
+            }
+            Ok(Event::Text(text)) => {
+                let Ok(text) = text.unescape() else {
+                    continue;
+                };
+                let text = text.to_string();
+                match stack.last().map(Vec::as_slice) {
+                    Some(b"xmp:Rating") => sidecar.rating = parse_rating(&text),
+                    Some(b"xmp:Label") => sidecar.label = Some(text),
+                    Some(b"rdf:li") if stack.iter().any(|name| name == b"dc:subject") => sidecar.keywords.push(text),
+                    Some(name) if in_history => {
+                        if let Some(event) = &mut event {
+                            event.set(name, text);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::End(_)) => {
+                if stack.pop().as_deref() == Some(b"rdf:li")
+                    && in_history
+                    && let Some(event) = event.take()
+                {
+                    sidecar.history.extend(event.describe());
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                debug!("XMP malformato alla posizione {}: {}", reader.error_position(), e);
+                break;
+            }
+            Ok(_) => {}
+        }
+    }
+    
+    sidecar
+}
+
+// Rating e Label compaiono spesso come attributi di rdf:Description
+fn read_attributes(element: &BytesStart, sidecar: &mut XmpSidecar) {
+    for (name, value) in attributes(element) {
+        match name.as_slice() {
+            b"xmp:Rating" => sidecar.rating = parse_rating(&value),
+            b"xmp:Label" => sidecar.label = Some(value),
+            _ => {}
+        }
+    }
+}
+
+fn attributes(element: &BytesStart) -> Vec<(Vec<u8>, String)> {
+    element.attributes()
+        .flatten()
+        .filter_map(|attribute| {
+            let value = attribute.unescape_value().ok()?.to_string();
+            Some((attribute.key.as_ref().to_vec(), value))
+        })
+        .collect()
+}
+
+fn parse_rating(value: &str) -> Option<i8> {
+    let rating: f32 = value.trim().parse().ok()?;
+    Some((rating.round() as i8).clamp(-1, 5))
+}
\ No newline at end of file